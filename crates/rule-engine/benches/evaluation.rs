@@ -0,0 +1,149 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rule_engine::compiler::compile_rule;
+use rule_engine::evaluator::{evaluate_rule, EvalContext};
+use rule_engine::models::{DataType, Field, LogicalOperator, Operand, Operator, Rule, Transaction};
+
+#[path = "../tests/common/mod.rs"]
+pub mod common;
+use common::{field, group, leaf, list, parameter, reference_list, snippet, snippet_ref, value};
+
+/// A rule shaped like the ones analysts write: numeric thresholds, sets, a regex,
+/// a reference list, a parameter and a snippet
+fn sample_rule() -> Rule {
+    let mut rule = Rule::new(
+        "Benchmark rule".to_string(),
+        "High-value transactions from risky sources".to_string(),
+    );
+    rule.root = group(
+        LogicalOperator::And,
+        vec![
+            leaf(
+                field(Field::TransactionAmount),
+                Operator::GreaterThan,
+                Operand::Parameter {
+                    name: "HIGH_AMOUNT".to_string(),
+                },
+            ),
+            leaf(
+                field(Field::TransactionCurrency),
+                Operator::NotEquals,
+                value("JPY"),
+            ),
+            leaf(field(Field::UserId), Operator::Regex, value("^u-1[0-9]+$")),
+            group(
+                LogicalOperator::Or,
+                vec![
+                    leaf(
+                        field(Field::UserCountry),
+                        Operator::In,
+                        list(DataType::String, &["NG", "RU", "BR", "VN", "ID", "PK"]),
+                    ),
+                    leaf(
+                        field(Field::DeviceFingerprint),
+                        Operator::In,
+                        Operand::ListRef {
+                            name: "blocked_devices".to_string(),
+                        },
+                    ),
+                    leaf(
+                        field(Field::IpAddress),
+                        Operator::InCidr,
+                        Operand::ListRef {
+                            name: "high_risk_ips".to_string(),
+                        },
+                    ),
+                    snippet_ref("new_account"),
+                ],
+            ),
+        ],
+    );
+    rule
+}
+
+/// The lists, parameter and snippet the sample rule references, with entries that
+/// occur in the sample dataset
+fn sample_context() -> EvalContext {
+    let lists = [
+        reference_list(
+            "blocked_devices",
+            DataType::String,
+            &["emu-000001", "emu-000002", "emu-000003"],
+        ),
+        reference_list(
+            "high_risk_ips",
+            DataType::Ip,
+            &["192.0.2.44", "192.0.2.77", "198.51.100.200"],
+        ),
+    ];
+    let new_account = snippet(
+        "new_account",
+        group(
+            LogicalOperator::And,
+            vec![
+                leaf(field(Field::AccountAge), Operator::LessThan, value("7")),
+                leaf(
+                    field(Field::TransactionCount24h),
+                    Operator::GreaterThan,
+                    value("3"),
+                ),
+            ],
+        ),
+    );
+    EvalContext {
+        lists: lists
+            .into_iter()
+            .map(|list| (list.name.clone(), list))
+            .collect(),
+        parameters: [(
+            "HIGH_AMOUNT".to_string(),
+            parameter("HIGH_AMOUNT", DataType::Number, "1000"),
+        )]
+        .into(),
+        snippets: [(new_account.name.clone(), new_account)].into(),
+        ..EvalContext::default()
+    }
+}
+
+fn bench_evaluation(c: &mut Criterion) {
+    let transactions =
+        Transaction::parse_jsonl(include_str!("../../../data/sample_transactions.jsonl")).unwrap();
+    let ctx = sample_context();
+    let rule = sample_rule();
+    let compiled = compile_rule(&rule, &ctx).expect("benchmark rule is valid");
+
+    // Both paths must agree before their speed is worth comparing
+    for tx in &transactions {
+        assert_eq!(
+            evaluate_rule(&rule, tx, &ctx).matched,
+            compiled.matches(tx, &ctx),
+            "compiled rule disagrees with the tree walk on {:?}",
+            tx
+        );
+    }
+
+    let mut group = c.benchmark_group("evaluate_sample_dataset");
+    group.bench_function("tree_walk", |b| {
+        b.iter(|| {
+            transactions
+                .iter()
+                .filter(|tx| evaluate_rule(black_box(&rule), tx, &ctx).matched)
+                .count()
+        })
+    });
+    group.bench_function("compiled", |b| {
+        b.iter(|| {
+            transactions
+                .iter()
+                .filter(|tx| black_box(&compiled).matches(tx, &ctx))
+                .count()
+        })
+    });
+    group.finish();
+
+    c.bench_function("compile_rule", |b| {
+        b.iter(|| compile_rule(black_box(&rule), &ctx))
+    });
+}
+
+criterion_group!(benches, bench_evaluation);
+criterion_main!(benches);
@@ -0,0 +1,571 @@
+//! Static analysis of condition trees: groups that can never match or always match,
+//! conditions made redundant by a sibling, and siblings written twice.
+//!
+//! Conditions comparing a field with constants are reasoned about per field. The
+//! constants a tree compares each field with split that field's values into a few
+//! regions: for numbers and date/times each constant and the open intervals between
+//! them, for text each constant and "anything else". Every such condition holds on
+//! all of a region or none of it, so a condition is the set of regions it holds on,
+//! found by evaluating it once per region. Intersections, unions and implication of
+//! conditions on a field are then exact set operations.
+//!
+//! Conditions on a field are false when it is missing, so "always true" means always
+//! true for transactions that have the field. Other conditions are not reasoned about
+//! beyond spotting duplicates.
+
+use crate::evaluator::{compare, resolve_operand, EvalContext, Value};
+use crate::models::{
+    parse_datetime, ConditionNode, DataType, Field, LogicalOperator, Operand, Operator, Rule,
+    Transaction,
+};
+use chrono::DateTime;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// What a warning points out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningKind {
+    /// The node can never match
+    Unsatisfiable,
+    /// The node matches every transaction that has the fields it reads
+    AlwaysTrue,
+    /// The node can be removed without changing what its group matches
+    Redundant,
+    /// The node repeats an earlier sibling
+    Duplicate,
+}
+
+/// A likely mistake in a rule that is still valid
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Warning {
+    /// The node the warning is about
+    pub node_id: Uuid,
+    pub kind: WarningKind,
+    pub message: String,
+}
+
+/// Analyze a rule's conditions. Pass the rule's evaluation context so parameters,
+/// lists and local date/times resolve as they do when it runs.
+///
+/// ```
+/// use rule_engine::analysis::{analyze_rule, WarningKind};
+/// use rule_engine::{EvalContext, Rule};
+///
+/// let rule: Rule = serde_json::from_value(serde_json::json!({
+///     "id": "6f1c1a8e-8f1e-4d33-9b7e-0d6a2d1f7c11",
+///     "name": "Impossible amount",
+///     "description": "",
+///     "root": {
+///         "type": "group",
+///         "id": "0b5c2b1e-3f3a-4d7e-8c55-2f0e6f4a9d01",
+///         "operator": "AND",
+///         "children": [
+///             {
+///                 "type": "leaf",
+///                 "id": "9d7e4c2a-1b3f-4e5d-8a6c-7f8e9d0a1b2c",
+///                 "left": { "type": "field", "field": "transaction_amount" },
+///                 "operator": "greater_than",
+///                 "right": { "type": "value", "value": "1000" }
+///             },
+///             {
+///                 "type": "leaf",
+///                 "id": "1a2b3c4d-5e6f-4a7b-8c9d-0e1f2a3b4c5d",
+///                 "left": { "type": "field", "field": "transaction_amount" },
+///                 "operator": "less_than",
+///                 "right": { "type": "value", "value": "500" }
+///             }
+///         ]
+///     },
+///     "actions": []
+/// }))
+/// .unwrap();
+///
+/// let warnings = analyze_rule(&rule, &EvalContext::default());
+/// assert_eq!(warnings.len(), 1);
+/// assert_eq!(warnings[0].kind, WarningKind::Unsatisfiable);
+/// ```
+pub fn analyze_rule(rule: &Rule, ctx: &EvalContext) -> Vec<Warning> {
+    analyze(&rule.root, ctx)
+}
+
+/// Analyze a condition tree. Snippets it references are reasoned about as a whole
+/// but not analyzed inside; they are analyzed on their own.
+pub fn analyze(root: &ConditionNode, ctx: &EvalContext) -> Vec<Warning> {
+    let mut analyzer = Analyzer::new(&[root], ctx);
+    analyzer.visit(root);
+    analyzer.warnings
+}
+
+/// The regions of one field's values that some condition holds on
+pub(crate) type Regions = Vec<bool>;
+
+/// Splits each field's values into regions for the conditions of some trees
+pub(crate) struct Analyzer<'a> {
+    ctx: &'a EvalContext,
+    /// A representative value of each region, per field
+    regions: HashMap<Field, Vec<Value>>,
+    warnings: Vec<Warning>,
+}
+
+impl<'a> Analyzer<'a> {
+    pub(crate) fn new(roots: &[&ConditionNode], ctx: &'a EvalContext) -> Self {
+        let mut constants: HashMap<Field, Vec<Value>> = HashMap::new();
+        let mut snippets = HashSet::new();
+        for root in roots {
+            collect_constants(root, ctx, &mut constants, &mut snippets);
+        }
+        let regions = constants
+            .into_iter()
+            .map(|(field, values)| (field.clone(), representatives(field.data_type(), values)))
+            .collect();
+        Analyzer {
+            ctx,
+            regions,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// The field a node constrains and the regions it holds on, when the node only
+    /// compares one field with constants
+    pub(crate) fn summary(&self, node: &ConditionNode) -> Option<(Field, Regions)> {
+        self.summary_within(node, &mut Vec::new())
+    }
+
+    /// The summary of a node inside the snippets of `chain`. A snippet that references
+    /// itself has none; validation reports the cycle.
+    fn summary_within(
+        &self,
+        node: &ConditionNode,
+        chain: &mut Vec<String>,
+    ) -> Option<(Field, Regions)> {
+        match node {
+            ConditionNode::Leaf {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let (field, right) = comparison(left, operator, right, self.ctx)?;
+                let regions = self.regions.get(&field)?;
+                let holds = regions
+                    .iter()
+                    .map(|value| compare(operator, value, &right, self.ctx))
+                    .collect();
+                Some((field, holds))
+            }
+            ConditionNode::Group {
+                operator, children, ..
+            } => {
+                let mut summaries = children
+                    .iter()
+                    .map(|child| self.summary_within(child, chain));
+                let (field, mut holds) = summaries.next()??;
+                for summary in summaries {
+                    let (other, other_holds) = summary?;
+                    if other != field {
+                        return None;
+                    }
+                    for (held, other_held) in holds.iter_mut().zip(other_holds) {
+                        *held = match operator {
+                            LogicalOperator::And => *held && other_held,
+                            LogicalOperator::Or => *held || other_held,
+                        };
+                    }
+                }
+                Some((field, holds))
+            }
+            ConditionNode::SnippetRef { name, .. } => {
+                let snippet = self.ctx.snippets.get(name)?;
+                if chain.contains(name) {
+                    return None;
+                }
+                chain.push(name.clone());
+                let summary = self.summary_within(&snippet.root, chain);
+                chain.pop();
+                summary
+            }
+        }
+    }
+
+    fn warn(&mut self, node: &ConditionNode, kind: WarningKind, message: String) {
+        self.warnings.push(Warning {
+            node_id: node.id(),
+            kind,
+            message,
+        });
+    }
+
+    fn visit(&mut self, node: &ConditionNode) {
+        match node {
+            ConditionNode::Leaf { .. } => match self.summary(node) {
+                Some((_, holds)) if holds.iter().all(|held| !held) => self.warn(
+                    node,
+                    WarningKind::Unsatisfiable,
+                    format!("{} never matches", node.display()),
+                ),
+                Some((field, holds)) if holds.iter().all(|held| *held) => self.warn(
+                    node,
+                    WarningKind::AlwaysTrue,
+                    format!(
+                        "{} matches every transaction that has {}",
+                        node.display(),
+                        field.display_name()
+                    ),
+                ),
+                _ => {}
+            },
+            ConditionNode::Group {
+                operator, children, ..
+            } => {
+                self.visit_group(node, operator, children);
+                for child in children {
+                    self.visit(child);
+                }
+            }
+            ConditionNode::SnippetRef { .. } => {}
+        }
+    }
+
+    fn visit_group(
+        &mut self,
+        group: &ConditionNode,
+        operator: &LogicalOperator,
+        children: &[ConditionNode],
+    ) {
+        // Duplicates first, so a repeated child is not also reported as redundant
+        let shapes: Vec<serde_json::Value> = children.iter().map(shape).collect();
+        let mut duplicate = vec![false; children.len()];
+        for (i, child) in children.iter().enumerate() {
+            if shapes[..i].contains(&shapes[i]) {
+                duplicate[i] = true;
+                self.warn(
+                    child,
+                    WarningKind::Duplicate,
+                    format!("{} appears more than once in this group", child.display()),
+                );
+            }
+        }
+
+        let summaries: Vec<Option<(Field, Regions)>> =
+            children.iter().map(|child| self.summary(child)).collect();
+        let mut fields: Vec<&Field> = Vec::new();
+        for (field, _) in summaries.iter().flatten() {
+            if !fields.contains(&field) {
+                fields.push(field);
+            }
+        }
+
+        for field in fields {
+            let members: Vec<(usize, &Regions)> = summaries
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !duplicate[*i])
+                .filter_map(|(i, summary)| match summary {
+                    Some((f, holds)) if f == field => Some((i, holds)),
+                    _ => None,
+                })
+                .collect();
+            // A member that never or always holds has been reported on its own
+            if members
+                .iter()
+                .any(|(_, holds)| holds.iter().all(|held| !held) || holds.iter().all(|held| *held))
+            {
+                continue;
+            }
+            if members.len() < 2 {
+                continue;
+            }
+
+            let combined: Regions = (0..members[0].1.len())
+                .map(|region| match operator {
+                    LogicalOperator::And => members.iter().all(|(_, holds)| holds[region]),
+                    LogicalOperator::Or => members.iter().any(|(_, holds)| holds[region]),
+                })
+                .collect();
+            let describe = |indices: &[usize], joiner: &str| {
+                indices
+                    .iter()
+                    .map(|i| children[*i].display())
+                    .collect::<Vec<_>>()
+                    .join(joiner)
+            };
+            let all: Vec<usize> = members.iter().map(|(i, _)| *i).collect();
+
+            match operator {
+                LogicalOperator::And if combined.iter().all(|held| !held) => {
+                    let pair = members.iter().enumerate().find_map(|(a, (i, first))| {
+                        members[a + 1..].iter().find_map(|(j, second)| {
+                            (!first.iter().zip(second.iter()).any(|(x, y)| *x && *y))
+                                .then_some([*i, *j])
+                        })
+                    });
+                    let message = match pair {
+                        Some(pair) => format!(
+                            "Never matches: {} cannot both hold",
+                            describe(&pair, " and ")
+                        ),
+                        None => {
+                            format!("Never matches: {} cannot all hold", describe(&all, " and "))
+                        }
+                    };
+                    self.warn(group, WarningKind::Unsatisfiable, message);
+                    continue;
+                }
+                LogicalOperator::Or if combined.iter().all(|held| *held) => {
+                    self.warn(
+                        group,
+                        WarningKind::AlwaysTrue,
+                        format!(
+                            "Matches every transaction that has {}: {} cover every value",
+                            field.display_name(),
+                            describe(&all, " or ")
+                        ),
+                    );
+                    continue;
+                }
+                _ => {}
+            }
+
+            // In an AND a member is redundant when another implies it; in an OR when
+            // it implies another. Of two equivalent members the later is reported.
+            for (i, holds) in &members {
+                let implied_by = members.iter().find(|(j, other)| {
+                    let (narrow, wide) = match operator {
+                        LogicalOperator::And => (*other, *holds),
+                        LogicalOperator::Or => (*holds, *other),
+                    };
+                    j != i && subset(narrow, wide) && (narrow != wide || j < i)
+                });
+                if let Some((j, _)) = implied_by {
+                    let message = match operator {
+                        LogicalOperator::And => format!(
+                            "{} is redundant: {} already implies it",
+                            children[*i].display(),
+                            children[*j].display()
+                        ),
+                        LogicalOperator::Or => format!(
+                            "{} is redundant: {} matches everything it does",
+                            children[*i].display(),
+                            children[*j].display()
+                        ),
+                    };
+                    self.warn(&children[*i], WarningKind::Redundant, message);
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn subset(narrow: &Regions, wide: &Regions) -> bool {
+    narrow.iter().zip(wide).all(|(n, w)| !n || *w)
+}
+
+/// A node as JSON without its ids, so equal conditions compare equal
+pub(crate) fn shape(node: &ConditionNode) -> serde_json::Value {
+    fn strip(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(map) => {
+                map.remove("id");
+                map.values_mut().for_each(strip);
+            }
+            serde_json::Value::Array(items) => items.iter_mut().for_each(strip),
+            _ => {}
+        }
+    }
+    let mut value = serde_json::to_value(node).unwrap_or_default();
+    strip(&mut value);
+    value
+}
+
+/// A leaf comparing a field with constants, as the field and the resolved constants
+fn comparison(
+    left: &Operand,
+    operator: &Operator,
+    right: &Operand,
+    ctx: &EvalContext,
+) -> Option<(Field, Value)> {
+    let Operand::Field { field } = left else {
+        return None;
+    };
+    let supported = match field.data_type() {
+        DataType::Number => matches!(
+            operator,
+            Operator::Equals
+                | Operator::NotEquals
+                | Operator::GreaterThan
+                | Operator::LessThan
+                | Operator::GreaterThanOrEqual
+                | Operator::LessThanOrEqual
+                | Operator::Between
+                | Operator::In
+                | Operator::NotIn
+        ),
+        DataType::String | DataType::Ip => matches!(
+            operator,
+            Operator::Equals | Operator::NotEquals | Operator::In | Operator::NotIn
+        ),
+        DataType::DateTime => matches!(operator, Operator::Before | Operator::After),
+    };
+    if !supported
+        || !matches!(
+            right,
+            Operand::Value { .. }
+                | Operand::Parameter { .. }
+                | Operand::Range { .. }
+                | Operand::List { .. }
+                | Operand::ListRef { .. }
+        )
+    {
+        return None;
+    }
+    let value = resolve_operand(right, &Transaction::default(), ctx)?;
+    // Every constant must fall in the field's regions; ranges of addresses do not
+    let mut constants = Vec::new();
+    constants_of(&value, field.data_type(), ctx, &mut constants).then_some((field.clone(), value))
+}
+
+/// Add a resolved operand's constants as values of `data_type`; false when one is
+/// not such a value
+fn constants_of(
+    value: &Value,
+    data_type: DataType,
+    ctx: &EvalContext,
+    out: &mut Vec<Value>,
+) -> bool {
+    match (data_type, value) {
+        (_, Value::List(values)) => values
+            .iter()
+            .all(|value| constants_of(value, data_type, ctx, out)),
+        (DataType::Number, Value::Number(n)) => {
+            out.push(Value::Number(*n));
+            true
+        }
+        (DataType::Number, Value::Range(min, max)) => {
+            out.extend([Value::Number(*min), Value::Number(*max)]);
+            true
+        }
+        (DataType::String, Value::Number(_) | Value::Text(_)) => {
+            out.push(Value::Text(value.to_string()));
+            true
+        }
+        (DataType::Ip, Value::Ip(_)) => {
+            out.push(value.clone());
+            true
+        }
+        (DataType::Ip, Value::Text(text)) => match text.trim().parse() {
+            Ok(ip) => {
+                out.push(Value::Ip(ip));
+                true
+            }
+            Err(_) => false,
+        },
+        (DataType::DateTime, Value::DateTime(at)) => {
+            out.push(Value::Number(at.timestamp() as f64));
+            true
+        }
+        (DataType::DateTime, Value::Text(text)) => match parse_datetime(text, ctx.timezone) {
+            Some(at) => {
+                out.push(Value::Number(at.timestamp() as f64));
+                true
+            }
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+/// Collect the constants each field is compared with; `snippets` holds the snippets
+/// already collected from, so each is visited once even when they reference each other
+fn collect_constants(
+    node: &ConditionNode,
+    ctx: &EvalContext,
+    constants: &mut HashMap<Field, Vec<Value>>,
+    snippets: &mut HashSet<String>,
+) {
+    match node {
+        ConditionNode::Leaf {
+            left,
+            operator,
+            right,
+            ..
+        } => {
+            if let Some((field, value)) = comparison(left, operator, right, ctx) {
+                let values = constants.entry(field.clone()).or_default();
+                constants_of(&value, field.data_type(), ctx, values);
+            }
+        }
+        ConditionNode::Group { children, .. } => {
+            for child in children {
+                collect_constants(child, ctx, constants, snippets);
+            }
+        }
+        ConditionNode::SnippetRef { name, .. } => {
+            if let Some(snippet) = ctx.snippets.get(name) {
+                if snippets.insert(name.clone()) {
+                    collect_constants(&snippet.root, ctx, constants, snippets);
+                }
+            }
+        }
+    }
+}
+
+/// One value from each region the constants split a field's values into
+fn representatives(data_type: DataType, constants: Vec<Value>) -> Vec<Value> {
+    match data_type {
+        DataType::Number | DataType::DateTime => {
+            let mut points: Vec<f64> = constants
+                .iter()
+                .filter_map(|value| match value {
+                    Value::Number(n) => Some(*n),
+                    _ => None,
+                })
+                .collect();
+            points.sort_by(f64::total_cmp);
+            points.dedup();
+            let (Some(first), Some(last)) = (points.first(), points.last()) else {
+                // No constants: all values are alike
+                return vec![match data_type {
+                    DataType::DateTime => Value::DateTime(DateTime::UNIX_EPOCH),
+                    _ => Value::Number(0.0),
+                }];
+            };
+            let mut values = vec![first - 1.0];
+            for (i, point) in points.iter().enumerate() {
+                values.push(*point);
+                values.push(
+                    points
+                        .get(i + 1)
+                        .map_or(last + 1.0, |next| (point + next) / 2.0),
+                );
+            }
+            if data_type == DataType::DateTime {
+                values
+                    .into_iter()
+                    .filter_map(|seconds| {
+                        let nanos = (seconds.fract().abs() * 1e9) as u32;
+                        DateTime::from_timestamp(seconds.floor() as i64, nanos).map(Value::DateTime)
+                    })
+                    .collect()
+            } else {
+                values.into_iter().map(Value::Number).collect()
+            }
+        }
+        DataType::String | DataType::Ip => {
+            let mut values: Vec<Value> = Vec::new();
+            for constant in constants {
+                if !values
+                    .iter()
+                    .any(|value| value.to_string() == constant.to_string())
+                {
+                    values.push(constant);
+                }
+            }
+            // Anything else: longer than every constant, so equal to none of them
+            let longest = values.iter().map(|value| value.to_string().len()).max();
+            values.push(Value::Text("\u{0}".repeat(longest.unwrap_or(0) + 1)));
+            values
+        }
+    }
+}
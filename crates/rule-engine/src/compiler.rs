@@ -0,0 +1,578 @@
+use crate::evaluator::{evaluate_leaf, resolve_operand, EvalContext, Value};
+use crate::models::{
+    Action, ConditionNode, DataType, Field, LogicalOperator, Operand, Operator, Rule, RuleMode,
+    ScoreThreshold, Transaction,
+};
+use ipnet::IpNet;
+use regex::Regex;
+use std::collections::HashSet;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+/// One step of a compiled program. A program keeps a single boolean accumulator:
+/// tests and constants set it, jumps read it, so running a program needs no stack.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Instr {
+    /// Set the accumulator to the result of the test at this index
+    Test(usize),
+    Const(bool),
+    /// Leave an AND group as soon as a child is false
+    JumpIfFalse(usize),
+    /// Leave an OR group as soon as a child is true
+    JumpIfTrue(usize),
+    Return,
+}
+
+/// A transaction value borrowed from the record rather than copied into a `Value`
+#[derive(Debug, Clone, Copy)]
+enum Scalar<'a> {
+    Number(f64),
+    Text(&'a str),
+}
+
+impl<'a> Scalar<'a> {
+    /// Read a field the way `Value::from_json` does: booleans compare as text
+    fn read(tx: &'a Transaction, field: &Field) -> Option<Scalar<'a>> {
+        match tx.get(field)? {
+            serde_json::Value::Number(n) => n.as_f64().map(Scalar::Number),
+            serde_json::Value::String(s) => Some(Scalar::Text(s)),
+            serde_json::Value::Bool(true) => Some(Scalar::Text("true")),
+            serde_json::Value::Bool(false) => Some(Scalar::Text("false")),
+            _ => None,
+        }
+    }
+
+    /// Apply `f` to the value's text; only numbers need to be formatted
+    fn with_text<R>(self, f: impl FnOnce(&str) -> R) -> R {
+        match self {
+            Scalar::Text(s) => f(s),
+            Scalar::Number(n) => f(&n.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Comparison {
+    GreaterThan,
+    LessThan,
+    GreaterThanOrEqual,
+    LessThanOrEqual,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TextMatch {
+    Contains,
+    StartsWith,
+    EndsWith,
+}
+
+/// Hash key of a number; `-0` and `0` are equal, as they are for `==`
+fn number_key(n: f64) -> u64 {
+    (n + 0.0).to_bits()
+}
+
+/// The elements of an In / Not In list, hashed once at compile time
+#[derive(Debug, Clone, Default)]
+struct ScalarSet {
+    numbers: HashSet<u64>,
+    /// Every element as text, for text values
+    texts: HashSet<String>,
+    /// Elements that are not numbers, which numbers can only equal as text
+    non_numbers: HashSet<String>,
+}
+
+impl ScalarSet {
+    /// `None` when an element is not a plain number or text, e.g. an IP address
+    fn new(elements: &[Value]) -> Option<Self> {
+        let mut set = ScalarSet::default();
+        for element in elements {
+            match element {
+                Value::Number(n) => {
+                    if !n.is_nan() {
+                        set.numbers.insert(number_key(*n));
+                    }
+                }
+                Value::Text(s) => {
+                    set.non_numbers.insert(s.clone());
+                }
+                _ => return None,
+            }
+            set.texts.insert(element.to_string());
+        }
+        Some(set)
+    }
+
+    fn contains(&self, value: Scalar) -> bool {
+        match value {
+            Scalar::Text(s) => self.texts.contains(s),
+            Scalar::Number(n) => {
+                self.numbers.contains(&number_key(n))
+                    || (!self.non_numbers.is_empty() && self.non_numbers.contains(&n.to_string()))
+            }
+        }
+    }
+}
+
+/// An operand of a dynamic test: constants are resolved once at compile time
+#[derive(Debug, Clone)]
+enum Load {
+    Const(Option<Value>),
+    Operand(Operand),
+}
+
+impl Load {
+    fn new(operand: &Operand, ctx: &EvalContext) -> Self {
+        match operand {
+            Operand::Field { .. } | Operand::Aggregate { .. } | Operand::Expression { .. } => {
+                Load::Operand(operand.clone())
+            }
+            _ => Load::Const(resolve_operand(operand, &Transaction::default(), ctx)),
+        }
+    }
+
+    fn resolve(&self, tx: &Transaction, ctx: &EvalContext) -> Option<Value> {
+        match self {
+            Load::Const(value) => value.clone(),
+            Load::Operand(operand) => resolve_operand(operand, tx, ctx),
+        }
+    }
+}
+
+/// A condition lowered to a specialised check. Tests on a field borrow the transaction's
+/// value, formatting it only when a number is matched as text; anything without a fast
+/// path falls back to the tree evaluator's comparison, with its constant operands
+/// resolved up front and the rest resolved into `Value`s on every evaluation.
+#[derive(Debug, Clone)]
+enum Test {
+    Compare {
+        field: Field,
+        comparison: Comparison,
+        rhs: f64,
+    },
+    Between {
+        field: Field,
+        min: f64,
+        max: f64,
+    },
+    /// `number` is set when the literal is a number; `text` is how it displays
+    Equals {
+        field: Field,
+        number: Option<f64>,
+        text: String,
+        negate: bool,
+    },
+    Text {
+        field: Field,
+        kind: TextMatch,
+        needle: String,
+    },
+    Regex {
+        field: Field,
+        regex: Regex,
+    },
+    InSet {
+        field: Field,
+        set: ScalarSet,
+        negate: bool,
+    },
+    InCidr {
+        field: Field,
+        networks: Vec<IpNet>,
+        negate: bool,
+    },
+    IsEmpty {
+        field: Field,
+    },
+    Dynamic {
+        left: Load,
+        operator: Operator,
+        right: Load,
+    },
+}
+
+impl Test {
+    fn eval(&self, tx: &Transaction, ctx: &EvalContext) -> bool {
+        match self {
+            Test::Compare {
+                field,
+                comparison,
+                rhs,
+            } => match Scalar::read(tx, field) {
+                Some(Scalar::Number(n)) => match comparison {
+                    Comparison::GreaterThan => n > *rhs,
+                    Comparison::LessThan => n < *rhs,
+                    Comparison::GreaterThanOrEqual => n >= *rhs,
+                    Comparison::LessThanOrEqual => n <= *rhs,
+                },
+                _ => false,
+            },
+            Test::Between { field, min, max } => match Scalar::read(tx, field) {
+                Some(Scalar::Number(n)) => *min <= n && n <= *max,
+                _ => false,
+            },
+            Test::Equals {
+                field,
+                number,
+                text,
+                negate,
+            } => match Scalar::read(tx, field) {
+                Some(Scalar::Number(n)) => number.is_some_and(|number| n == number) != *negate,
+                Some(Scalar::Text(s)) => (s == text) != *negate,
+                None => false,
+            },
+            Test::Text {
+                field,
+                kind,
+                needle,
+            } => Scalar::read(tx, field).is_some_and(|value| {
+                value.with_text(|s| match kind {
+                    TextMatch::Contains => s.contains(needle.as_str()),
+                    TextMatch::StartsWith => s.starts_with(needle.as_str()),
+                    TextMatch::EndsWith => s.ends_with(needle.as_str()),
+                })
+            }),
+            Test::Regex { field, regex } => {
+                Scalar::read(tx, field).is_some_and(|value| value.with_text(|s| regex.is_match(s)))
+            }
+            Test::InSet { field, set, negate } => {
+                Scalar::read(tx, field).is_some_and(|value| set.contains(value) != *negate)
+            }
+            Test::InCidr {
+                field,
+                networks,
+                negate,
+            } => match Scalar::read(tx, field) {
+                Some(Scalar::Text(s)) => match s.trim().parse::<IpAddr>() {
+                    Ok(ip) => networks.iter().any(|net| net.contains(&ip)) != *negate,
+                    Err(_) => false,
+                },
+                _ => false,
+            },
+            Test::IsEmpty { field } => match Scalar::read(tx, field) {
+                Some(Scalar::Text(s)) => s.trim().is_empty(),
+                Some(Scalar::Number(_)) => false,
+                None => true,
+            },
+            Test::Dynamic {
+                left,
+                operator,
+                right,
+            } => {
+                let right = if operator.is_unary() {
+                    None
+                } else {
+                    right.resolve(tx, ctx)
+                };
+                evaluate_leaf(
+                    left.resolve(tx, ctx).as_ref(),
+                    operator,
+                    right.as_ref(),
+                    ctx,
+                )
+            }
+        }
+    }
+}
+
+/// A rule lowered into a flat program with pre-parsed literals, pre-hashed `In` sets
+/// and pre-compiled regexes, for evaluating many transactions against one rule.
+///
+/// Lists, parameters and snippets are captured when the rule is compiled, so compile
+/// again after they change. Evaluate with the same kind of context the tree
+/// evaluator takes: aggregates and date/time operators read `now` and `timezone` from it.
+#[derive(Debug, Clone)]
+pub struct CompiledRule {
+    pub rule_id: Uuid,
+    mode: RuleMode,
+    code: Vec<Instr>,
+    tests: Vec<Test>,
+    /// Entry points of weighted nodes and their weights, for scoring rules
+    weighted: Vec<(usize, f64)>,
+    actions: Vec<Action>,
+    thresholds: Vec<ScoreThreshold>,
+}
+
+/// Type-check a rule and compile it. Fails with the rule's validation errors.
+///
+/// ```
+/// use rule_engine::{compile_rule, EvalContext, Rule};
+///
+/// // A new rule has an empty AND group, which validation rejects
+/// let rule = Rule::new("Empty".to_string(), String::new());
+/// assert!(compile_rule(&rule, &EvalContext::default()).is_err());
+/// ```
+pub fn compile_rule(rule: &Rule, ctx: &EvalContext) -> Result<CompiledRule, Vec<String>> {
+    rule.validate(ctx)?;
+
+    let mut compiler = Compiler {
+        code: Vec::new(),
+        tests: Vec::new(),
+        ctx,
+    };
+    let mut weighted = Vec::new();
+    match rule.mode {
+        RuleMode::Boolean => compiler.block(&rule.root),
+        RuleMode::Scoring => compiler.weighted_blocks(&rule.root, &mut weighted),
+    }
+
+    Ok(CompiledRule {
+        rule_id: rule.id,
+        mode: rule.mode.clone(),
+        code: compiler.code,
+        tests: compiler.tests,
+        weighted,
+        actions: rule.actions.clone(),
+        thresholds: rule.thresholds.clone(),
+    })
+}
+
+struct Compiler<'a> {
+    code: Vec<Instr>,
+    tests: Vec<Test>,
+    ctx: &'a EvalContext,
+}
+
+impl Compiler<'_> {
+    /// Emit a program for `node` that returns its result
+    fn block(&mut self, node: &ConditionNode) {
+        self.node(node);
+        self.code.push(Instr::Return);
+    }
+
+    /// Emit one block per weighted node. Like the scoring evaluator, a snippet counts
+    /// as a single node and the weights inside it are ignored.
+    fn weighted_blocks(&mut self, node: &ConditionNode, weighted: &mut Vec<(usize, f64)>) {
+        if let Some(weight) = node.weight() {
+            weighted.push((self.code.len(), weight));
+            self.block(node);
+        }
+        if let ConditionNode::Group { children, .. } = node {
+            for child in children {
+                self.weighted_blocks(child, weighted);
+            }
+        }
+    }
+
+    fn node(&mut self, node: &ConditionNode) {
+        match node {
+            ConditionNode::Leaf {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let test = self.test(left, operator, right);
+                self.code.push(Instr::Test(self.tests.len()));
+                self.tests.push(test);
+            }
+            ConditionNode::Group {
+                operator, children, ..
+            } => {
+                if children.is_empty() {
+                    self.code
+                        .push(Instr::Const(*operator == LogicalOperator::And));
+                    return;
+                }
+                let mut exits = Vec::new();
+                for (index, child) in children.iter().enumerate() {
+                    self.node(child);
+                    if index + 1 < children.len() {
+                        exits.push(self.code.len());
+                        self.code.push(match operator {
+                            LogicalOperator::And => Instr::JumpIfFalse(0),
+                            LogicalOperator::Or => Instr::JumpIfTrue(0),
+                        });
+                    }
+                }
+                let end = self.code.len();
+                for exit in exits {
+                    self.code[exit] = match self.code[exit] {
+                        Instr::JumpIfFalse(_) => Instr::JumpIfFalse(end),
+                        _ => Instr::JumpIfTrue(end),
+                    };
+                }
+            }
+            // Validation rejects unknown and cyclic snippets
+            ConditionNode::SnippetRef { name, .. } => match self.ctx.snippets.get(name) {
+                Some(snippet) => self.node(&snippet.root),
+                None => self.code.push(Instr::Const(false)),
+            },
+        }
+    }
+
+    fn test(&self, left: &Operand, operator: &Operator, right: &Operand) -> Test {
+        let right = Load::new(right, self.ctx);
+        if let (Operand::Field { field }, Load::Const(constant)) = (left, &right) {
+            if let Some(test) = fast_test(field, operator, constant.as_ref()) {
+                return test;
+            }
+        }
+        Test::Dynamic {
+            left: Load::new(left, self.ctx),
+            operator: operator.clone(),
+            right,
+        }
+    }
+}
+
+/// A specialised test for a field compared with a constant, where one exists
+fn fast_test(field: &Field, operator: &Operator, right: Option<&Value>) -> Option<Test> {
+    let field = field.clone();
+    if *operator == Operator::IsEmpty {
+        return Some(Test::IsEmpty { field });
+    }
+    // IP and date/time fields are converted before comparison; leave them to the evaluator
+    let plain = matches!(field.data_type(), DataType::Number | DataType::String);
+    let comparison = match operator {
+        Operator::GreaterThan => Some(Comparison::GreaterThan),
+        Operator::LessThan => Some(Comparison::LessThan),
+        Operator::GreaterThanOrEqual => Some(Comparison::GreaterThanOrEqual),
+        Operator::LessThanOrEqual => Some(Comparison::LessThanOrEqual),
+        _ => None,
+    };
+    let text_match = match operator {
+        Operator::Contains => Some(TextMatch::Contains),
+        Operator::StartsWith => Some(TextMatch::StartsWith),
+        Operator::EndsWith => Some(TextMatch::EndsWith),
+        _ => None,
+    };
+
+    match (operator, right?) {
+        (_, Value::Number(rhs)) if plain && comparison.is_some() => Some(Test::Compare {
+            field,
+            comparison: comparison?,
+            rhs: *rhs,
+        }),
+        (Operator::Between, Value::Range(min, max)) if plain => Some(Test::Between {
+            field,
+            min: *min,
+            max: *max,
+        }),
+        (Operator::Equals | Operator::NotEquals, value @ (Value::Number(_) | Value::Text(_)))
+            if plain =>
+        {
+            Some(Test::Equals {
+                field,
+                number: match value {
+                    Value::Number(n) => Some(*n),
+                    _ => None,
+                },
+                text: value.to_string(),
+                negate: *operator == Operator::NotEquals,
+            })
+        }
+        (_, value) if plain && text_match.is_some() => Some(Test::Text {
+            field,
+            kind: text_match?,
+            needle: value.to_string(),
+        }),
+        (Operator::Regex, value) if plain => Regex::new(&value.to_string())
+            .ok()
+            .map(|regex| Test::Regex { field, regex }),
+        (Operator::In | Operator::NotIn, Value::List(elements)) if plain => Some(Test::InSet {
+            field,
+            set: ScalarSet::new(elements)?,
+            negate: *operator == Operator::NotIn,
+        }),
+        (Operator::InCidr | Operator::NotInCidr, Value::List(elements))
+            if field.data_type() == DataType::Ip =>
+        {
+            let networks = elements
+                .iter()
+                .map(|element| match element {
+                    Value::Network(net) => Some(*net),
+                    Value::Ip(ip) => Some(IpNet::from(*ip)),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some(Test::InCidr {
+                field,
+                networks,
+                negate: *operator == Operator::NotInCidr,
+            })
+        }
+        _ => None,
+    }
+}
+
+impl CompiledRule {
+    fn run(&self, entry: usize, tx: &Transaction, ctx: &EvalContext) -> bool {
+        let mut pc = entry;
+        let mut acc = false;
+        loop {
+            match self.code[pc] {
+                Instr::Test(index) => acc = self.tests[index].eval(tx, ctx),
+                Instr::Const(value) => acc = value,
+                Instr::JumpIfFalse(target) if !acc => {
+                    pc = target;
+                    continue;
+                }
+                Instr::JumpIfTrue(target) if acc => {
+                    pc = target;
+                    continue;
+                }
+                Instr::JumpIfFalse(_) | Instr::JumpIfTrue(_) => {}
+                Instr::Return => return acc,
+            }
+            pc += 1;
+        }
+    }
+
+    /// Sum of the weights of matched nodes; always 0 for boolean rules
+    pub fn score(&self, tx: &Transaction, ctx: &EvalContext) -> f64 {
+        self.weighted
+            .iter()
+            .filter(|(entry, _)| self.run(*entry, tx, ctx))
+            .fold(0.0, |score, (_, weight)| score + weight)
+    }
+
+    /// Whether the rule matches, its score and its actions, running the program once
+    pub fn outcome(&self, tx: &Transaction, ctx: &EvalContext) -> CompiledOutcome<'_> {
+        match self.mode {
+            RuleMode::Boolean => {
+                let matched = self.run(0, tx, ctx);
+                CompiledOutcome {
+                    matched,
+                    score: None,
+                    actions: if matched { &self.actions } else { &[] },
+                }
+            }
+            RuleMode::Scoring => {
+                let score = self.score(tx, ctx);
+                let threshold = self
+                    .thresholds
+                    .iter()
+                    .filter(|threshold| score >= threshold.min_score)
+                    .max_by(|a, b| a.min_score.total_cmp(&b.min_score));
+                CompiledOutcome {
+                    matched: threshold.is_some(),
+                    score: Some(score),
+                    actions: threshold
+                        .map(|threshold| threshold.actions.as_slice())
+                        .unwrap_or_default(),
+                }
+            }
+        }
+    }
+
+    /// The actions the rule takes for a transaction; empty when it does not match
+    pub fn actions(&self, tx: &Transaction, ctx: &EvalContext) -> &[Action] {
+        self.outcome(tx, ctx).actions
+    }
+
+    /// Whether the rule matches: its root condition, or for scoring rules a threshold
+    pub fn matches(&self, tx: &Transaction, ctx: &EvalContext) -> bool {
+        self.outcome(tx, ctx).matched
+    }
+}
+
+/// What a compiled rule decides for a transaction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompiledOutcome<'a> {
+    pub matched: bool,
+    /// The score, for scoring rules
+    pub score: Option<f64>,
+    /// The actions of the rule, or of the highest threshold reached; empty when it
+    /// does not match
+    pub actions: &'a [Action],
+}
@@ -0,0 +1,815 @@
+use crate::expression::{ArithOp, Expr};
+use crate::models::{
+    parse_datetime, parse_hour, parse_ip_range, parse_weekday, Action, AggregateFunction,
+    ConditionNode, DataType, Decision, Field, LogicalOperator, MatchStrategy, Operand, Operator,
+    Parameter, ReferenceList, Rule, RuleMode, RuleSet, Snippet, TestCase, TimeWindow, Transaction,
+};
+use crate::velocity::{group_key, VelocityStore};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use chrono_tz::Tz;
+use ipnet::IpNet;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Shared data that conditions can reference by name, resolved at evaluation time
+#[derive(Debug, Clone, Default)]
+pub struct EvalContext {
+    pub lists: BTreeMap<String, ReferenceList>,
+    pub parameters: BTreeMap<String, Parameter>,
+    /// Snippets that `SnippetRef` nodes resolve to; the snippet store keeps them acyclic,
+    /// and validation reports cycles in snippets read from elsewhere
+    pub snippets: BTreeMap<String, Snippet>,
+    /// Transaction history for aggregate operands
+    pub velocity: Arc<VelocityStore>,
+    /// Evaluation time in Unix seconds; aggregate windows and Within Last end here
+    pub now: u64,
+    /// Timezone of the rule being evaluated, for local date/times, hours and weekdays
+    pub timezone: Tz,
+}
+
+/// A resolved operand value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    Number(f64),
+    Text(String),
+    /// Inclusive numeric bounds
+    Range(f64, f64),
+    List(Vec<Value>),
+    Ip(IpAddr),
+    Network(IpNet),
+    DateTime(DateTime<Utc>),
+    Duration(TimeWindow),
+}
+
+impl Value {
+    /// Interpret a literal typed by the user: numbers when they parse, text otherwise
+    fn from_literal(literal: &str) -> Self {
+        match literal.trim().parse::<f64>() {
+            Ok(n) => Value::Number(n),
+            Err(_) => Value::Text(literal.to_string()),
+        }
+    }
+
+    fn from_json(value: &serde_json::Value) -> Option<Self> {
+        match value {
+            serde_json::Value::Number(n) => n.as_f64().map(Value::Number),
+            serde_json::Value::String(s) => Some(Value::Text(s.clone())),
+            serde_json::Value::Bool(b) => Some(Value::Text(b.to_string())),
+            _ => None,
+        }
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// The value as an IP address, parsing text that holds one
+    fn as_ip(&self) -> Option<IpAddr> {
+        match self {
+            Value::Ip(ip) => Some(*ip),
+            Value::Text(s) => s.trim().parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// The value as a point in time: date/time text is read in `timezone`,
+    /// numbers are Unix seconds
+    fn as_datetime(&self, timezone: Tz) -> Option<DateTime<Utc>> {
+        match self {
+            Value::DateTime(datetime) => Some(*datetime),
+            Value::Text(s) => parse_datetime(s, timezone),
+            Value::Number(n) if n.is_finite() => DateTime::from_timestamp(*n as i64, 0),
+            _ => None,
+        }
+    }
+
+    /// Interpret a transaction value as the given field type
+    fn typed(self, data_type: DataType, timezone: Tz) -> Self {
+        match (data_type, &self) {
+            (DataType::Ip, Value::Text(s)) => s.trim().parse().map(Value::Ip).unwrap_or(self),
+            (DataType::DateTime, _) => self
+                .as_datetime(timezone)
+                .map(Value::DateTime)
+                .unwrap_or(self),
+            _ => self,
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Text(s) => write!(f, "{}", s),
+            Value::Range(min, max) => write!(f, "{}..{}", min, max),
+            Value::Ip(ip) => write!(f, "{}", ip),
+            Value::Network(net) => write!(f, "{}", net),
+            Value::DateTime(datetime) => write!(f, "{}", datetime.to_rfc3339()),
+            Value::Duration(window) => write!(f, "{}", window.display()),
+            Value::List(values) => write!(
+                f,
+                "[{}]",
+                values
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+/// Resolve an operand against a transaction. Missing fields and unknown lists resolve to `None`.
+pub fn resolve_operand(operand: &Operand, tx: &Transaction, ctx: &EvalContext) -> Option<Value> {
+    match operand {
+        Operand::Field { field } => tx
+            .get(field)
+            .and_then(Value::from_json)
+            .map(|value| value.typed(field.data_type(), ctx.timezone)),
+        Operand::Value { value } => Some(Value::from_literal(value)),
+        Operand::Range { min, max } => {
+            match (min.trim().parse::<f64>(), max.trim().parse::<f64>()) {
+                (Ok(min), Ok(max)) => Some(Value::Range(min, max)),
+                _ => None,
+            }
+        }
+        Operand::List {
+            element_type,
+            values,
+        } => Some(typed_list(*element_type, values)),
+        Operand::ListRef { name } => ctx
+            .lists
+            .get(name)
+            .map(|list| typed_list(list.element_type, &list.entries)),
+        Operand::Aggregate {
+            function,
+            field,
+            group_by,
+            window,
+        } => aggregate(*function, field.as_ref(), group_by, window, tx, ctx),
+        Operand::Expression { expr } => evaluate_expr(expr, tx).map(Value::Number),
+        Operand::Duration { window } => Some(Value::Duration(*window)),
+        Operand::Parameter { name } => ctx.parameters.get(name).map(|parameter| {
+            Value::from_literal(&parameter.value).typed(parameter.data_type, ctx.timezone)
+        }),
+    }
+}
+
+/// Evaluate an arithmetic expression against a transaction.
+///
+/// `None` when a field is missing or not a number, and when the result is undefined:
+/// dividing by zero makes the expression, and so its condition, fail rather than
+/// produce infinity.
+pub fn evaluate_expr(expr: &Expr, tx: &Transaction) -> Option<f64> {
+    let result = match expr {
+        Expr::Number { value } => *value,
+        Expr::Field { field } => tx.get(field).and_then(Value::from_json)?.as_number()?,
+        Expr::Negate { expr } => -evaluate_expr(expr, tx)?,
+        Expr::Binary { op, left, right } => {
+            let (left, right) = (evaluate_expr(left, tx)?, evaluate_expr(right, tx)?);
+            match op {
+                ArithOp::Add => left + right,
+                ArithOp::Subtract => left - right,
+                ArithOp::Multiply => left * right,
+                ArithOp::Divide if right == 0.0 => return None,
+                ArithOp::Divide => left / right,
+            }
+        }
+    };
+    result.is_finite().then_some(result)
+}
+
+/// Aggregate a field over the transaction's group history within the window,
+/// counting the transaction being evaluated as part of its own group.
+/// `None` when the transaction has no value for the group-by field.
+fn aggregate(
+    function: AggregateFunction,
+    field: Option<&Field>,
+    group_by: &Field,
+    window: &TimeWindow,
+    tx: &Transaction,
+    ctx: &EvalContext,
+) -> Option<Value> {
+    let key = group_key(tx, group_by)?;
+    let history = ctx.velocity.history(group_by, &key, window, ctx.now);
+    let transactions = history.iter().map(|tx| tx.as_ref()).chain([tx]);
+
+    if function == AggregateFunction::Count {
+        return Some(Value::Number(transactions.count() as f64));
+    }
+    let field = field?;
+    let values: Vec<Value> = transactions
+        .filter_map(|tx| tx.get(field).and_then(Value::from_json))
+        .collect();
+    let numbers = || values.iter().filter_map(Value::as_number);
+
+    let result = match function {
+        AggregateFunction::Count => values.len() as f64,
+        AggregateFunction::Sum => numbers().sum(),
+        AggregateFunction::Avg => {
+            let count = numbers().count();
+            if count == 0 {
+                return None;
+            }
+            numbers().sum::<f64>() / count as f64
+        }
+        AggregateFunction::Min => numbers().reduce(f64::min)?,
+        AggregateFunction::Max => numbers().reduce(f64::max)?,
+        AggregateFunction::DistinctCount => {
+            let mut distinct: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+            distinct.sort();
+            distinct.dedup();
+            distinct.len() as f64
+        }
+    };
+    Some(Value::Number(result))
+}
+
+fn typed_list(element_type: DataType, values: &[String]) -> Value {
+    Value::List(
+        values
+            .iter()
+            .filter_map(|value| match element_type {
+                // Elements that are not numbers are reported by validation
+                DataType::Number => value.trim().parse().ok().map(Value::Number),
+                DataType::String | DataType::DateTime => Some(Value::Text(value.clone())),
+                DataType::Ip => match value.trim().parse::<IpAddr>() {
+                    Ok(ip) => Some(Value::Ip(ip)),
+                    Err(_) => parse_ip_range(value).map(Value::Network),
+                },
+            })
+            .collect(),
+    )
+}
+
+/// Evaluate a leaf from its resolved operands.
+///
+/// Binary operators are false when either side is missing; unary operators ignore the right side.
+pub fn evaluate_leaf(
+    left: Option<&Value>,
+    operator: &Operator,
+    right: Option<&Value>,
+    ctx: &EvalContext,
+) -> bool {
+    if operator.is_unary() {
+        return match operator {
+            Operator::IsEmpty => left.is_none_or(|l| l.to_string().trim().is_empty()),
+            _ => false,
+        };
+    }
+
+    match (left, right) {
+        (Some(l), Some(r)) => compare(operator, l, r, ctx),
+        _ => false,
+    }
+}
+
+/// Apply a comparison operator to two resolved values.
+/// Date/time operators read local times, hours and weekdays in `ctx.timezone`.
+pub fn compare(operator: &Operator, left: &Value, right: &Value, ctx: &EvalContext) -> bool {
+    match operator {
+        Operator::Equals => values_equal(left, right),
+        Operator::NotEquals => !values_equal(left, right),
+        Operator::GreaterThan => numeric(left, right, |l, r| l > r),
+        Operator::LessThan => numeric(left, right, |l, r| l < r),
+        Operator::GreaterThanOrEqual => numeric(left, right, |l, r| l >= r),
+        Operator::LessThanOrEqual => numeric(left, right, |l, r| l <= r),
+        Operator::Between => match (left.as_number(), right) {
+            (Some(l), Value::Range(min, max)) => *min <= l && l <= *max,
+            _ => false,
+        },
+        Operator::Contains => left.to_string().contains(&right.to_string()),
+        Operator::StartsWith => left.to_string().starts_with(&right.to_string()),
+        Operator::EndsWith => left.to_string().ends_with(&right.to_string()),
+        // An invalid pattern never matches; validation reports it
+        Operator::Regex => regex::Regex::new(&right.to_string())
+            .map(|re| re.is_match(&left.to_string()))
+            .unwrap_or(false),
+        Operator::In => in_list(left, right),
+        Operator::NotIn => !in_list(left, right),
+        Operator::InCidr => in_cidr(left, right),
+        Operator::NotInCidr => left.as_ip().is_some() && !in_cidr(left, right),
+        Operator::IsEmpty => evaluate_leaf(Some(left), operator, None, ctx),
+        Operator::WithinLast => match (left.as_datetime(ctx.timezone), right) {
+            (Some(at), Value::Duration(window)) => {
+                let now = ctx.now as i64;
+                let since = now.saturating_sub(window.seconds() as i64);
+                (since..=now).contains(&at.timestamp())
+            }
+            _ => false,
+        },
+        Operator::Before => datetimes(left, right, ctx.timezone, |l, r| l < r),
+        Operator::After => datetimes(left, right, ctx.timezone, |l, r| l > r),
+        Operator::HourOfDayIn => match (left.as_datetime(ctx.timezone), right) {
+            (Some(at), Value::List(hours)) => {
+                let hour = at.with_timezone(&ctx.timezone).hour();
+                hours
+                    .iter()
+                    .any(|h| parse_hour(&h.to_string()) == Some(hour))
+            }
+            _ => false,
+        },
+        Operator::DayOfWeekIn => match (left.as_datetime(ctx.timezone), right) {
+            (Some(at), Value::List(days)) => {
+                let weekday = at.with_timezone(&ctx.timezone).weekday();
+                days.iter()
+                    .any(|day| parse_weekday(&day.to_string()) == Some(weekday))
+            }
+            _ => false,
+        },
+    }
+}
+
+fn datetimes(
+    left: &Value,
+    right: &Value,
+    timezone: Tz,
+    cmp: impl Fn(DateTime<Utc>, DateTime<Utc>) -> bool,
+) -> bool {
+    match (left.as_datetime(timezone), right.as_datetime(timezone)) {
+        (Some(l), Some(r)) => cmp(l, r),
+        _ => false,
+    }
+}
+
+/// Set membership of `left` in a list on the right
+fn in_list(left: &Value, right: &Value) -> bool {
+    match right {
+        Value::List(values) => values.iter().any(|value| values_equal(left, value)),
+        _ => false,
+    }
+}
+
+/// Whether `left` is an IP address inside any range of the list on the right.
+/// Bare addresses in the list match only themselves.
+fn in_cidr(left: &Value, right: &Value) -> bool {
+    let (Some(ip), Value::List(ranges)) = (left.as_ip(), right) else {
+        return false;
+    };
+    ranges.iter().any(|range| match range {
+        Value::Network(net) => net.contains(&ip),
+        Value::Ip(other) => *other == ip,
+        _ => false,
+    })
+}
+
+fn values_equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Ip(_), _) | (_, Value::Ip(_)) => {
+            left.as_ip().is_some() && left.as_ip() == right.as_ip()
+        }
+        _ => match (left.as_number(), right.as_number()) {
+            (Some(l), Some(r)) => l == r,
+            _ => left.to_string() == right.to_string(),
+        },
+    }
+}
+
+fn numeric(left: &Value, right: &Value, cmp: impl Fn(f64, f64) -> bool) -> bool {
+    match (left.as_number(), right.as_number()) {
+        (Some(l), Some(r)) => cmp(l, r),
+        _ => false,
+    }
+}
+
+/// Evaluate a condition node against a transaction.
+///
+/// A leaf whose operands cannot be resolved (e.g. a missing field) is false.
+/// An empty AND group is true, an empty OR group is false.
+/// A snippet reference evaluates the snippet's conditions, and is false when the snippet is unknown.
+pub fn evaluate(node: &ConditionNode, tx: &Transaction, ctx: &EvalContext) -> bool {
+    match node {
+        ConditionNode::Leaf {
+            left,
+            operator,
+            right,
+            ..
+        } => evaluate_leaf(
+            resolve_operand(left, tx, ctx).as_ref(),
+            operator,
+            resolve_operand(right, tx, ctx).as_ref(),
+            ctx,
+        ),
+        ConditionNode::Group {
+            operator, children, ..
+        } => match operator {
+            LogicalOperator::And => children.iter().all(|child| evaluate(child, tx, ctx)),
+            LogicalOperator::Or => children.iter().any(|child| evaluate(child, tx, ctx)),
+        },
+        ConditionNode::SnippetRef { name, .. } => ctx
+            .snippets
+            .get(name)
+            .is_some_and(|snippet| evaluate(&snippet.root, tx, ctx)),
+    }
+}
+
+/// Evaluation trace of a node, mirroring the condition tree
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceNode {
+    pub id: Uuid,
+    /// `None` when the node was short-circuited and never evaluated
+    pub outcome: Option<bool>,
+    #[serde(flatten)]
+    pub detail: TraceDetail,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum TraceDetail {
+    Leaf {
+        left: Operand,
+        left_value: Option<Value>,
+        operator: Operator,
+        right: Operand,
+        right_value: Option<Value>,
+    },
+    Group {
+        operator: LogicalOperator,
+        children: Vec<TraceNode>,
+    },
+    Snippet {
+        name: String,
+        /// Trace of the snippet's conditions; `None` when the snippet is unknown or skipped
+        root: Option<Box<TraceNode>>,
+    },
+}
+
+impl TraceNode {
+    pub fn is_skipped(&self) -> bool {
+        self.outcome.is_none()
+    }
+}
+
+/// Evaluate a node like [`evaluate`], recording resolved values and outcomes for every node.
+///
+/// Groups short-circuit in child order: once an AND child is false or an OR child is true,
+/// the remaining siblings are reported as skipped.
+pub fn trace(node: &ConditionNode, tx: &Transaction, ctx: &EvalContext) -> TraceNode {
+    match node {
+        ConditionNode::Leaf {
+            id,
+            left,
+            operator,
+            right,
+            ..
+        } => {
+            let left_value = resolve_operand(left, tx, ctx);
+            let right_value = if operator.is_unary() {
+                None
+            } else {
+                resolve_operand(right, tx, ctx)
+            };
+            let outcome = evaluate_leaf(left_value.as_ref(), operator, right_value.as_ref(), ctx);
+            TraceNode {
+                id: *id,
+                outcome: Some(outcome),
+                detail: TraceDetail::Leaf {
+                    left: left.clone(),
+                    left_value,
+                    operator: operator.clone(),
+                    right: right.clone(),
+                    right_value,
+                },
+            }
+        }
+        ConditionNode::Group {
+            id,
+            operator,
+            children,
+            ..
+        } => {
+            // The result of an empty group, and the child result that decides early
+            let (mut outcome, decisive) = match operator {
+                LogicalOperator::And => (true, false),
+                LogicalOperator::Or => (false, true),
+            };
+            let mut decided = false;
+            let mut traced = Vec::with_capacity(children.len());
+
+            for child in children {
+                if decided {
+                    traced.push(skipped(child));
+                    continue;
+                }
+                let child_trace = trace(child, tx, ctx);
+                if child_trace.outcome == Some(decisive) {
+                    outcome = decisive;
+                    decided = true;
+                }
+                traced.push(child_trace);
+            }
+
+            TraceNode {
+                id: *id,
+                outcome: Some(outcome),
+                detail: TraceDetail::Group {
+                    operator: operator.clone(),
+                    children: traced,
+                },
+            }
+        }
+        ConditionNode::SnippetRef { id, name, .. } => {
+            let root = ctx
+                .snippets
+                .get(name)
+                .map(|snippet| Box::new(trace(&snippet.root, tx, ctx)));
+            TraceNode {
+                id: *id,
+                outcome: Some(root.as_ref().is_some_and(|root| root.outcome == Some(true))),
+                detail: TraceDetail::Snippet {
+                    name: name.clone(),
+                    root,
+                },
+            }
+        }
+    }
+}
+
+/// Trace of a node that was never evaluated
+fn skipped(node: &ConditionNode) -> TraceNode {
+    match node {
+        ConditionNode::Leaf {
+            id,
+            left,
+            operator,
+            right,
+            ..
+        } => TraceNode {
+            id: *id,
+            outcome: None,
+            detail: TraceDetail::Leaf {
+                left: left.clone(),
+                left_value: None,
+                operator: operator.clone(),
+                right: right.clone(),
+                right_value: None,
+            },
+        },
+        ConditionNode::Group {
+            id,
+            operator,
+            children,
+            ..
+        } => TraceNode {
+            id: *id,
+            outcome: None,
+            detail: TraceDetail::Group {
+                operator: operator.clone(),
+                children: children.iter().map(skipped).collect(),
+            },
+        },
+        ConditionNode::SnippetRef { id, name, .. } => TraceNode {
+            id: *id,
+            outcome: None,
+            detail: TraceDetail::Snippet {
+                name: name.clone(),
+                root: None,
+            },
+        },
+    }
+}
+
+/// A weighted node that matched and added to the score
+#[derive(Debug, Clone, Serialize)]
+pub struct ScoreContribution {
+    pub id: Uuid,
+    pub condition: String,
+    pub weight: f64,
+}
+
+/// The score of a transaction and the nodes it came from
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScoreBreakdown {
+    pub score: f64,
+    pub contributions: Vec<ScoreContribution>,
+}
+
+/// Sum the weights of every matched node in the tree.
+///
+/// Every node is evaluated: a weighted child counts even when its group as a whole
+/// does not match.
+pub fn score(node: &ConditionNode, tx: &Transaction, ctx: &EvalContext) -> ScoreBreakdown {
+    let mut breakdown = ScoreBreakdown::default();
+    score_node(node, tx, ctx, &mut breakdown);
+    breakdown
+}
+
+fn score_node(
+    node: &ConditionNode,
+    tx: &Transaction,
+    ctx: &EvalContext,
+    breakdown: &mut ScoreBreakdown,
+) -> bool {
+    let matched = match node {
+        ConditionNode::Leaf { .. } | ConditionNode::SnippetRef { .. } => evaluate(node, tx, ctx),
+        ConditionNode::Group {
+            operator, children, ..
+        } => {
+            let results: Vec<bool> = children
+                .iter()
+                .map(|child| score_node(child, tx, ctx, breakdown))
+                .collect();
+            match operator {
+                LogicalOperator::And => results.iter().all(|r| *r),
+                LogicalOperator::Or => results.iter().any(|r| *r),
+            }
+        }
+    };
+
+    if let (true, Some(weight)) = (matched, node.weight()) {
+        breakdown.score += weight;
+        breakdown.contributions.push(ScoreContribution {
+            id: node.id(),
+            condition: node.display(),
+            weight,
+        });
+    }
+    matched
+}
+
+/// The decision a rule makes for a transaction
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleOutcome {
+    pub matched: bool,
+    /// Score breakdown, for scoring rules
+    pub score: Option<ScoreBreakdown>,
+    pub actions: Vec<Action>,
+}
+
+/// Evaluate a whole rule: its condition tree, and for scoring rules the score thresholds
+pub fn evaluate_rule(rule: &Rule, tx: &Transaction, ctx: &EvalContext) -> RuleOutcome {
+    match rule.mode {
+        RuleMode::Boolean => {
+            let matched = evaluate(&rule.root, tx, ctx);
+            RuleOutcome {
+                matched,
+                score: None,
+                actions: if matched {
+                    rule.actions.clone()
+                } else {
+                    Vec::new()
+                },
+            }
+        }
+        RuleMode::Scoring => {
+            let breakdown = score(&rule.root, tx, ctx);
+            let threshold = rule
+                .thresholds
+                .iter()
+                .filter(|threshold| breakdown.score >= threshold.min_score)
+                .max_by(|a, b| a.min_score.total_cmp(&b.min_score));
+            RuleOutcome {
+                matched: threshold.is_some(),
+                actions: threshold
+                    .map(|threshold| threshold.actions.clone())
+                    .unwrap_or_default(),
+                score: Some(breakdown),
+            }
+        }
+    }
+}
+
+/// How one rule of a rule set fared against a transaction
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleContribution {
+    pub rule_id: Uuid,
+    pub rule_name: String,
+    /// 1-based position in the rule set
+    pub priority: usize,
+    /// The scoring rule's score, or the risk score a matched boolean rule adds
+    pub score: f64,
+    pub outcome: RuleOutcome,
+    /// Whether the match strategy applied this rule's actions
+    pub contributed: bool,
+}
+
+/// The decision a rule set makes for a transaction
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleSetOutcome {
+    pub decision: Decision,
+    /// True when no applied action implied a decision, so the default was used
+    pub defaulted: bool,
+    /// Actions of the contributing rules, in priority order and without duplicates
+    pub actions: Vec<Action>,
+    /// Every rule that was evaluated, in priority order
+    pub rules: Vec<RuleContribution>,
+}
+
+fn rule_score(outcome: &RuleOutcome) -> f64 {
+    match &outcome.score {
+        Some(breakdown) => breakdown.score,
+        None => outcome
+            .actions
+            .iter()
+            .fold(0.0, |total, action| match action {
+                Action::AddRiskScore { score } => total + *score as f64,
+                _ => total,
+            }),
+    }
+}
+
+/// Evaluate the enabled rules of a rule set in priority order and combine their
+/// outcomes with the set's match strategy. Each rule is evaluated in its own
+/// timezone; entries whose rule no longer exists are skipped.
+pub fn evaluate_rule_set(
+    rule_set: &RuleSet,
+    rules: &[Rule],
+    tx: &Transaction,
+    ctx: &EvalContext,
+) -> RuleSetOutcome {
+    let mut results: Vec<RuleContribution> = Vec::new();
+    for (index, entry) in rule_set.entries.iter().enumerate() {
+        let Some(rule) = rules.iter().find(|rule| rule.id == entry.rule_id) else {
+            continue;
+        };
+        if !entry.enabled {
+            continue;
+        }
+        let rule_ctx = EvalContext {
+            timezone: rule.tz(),
+            ..ctx.clone()
+        };
+        let outcome = evaluate_rule(rule, tx, &rule_ctx);
+        let matched = outcome.matched;
+        results.push(RuleContribution {
+            rule_id: rule.id,
+            rule_name: rule.name.clone(),
+            priority: index + 1,
+            score: rule_score(&outcome),
+            outcome,
+            contributed: false,
+        });
+        if matched && rule_set.strategy == MatchStrategy::FirstMatch {
+            break;
+        }
+    }
+
+    match rule_set.strategy {
+        MatchStrategy::FirstMatch | MatchStrategy::AllMatches => {
+            for result in results.iter_mut() {
+                result.contributed = result.outcome.matched;
+            }
+        }
+        MatchStrategy::HighestScore => {
+            let mut best: Option<usize> = None;
+            for (index, result) in results.iter().enumerate() {
+                if !result.outcome.matched {
+                    continue;
+                }
+                if best.is_none_or(|best| result.score > results[best].score) {
+                    best = Some(index);
+                }
+            }
+            if let Some(best) = best {
+                results[best].contributed = true;
+            }
+        }
+    }
+
+    let mut actions: Vec<Action> = Vec::new();
+    for result in results.iter().filter(|result| result.contributed) {
+        for action in &result.outcome.actions {
+            if !actions.contains(action) {
+                actions.push(action.clone());
+            }
+        }
+    }
+    let decision = actions.iter().filter_map(Decision::from_action).max();
+    RuleSetOutcome {
+        decision: decision.unwrap_or(rule_set.default_decision),
+        defaulted: decision.is_none(),
+        actions,
+        rules: results,
+    }
+}
+
+/// Outcome of running a single rule test case
+#[derive(Debug, Clone)]
+pub struct TestCaseResult {
+    pub test_case: TestCase,
+    pub flagged: bool,
+}
+
+impl TestCaseResult {
+    pub fn passed(&self) -> bool {
+        self.flagged == self.test_case.expect_flagged
+    }
+}
+
+/// Evaluate every test case attached to a rule
+pub fn run_test_cases(rule: &Rule, ctx: &EvalContext) -> Vec<TestCaseResult> {
+    rule.test_cases
+        .iter()
+        .map(|test_case| TestCaseResult {
+            flagged: evaluate_rule(rule, &test_case.transaction, ctx).matched,
+            test_case: test_case.clone(),
+        })
+        .collect()
+}
@@ -0,0 +1,326 @@
+use crate::models::{DataType, Field};
+use serde::{Deserialize, Serialize};
+
+/// Arithmetic operators usable in expressions
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ArithOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl ArithOp {
+    pub fn symbol(&self) -> &str {
+        match self {
+            ArithOp::Add => "+",
+            ArithOp::Subtract => "-",
+            ArithOp::Multiply => "*",
+            ArithOp::Divide => "/",
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            ArithOp::Add | ArithOp::Subtract => 1,
+            ArithOp::Multiply | ArithOp::Divide => 2,
+        }
+    }
+}
+
+/// A numeric expression over transaction fields, e.g. `3 * average_amount_30d`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Expr {
+    Number {
+        value: f64,
+    },
+    Field {
+        field: Field,
+    },
+    Negate {
+        expr: Box<Expr>,
+    },
+    Binary {
+        op: ArithOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+}
+
+impl Expr {
+    /// Most parentheses and unary minuses an expression may nest
+    pub const MAX_NESTING: usize = 32;
+    /// Most tokens an expression may have, which bounds the depth of operator chains
+    pub const MAX_TOKENS: usize = 256;
+
+    /// Parse an expression such as `user_age - account_age / 365`.
+    ///
+    /// Supports `+ - * /`, unary minus, parentheses, numbers and field names;
+    /// `*` and `/` bind tighter than `+` and `-`, and operators of equal
+    /// precedence associate to the left. Expressions are limited in size and
+    /// nesting so evaluating them cannot exhaust the stack.
+    pub fn parse(source: &str) -> Result<Expr, String> {
+        let tokens = tokenize(source)?;
+        if tokens.is_empty() {
+            return Err("Expression is empty".to_string());
+        }
+        if tokens.len() > Self::MAX_TOKENS {
+            return Err(format!(
+                "Expression is too long; it may have at most {} numbers, fields and operators",
+                Self::MAX_TOKENS
+            ));
+        }
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            nesting: 0,
+        };
+        let expr = parser.expr()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(format!("Unexpected {}", token.describe())),
+        }
+    }
+
+    /// Human-readable form using field display names
+    pub fn display(&self) -> String {
+        self.render(&|field| field.display_name().to_string())
+    }
+
+    /// Source form using field names, which parses back to the same expression
+    pub fn source(&self) -> String {
+        self.render(&|field| field.as_str().to_string())
+    }
+
+    fn render(&self, field_name: &dyn Fn(&Field) -> String) -> String {
+        match self {
+            Expr::Number { value } => value.to_string(),
+            Expr::Field { field } => field_name(field),
+            Expr::Negate { expr } => match expr.as_ref() {
+                Expr::Binary { .. } => format!("-({})", expr.render(field_name)),
+                _ => format!("-{}", expr.render(field_name)),
+            },
+            Expr::Binary { op, left, right } => {
+                let left_str = left.render(field_name);
+                let right_str = right.render(field_name);
+                // Parenthesize looser children, and equal-precedence right children
+                // since `a - (b - c)` differs from `a - b - c`
+                let left_str = match left.as_ref() {
+                    Expr::Binary { op: child, .. } if child.precedence() < op.precedence() => {
+                        format!("({})", left_str)
+                    }
+                    _ => left_str,
+                };
+                let right_str = match right.as_ref() {
+                    Expr::Binary { op: child, .. } if child.precedence() <= op.precedence() => {
+                        format!("({})", right_str)
+                    }
+                    _ => right_str,
+                };
+                format!("{} {} {}", left_str, op.symbol(), right_str)
+            }
+        }
+    }
+
+    /// Type-check the expression: every field must be numeric and literal
+    /// divisors must not be zero
+    pub fn check(&self, errors: &mut Vec<String>) {
+        match self {
+            Expr::Number { value } if !value.is_finite() => {
+                errors.push("Expression numbers must be finite".to_string());
+            }
+            Expr::Number { .. } => {}
+            Expr::Field { field } if field.data_type() != DataType::Number => {
+                errors.push(format!(
+                    "Expression uses {}, which is not numeric",
+                    field.display_name()
+                ));
+            }
+            Expr::Field { .. } => {}
+            Expr::Negate { expr } => expr.check(errors),
+            Expr::Binary { op, left, right } => {
+                left.check(errors);
+                right.check(errors);
+                if *op == ArithOp::Divide
+                    && matches!(right.as_ref(), Expr::Number { value } if *value == 0.0)
+                {
+                    errors.push(format!("Expression divides by zero: {}", self.display()));
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(ArithOp),
+    LParen,
+    RParen,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Number(n) => format!("number {}", n),
+            Token::Ident(name) => format!("\"{}\"", name),
+            Token::Op(op) => format!("\"{}\"", op.symbol()),
+            Token::LParen => "\"(\"".to_string(),
+            Token::RParen => "\")\"".to_string(),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '+' | '-' | '*' | '/' => {
+                chars.next();
+                tokens.push(Token::Op(match c {
+                    '+' => ArithOp::Add,
+                    '-' => ArithOp::Subtract,
+                    '*' => ArithOp::Multiply,
+                    _ => ArithOp::Divide,
+                }));
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut end = start;
+                while let Some(&(i, c)) = chars.peek() {
+                    if !(c.is_ascii_digit() || c == '.') {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                let literal = &source[start..end];
+                let value = literal
+                    .parse()
+                    .map_err(|_| format!("Invalid number \"{}\"", literal))?;
+                tokens.push(Token::Number(value));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut end = start;
+                while let Some(&(i, c)) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                tokens.push(Token::Ident(source[start..end].to_string()));
+            }
+            other => return Err(format!("Unexpected character \"{}\"", other)),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Parentheses and unary minuses around the current position
+    nesting: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// expr := term (("+" | "-") term)*
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut left = self.term()?;
+        while let Some(Token::Op(op @ (ArithOp::Add | ArithOp::Subtract))) = self.peek() {
+            let op = *op;
+            self.pos += 1;
+            let right = self.term()?;
+            left = Expr::Binary {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+        }
+        Ok(left)
+    }
+
+    /// term := factor (("*" | "/") factor)*
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut left = self.factor()?;
+        while let Some(Token::Op(op @ (ArithOp::Multiply | ArithOp::Divide))) = self.peek() {
+            let op = *op;
+            self.pos += 1;
+            let right = self.factor()?;
+            left = Expr::Binary {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+        }
+        Ok(left)
+    }
+
+    /// Parse a nested part of the expression, unless it nests too deeply
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, String>,
+    ) -> Result<T, String> {
+        if self.nesting == Expr::MAX_NESTING {
+            return Err(format!(
+                "Expression nests more than {} levels deep",
+                Expr::MAX_NESTING
+            ));
+        }
+        self.nesting += 1;
+        let result = parse(self);
+        self.nesting -= 1;
+        result
+    }
+
+    /// factor := "-" factor | number | field | "(" expr ")"
+    fn factor(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Op(ArithOp::Subtract)) => Ok(Expr::Negate {
+                expr: Box::new(self.nested(Self::factor)?),
+            }),
+            Some(Token::Number(value)) => Ok(Expr::Number { value }),
+            Some(Token::Ident(name)) => Field::all()
+                .into_iter()
+                .find(|field| field.as_str() == name)
+                .map(|field| Expr::Field { field })
+                .ok_or_else(|| format!("Unknown field \"{}\"", name)),
+            Some(Token::LParen) => {
+                let expr = self.nested(Self::expr)?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    Some(token) => Err(format!("Expected \")\" but found {}", token.describe())),
+                    None => Err("Missing closing \")\"".to_string()),
+                }
+            }
+            Some(token) => Err(format!("Unexpected {}", token.describe())),
+            None => Err("Expression ends unexpectedly".to_string()),
+        }
+    }
+}
@@ -0,0 +1,477 @@
+//! Convert condition trees to and from [JSONLogic](https://jsonlogic.com) expressions.
+//!
+//! Supported: `and`, `or`, `==`, `!=`, `>`, `>=`, `<`, `<=` (three arguments for
+//! Between), `in` over an array (In) or a string (Contains), `!` around `in` (Not In),
+//! `var`, and `+ - * /` for expressions. Anything else is reported rather than dropped.
+
+use crate::evaluator::EvalContext;
+use crate::expression::{ArithOp, Expr};
+use crate::models::{ConditionNode, DataType, Field, LogicalOperator, Operand, Operator};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+/// Export a condition tree as JSONLogic, inlining snippets, reference lists and
+/// parameters from `ctx`. Fails listing the conditions JSONLogic cannot express.
+///
+/// ```
+/// use rule_engine::jsonlogic::to_jsonlogic;
+/// use rule_engine::models::{ConditionNode, Field, Operand, Operator};
+/// use rule_engine::EvalContext;
+///
+/// let node = ConditionNode::Leaf {
+///     id: uuid::Uuid::new_v4(),
+///     left: Operand::Field { field: Field::TransactionAmount },
+///     operator: Operator::GreaterThan,
+///     right: Operand::Value { value: "1000".to_string() },
+///     weight: None,
+/// };
+/// let logic = to_jsonlogic(&node, &EvalContext::default()).unwrap();
+/// assert_eq!(logic, serde_json::json!({">": [{"var": "transaction_amount"}, 1000]}));
+/// ```
+pub fn to_jsonlogic(node: &ConditionNode, ctx: &EvalContext) -> Result<Value, Vec<String>> {
+    let mut errors = Vec::new();
+    let logic = export_node(node, ctx, &mut errors);
+    if errors.is_empty() {
+        Ok(logic)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Import a JSONLogic expression as a condition tree with fresh ids. Fails listing
+/// the operations that have no condition equivalent.
+///
+/// ```
+/// use rule_engine::jsonlogic::from_jsonlogic;
+/// use rule_engine::models::ConditionNode;
+///
+/// let logic = serde_json::json!({"or": [
+///     {"in": [{"var": "user_country"}, ["NG", "RU"]]},
+///     {"!": {"in": [{"var": "transaction_currency"}, ["USD", "EUR"]]}}
+/// ]});
+/// let node = from_jsonlogic(&logic).unwrap();
+/// assert_eq!(
+///     node.display(),
+///     "(User Country In [NG, RU] OR Transaction Currency Not In [USD, EUR])"
+/// );
+///
+/// let errors = from_jsonlogic(&serde_json::json!({"missing": ["user_id"]})).unwrap_err();
+/// assert_eq!(errors, vec![r#"Unsupported operation "missing" in {"missing":["user_id"]}"#]);
+/// ```
+pub fn from_jsonlogic(logic: &Value) -> Result<ConditionNode, Vec<String>> {
+    let mut errors = Vec::new();
+    match import_node(logic, &mut errors) {
+        Some(node) if errors.is_empty() => Ok(node),
+        _ => Err(errors),
+    }
+}
+
+// ============================================================================
+// Export
+// ============================================================================
+
+fn export_node(node: &ConditionNode, ctx: &EvalContext, errors: &mut Vec<String>) -> Value {
+    if node.weight().is_some() {
+        errors.push(format!(
+            "{}: JSONLogic has no condition weights",
+            node.display()
+        ));
+    }
+    match node {
+        ConditionNode::Leaf {
+            left,
+            operator,
+            right,
+            ..
+        } => export_leaf(left, operator, right, ctx).unwrap_or_else(|reason| {
+            errors.push(format!("{}: {}", node.display(), reason));
+            Value::Null
+        }),
+        ConditionNode::Group {
+            operator, children, ..
+        } => {
+            let key = match operator {
+                LogicalOperator::And => "and",
+                LogicalOperator::Or => "or",
+            };
+            let children: Vec<Value> = children
+                .iter()
+                .map(|child| export_node(child, ctx, errors))
+                .collect();
+            json!({ key: children })
+        }
+        ConditionNode::SnippetRef { name, .. } => match ctx.snippets.get(name) {
+            Some(snippet) => export_node(&snippet.root, ctx, errors),
+            None => {
+                errors.push(format!("Snippet \"{}\" does not exist", name));
+                Value::Null
+            }
+        },
+    }
+}
+
+fn export_leaf(
+    left: &Operand,
+    operator: &Operator,
+    right: &Operand,
+    ctx: &EvalContext,
+) -> Result<Value, String> {
+    // Literals are written as numbers when compared with a number
+    let data_type = match (left, right) {
+        (_, Operand::Field { field }) | (Operand::Field { field }, _) => field.data_type(),
+        (Operand::Expression { .. }, _) | (_, Operand::Expression { .. }) => DataType::Number,
+        (Operand::Value { value }, _) if value.trim().parse::<f64>().is_ok() => DataType::Number,
+        _ => DataType::String,
+    };
+    let symbol = match operator {
+        Operator::Equals => "==",
+        Operator::NotEquals => "!=",
+        Operator::GreaterThan => ">",
+        Operator::GreaterThanOrEqual => ">=",
+        Operator::LessThan => "<",
+        Operator::LessThanOrEqual => "<=",
+        Operator::Between => {
+            let Operand::Range { min, max } = right else {
+                return Err("Between needs a lower and an upper bound".to_string());
+            };
+            return Ok(json!({ "<=": [
+                literal(min, DataType::Number)?,
+                export_operand(left, data_type, ctx)?,
+                literal(max, DataType::Number)?,
+            ]}));
+        }
+        Operator::In | Operator::NotIn => {
+            let (element_type, values) = match right {
+                Operand::List {
+                    element_type,
+                    values,
+                } => (*element_type, values.clone()),
+                Operand::ListRef { name } => ctx
+                    .lists
+                    .get(name)
+                    .map(|list| (list.element_type, list.entries.clone()))
+                    .ok_or_else(|| format!("reference list \"{}\" does not exist", name))?,
+                _ => return Err(format!("{} is not a list", right.display())),
+            };
+            let values = values
+                .iter()
+                .map(|value| literal(value, element_type))
+                .collect::<Result<Vec<_>, _>>()?;
+            let test = json!({ "in": [export_operand(left, data_type, ctx)?, values] });
+            return Ok(if *operator == Operator::NotIn {
+                json!({ "!": test })
+            } else {
+                test
+            });
+        }
+        // `in` with a string on the right is a substring test
+        Operator::Contains => {
+            return Ok(json!({ "in": [
+                export_operand(right, DataType::String, ctx)?,
+                export_operand(left, DataType::String, ctx)?,
+            ]}));
+        }
+        _ => return Err(format!("JSONLogic has no {}", operator.display_name())),
+    };
+    Ok(json!({ symbol: [
+        export_operand(left, data_type, ctx)?,
+        export_operand(right, data_type, ctx)?,
+    ]}))
+}
+
+fn export_operand(
+    operand: &Operand,
+    data_type: DataType,
+    ctx: &EvalContext,
+) -> Result<Value, String> {
+    match operand {
+        Operand::Field { field } => Ok(json!({ "var": field.as_str() })),
+        Operand::Value { value } => literal(value, data_type),
+        Operand::Parameter { name } => {
+            let parameter = ctx
+                .parameters
+                .get(name)
+                .ok_or_else(|| format!("parameter ${} does not exist", name))?;
+            literal(&parameter.value, parameter.data_type)
+        }
+        Operand::Expression { expr } => export_expr(expr),
+        Operand::Aggregate { .. } => Err("JSONLogic has no velocity aggregates".to_string()),
+        _ => Err(format!("{} is not a single value", operand.display())),
+    }
+}
+
+fn number(value: f64) -> Result<Value, String> {
+    if value.fract() == 0.0 && value.abs() < 2f64.powi(53) {
+        Ok(json!(value as i64))
+    } else {
+        serde_json::Number::from_f64(value)
+            .map(Value::Number)
+            .ok_or_else(|| format!("{} is not a finite number", value))
+    }
+}
+
+/// A literal as JSON: a number when it is compared as one, a string otherwise
+fn literal(literal: &str, data_type: DataType) -> Result<Value, String> {
+    match (data_type, literal.trim().parse::<f64>()) {
+        (DataType::Number, Ok(value)) => number(value),
+        _ => Ok(Value::String(literal.to_string())),
+    }
+}
+
+fn export_expr(expr: &Expr) -> Result<Value, String> {
+    match expr {
+        Expr::Number { value } => number(*value),
+        Expr::Field { field } => Ok(json!({ "var": field.as_str() })),
+        Expr::Negate { expr } => Ok(json!({ "-": [export_expr(expr)?] })),
+        Expr::Binary { op, left, right } => Ok(json!({
+            op.symbol(): [export_expr(left)?, export_expr(right)?]
+        })),
+    }
+}
+
+// ============================================================================
+// Import
+// ============================================================================
+
+/// The single `{"operation": arguments}` pair of a JSONLogic operation
+fn operation(logic: &Value) -> Option<(&str, &Value)> {
+    match logic {
+        Value::Object(map) if map.len() == 1 => map.iter().next().map(|(k, v)| (k.as_str(), v)),
+        _ => None,
+    }
+}
+
+/// Operation arguments; a lone non-array argument is shorthand for a one-element array
+fn arguments(args: &Value) -> Vec<&Value> {
+    match args {
+        Value::Array(items) => items.iter().collect(),
+        other => vec![other],
+    }
+}
+
+fn leaf(left: Operand, operator: Operator, right: Operand) -> ConditionNode {
+    ConditionNode::Leaf {
+        id: Uuid::new_v4(),
+        left,
+        operator,
+        right,
+        weight: None,
+    }
+}
+
+fn unsupported(logic: &Value, errors: &mut Vec<String>) -> Option<ConditionNode> {
+    let name = operation(logic).map_or("", |(name, _)| name);
+    if name.is_empty() {
+        errors.push(format!("Expected an operation, found {}", logic));
+    } else {
+        errors.push(format!("Unsupported operation \"{}\" in {}", name, logic));
+    }
+    None
+}
+
+fn import_node(logic: &Value, errors: &mut Vec<String>) -> Option<ConditionNode> {
+    let Some((name, args)) = operation(logic) else {
+        return unsupported(logic, errors);
+    };
+    let args = arguments(args);
+    match name {
+        "and" | "or" => {
+            let children: Vec<Option<ConditionNode>> = args
+                .iter()
+                .map(|child| import_node(child, errors))
+                .collect();
+            Some(ConditionNode::Group {
+                id: Uuid::new_v4(),
+                operator: if name == "and" {
+                    LogicalOperator::And
+                } else {
+                    LogicalOperator::Or
+                },
+                children: children.into_iter().collect::<Option<Vec<_>>>()?,
+                weight: None,
+            })
+        }
+        "!" => match args.as_slice() {
+            [inner] if operation(inner).is_some_and(|(name, _)| name == "in") => {
+                match import_node(inner, errors)? {
+                    ConditionNode::Leaf {
+                        id,
+                        left,
+                        operator: Operator::In,
+                        right,
+                        weight,
+                    } => Some(ConditionNode::Leaf {
+                        id,
+                        left,
+                        operator: Operator::NotIn,
+                        right,
+                        weight,
+                    }),
+                    _ => {
+                        errors.push(format!(
+                            "Only array membership can be negated, not {}",
+                            logic
+                        ));
+                        None
+                    }
+                }
+            }
+            _ => {
+                errors.push(format!("Only \"in\" can be negated, not {}", logic));
+                None
+            }
+        },
+        "==" | "===" | "!=" | "!==" | ">" | ">=" | "<" | "<=" if args.len() == 2 => {
+            let operator = match name {
+                "==" | "===" => Operator::Equals,
+                "!=" | "!==" => Operator::NotEquals,
+                ">" => Operator::GreaterThan,
+                ">=" => Operator::GreaterThanOrEqual,
+                "<" => Operator::LessThan,
+                _ => Operator::LessThanOrEqual,
+            };
+            let left = import_operand(args[0], errors);
+            let right = import_operand(args[1], errors);
+            Some(leaf(left?, operator, right?))
+        }
+        "<=" if args.len() == 3 => {
+            let (Some(min), Some(max)) = (args[0].as_f64(), args[2].as_f64()) else {
+                errors.push(format!("Between bounds must be numbers in {}", logic));
+                return None;
+            };
+            let left = import_operand(args[1], errors)?;
+            Some(leaf(
+                left,
+                Operator::Between,
+                Operand::Range {
+                    min: min.to_string(),
+                    max: max.to_string(),
+                },
+            ))
+        }
+        "in" if args.len() == 2 => match args[1] {
+            Value::Array(items) => {
+                let left = import_operand(args[0], errors)?;
+                let element_type = match &left {
+                    Operand::Field { field } => field.data_type(),
+                    Operand::Expression { .. } => DataType::Number,
+                    _ if items.iter().all(Value::is_number) => DataType::Number,
+                    _ => DataType::String,
+                };
+                let values = items
+                    .iter()
+                    .map(|item| import_literal(item, errors))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(leaf(
+                    left,
+                    Operator::In,
+                    Operand::list(element_type, values),
+                ))
+            }
+            haystack => {
+                let needle = import_operand(args[0], errors);
+                let haystack = import_operand(haystack, errors);
+                Some(leaf(haystack?, Operator::Contains, needle?))
+            }
+        },
+        _ => unsupported(logic, errors),
+    }
+}
+
+fn import_field(name: &Value, errors: &mut Vec<String>) -> Option<Field> {
+    let field = name
+        .as_str()
+        .and_then(|name| serde_json::from_value::<Field>(Value::String(name.to_string())).ok());
+    if field.is_none() {
+        errors.push(format!("Unknown field {}", name));
+    }
+    field
+}
+
+/// `{"var": "name"}`, also written `{"var": ["name"]}`; defaults are not supported
+fn import_var(args: &Value, errors: &mut Vec<String>) -> Option<Field> {
+    match arguments(args).as_slice() {
+        [name] => import_field(name, errors),
+        _ => {
+            errors.push(format!(
+                "Variable defaults are not supported in {}",
+                json!({ "var": args })
+            ));
+            None
+        }
+    }
+}
+
+fn import_literal(value: &Value, errors: &mut Vec<String>) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(number) => Some(match number.as_i64() {
+            Some(integer) => integer.to_string(),
+            None => number.as_f64().unwrap_or_default().to_string(),
+        }),
+        Value::Bool(flag) => Some(flag.to_string()),
+        _ => {
+            errors.push(format!("Unsupported value {}", value));
+            None
+        }
+    }
+}
+
+fn import_operand(logic: &Value, errors: &mut Vec<String>) -> Option<Operand> {
+    match operation(logic) {
+        Some(("var", args)) => import_var(args, errors).map(|field| Operand::Field { field }),
+        Some(("+" | "-" | "*" | "/", _)) => {
+            import_expr(logic, errors).map(|expr| Operand::Expression { expr })
+        }
+        Some(_) => {
+            unsupported(logic, errors);
+            None
+        }
+        None => import_literal(logic, errors).map(|value| Operand::Value { value }),
+    }
+}
+
+fn import_expr(logic: &Value, errors: &mut Vec<String>) -> Option<Expr> {
+    if let Some(value) = logic.as_f64() {
+        return Some(Expr::Number { value });
+    }
+    let Some((name, args)) = operation(logic) else {
+        errors.push(format!(
+            "Expected a number or an operation, found {}",
+            logic
+        ));
+        return None;
+    };
+    let op = match name {
+        "var" => return import_var(args, errors).map(|field| Expr::Field { field }),
+        "+" => ArithOp::Add,
+        "-" => ArithOp::Subtract,
+        "*" => ArithOp::Multiply,
+        "/" => ArithOp::Divide,
+        _ => {
+            unsupported(logic, errors);
+            return None;
+        }
+    };
+    let operands = arguments(args)
+        .into_iter()
+        .map(|arg| import_expr(arg, errors))
+        .collect::<Option<Vec<_>>>()?;
+    match (op, operands.len()) {
+        (ArithOp::Subtract, 1) => Some(Expr::Negate {
+            expr: Box::new(operands.into_iter().next()?),
+        }),
+        // `+` and `*` take any number of operands and associate to the left
+        (ArithOp::Add | ArithOp::Multiply, 2..) | (ArithOp::Subtract | ArithOp::Divide, 2) => {
+            operands.into_iter().reduce(|left, right| Expr::Binary {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            })
+        }
+        _ => {
+            errors.push(format!("Wrong number of operands in {}", logic));
+            None
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Fraud rule engine: rule models, validation, serialization and evaluation.
+//!
+//! Rules are trees of conditions over transaction fields, serialized as JSON. A rule is
+//! checked with [`Rule::validate`], evaluated by walking the tree with [`evaluate_rule`],
+//! or compiled once with [`compile_rule`] into a flat program for evaluating many
+//! transactions. Reference lists, parameters, snippets and velocity history that rules
+//! refer to are passed in an [`EvalContext`].
+//!
+//! ```
+//! use rule_engine::{compile_rule, evaluate_rule, Action, EvalContext, Rule, Transaction};
+//!
+//! let rule: Rule = serde_json::from_str(
+//!     r#"{
+//!         "id": "6f1c1a8e-8f1e-4d33-9b7e-0d6a2d1f7c11",
+//!         "name": "Large amount",
+//!         "description": "Flag transactions over 1000",
+//!         "root": {
+//!             "type": "group",
+//!             "id": "0b5c2b1e-3f3a-4d7e-8c55-2f0e6f4a9d01",
+//!             "operator": "AND",
+//!             "children": [{
+//!                 "type": "leaf",
+//!                 "id": "9d7e4c2a-1b3f-4e5d-8a6c-7f8e9d0a1b2c",
+//!                 "left": { "type": "field", "field": "transaction_amount" },
+//!                 "operator": "greater_than",
+//!                 "right": { "type": "value", "value": "1000" }
+//!             }]
+//!         },
+//!         "actions": [{ "type": "flag_for_review" }]
+//!     }"#,
+//! )
+//! .unwrap();
+//!
+//! let ctx = EvalContext::default();
+//! assert!(rule.validate(&ctx).is_ok());
+//!
+//! let txs = Transaction::parse_jsonl(
+//!     "{\"transaction_amount\": 2500}\n{\"transaction_amount\": 40}",
+//! )
+//! .unwrap();
+//! let outcome = evaluate_rule(&rule, &txs[0], &ctx);
+//! assert!(outcome.matched);
+//! assert_eq!(outcome.actions, vec![Action::FlagForReview]);
+//!
+//! let compiled = compile_rule(&rule, &ctx).unwrap();
+//! assert!(compiled.matches(&txs[0], &ctx));
+//! assert!(!compiled.matches(&txs[1], &ctx));
+//! ```
+
+pub mod analysis;
+pub mod compiler;
+pub mod evaluator;
+pub mod expression;
+pub mod jsonlogic;
+pub mod models;
+pub mod overlap;
+pub mod policy;
+pub mod simplify;
+pub mod sql;
+pub mod stats;
+pub mod velocity;
+
+pub use compiler::{compile_rule, CompiledOutcome, CompiledRule};
+pub use evaluator::{
+    evaluate_rule, evaluate_rule_set, run_test_cases, trace, EvalContext, RuleOutcome,
+    RuleSetOutcome, TestCaseResult, Value,
+};
+pub use models::{
+    Action, ConditionNode, DataType, Decision, Field, LogicalOperator, MatchStrategy, Operand,
+    Operator, Parameter, ReferenceList, Rule, RuleMode, RuleSet, RuleSetEntry, ScoreThreshold,
+    Snippet, TestCase, Transaction,
+};
+pub use velocity::VelocityStore;
@@ -0,0 +1,1869 @@
+use crate::evaluator::EvalContext;
+use crate::expression::Expr;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Offset, TimeDelta, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use ipnet::IpNet;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+/// Represents a field in the fraud detection system
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    UserId,
+    TransactionAmount,
+    TransactionCurrency,
+    UserCountry,
+    UserAge,
+    IpAddress,
+    DeviceFingerprint,
+    #[serde(rename = "transaction_count_24h")]
+    TransactionCount24h,
+    AccountAge,
+    #[serde(rename = "average_amount_30d")]
+    AverageAmount30d,
+    TransactionTime,
+    AccountCreatedAt,
+    LastPasswordChange,
+}
+
+impl Field {
+    pub fn all() -> Vec<Field> {
+        vec![
+            Field::UserId,
+            Field::TransactionAmount,
+            Field::TransactionCurrency,
+            Field::UserCountry,
+            Field::UserAge,
+            Field::IpAddress,
+            Field::DeviceFingerprint,
+            Field::TransactionCount24h,
+            Field::AccountAge,
+            Field::AverageAmount30d,
+            Field::TransactionTime,
+            Field::AccountCreatedAt,
+            Field::LastPasswordChange,
+        ]
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Field::UserId => "user_id",
+            Field::TransactionAmount => "transaction_amount",
+            Field::TransactionCurrency => "transaction_currency",
+            Field::UserCountry => "user_country",
+            Field::UserAge => "user_age",
+            Field::IpAddress => "ip_address",
+            Field::DeviceFingerprint => "device_fingerprint",
+            Field::TransactionCount24h => "transaction_count_24h",
+            Field::AccountAge => "account_age",
+            Field::AverageAmount30d => "average_amount_30d",
+            Field::TransactionTime => "transaction_time",
+            Field::AccountCreatedAt => "account_created_at",
+            Field::LastPasswordChange => "last_password_change",
+        }
+    }
+
+    pub fn data_type(&self) -> DataType {
+        match self {
+            Field::TransactionAmount
+            | Field::UserAge
+            | Field::TransactionCount24h
+            | Field::AccountAge
+            | Field::AverageAmount30d => DataType::Number,
+            Field::UserId
+            | Field::TransactionCurrency
+            | Field::UserCountry
+            | Field::DeviceFingerprint => DataType::String,
+            Field::IpAddress => DataType::Ip,
+            Field::TransactionTime | Field::AccountCreatedAt | Field::LastPasswordChange => {
+                DataType::DateTime
+            }
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        match self {
+            Field::UserId => "User ID",
+            Field::TransactionAmount => "Transaction Amount",
+            Field::TransactionCurrency => "Transaction Currency",
+            Field::UserCountry => "User Country",
+            Field::UserAge => "User Age",
+            Field::IpAddress => "IP Address",
+            Field::DeviceFingerprint => "Device Fingerprint",
+            Field::TransactionCount24h => "Transaction Count (24h)",
+            Field::AccountAge => "Account Age",
+            Field::AverageAmount30d => "Average Amount (30d)",
+            Field::TransactionTime => "Transaction Time",
+            Field::AccountCreatedAt => "Account Created At",
+            Field::LastPasswordChange => "Last Password Change",
+        }
+    }
+}
+
+/// The type of value a field holds, which decides the operators it supports
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DataType {
+    Number,
+    String,
+    /// IPv4 or IPv6 address; literals may also be CIDR ranges
+    Ip,
+    /// Point in time: RFC 3339, a local `YYYY-MM-DDTHH:MM[:SS]` read in the rule's
+    /// timezone, or Unix seconds in transaction data
+    #[serde(rename = "datetime")]
+    DateTime,
+}
+
+impl DataType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            DataType::Number => "number",
+            DataType::String => "string",
+            DataType::Ip => "ip",
+            DataType::DateTime => "datetime",
+        }
+    }
+
+    /// Check that a literal is a valid value of this type
+    pub fn check_literal(&self, literal: &str) -> Result<(), String> {
+        match self {
+            DataType::Number if literal.trim().parse::<f64>().is_err() => {
+                Err(format!("\"{}\" is not a number", literal))
+            }
+            DataType::Ip if parse_ip_range(literal).is_none() => Err(format!(
+                "\"{}\" is not an IP address or CIDR range",
+                literal
+            )),
+            DataType::DateTime if parse_datetime(literal, Tz::UTC).is_none() => Err(format!(
+                "\"{}\" is not a date/time (use YYYY-MM-DDTHH:MM)",
+                literal
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether two literals denote the same value of this type
+    pub fn same_value(&self, a: &str, b: &str) -> bool {
+        match self {
+            DataType::Number => match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+                (Ok(a), Ok(b)) => a == b,
+                _ => a == b,
+            },
+            DataType::String => a == b,
+            DataType::Ip => match (parse_ip_range(a), parse_ip_range(b)) {
+                (Some(a), Some(b)) => a == b,
+                _ => a == b,
+            },
+            DataType::DateTime => match (parse_datetime(a, Tz::UTC), parse_datetime(b, Tz::UTC)) {
+                (Some(a), Some(b)) => a == b,
+                _ => a == b,
+            },
+        }
+    }
+}
+
+/// Parse a CIDR range, treating a bare address as a single-host range
+pub fn parse_ip_range(literal: &str) -> Option<IpNet> {
+    let literal = literal.trim();
+    literal
+        .parse::<IpNet>()
+        .ok()
+        .or_else(|| literal.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+/// Parse a date/time literal. RFC 3339 values carry their own offset; local values
+/// (`2024-05-01T09:30`, `2024-05-01 09:30:00`, or a bare date meaning midnight) are
+/// read in `timezone`, taking the earlier instant when a clock change makes them ambiguous,
+/// and the offset from before the change when it skips them.
+pub fn parse_datetime(literal: &str, timezone: Tz) -> Option<DateTime<Utc>> {
+    let literal = literal.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(literal) {
+        return Some(datetime.with_timezone(&Utc));
+    }
+    let naive = [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(literal, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(literal, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })?;
+    match timezone.from_local_datetime(&naive).earliest() {
+        Some(datetime) => Some(datetime.with_timezone(&Utc)),
+        None => {
+            // Clock changes skip at most a few hours
+            let before = timezone
+                .from_local_datetime(&(naive - TimeDelta::hours(3)))
+                .earliest()?;
+            let offset = before.offset().fix();
+            Some((naive - offset).and_utc())
+        }
+    }
+}
+
+/// Parse a day of the week such as `mon` or `Saturday`
+pub fn parse_weekday(literal: &str) -> Option<Weekday> {
+    literal.trim().parse().ok()
+}
+
+/// Parse an hour of the day, 0 to 23
+pub fn parse_hour(literal: &str) -> Option<u32> {
+    literal.trim().parse().ok().filter(|hour| *hour < 24)
+}
+
+/// Operators for comparisons
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Operator {
+    Equals,
+    NotEquals,
+    GreaterThan,
+    LessThan,
+    GreaterThanOrEqual,
+    LessThanOrEqual,
+    /// Inclusive numeric range, right side is an `Operand::Range`
+    Between,
+    Contains,
+    StartsWith,
+    EndsWith,
+    Regex,
+    In,
+    NotIn,
+    /// IP address inside any of the listed CIDR ranges
+    InCidr,
+    NotInCidr,
+    /// Missing or blank value, takes no right side
+    IsEmpty,
+    /// Date/time no older than a duration before now, and not in the future
+    WithinLast,
+    Before,
+    After,
+    /// Hour of the date/time in the rule's timezone is one of the listed hours (0-23)
+    HourOfDayIn,
+    /// Day of the week in the rule's timezone is one of the listed days
+    DayOfWeekIn,
+}
+
+impl Operator {
+    pub fn all() -> Vec<Operator> {
+        vec![
+            Operator::Equals,
+            Operator::NotEquals,
+            Operator::GreaterThan,
+            Operator::LessThan,
+            Operator::GreaterThanOrEqual,
+            Operator::LessThanOrEqual,
+            Operator::Between,
+            Operator::Contains,
+            Operator::StartsWith,
+            Operator::EndsWith,
+            Operator::Regex,
+            Operator::In,
+            Operator::NotIn,
+            Operator::InCidr,
+            Operator::NotInCidr,
+            Operator::IsEmpty,
+            Operator::WithinLast,
+            Operator::Before,
+            Operator::After,
+            Operator::HourOfDayIn,
+            Operator::DayOfWeekIn,
+        ]
+    }
+
+    /// Operators available for a left side of the given type
+    pub fn for_type(data_type: DataType) -> Vec<Operator> {
+        match data_type {
+            DataType::Number => vec![
+                Operator::Equals,
+                Operator::NotEquals,
+                Operator::GreaterThan,
+                Operator::LessThan,
+                Operator::GreaterThanOrEqual,
+                Operator::LessThanOrEqual,
+                Operator::Between,
+                Operator::In,
+                Operator::NotIn,
+                Operator::IsEmpty,
+            ],
+            DataType::String => vec![
+                Operator::Equals,
+                Operator::NotEquals,
+                Operator::Contains,
+                Operator::StartsWith,
+                Operator::EndsWith,
+                Operator::Regex,
+                Operator::In,
+                Operator::NotIn,
+                Operator::IsEmpty,
+            ],
+            DataType::Ip => vec![
+                Operator::Equals,
+                Operator::NotEquals,
+                Operator::In,
+                Operator::NotIn,
+                Operator::InCidr,
+                Operator::NotInCidr,
+                Operator::IsEmpty,
+            ],
+            DataType::DateTime => vec![
+                Operator::WithinLast,
+                Operator::Before,
+                Operator::After,
+                Operator::HourOfDayIn,
+                Operator::DayOfWeekIn,
+                Operator::IsEmpty,
+            ],
+        }
+    }
+
+    /// Unary operators only look at the left side
+    pub fn is_unary(&self) -> bool {
+        matches!(self, Operator::IsEmpty)
+    }
+
+    /// Operators whose right side is a list of values
+    pub fn takes_list(&self) -> bool {
+        matches!(
+            self,
+            Operator::In
+                | Operator::NotIn
+                | Operator::InCidr
+                | Operator::NotInCidr
+                | Operator::HourOfDayIn
+                | Operator::DayOfWeekIn
+        )
+    }
+
+    /// Type of the list elements this operator expects for a left side of `left_type`.
+    /// Hours are numbers and days are names, whatever the left side holds.
+    pub fn list_element_type(&self, left_type: DataType) -> DataType {
+        match self {
+            Operator::HourOfDayIn => DataType::Number,
+            Operator::DayOfWeekIn => DataType::String,
+            _ => left_type,
+        }
+    }
+
+    /// Check one list element beyond its type, e.g. that hours are 0-23
+    fn check_list_element(&self, element_type: DataType, value: &str) -> Result<(), String> {
+        match self {
+            Operator::HourOfDayIn if parse_hour(value).is_none() => {
+                Err(format!("\"{}\" is not an hour of the day (0-23)", value))
+            }
+            Operator::DayOfWeekIn if parse_weekday(value).is_none() => {
+                Err(format!("\"{}\" is not a day of the week", value))
+            }
+            _ => element_type.check_literal(value),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Operator::Equals => "equals",
+            Operator::NotEquals => "not_equals",
+            Operator::GreaterThan => "greater_than",
+            Operator::LessThan => "less_than",
+            Operator::GreaterThanOrEqual => "greater_than_or_equal",
+            Operator::LessThanOrEqual => "less_than_or_equal",
+            Operator::Between => "between",
+            Operator::Contains => "contains",
+            Operator::StartsWith => "starts_with",
+            Operator::EndsWith => "ends_with",
+            Operator::Regex => "regex",
+            Operator::In => "in",
+            Operator::NotIn => "not_in",
+            Operator::InCidr => "in_cidr",
+            Operator::NotInCidr => "not_in_cidr",
+            Operator::IsEmpty => "is_empty",
+            Operator::WithinLast => "within_last",
+            Operator::Before => "before",
+            Operator::After => "after",
+            Operator::HourOfDayIn => "hour_of_day_in",
+            Operator::DayOfWeekIn => "day_of_week_in",
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        match self {
+            Operator::Equals => "Equals",
+            Operator::NotEquals => "Not Equals",
+            Operator::GreaterThan => "Greater Than",
+            Operator::LessThan => "Less Than",
+            Operator::GreaterThanOrEqual => "Greater Than or Equal",
+            Operator::LessThanOrEqual => "Less Than or Equal",
+            Operator::Between => "Between",
+            Operator::Contains => "Contains",
+            Operator::StartsWith => "Starts With",
+            Operator::EndsWith => "Ends With",
+            Operator::Regex => "Matches Regex",
+            Operator::In => "In",
+            Operator::NotIn => "Not In",
+            Operator::InCidr => "In CIDR",
+            Operator::NotInCidr => "Not In CIDR",
+            Operator::IsEmpty => "Is Empty",
+            Operator::WithinLast => "Within Last",
+            Operator::Before => "Before",
+            Operator::After => "After",
+            Operator::HourOfDayIn => "Hour of Day In",
+            Operator::DayOfWeekIn => "Day of Week In",
+        }
+    }
+}
+
+/// Aggregation applied to a field over a group's recent transactions
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    DistinctCount,
+}
+
+impl AggregateFunction {
+    pub fn all() -> Vec<AggregateFunction> {
+        vec![
+            AggregateFunction::Count,
+            AggregateFunction::Sum,
+            AggregateFunction::Avg,
+            AggregateFunction::Min,
+            AggregateFunction::Max,
+            AggregateFunction::DistinctCount,
+        ]
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            AggregateFunction::Count => "count",
+            AggregateFunction::Sum => "sum",
+            AggregateFunction::Avg => "avg",
+            AggregateFunction::Min => "min",
+            AggregateFunction::Max => "max",
+            AggregateFunction::DistinctCount => "distinct_count",
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        match self {
+            AggregateFunction::Count => "Count",
+            AggregateFunction::Sum => "Sum",
+            AggregateFunction::Avg => "Average",
+            AggregateFunction::Min => "Minimum",
+            AggregateFunction::Max => "Maximum",
+            AggregateFunction::DistinctCount => "Distinct Count",
+        }
+    }
+
+    /// Count works on whole transactions; every other function reads a field
+    pub fn needs_field(&self) -> bool {
+        !matches!(self, AggregateFunction::Count)
+    }
+
+    pub fn needs_numeric_field(&self) -> bool {
+        matches!(
+            self,
+            AggregateFunction::Sum
+                | AggregateFunction::Avg
+                | AggregateFunction::Min
+                | AggregateFunction::Max
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowUnit {
+    Minutes,
+    Hours,
+    Days,
+}
+
+impl WindowUnit {
+    pub fn all() -> Vec<WindowUnit> {
+        vec![WindowUnit::Minutes, WindowUnit::Hours, WindowUnit::Days]
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            WindowUnit::Minutes => "minutes",
+            WindowUnit::Hours => "hours",
+            WindowUnit::Days => "days",
+        }
+    }
+
+    fn seconds(&self) -> u64 {
+        match self {
+            WindowUnit::Minutes => 60,
+            WindowUnit::Hours => 60 * 60,
+            WindowUnit::Days => 24 * 60 * 60,
+        }
+    }
+}
+
+/// How far back an aggregate or Within Last looks, e.g. 1 hour or 7 days
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct TimeWindow {
+    pub amount: u32,
+    pub unit: WindowUnit,
+}
+
+impl TimeWindow {
+    /// Longest window the velocity store keeps history for
+    pub const MAX_SECONDS: u64 = 30 * 24 * 60 * 60;
+
+    pub fn seconds(&self) -> u64 {
+        u64::from(self.amount) * self.unit.seconds()
+    }
+
+    /// Compact form, e.g. `1h` or `7d`
+    pub fn display(&self) -> String {
+        let suffix = match self.unit {
+            WindowUnit::Minutes => "m",
+            WindowUnit::Hours => "h",
+            WindowUnit::Days => "d",
+        };
+        format!("{}{}", self.amount, suffix)
+    }
+}
+
+/// Represents either a field reference or a literal value
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Operand {
+    Field {
+        field: Field,
+    },
+    Value {
+        value: String,
+    },
+    /// Inclusive bounds for the Between operator
+    Range {
+        min: String,
+        max: String,
+    },
+    /// A set of literals for the In / Not In / In CIDR operators
+    List {
+        element_type: DataType,
+        values: Vec<String>,
+    },
+    /// A managed reference list, looked up by name at evaluation time
+    #[serde(rename = "list_ref")]
+    ListRef {
+        name: String,
+    },
+    /// A velocity aggregate over the recent transactions sharing this transaction's
+    /// `group_by` value, e.g. the sum of amounts for this device over the last hour
+    Aggregate {
+        function: AggregateFunction,
+        /// Field to aggregate; not used by Count
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        field: Option<Field>,
+        group_by: Field,
+        window: TimeWindow,
+    },
+    /// Arithmetic over numeric fields and literals, e.g. `3 * average_amount_30d`
+    Expression {
+        expr: Expr,
+    },
+    /// A length of time for the Within Last operator
+    Duration {
+        window: TimeWindow,
+    },
+    /// A named constant from the parameter table, looked up by name at evaluation time
+    Parameter {
+        name: String,
+    },
+}
+
+impl Operand {
+    pub fn display(&self) -> String {
+        match self {
+            Operand::Field { field } => field.display_name().to_string(),
+            Operand::Value { value } => format!("\"{}\"", value),
+            Operand::Range { min, max } => format!("{} and {}", min, max),
+            Operand::List { values, .. } => format!("[{}]", values.join(", ")),
+            Operand::ListRef { name } => format!("LIST({})", name),
+            Operand::Aggregate {
+                function,
+                field,
+                group_by,
+                window,
+            } => {
+                let target = match (function, field) {
+                    (AggregateFunction::Count, _) | (_, None) => "transactions",
+                    (_, Some(field)) => field.display_name(),
+                };
+                format!(
+                    "{} of {} per {} over {}",
+                    function.display_name(),
+                    target,
+                    group_by.display_name(),
+                    window.display()
+                )
+            }
+            Operand::Expression { expr } => expr.display(),
+            Operand::Duration { window } => window.display(),
+            Operand::Parameter { name } => format!("${}", name),
+        }
+    }
+
+    /// Element type of a list operand, looking up reference lists in `ctx`
+    fn list_element_type(&self, ctx: &EvalContext) -> Option<DataType> {
+        match self {
+            Operand::List { element_type, .. } => Some(*element_type),
+            Operand::ListRef { name } => ctx.lists.get(name).map(|list| list.element_type),
+            _ => None,
+        }
+    }
+
+    /// Build a list operand, trimming elements and dropping blanks and duplicates.
+    /// Numeric elements are compared by value, so `1` and `1.0` are duplicates.
+    pub fn list(element_type: DataType, values: Vec<String>) -> Operand {
+        let mut unique: Vec<String> = Vec::new();
+        for value in values {
+            let value = value.trim().to_string();
+            if !value.is_empty() && !unique.iter().any(|v| element_type.same_value(v, &value)) {
+                unique.push(value);
+            }
+        }
+        Operand::List {
+            element_type,
+            values: unique,
+        }
+    }
+}
+
+/// A node in the condition tree - either a leaf (condition) or a group
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ConditionNode {
+    Leaf {
+        id: Uuid,
+        left: Operand,
+        operator: Operator,
+        right: Operand,
+        /// Score added when the node matches, for scoring rules
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        weight: Option<f64>,
+    },
+    Group {
+        id: Uuid,
+        operator: LogicalOperator,
+        children: Vec<ConditionNode>,
+        /// Score added when the node matches, for scoring rules
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        weight: Option<f64>,
+    },
+    /// A reference to a named snippet, resolved at evaluation time
+    #[serde(rename = "snippet_ref")]
+    SnippetRef {
+        id: Uuid,
+        name: String,
+        /// Score added when the node matches, for scoring rules
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        weight: Option<f64>,
+    },
+}
+
+impl ConditionNode {
+    pub fn id(&self) -> Uuid {
+        match self {
+            ConditionNode::Leaf { id, .. } => *id,
+            ConditionNode::Group { id, .. } => *id,
+            ConditionNode::SnippetRef { id, .. } => *id,
+        }
+    }
+
+    pub fn weight(&self) -> Option<f64> {
+        match self {
+            ConditionNode::Leaf { weight, .. } => *weight,
+            ConditionNode::Group { weight, .. } => *weight,
+            ConditionNode::SnippetRef { weight, .. } => *weight,
+        }
+    }
+
+    pub fn set_weight(&mut self, new_weight: Option<f64>) {
+        match self {
+            ConditionNode::Leaf { weight, .. } => *weight = new_weight,
+            ConditionNode::Group { weight, .. } => *weight = new_weight,
+            ConditionNode::SnippetRef { weight, .. } => *weight = new_weight,
+        }
+    }
+
+    pub fn is_leaf(&self) -> bool {
+        matches!(self, ConditionNode::Leaf { .. })
+    }
+
+    pub fn is_group(&self) -> bool {
+        matches!(self, ConditionNode::Group { .. })
+    }
+
+    /// Human readable form of the condition, e.g. `(Transaction Amount Greater Than "100" AND ...)`
+    pub fn display(&self) -> String {
+        match self {
+            ConditionNode::Leaf { left, operator, .. } if operator.is_unary() => {
+                format!("{} {}", left.display(), operator.display_name())
+            }
+            ConditionNode::Leaf {
+                left,
+                operator,
+                right,
+                ..
+            } => format!(
+                "{} {} {}",
+                left.display(),
+                operator.display_name(),
+                right.display()
+            ),
+            ConditionNode::Group {
+                operator, children, ..
+            } => format!(
+                "({})",
+                children
+                    .iter()
+                    .map(|child| child.display())
+                    .collect::<Vec<_>>()
+                    .join(&format!(" {} ", operator))
+            ),
+            ConditionNode::SnippetRef { name, .. } => format!("SNIPPET({})", name),
+        }
+    }
+
+    /// Names of the snippets referenced directly by this node and its descendants,
+    /// not following the snippets themselves
+    pub fn referenced_snippets(&self) -> Vec<String> {
+        match self {
+            ConditionNode::Leaf { .. } => Vec::new(),
+            ConditionNode::Group { children, .. } => children
+                .iter()
+                .flat_map(|child| child.referenced_snippets())
+                .collect(),
+            ConditionNode::SnippetRef { name, .. } => vec![name.clone()],
+        }
+    }
+
+    /// Copy of the tree with every snippet reference replaced by the snippet's conditions.
+    ///
+    /// The expanded copy takes the reference's id and weight; nodes inside it get ids
+    /// derived from the reference and the snippet node, so a snippet used twice still
+    /// yields unique, stable ids. Unknown snippets are left as references.
+    pub fn expand_snippets(&self, snippets: &BTreeMap<String, Snippet>) -> ConditionNode {
+        match self {
+            ConditionNode::Leaf { .. } => self.clone(),
+            ConditionNode::Group {
+                id,
+                operator,
+                children,
+                weight,
+            } => ConditionNode::Group {
+                id: *id,
+                operator: operator.clone(),
+                children: children
+                    .iter()
+                    .map(|child| child.expand_snippets(snippets))
+                    .collect(),
+                weight: *weight,
+            },
+            ConditionNode::SnippetRef { id, name, weight } => match snippets.get(name) {
+                Some(snippet) => {
+                    let mut expanded = snippet.root.expand_snippets(snippets).derive_ids(*id);
+                    if let ConditionNode::Group { id: root_id, .. } = &mut expanded {
+                        *root_id = *id;
+                    }
+                    expanded.set_weight(*weight);
+                    expanded
+                }
+                None => self.clone(),
+            },
+        }
+    }
+
+    /// Copy of the tree with every id combined with `seed`
+    fn derive_ids(&self, seed: Uuid) -> ConditionNode {
+        let derive = |id: &Uuid| Uuid::from_u128(id.as_u128() ^ seed.as_u128());
+        match self {
+            ConditionNode::Leaf {
+                id,
+                left,
+                operator,
+                right,
+                weight,
+            } => ConditionNode::Leaf {
+                id: derive(id),
+                left: left.clone(),
+                operator: operator.clone(),
+                right: right.clone(),
+                weight: *weight,
+            },
+            ConditionNode::Group {
+                id,
+                operator,
+                children,
+                weight,
+            } => ConditionNode::Group {
+                id: derive(id),
+                operator: operator.clone(),
+                children: children
+                    .iter()
+                    .map(|child| child.derive_ids(seed))
+                    .collect(),
+                weight: *weight,
+            },
+            ConditionNode::SnippetRef { id, name, weight } => ConditionNode::SnippetRef {
+                id: derive(id),
+                name: name.clone(),
+                weight: *weight,
+            },
+        }
+    }
+
+    /// Copy of the tree with fresh ids, e.g. to save part of a rule as a snippet
+    pub fn with_new_ids(&self) -> ConditionNode {
+        self.derive_ids(Uuid::new_v4())
+    }
+
+    /// Names of the reference lists used by this node and its descendants
+    pub fn referenced_lists(&self) -> Vec<String> {
+        match self {
+            ConditionNode::Leaf { left, right, .. } => [left, right]
+                .into_iter()
+                .filter_map(|operand| match operand {
+                    Operand::ListRef { name } => Some(name.clone()),
+                    _ => None,
+                })
+                .collect(),
+            ConditionNode::Group { children, .. } => children
+                .iter()
+                .flat_map(|child| child.referenced_lists())
+                .collect(),
+            ConditionNode::SnippetRef { .. } => Vec::new(),
+        }
+    }
+
+    /// Names of the parameters used by this node and its descendants
+    pub fn referenced_parameters(&self) -> Vec<String> {
+        match self {
+            ConditionNode::Leaf { left, right, .. } => [left, right]
+                .into_iter()
+                .filter_map(|operand| match operand {
+                    Operand::Parameter { name } => Some(name.clone()),
+                    _ => None,
+                })
+                .collect(),
+            ConditionNode::Group { children, .. } => children
+                .iter()
+                .flat_map(|child| child.referenced_parameters())
+                .collect(),
+            ConditionNode::SnippetRef { .. } => Vec::new(),
+        }
+    }
+
+    /// The leaves that use a parameter, in tree order
+    pub fn leaves_using_parameter(&self, name: &str) -> Vec<&ConditionNode> {
+        match self {
+            ConditionNode::Leaf { .. } => {
+                if self.referenced_parameters().iter().any(|used| used == name) {
+                    vec![self]
+                } else {
+                    Vec::new()
+                }
+            }
+            ConditionNode::Group { children, .. } => children
+                .iter()
+                .flat_map(|child| child.leaves_using_parameter(name))
+                .collect(),
+            ConditionNode::SnippetRef { .. } => Vec::new(),
+        }
+    }
+
+    /// Whether this node or any descendant carries a weight
+    pub fn has_weights(&self) -> bool {
+        self.weight().is_some()
+            || match self {
+                ConditionNode::Group { children, .. } => children.iter().any(|c| c.has_weights()),
+                ConditionNode::Leaf { .. } | ConditionNode::SnippetRef { .. } => false,
+            }
+    }
+
+    /// Navigate to a node at the given path
+    pub fn get_at_path(&self, path: &[usize]) -> Option<&ConditionNode> {
+        if path.is_empty() {
+            return Some(self);
+        }
+
+        match self {
+            ConditionNode::Group { children, .. } => children
+                .get(path[0])
+                .and_then(|child| child.get_at_path(&path[1..])),
+            ConditionNode::Leaf { .. } | ConditionNode::SnippetRef { .. } => None,
+        }
+    }
+
+    /// Navigate to a mutable node at the given path
+    pub fn get_at_path_mut(&mut self, path: &[usize]) -> Option<&mut ConditionNode> {
+        if path.is_empty() {
+            return Some(self);
+        }
+
+        match self {
+            ConditionNode::Group { children, .. } => children
+                .get_mut(path[0])
+                .and_then(|child| child.get_at_path_mut(&path[1..])),
+            ConditionNode::Leaf { .. } | ConditionNode::SnippetRef { .. } => None,
+        }
+    }
+
+    /// Add a child to a group at the given path
+    pub fn add_child_at_path(&mut self, path: &[usize], child: ConditionNode) -> bool {
+        if let Some(ConditionNode::Group { children, .. }) = self.get_at_path_mut(path) {
+            children.push(child);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Delete a node at the given path
+    pub fn delete_at_path(&mut self, path: &[usize]) -> bool {
+        if path.is_empty() {
+            return false; // Can't delete root
+        }
+
+        let parent_path = &path[..path.len() - 1];
+        let child_index = path[path.len() - 1];
+
+        if let Some(ConditionNode::Group { children, .. }) = self.get_at_path_mut(parent_path) {
+            if child_index < children.len() {
+                children.remove(child_index);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Logical operator for combining conditions
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum LogicalOperator {
+    And,
+    Or,
+}
+
+impl std::fmt::Display for LogicalOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogicalOperator::And => write!(f, "AND"),
+            LogicalOperator::Or => write!(f, "OR"),
+        }
+    }
+}
+
+/// An action taken when a rule matches
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Action {
+    Block,
+    FlagForReview,
+    RequireStepUp,
+    AddRiskScore { score: i64 },
+    Tag { label: String },
+    SendWebhook { url: String },
+}
+
+impl Action {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Action::Block => "block",
+            Action::FlagForReview => "flag_for_review",
+            Action::RequireStepUp => "require_step_up",
+            Action::AddRiskScore { .. } => "add_risk_score",
+            Action::Tag { .. } => "tag",
+            Action::SendWebhook { .. } => "send_webhook",
+        }
+    }
+
+    pub fn display(&self) -> String {
+        match self {
+            Action::Block => "Block".to_string(),
+            Action::FlagForReview => "Flag for review".to_string(),
+            Action::RequireStepUp => "Require step-up authentication".to_string(),
+            Action::AddRiskScore { score } => format!("Add risk score {}", score),
+            Action::Tag { label } => format!("Tag with \"{}\"", label),
+            Action::SendWebhook { url } => format!("Send webhook to {}", url),
+        }
+    }
+
+    /// The action for a free-form `action` string of rules saved before actions
+    /// were typed; unknown names are kept as a tag rather than dropped
+    pub fn from_legacy(name: &str) -> Option<Action> {
+        match name.trim() {
+            "" => None,
+            "block" => Some(Action::Block),
+            "flag_for_review" => Some(Action::FlagForReview),
+            "require_step_up" => Some(Action::RequireStepUp),
+            other => Some(Action::Tag {
+                label: other.to_string(),
+            }),
+        }
+    }
+}
+
+/// Read a rule's actions from a list, or from the single `action` string rules
+/// were saved with before actions were typed
+fn deserialize_actions<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Action>, D::Error> {
+    struct ActionsVisitor;
+
+    impl<'de> de::Visitor<'de> for ActionsVisitor {
+        type Value = Vec<Action>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a list of actions or an action name")
+        }
+
+        fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+            Ok(Action::from_legacy(name).into_iter().collect())
+        }
+
+        fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+            Vec::deserialize(de::value::SeqAccessDeserializer::new(seq))
+        }
+    }
+
+    deserializer.deserialize_any(ActionsVisitor)
+}
+
+/// How a rule turns its condition tree into a decision
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleMode {
+    /// The rule matches when the root condition is true and takes its actions
+    #[default]
+    Boolean,
+    /// Matched nodes add their weights to a score, and thresholds pick the actions
+    Scoring,
+}
+
+/// Actions taken when a scoring rule's score reaches `min_score`.
+/// The highest threshold reached applies.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoreThreshold {
+    pub min_score: f64,
+    pub actions: Vec<Action>,
+}
+
+/// The main rule structure - represents an AST with tree-based conditions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub id: Uuid,
+    pub name: String,
+    pub description: String,
+    pub root: ConditionNode, // Tree structure
+    #[serde(alias = "action", deserialize_with = "deserialize_actions")]
+    pub actions: Vec<Action>,
+    #[serde(default)]
+    pub mode: RuleMode,
+    /// Score thresholds for scoring rules, sorted by ascending `min_score`
+    #[serde(default)]
+    pub thresholds: Vec<ScoreThreshold>,
+    #[serde(default)]
+    pub test_cases: Vec<TestCase>,
+    /// IANA timezone that local date/times, hours and weekdays are read in
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+/// A named sample transaction with the outcome the rule is expected to produce
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestCase {
+    pub id: Uuid,
+    pub name: String,
+    pub transaction: Transaction,
+    /// Whether the rule should flag this transaction
+    pub expect_flagged: bool,
+}
+
+impl Rule {
+    pub fn new(name: String, description: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name,
+            description,
+            root: ConditionNode::Group {
+                id: Uuid::new_v4(),
+                operator: LogicalOperator::And,
+                children: Vec::new(),
+                weight: None,
+            },
+            actions: vec![Action::FlagForReview],
+            mode: RuleMode::Boolean,
+            thresholds: Vec::new(),
+            test_cases: Vec::new(),
+            timezone: default_timezone(),
+        }
+    }
+
+    /// The rule's timezone, falling back to UTC when it is unknown (validation reports it)
+    pub fn tz(&self) -> Tz {
+        self.timezone.parse().unwrap_or(Tz::UTC)
+    }
+
+    pub fn is_scoring(&self) -> bool {
+        self.mode == RuleMode::Scoring
+    }
+
+    pub fn references_list(&self, name: &str) -> bool {
+        self.root.referenced_lists().iter().any(|used| used == name)
+    }
+
+    /// Copy of the rule with snippet references expanded inline, for export
+    pub fn expand_snippets(&self, snippets: &BTreeMap<String, Snippet>) -> Rule {
+        Rule {
+            root: self.root.expand_snippets(snippets),
+            ..self.clone()
+        }
+    }
+
+    pub fn references_snippet(&self, name: &str) -> bool {
+        self.root
+            .referenced_snippets()
+            .iter()
+            .any(|used| used == name)
+    }
+
+    /// Validate the rule. Reference lists, parameters and snippets are checked against `ctx`.
+    pub fn validate(&self, ctx: &EvalContext) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        if self.name.is_empty() {
+            errors.push("Rule name cannot be empty".to_string());
+        }
+        if self.timezone.parse::<Tz>().is_err() {
+            errors.push(format!("Unknown timezone \"{}\"", self.timezone));
+        }
+
+        // Validate the tree
+        self.validate_node(&self.root, ctx, &mut errors);
+
+        match self.mode {
+            RuleMode::Boolean => {
+                if self.actions.is_empty() {
+                    errors.push("Rule must have at least one action".to_string());
+                }
+                Self::validate_actions(&self.actions, &mut errors);
+            }
+            RuleMode::Scoring => self.validate_scoring(&mut errors),
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn validate_scoring(&self, errors: &mut Vec<String>) {
+        if !self.root.has_weights() {
+            errors.push("Scoring rule must have at least one weighted condition".to_string());
+        }
+        if self.thresholds.is_empty() {
+            errors.push("Scoring rule must have at least one score threshold".to_string());
+        }
+
+        for (i, threshold) in self.thresholds.iter().enumerate() {
+            if !threshold.min_score.is_finite() {
+                errors.push("Score threshold must be a finite number".to_string());
+            }
+            if self.thresholds[..i]
+                .iter()
+                .any(|other| other.min_score == threshold.min_score)
+            {
+                errors.push(format!(
+                    "Duplicate score threshold: {}",
+                    threshold.min_score
+                ));
+            }
+            if threshold.actions.is_empty() {
+                errors.push(format!(
+                    "Score threshold {} must have at least one action",
+                    threshold.min_score
+                ));
+            }
+            Self::validate_actions(&threshold.actions, errors);
+        }
+    }
+
+    fn validate_actions(actions: &[Action], errors: &mut Vec<String>) {
+        for (i, action) in actions.iter().enumerate() {
+            if actions[..i].contains(action) {
+                errors.push(format!("Duplicate action: {}", action.display()));
+            }
+            match action {
+                Action::AddRiskScore { score } if *score == 0 => {
+                    errors.push("Risk score must not be zero".to_string());
+                }
+                Action::Tag { label } if label.trim().is_empty() => {
+                    errors.push("Tag label cannot be empty".to_string());
+                }
+                Action::SendWebhook { url }
+                    if !(url.starts_with("https://") || url.starts_with("http://")) =>
+                {
+                    errors.push(format!("Webhook URL must start with http(s)://: {}", url));
+                }
+                _ => {}
+            }
+        }
+
+        if actions.contains(&Action::Block) && actions.contains(&Action::RequireStepUp) {
+            errors.push("A rule cannot both block and require step-up authentication".to_string());
+        }
+    }
+
+    fn validate_right_operand(
+        &self,
+        operator: &Operator,
+        right: &Operand,
+        ctx: &EvalContext,
+        errors: &mut Vec<String>,
+    ) {
+        match (operator, right) {
+            (Operator::Between, Operand::Range { min, max }) => {
+                match (min.trim().parse::<f64>(), max.trim().parse::<f64>()) {
+                    (Ok(min), Ok(max)) if min > max => errors.push(format!(
+                        "Between lower bound {} is greater than upper bound {}",
+                        min, max
+                    )),
+                    (Ok(_), Ok(_)) => {}
+                    _ => errors.push("Between bounds must be numbers".to_string()),
+                }
+            }
+            (Operator::Between, _) => {
+                errors.push("Between needs a lower and an upper bound".to_string());
+            }
+            (_, Operand::Range { .. }) => errors.push(format!(
+                "Operator {} does not take a range",
+                operator.display_name()
+            )),
+            (
+                operator,
+                Operand::List {
+                    element_type,
+                    values,
+                },
+            ) if operator.takes_list() => {
+                if values.is_empty() {
+                    errors.push(format!("{} list cannot be empty", operator.display_name()));
+                }
+                for (i, value) in values.iter().enumerate() {
+                    if let Err(err) = operator.check_list_element(*element_type, value) {
+                        errors.push(format!("List element {}", err));
+                    }
+                    if values[..i]
+                        .iter()
+                        .any(|v| element_type.same_value(v, value))
+                    {
+                        errors.push(format!("Duplicate list element \"{}\"", value));
+                    }
+                }
+            }
+            (operator, Operand::ListRef { name })
+                if operator.takes_list() && !ctx.lists.contains_key(name) =>
+            {
+                errors.push(format!("Reference list \"{}\" does not exist", name));
+            }
+            (operator, Operand::ListRef { .. }) if operator.takes_list() => {}
+            (operator, _) if operator.takes_list() => errors.push(format!(
+                "{} needs a list of values",
+                operator.display_name()
+            )),
+            (_, Operand::List { .. } | Operand::ListRef { .. }) => errors.push(format!(
+                "Operator {} does not take a list",
+                operator.display_name()
+            )),
+            (Operator::WithinLast, Operand::Duration { window }) if window.amount == 0 => {
+                errors.push("Within Last duration must be longer than zero".to_string());
+            }
+            (Operator::WithinLast, Operand::Duration { .. }) => {}
+            (Operator::WithinLast, _) => errors.push("Within Last needs a duration".to_string()),
+            (_, Operand::Duration { .. }) => errors.push(format!(
+                "Operator {} does not take a duration",
+                operator.display_name()
+            )),
+            (Operator::Before | Operator::After, Operand::Value { value }) if !value.is_empty() => {
+                if let Err(err) = DataType::DateTime.check_literal(value) {
+                    errors.push(err);
+                }
+            }
+            (Operator::Before | Operator::After, Operand::Field { field })
+                if field.data_type() != DataType::DateTime =>
+            {
+                errors.push(format!(
+                    "{} compares with a date/time, not {}",
+                    operator.display_name(),
+                    field.display_name()
+                ));
+            }
+            (_, Operand::Value { value }) if value.is_empty() => {
+                errors.push("Condition value cannot be empty".to_string());
+            }
+            (Operator::Regex, Operand::Value { value }) => {
+                if let Err(err) = regex::Regex::new(value) {
+                    errors.push(format!("Invalid regex \"{}\": {}", value, err));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn validate_aggregate(operand: &Operand, errors: &mut Vec<String>) {
+        let Operand::Aggregate {
+            function,
+            field,
+            window,
+            ..
+        } = operand
+        else {
+            return;
+        };
+        match field {
+            None if function.needs_field() => errors.push(format!(
+                "{} aggregate needs a field",
+                function.display_name()
+            )),
+            Some(field)
+                if function.needs_numeric_field() && field.data_type() != DataType::Number =>
+            {
+                errors.push(format!(
+                    "{} aggregate needs a numeric field, not {}",
+                    function.display_name(),
+                    field.display_name()
+                ))
+            }
+            _ => {}
+        }
+        if window.amount == 0 {
+            errors.push("Aggregate window must be longer than zero".to_string());
+        } else if window.seconds() > TimeWindow::MAX_SECONDS {
+            errors.push(format!(
+                "Aggregate window {} is longer than the 30 day history",
+                window.display()
+            ));
+        }
+    }
+
+    /// IP equality and membership compare single addresses; ranges need In CIDR
+    fn validate_ip_operand(operator: &Operator, right: &Operand, errors: &mut Vec<String>) {
+        let addresses: Vec<&String> = match (operator, right) {
+            (Operator::Equals | Operator::NotEquals, Operand::Value { value }) => vec![value],
+            (Operator::In | Operator::NotIn, Operand::List { values, .. }) => {
+                values.iter().collect()
+            }
+            _ => Vec::new(),
+        };
+        for address in addresses {
+            if !address.is_empty() && address.trim().parse::<IpAddr>().is_err() {
+                errors.push(format!(
+                    "\"{}\" is not a valid IP address (use In CIDR for ranges)",
+                    address
+                ));
+            }
+        }
+    }
+
+    fn validate_node(&self, node: &ConditionNode, ctx: &EvalContext, errors: &mut Vec<String>) {
+        if node.weight().is_some_and(|w| !w.is_finite()) {
+            errors.push("Condition weight must be a finite number".to_string());
+        }
+
+        match node {
+            ConditionNode::Leaf {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                // Validate that value operands are not empty
+                if let Operand::Value { value } = left {
+                    if value.is_empty() {
+                        errors.push("Condition value cannot be empty".to_string());
+                    }
+                }
+                if let Operand::Field { field } = left {
+                    if !Operator::for_type(field.data_type()).contains(operator) {
+                        errors.push(format!(
+                            "Operator {} cannot be used with {}",
+                            operator.display_name(),
+                            field.display_name()
+                        ));
+                    }
+                }
+                // Aggregates and expressions are numbers
+                let numeric_kind = match left {
+                    Operand::Aggregate { .. } => Some("an aggregate"),
+                    Operand::Expression { .. } => Some("an expression"),
+                    _ => None,
+                };
+                if let Some(kind) = numeric_kind {
+                    if !Operator::for_type(DataType::Number).contains(operator) {
+                        errors.push(format!(
+                            "Operator {} cannot be used with {}",
+                            operator.display_name(),
+                            kind
+                        ));
+                    }
+                }
+                for operand in [left, right] {
+                    if let Operand::Expression { expr } = operand {
+                        expr.check(errors);
+                    }
+                }
+                Self::validate_aggregate(left, errors);
+                Self::validate_aggregate(right, errors);
+                for operand in [left, right] {
+                    if let Operand::Parameter { name } = operand {
+                        if !ctx.parameters.contains_key(name) {
+                            errors.push(format!("Parameter ${} does not exist", name));
+                        }
+                    }
+                }
+                if let (Operand::Field { field }, Operand::Parameter { name }) = (left, right) {
+                    let parameter_type = ctx.parameters.get(name).map(|p| p.data_type);
+                    if parameter_type.is_some_and(|t| t != field.data_type()) {
+                        errors.push(format!(
+                            "{} holds {} values but ${} is a {}",
+                            field.display_name(),
+                            field.data_type().as_str(),
+                            name,
+                            parameter_type.unwrap_or(DataType::String).as_str()
+                        ));
+                    }
+                }
+                if let (Operand::Field { field }, Some(element_type)) =
+                    (left, right.list_element_type(ctx))
+                {
+                    let expected = operator.list_element_type(field.data_type());
+                    if expected != element_type && expected == field.data_type() {
+                        errors.push(format!(
+                            "{} holds {} values but the list holds {} values",
+                            field.display_name(),
+                            field.data_type().as_str(),
+                            element_type.as_str()
+                        ));
+                    } else if expected != element_type {
+                        errors.push(format!(
+                            "{} needs a list of {} values but the list holds {} values",
+                            operator.display_name(),
+                            expected.as_str(),
+                            element_type.as_str()
+                        ));
+                    }
+                }
+                if !operator.is_unary() {
+                    self.validate_right_operand(operator, right, ctx, errors);
+                }
+                if matches!(left, Operand::Field { field } if field.data_type() == DataType::Ip) {
+                    Self::validate_ip_operand(operator, right, errors);
+                }
+            }
+            ConditionNode::Group { children, .. } => {
+                if children.is_empty() {
+                    errors.push("Group must have at least one condition".to_string());
+                }
+                // Recursively validate children
+                for child in children {
+                    self.validate_node(child, ctx, errors);
+                }
+            }
+            ConditionNode::SnippetRef { name, .. } => match ctx.snippets.get(name) {
+                None => errors.push(format!("Snippet \"{}\" does not exist", name)),
+                Some(snippet) => match find_snippet_cycle(name, &snippet.root, &ctx.snippets) {
+                    Some(cycle) => errors.push(format!(
+                        "Snippet \"{}\" references itself: {}",
+                        name,
+                        cycle.join(" → ")
+                    )),
+                    None => {
+                        let mut snippet_errors = Vec::new();
+                        self.validate_node(&snippet.root, ctx, &mut snippet_errors);
+                        errors.extend(
+                            snippet_errors
+                                .into_iter()
+                                .map(|err| format!("Snippet \"{}\": {}", name, err)),
+                        );
+                    }
+                },
+            },
+        }
+    }
+}
+
+/// A transaction record to evaluate rules against, keyed by field name
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Transaction(pub serde_json::Map<String, serde_json::Value>);
+
+impl Transaction {
+    pub fn get(&self, field: &Field) -> Option<&serde_json::Value> {
+        self.0.get(field.as_str())
+    }
+
+    /// Parse newline-delimited JSON, one transaction object per line
+    ///
+    /// ```
+    /// use rule_engine::{Field, Transaction};
+    ///
+    /// let txs = Transaction::parse_jsonl("{\"user_id\": \"u1\"}\n\n{\"user_id\": \"u2\"}").unwrap();
+    /// assert_eq!(txs.len(), 2);
+    /// assert_eq!(txs[1].get(&Field::UserId), Some(&serde_json::json!("u2")));
+    ///
+    /// let err = Transaction::parse_jsonl("{}\nnot json").unwrap_err();
+    /// assert!(err.starts_with("Line 2:"));
+    /// ```
+    pub fn parse_jsonl(input: &str) -> Result<Vec<Transaction>, String> {
+        input
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line).map_err(|e| format!("Line {}: {}", i + 1, e))
+            })
+            .collect()
+    }
+}
+
+/// A named, shared list of typed entries (e.g. a blocklist) that rules reference by name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReferenceList {
+    pub name: String,
+    pub description: String,
+    pub element_type: DataType,
+    pub entries: Vec<String>,
+}
+
+/// Outcome of adding entries to a reference list
+#[derive(Debug, Clone, Default)]
+pub struct EntryImport {
+    pub added: usize,
+    pub duplicates: usize,
+    /// Entries that are not valid for the list's element type
+    pub rejected: Vec<String>,
+}
+
+impl ReferenceList {
+    pub fn new(name: String, description: String, element_type: DataType) -> Self {
+        Self {
+            name,
+            description,
+            element_type,
+            entries: Vec::new(),
+        }
+    }
+
+    /// List names are used in `LIST(name)`, so they are limited to lowercase
+    /// letters, digits and underscores
+    pub fn validate_name(name: &str) -> Result<(), String> {
+        validate_lowercase_name("List", name)
+    }
+
+    /// Read entries from the first column of CSV data, optionally skipping a header row
+    pub fn parse_csv(data: &str, has_header: bool) -> Result<Vec<String>, String> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(has_header)
+            .flexible(true)
+            .from_reader(data.as_bytes());
+        reader
+            .records()
+            .enumerate()
+            .map(|(i, record)| {
+                record
+                    .map(|record| record.get(0).unwrap_or_default().to_string())
+                    .map_err(|err| format!("Row {}: {}", i + 1 + has_header as usize, err))
+            })
+            .collect()
+    }
+
+    /// Add entries, trimming them and skipping blanks and duplicates
+    pub fn add_entries(&mut self, entries: impl IntoIterator<Item = String>) -> EntryImport {
+        let mut import = EntryImport::default();
+        for entry in entries {
+            let entry = entry.trim().to_string();
+            if entry.is_empty() {
+                continue;
+            }
+            if self.element_type.check_literal(&entry).is_err() {
+                import.rejected.push(entry);
+            } else if self
+                .entries
+                .iter()
+                .any(|existing| self.element_type.same_value(existing, &entry))
+            {
+                import.duplicates += 1;
+            } else {
+                self.entries.push(entry);
+                import.added += 1;
+            }
+        }
+        import
+    }
+}
+
+/// A named, typed constant such as `$HIGH_AMOUNT = 1000` that rules reference instead
+/// of repeating the literal, so changing it updates every rule at once
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Parameter {
+    pub name: String,
+    pub description: String,
+    pub data_type: DataType,
+    pub value: String,
+}
+
+impl Parameter {
+    /// Parameter names are written `$NAME`, so they are limited to uppercase
+    /// letters, digits and underscores, starting with a letter
+    pub fn validate_name(name: &str) -> Result<(), String> {
+        if name.is_empty() {
+            Err("Parameter name cannot be empty".to_string())
+        } else if !name.starts_with(|c: char| c.is_ascii_uppercase())
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        {
+            Err(format!(
+                "Parameter name \"{}\" must start with a letter and may only contain uppercase letters, digits and underscores",
+                name
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check a new value against the parameter's type
+    pub fn check_value(&self, value: &str) -> Result<(), String> {
+        if value.trim().is_empty() {
+            return Err(format!("${} needs a value", self.name));
+        }
+        self.data_type.check_literal(value)
+    }
+}
+
+/// A named, reusable condition group that rules reference with `SNIPPET(name)`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snippet {
+    pub name: String,
+    pub description: String,
+    /// Always a group
+    pub root: ConditionNode,
+}
+
+impl Snippet {
+    /// Snippet names are used in `SNIPPET(name)`, so they follow the same rules as list names
+    pub fn validate_name(name: &str) -> Result<(), String> {
+        validate_lowercase_name("Snippet", name)
+    }
+}
+
+fn validate_lowercase_name(kind: &str, name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        Err(format!("{} name cannot be empty", kind))
+    } else if !name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        Err(format!(
+            "{} name \"{}\" may only contain lowercase letters, digits and underscores",
+            kind, name
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Find a chain of snippet references from `root` back to the snippet `name`,
+/// e.g. `["new_account", "risky_device", "new_account"]`
+pub fn find_snippet_cycle(
+    name: &str,
+    root: &ConditionNode,
+    snippets: &BTreeMap<String, Snippet>,
+) -> Option<Vec<String>> {
+    fn visit(
+        node: &ConditionNode,
+        name: &str,
+        snippets: &BTreeMap<String, Snippet>,
+        chain: &mut Vec<String>,
+    ) -> Option<Vec<String>> {
+        for referenced in node.referenced_snippets() {
+            if referenced == name || chain.contains(&referenced) {
+                let mut cycle = chain.clone();
+                cycle.push(referenced);
+                return Some(cycle);
+            }
+            if let Some(snippet) = snippets.get(&referenced) {
+                chain.push(referenced);
+                if let Some(cycle) = visit(&snippet.root, name, snippets, chain) {
+                    return Some(cycle);
+                }
+                chain.pop();
+            }
+        }
+        None
+    }
+
+    visit(root, name, snippets, &mut vec![name.to_string()])
+}
+
+/// The final decision a rule set makes for a transaction, from least to most severe
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    #[default]
+    Allow,
+    Review,
+    StepUp,
+    Block,
+}
+
+impl Decision {
+    pub fn all() -> Vec<Decision> {
+        vec![
+            Decision::Allow,
+            Decision::Review,
+            Decision::StepUp,
+            Decision::Block,
+        ]
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Decision::Allow => "allow",
+            Decision::Review => "review",
+            Decision::StepUp => "step_up",
+            Decision::Block => "block",
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        match self {
+            Decision::Allow => "Allow",
+            Decision::Review => "Review",
+            Decision::StepUp => "Step-up",
+            Decision::Block => "Block",
+        }
+    }
+
+    /// The decision an action implies; scores, tags and webhooks imply none
+    pub fn from_action(action: &Action) -> Option<Decision> {
+        match action {
+            Action::Block => Some(Decision::Block),
+            Action::RequireStepUp => Some(Decision::StepUp),
+            Action::FlagForReview => Some(Decision::Review),
+            _ => None,
+        }
+    }
+}
+
+/// How a rule set combines the outcomes of its rules
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchStrategy {
+    /// The highest-priority matching rule decides, and later rules are not evaluated
+    #[default]
+    FirstMatch,
+    /// Every matching rule's actions apply, and the most severe decision wins
+    AllMatches,
+    /// The matching rule with the highest score decides; ties go to the higher priority
+    HighestScore,
+}
+
+impl MatchStrategy {
+    pub fn all() -> Vec<MatchStrategy> {
+        vec![
+            MatchStrategy::FirstMatch,
+            MatchStrategy::AllMatches,
+            MatchStrategy::HighestScore,
+        ]
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            MatchStrategy::FirstMatch => "first_match",
+            MatchStrategy::AllMatches => "all_matches",
+            MatchStrategy::HighestScore => "highest_score",
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        match self {
+            MatchStrategy::FirstMatch => "First match wins",
+            MatchStrategy::AllMatches => "All matches",
+            MatchStrategy::HighestScore => "Highest score",
+        }
+    }
+}
+
+/// A rule's membership in a rule set
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleSetEntry {
+    pub rule_id: Uuid,
+    /// Disabled rules keep their place in the order but are not evaluated
+    pub enabled: bool,
+}
+
+/// An ordered collection of rules that together make one decision
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleSet {
+    pub id: Uuid,
+    pub name: String,
+    pub description: String,
+    /// Member rules, highest priority first
+    pub entries: Vec<RuleSetEntry>,
+    pub strategy: MatchStrategy,
+    /// Used when no applied action implies a decision
+    pub default_decision: Decision,
+}
+
+impl RuleSet {
+    pub fn new(name: String, description: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name,
+            description,
+            entries: Vec::new(),
+            strategy: MatchStrategy::default(),
+            default_decision: Decision::default(),
+        }
+    }
+
+    pub fn contains(&self, rule_id: Uuid) -> bool {
+        self.entries.iter().any(|entry| entry.rule_id == rule_id)
+    }
+
+    /// Reorder the entries to follow `order`. Rules missing from `order` keep their
+    /// relative order after the listed ones, and unknown ids are ignored.
+    pub fn reorder(&mut self, order: &[Uuid]) {
+        let position = |rule_id: &Uuid| {
+            order
+                .iter()
+                .position(|id| id == rule_id)
+                .unwrap_or(order.len())
+        };
+        self.entries.sort_by_key(|entry| position(&entry.rule_id));
+    }
+}
@@ -0,0 +1,408 @@
+//! Comparing rules with each other: which rules cover others, which fire on nearly
+//! the same transactions, and which never get to decide behind higher-priority rules.
+//!
+//! Boolean rules are compared symbolically by rewriting their conditions into
+//! disjunctive normal form: a rule is within another when each of its terms implies
+//! some term of the other, reasoning per field as the [analyzer](crate::analysis)
+//! does. The proof is sound but not complete, so "unknown" does not mean the rules
+//! differ. Every rule is also compared empirically by its hits on a sample dataset.
+
+use crate::analysis::{shape, subset, Analyzer, Regions};
+use crate::evaluator::{evaluate_rule, EvalContext};
+use crate::models::{ConditionNode, Field, LogicalOperator, Rule, RuleMode, Transaction};
+use crate::simplify::{normalize, NormalForm};
+use serde::Serialize;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// What the conditions of two rules prove about the transactions they match
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Relation {
+    /// Both match exactly the same transactions
+    Equivalent,
+    /// Every transaction the first matches, the second matches too
+    FirstWithinSecond,
+    /// Every transaction the second matches, the first matches too
+    SecondWithinFirst,
+    /// No transaction matches both
+    Disjoint,
+    /// Nothing could be proven
+    Unknown,
+}
+
+impl Relation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Relation::Equivalent => "equivalent",
+            Relation::FirstWithinSecond => "first_within_second",
+            Relation::SecondWithinFirst => "second_within_first",
+            Relation::Disjoint => "disjoint",
+            Relation::Unknown => "unknown",
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Relation::Equivalent => "Equivalent",
+            Relation::FirstWithinSecond => "First within second",
+            Relation::SecondWithinFirst => "Second within first",
+            Relation::Disjoint => "Disjoint",
+            Relation::Unknown => "Unknown",
+        }
+    }
+}
+
+/// How two rules overlap
+#[derive(Debug, Clone, Serialize)]
+pub struct PairOverlap {
+    pub first: Uuid,
+    pub second: Uuid,
+    pub relation: Relation,
+    /// Sample transactions each rule matches, and both match
+    pub first_hits: usize,
+    pub second_hits: usize,
+    pub both_hits: usize,
+}
+
+impl PairOverlap {
+    /// Transactions both match over those either matches; `None` when neither does
+    pub fn jaccard(&self) -> Option<f64> {
+        let either = self.first_hits + self.second_hits - self.both_hits;
+        (either > 0).then(|| self.both_hits as f64 / either as f64)
+    }
+}
+
+/// How much of a rule's population higher-priority rules decide first, under
+/// first-match evaluation
+#[derive(Debug, Clone, Serialize)]
+pub struct Shadowing {
+    pub rule_id: Uuid,
+    /// Higher-priority rules proven to match everything this rule matches; empty
+    /// when that could not be proven
+    pub covered_by: Vec<Uuid>,
+    /// Sample transactions this rule matches
+    pub hits: usize,
+    /// Of those, the ones a higher-priority rule matches
+    pub taken: usize,
+}
+
+impl Shadowing {
+    /// Proven never to decide
+    pub fn proven(&self) -> bool {
+        !self.covered_by.is_empty()
+    }
+
+    /// Never decides on the sample although it matches some of it
+    pub fn shadowed_on_sample(&self) -> bool {
+        self.hits > 0 && self.taken == self.hits
+    }
+}
+
+/// Pairwise overlap of rules and, taking them as in priority order, their shadowing
+#[derive(Debug, Clone, Serialize)]
+pub struct OverlapReport {
+    pub sample_size: usize,
+    /// Every pair of rules, in the order given
+    pub pairs: Vec<PairOverlap>,
+    /// Every rule after the first, in the order given
+    pub shadowing: Vec<Shadowing>,
+}
+
+/// Compare `rules`, highest priority first, against each other and over `sample`.
+/// Each rule is evaluated in its own timezone, with `ctx` for everything else.
+///
+/// ```
+/// use rule_engine::models::{Rule, Transaction};
+/// use rule_engine::overlap::{compare_rules, Relation};
+/// use rule_engine::EvalContext;
+///
+/// let rule = |name: &str, amount: &str| -> Rule {
+///     serde_json::from_value(serde_json::json!({
+///         "id": uuid::Uuid::new_v4(),
+///         "name": name,
+///         "description": "",
+///         "root": {
+///             "type": "group", "id": uuid::Uuid::new_v4(), "operator": "AND",
+///             "children": [{
+///                 "type": "leaf", "id": uuid::Uuid::new_v4(),
+///                 "left": { "type": "field", "field": "transaction_amount" },
+///                 "operator": "greater_than",
+///                 "right": { "type": "value", "value": amount }
+///             }]
+///         },
+///         "actions": [{ "type": "flag_for_review" }]
+///     }))
+///     .unwrap()
+/// };
+/// let rules = [rule("Over 100", "100"), rule("Over 1000", "1000")];
+/// let sample = Transaction::parse_jsonl(
+///     "{\"transaction_amount\": 50}\n{\"transaction_amount\": 500}\n{\"transaction_amount\": 5000}",
+/// )
+/// .unwrap();
+///
+/// let report = compare_rules(&rules, &sample, &EvalContext::default());
+/// assert_eq!(report.pairs[0].relation, Relation::SecondWithinFirst);
+/// assert_eq!(report.pairs[0].jaccard(), Some(0.5));
+/// assert!(report.shadowing[0].proven());
+/// ```
+pub fn compare_rules(rules: &[Rule], sample: &[Transaction], ctx: &EvalContext) -> OverlapReport {
+    let hits: Vec<Vec<bool>> = rules
+        .iter()
+        .map(|rule| {
+            let ctx = EvalContext {
+                timezone: rule.tz(),
+                ..ctx.clone()
+            };
+            sample
+                .iter()
+                .map(|tx| evaluate_rule(rule, tx, &ctx).matched)
+                .collect()
+        })
+        .collect();
+    let terms: Vec<Option<Vec<Vec<ConditionNode>>>> =
+        rules.iter().map(|rule| dnf_terms(rule, ctx)).collect();
+    let count = |hits: &[bool]| hits.iter().filter(|hit| **hit).count();
+
+    let mut pairs = Vec::new();
+    for (i, first) in rules.iter().enumerate() {
+        for (j, second) in rules.iter().enumerate().skip(i + 1) {
+            let both: Vec<bool> = hits[i]
+                .iter()
+                .zip(&hits[j])
+                .map(|(a, b)| *a && *b)
+                .collect();
+            let relation = match (&terms[i], &terms[j]) {
+                (Some(first_terms), Some(second_terms)) if first.timezone == second.timezone => {
+                    let ctx = EvalContext {
+                        timezone: first.tz(),
+                        ..ctx.clone()
+                    };
+                    relation(first_terms, second_terms, &ctx)
+                }
+                _ => Relation::Unknown,
+            };
+            pairs.push(PairOverlap {
+                first: first.id,
+                second: second.id,
+                relation,
+                first_hits: count(&hits[i]),
+                second_hits: count(&hits[j]),
+                both_hits: count(&both),
+            });
+        }
+    }
+
+    let shadowing = rules
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, rule)| {
+            let taken = (0..sample.len())
+                .filter(|&t| hits[i][t] && hits[..i].iter().any(|earlier| earlier[t]))
+                .count();
+            Shadowing {
+                rule_id: rule.id,
+                covered_by: covering_rules(i, rules, &terms, ctx),
+                hits: count(&hits[i]),
+                taken,
+            }
+        })
+        .collect();
+
+    OverlapReport {
+        sample_size: sample.len(),
+        pairs,
+        shadowing,
+    }
+}
+
+/// A valid boolean rule's conditions in disjunctive normal form, as the conditions
+/// of each term, with snippets expanded and weights dropped
+fn dnf_terms(rule: &Rule, ctx: &EvalContext) -> Option<Vec<Vec<ConditionNode>>> {
+    let ctx = EvalContext {
+        timezone: rule.tz(),
+        ..ctx.clone()
+    };
+    if rule.mode != RuleMode::Boolean || rule.validate(&ctx).is_err() {
+        return None;
+    }
+    let root = without_weights(&rule.root.expand_snippets(&ctx.snippets));
+    let atoms = |node: &ConditionNode| match node {
+        ConditionNode::Group {
+            operator: LogicalOperator::And,
+            children,
+            ..
+        } => children.clone(),
+        atom => vec![atom.clone()],
+    };
+    Some(match normalize(&root, NormalForm::Dnf).ok()? {
+        ConditionNode::Group {
+            operator: LogicalOperator::Or,
+            children,
+            ..
+        } => children.iter().map(atoms).collect(),
+        term => vec![atoms(&term)],
+    })
+}
+
+fn without_weights(node: &ConditionNode) -> ConditionNode {
+    let mut node = node.clone();
+    node.set_weight(None);
+    if let ConditionNode::Group { children, .. } = &mut node {
+        *children = children.iter().map(without_weights).collect();
+    }
+    node
+}
+
+/// Regions each field is confined to by a term's conditions; `None` when the term
+/// can never hold
+fn confinement(term: &[ConditionNode], analyzer: &Analyzer) -> Option<HashMap<Field, Regions>> {
+    let mut fields: HashMap<Field, Regions> = HashMap::new();
+    for (field, holds) in term.iter().filter_map(|atom| analyzer.summary(atom)) {
+        let regions = fields
+            .entry(field)
+            .or_insert_with(|| vec![true; holds.len()]);
+        for (region, held) in regions.iter_mut().zip(holds) {
+            *region &= held;
+        }
+    }
+    fields
+        .values()
+        .all(|regions| regions.iter().any(|held| *held))
+        .then_some(fields)
+}
+
+/// Whether every transaction matching `term` matches `other`
+fn term_implies(
+    term: &[ConditionNode],
+    confined: &HashMap<Field, Regions>,
+    other: &[ConditionNode],
+    analyzer: &Analyzer,
+) -> bool {
+    let shapes: Vec<serde_json::Value> = term.iter().map(shape).collect();
+    other.iter().all(|atom| {
+        shapes.contains(&shape(atom))
+            || analyzer.summary(atom).is_some_and(|(field, holds)| {
+                confined
+                    .get(&field)
+                    .is_some_and(|regions| subset(regions, &holds))
+            })
+    })
+}
+
+/// Whether every term of `first` that can hold implies a term of one of `others`;
+/// false when no term of `first` can hold, as such a rule is not worth comparing
+fn within(
+    first: &[Vec<ConditionNode>],
+    others: &[&[Vec<ConditionNode>]],
+    analyzer: &Analyzer,
+) -> bool {
+    let mut satisfiable = false;
+    let covered = first.iter().all(|term| match confinement(term, analyzer) {
+        None => true,
+        Some(confined) => {
+            satisfiable = true;
+            others
+                .iter()
+                .flat_map(|terms| terms.iter())
+                .any(|other| term_implies(term, &confined, other, analyzer))
+        }
+    });
+    satisfiable && covered
+}
+
+fn roots(terms: &[&[Vec<ConditionNode>]]) -> Vec<ConditionNode> {
+    // The analyzer splits fields by the constants of every condition involved
+    vec![ConditionNode::Group {
+        id: Uuid::nil(),
+        operator: LogicalOperator::Or,
+        children: terms
+            .iter()
+            .flat_map(|terms| terms.iter())
+            .map(|term| ConditionNode::Group {
+                id: Uuid::nil(),
+                operator: LogicalOperator::And,
+                children: term.clone(),
+                weight: None,
+            })
+            .collect(),
+        weight: None,
+    }]
+}
+
+fn relation(
+    first: &[Vec<ConditionNode>],
+    second: &[Vec<ConditionNode>],
+    ctx: &EvalContext,
+) -> Relation {
+    let all = roots(&[first, second]);
+    let analyzer = Analyzer::new(&all.iter().collect::<Vec<_>>(), ctx);
+    match (
+        within(first, &[second], &analyzer),
+        within(second, &[first], &analyzer),
+    ) {
+        (true, true) => Relation::Equivalent,
+        (true, false) => Relation::FirstWithinSecond,
+        (false, true) => Relation::SecondWithinFirst,
+        (false, false) => {
+            let disjoint = first.iter().all(|a| {
+                second.iter().all(|b| {
+                    let both: Vec<ConditionNode> = a.iter().chain(b).cloned().collect();
+                    confinement(&both, &analyzer).is_none()
+                })
+            });
+            if disjoint {
+                Relation::Disjoint
+            } else {
+                Relation::Unknown
+            }
+        }
+    }
+}
+
+/// Higher-priority rules that together are proven to match everything rule `index`
+/// matches, or none when that cannot be proven
+fn covering_rules(
+    index: usize,
+    rules: &[Rule],
+    terms: &[Option<Vec<Vec<ConditionNode>>>],
+    ctx: &EvalContext,
+) -> Vec<Uuid> {
+    let Some(own) = &terms[index] else {
+        return Vec::new();
+    };
+    let earlier: Vec<(&Rule, &[Vec<ConditionNode>])> = rules[..index]
+        .iter()
+        .zip(&terms[..index])
+        .filter(|(rule, _)| rule.timezone == rules[index].timezone)
+        .filter_map(|(rule, terms)| terms.as_deref().map(|terms| (rule, terms)))
+        .collect();
+    let mut all: Vec<&[Vec<ConditionNode>]> = vec![own];
+    all.extend(earlier.iter().map(|(_, terms)| *terms));
+    let ctx = EvalContext {
+        timezone: rules[index].tz(),
+        ..ctx.clone()
+    };
+    let roots = roots(&all);
+    let analyzer = Analyzer::new(&roots.iter().collect::<Vec<_>>(), &ctx);
+
+    let others: Vec<&[Vec<ConditionNode>]> = earlier.iter().map(|(_, terms)| *terms).collect();
+    if !within(own, &others, &analyzer) {
+        return Vec::new();
+    }
+    // Name the rules whose terms the proof used
+    earlier
+        .iter()
+        .filter(|(_, terms)| {
+            own.iter().any(|term| {
+                confinement(term, &analyzer).is_some_and(|confined| {
+                    terms
+                        .iter()
+                        .any(|other| term_implies(term, &confined, other, &analyzer))
+                })
+            })
+        })
+        .map(|(rule, _)| rule.id)
+        .collect()
+}
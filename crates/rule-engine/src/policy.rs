@@ -0,0 +1,940 @@
+//! Generate policy source from rules: Common Expression Language (CEL) expressions
+//! and Open Policy Agent Rego modules.
+//!
+//! Both read each field from a configurable input path. As in the evaluator, a
+//! condition on a missing field is false: CEL guards field reads with `has()`, and
+//! Rego leaves the rule body undefined.
+
+use crate::evaluator::EvalContext;
+use crate::expression::Expr;
+use crate::models::{
+    parse_datetime, parse_hour, parse_ip_range, parse_weekday, ConditionNode, DataType, Field,
+    LogicalOperator, Operand, Operator, Rule, RuleMode,
+};
+use chrono::Weekday;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Where generated policies read each field from
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PolicyOptions {
+    /// Dotted input path per field, e.g. `request.payment.amount`. Unmapped fields are
+    /// read from `transaction.<field>` in CEL and `input.<field>` in Rego.
+    #[serde(default)]
+    pub paths: HashMap<Field, String>,
+}
+
+/// Validate a boolean rule and generate a CEL expression that is true when it matches.
+/// Local date/times are read in `ctx.timezone`, so pass the rule's timezone as for
+/// evaluation. Fails listing the conditions CEL cannot express.
+///
+/// ```
+/// use rule_engine::models::{ConditionNode, Field, Operand, Operator, Rule};
+/// use rule_engine::policy::{rule_to_cel, PolicyOptions};
+/// use rule_engine::EvalContext;
+///
+/// let mut rule = Rule::new("Large amount".to_string(), String::new());
+/// rule.root = ConditionNode::Leaf {
+///     id: uuid::Uuid::new_v4(),
+///     left: Operand::Field { field: Field::TransactionAmount },
+///     operator: Operator::GreaterThan,
+///     right: Operand::Value { value: "1000".to_string() },
+///     weight: None,
+/// };
+/// let options = PolicyOptions {
+///     paths: [(Field::TransactionAmount, "payment.amount".to_string())].into(),
+/// };
+/// assert_eq!(
+///     rule_to_cel(&rule, &options, &EvalContext::default()).unwrap(),
+///     "// Large amount\nhas(payment.amount) && payment.amount > 1000.0\n"
+/// );
+/// ```
+pub fn rule_to_cel(
+    rule: &Rule,
+    options: &PolicyOptions,
+    ctx: &EvalContext,
+) -> Result<String, Vec<String>> {
+    check_rule(rule, ctx)?;
+    let writer = CelWriter { options, ctx };
+    let mut errors = Vec::new();
+    let expression = writer.node(&rule.root, 0, &mut errors);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(format!("{}{}\n", comment("//", rule), expression))
+}
+
+/// Validate a boolean rule and generate a Rego module whose `match` rule is true when
+/// it matches, with `actions` holding the rule's actions. OR groups become one
+/// definition per branch, and nested groups helper rules named after their position.
+/// Fails listing the conditions Rego cannot express.
+pub fn rule_to_rego(
+    rule: &Rule,
+    options: &PolicyOptions,
+    ctx: &EvalContext,
+) -> Result<String, Vec<String>> {
+    check_rule(rule, ctx)?;
+    let mut writer = RegoWriter {
+        options,
+        ctx,
+        rules: Vec::new(),
+        uses_is_blank: false,
+        errors: Vec::new(),
+    };
+    writer.define(&rule.root, "match");
+    if !writer.errors.is_empty() {
+        return Err(writer.errors);
+    }
+
+    let mut module = comment("#", rule);
+    module.push_str(&format!(
+        "package rules.{}\n\nimport rego.v1\n",
+        package_name(&rule.name)
+    ));
+    for definition in &writer.rules {
+        module.push('\n');
+        module.push_str(definition);
+    }
+    if writer.uses_is_blank {
+        module.push_str(
+            "\n# Missing, null or whitespace, as Is Empty treats them\n\
+             is_blank(null) := true\n\n\
+             is_blank(value) := true if trim_space(value) == \"\"\n",
+        );
+    }
+    module.push_str(&format!(
+        "\ndefault actions := []\n\nactions := {} if match\n",
+        serde_json::to_string(&rule.actions).unwrap_or_else(|_| "[]".to_string())
+    ));
+    Ok(module)
+}
+
+fn check_rule(rule: &Rule, ctx: &EvalContext) -> Result<(), Vec<String>> {
+    if rule.mode == RuleMode::Scoring {
+        return Err(vec![
+            "Scoring rules cannot be exported as policies; only boolean rules can".to_string(),
+        ]);
+    }
+    rule.validate(ctx)
+}
+
+/// The rule's name and description as line comments
+fn comment(marker: &str, rule: &Rule) -> String {
+    [rule.name.as_str(), rule.description.as_str()]
+        .iter()
+        .flat_map(|text| text.lines())
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| format!("{} {}\n", marker, line.trim()))
+        .collect()
+}
+
+/// A Rego package segment from a rule name, e.g. `High Risk!` becomes `high_risk`
+fn package_name(name: &str) -> String {
+    let mut package = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            package.push(c.to_ascii_lowercase());
+        } else if !package.is_empty() && !package.ends_with('_') {
+            package.push('_');
+        }
+    }
+    let package = package.trim_end_matches('_').to_string();
+    if package.is_empty() || package.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("rule_{}", package)
+    } else {
+        package
+    }
+}
+
+/// Words either language reserves, written as `["word"]` when they name a path segment
+const RESERVED: &[&str] = &[
+    "as",
+    "break",
+    "const",
+    "contains",
+    "continue",
+    "default",
+    "else",
+    "every",
+    "false",
+    "for",
+    "function",
+    "if",
+    "import",
+    "in",
+    "let",
+    "loop",
+    "namespace",
+    "not",
+    "null",
+    "package",
+    "return",
+    "some",
+    "true",
+    "var",
+    "void",
+    "while",
+    "with",
+];
+
+fn is_identifier(segment: &str) -> bool {
+    let mut chars = segment.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !RESERVED.contains(&segment)
+}
+
+/// A string literal with JSON escapes, which CEL and Rego both read
+fn quote(text: &str) -> String {
+    serde_json::to_string(text).unwrap_or_else(|_| "\"\"".to_string())
+}
+
+/// A parsed input path: a root variable and the keys below it
+struct InputPath {
+    root: String,
+    keys: Vec<String>,
+}
+
+impl InputPath {
+    fn parse(path: &str) -> Result<InputPath, String> {
+        let mut segments = path.split('.').map(str::to_string);
+        let root = segments.next().unwrap_or_default();
+        let keys: Vec<String> = segments.collect();
+        if !is_identifier(&root) || keys.iter().any(String::is_empty) {
+            return Err(format!("\"{}\" is not a valid input path", path));
+        }
+        Ok(InputPath { root, keys })
+    }
+
+    /// `a.b["c-d"]`, the same in both languages
+    fn reference(&self) -> String {
+        self.reference_to(self.keys.len())
+    }
+
+    fn reference_to(&self, depth: usize) -> String {
+        let mut reference = self.root.clone();
+        for key in &self.keys[..depth] {
+            if is_identifier(key) {
+                reference.push('.');
+                reference.push_str(key);
+            } else {
+                reference.push_str(&format!("[{}]", quote(key)));
+            }
+        }
+        reference
+    }
+}
+
+fn input_path(options: &PolicyOptions, field: &Field, root: &str) -> Result<InputPath, String> {
+    match options.paths.get(field) {
+        Some(path) => InputPath::parse(path),
+        None => InputPath::parse(&format!("{}.{}", root, field.as_str())),
+    }
+}
+
+/// A Value or Parameter operand's literal text and type
+fn literal_of(
+    operand: &Operand,
+    data_type: DataType,
+    ctx: &EvalContext,
+) -> Option<(String, DataType)> {
+    match operand {
+        Operand::Value { value } => Some((value.clone(), data_type)),
+        Operand::Parameter { name } => ctx
+            .parameters
+            .get(name)
+            .map(|parameter| (parameter.value.clone(), parameter.data_type)),
+        _ => None,
+    }
+}
+
+/// The type conditions compare as: the field's, or a number for arithmetic
+fn comparison_type(left: &Operand, right: &Operand, ctx: &EvalContext) -> DataType {
+    match (left, right) {
+        (Operand::Field { field }, _) | (Operand::Value { .. }, Operand::Field { field }) => {
+            field.data_type()
+        }
+        (Operand::Parameter { name }, _) => ctx
+            .parameters
+            .get(name)
+            .map_or(DataType::String, |parameter| parameter.data_type),
+        (Operand::Value { value }, _) if value.trim().parse::<f64>().is_err() => DataType::String,
+        _ => DataType::Number,
+    }
+}
+
+/// Elements of a literal list or a reference list, with their type
+fn list_of(operand: &Operand, ctx: &EvalContext) -> Result<(DataType, Vec<String>), String> {
+    match operand {
+        Operand::List {
+            element_type,
+            values,
+        } => Ok((*element_type, values.clone())),
+        Operand::ListRef { name } => ctx
+            .lists
+            .get(name)
+            .map(|list| (list.element_type, list.entries.clone()))
+            .ok_or_else(|| format!("reference list \"{}\" does not exist", name)),
+        _ => Err(format!("{} is not a list", operand.display())),
+    }
+}
+
+/// Hours (0-23) or days of the week (0 for Sunday) from a list operand
+fn clock_values(operator: &Operator, values: &[String]) -> Result<Vec<u32>, String> {
+    values
+        .iter()
+        .map(|value| {
+            let number = match operator {
+                Operator::HourOfDayIn => parse_hour(value),
+                _ => parse_weekday(value).map(|day| day.num_days_from_sunday()),
+            };
+            number.ok_or_else(|| format!("\"{}\" is not a valid list element", value))
+        })
+        .collect()
+}
+
+fn weekday_name(day: u32) -> &'static str {
+    match Weekday::try_from(((day + 6) % 7) as u8).unwrap_or(Weekday::Mon) {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+// ============================================================================
+// CEL
+// ============================================================================
+
+struct CelWriter<'a> {
+    options: &'a PolicyOptions,
+    ctx: &'a EvalContext,
+}
+
+/// A CEL double literal; `Debug` always writes a fraction or an exponent
+fn cel_double(value: f64) -> String {
+    format!("{:?}", value)
+}
+
+impl CelWriter<'_> {
+    fn path(&self, field: &Field) -> Result<InputPath, String> {
+        input_path(self.options, field, "transaction")
+    }
+
+    /// `has()` test for a field; a bare root variable is always present
+    fn presence(&self, field: &Field) -> Result<Option<String>, String> {
+        let path = self.path(field)?;
+        let Some(last) = path.keys.last() else {
+            return Ok(None);
+        };
+        Ok(Some(if is_identifier(last) {
+            format!("has({})", path.reference())
+        } else {
+            format!(
+                "{} in {}",
+                quote(last),
+                path.reference_to(path.keys.len() - 1)
+            )
+        }))
+    }
+
+    fn node(&self, node: &ConditionNode, indent: usize, errors: &mut Vec<String>) -> String {
+        match node {
+            ConditionNode::Leaf {
+                left,
+                operator,
+                right,
+                ..
+            } => self.leaf(left, operator, right).unwrap_or_else(|reason| {
+                errors.push(format!("{}: {}", node.display(), reason));
+                "false".to_string()
+            }),
+            ConditionNode::Group {
+                operator, children, ..
+            } => {
+                let (symbol, empty) = match operator {
+                    LogicalOperator::And => ("&&", "true"),
+                    LogicalOperator::Or => ("||", "false"),
+                };
+                let parts: Vec<String> = children
+                    .iter()
+                    .map(|child| match child {
+                        ConditionNode::Group { children, .. } if children.len() > 1 => format!(
+                            "(\n{}{}\n{})",
+                            "  ".repeat(indent + 1),
+                            self.node(child, indent + 1, errors),
+                            "  ".repeat(indent)
+                        ),
+                        // Guarded leaves are `has(...) && test`, which would bind
+                        // wrongly inside an OR without parentheses
+                        ConditionNode::Leaf { .. } if *operator == LogicalOperator::Or => {
+                            let leaf = self.node(child, indent, errors);
+                            if leaf.contains(" && ") && !leaf.starts_with('(') {
+                                format!("({})", leaf)
+                            } else {
+                                leaf
+                            }
+                        }
+                        _ => self.node(child, indent, errors),
+                    })
+                    .collect();
+                if parts.is_empty() {
+                    empty.to_string()
+                } else {
+                    parts.join(&format!("\n{}{} ", "  ".repeat(indent), symbol))
+                }
+            }
+            ConditionNode::SnippetRef { name, .. } => match self.ctx.snippets.get(name) {
+                Some(snippet) => self.node(&snippet.root, indent, errors),
+                None => {
+                    errors.push(format!("Snippet \"{}\" does not exist", name));
+                    "false".to_string()
+                }
+            },
+        }
+    }
+
+    fn leaf(&self, left: &Operand, operator: &Operator, right: &Operand) -> Result<String, String> {
+        if *operator == Operator::IsEmpty {
+            let Operand::Field { field } = left else {
+                return Err("Is Empty needs a field".to_string());
+            };
+            let reference = self.path(field)?.reference();
+            let blank = format!(
+                "{} == null || string({}).matches(\"^\\\\s*$\")",
+                reference, reference
+            );
+            return Ok(match self.presence(field)? {
+                Some(presence) => format!("(!({}) || {})", presence, blank),
+                None => format!("({})", blank),
+            });
+        }
+
+        let data_type = comparison_type(left, right, self.ctx);
+        let lhs = self.term(left, data_type)?;
+        let test = match operator {
+            Operator::Equals
+            | Operator::NotEquals
+            | Operator::GreaterThan
+            | Operator::LessThan
+            | Operator::GreaterThanOrEqual
+            | Operator::LessThanOrEqual
+            | Operator::Before
+            | Operator::After => {
+                let symbol = match operator {
+                    Operator::Equals => "==",
+                    Operator::NotEquals => "!=",
+                    Operator::GreaterThan | Operator::After => ">",
+                    Operator::LessThan | Operator::Before => "<",
+                    Operator::GreaterThanOrEqual => ">=",
+                    _ => "<=",
+                };
+                format!("{} {} {}", lhs, symbol, self.term(right, data_type)?)
+            }
+            Operator::Between => {
+                let Operand::Range { min, max } = right else {
+                    return Err("Between needs a lower and an upper bound".to_string());
+                };
+                let (Ok(min), Ok(max)) = (min.trim().parse(), max.trim().parse()) else {
+                    return Err("Between bounds must be numbers".to_string());
+                };
+                format!(
+                    "{0} >= {1} && {0} <= {2}",
+                    lhs,
+                    cel_double(min),
+                    cel_double(max)
+                )
+            }
+            Operator::Contains | Operator::StartsWith | Operator::EndsWith | Operator::Regex => {
+                let function = match operator {
+                    Operator::Contains => "contains",
+                    Operator::StartsWith => "startsWith",
+                    Operator::EndsWith => "endsWith",
+                    _ => "matches",
+                };
+                format!(
+                    "{}.{}({})",
+                    lhs,
+                    function,
+                    self.term(right, DataType::String)?
+                )
+            }
+            Operator::In | Operator::NotIn => {
+                let (element_type, values) = list_of(right, self.ctx)?;
+                let values: Vec<String> = values
+                    .iter()
+                    .map(|value| self.literal(value, element_type))
+                    .collect::<Result<_, _>>()?;
+                let test = format!("{} in [{}]", lhs, values.join(", "));
+                if *operator == Operator::NotIn {
+                    format!("!({})", test)
+                } else {
+                    test
+                }
+            }
+            Operator::HourOfDayIn | Operator::DayOfWeekIn => {
+                let (_, values) = list_of(right, self.ctx)?;
+                let values: Vec<String> = clock_values(operator, &values)?
+                    .iter()
+                    .map(u32::to_string)
+                    .collect();
+                format!(
+                    "{}.{}({}) in [{}]",
+                    lhs,
+                    if *operator == Operator::HourOfDayIn {
+                        "getHours"
+                    } else {
+                        "getDayOfWeek"
+                    },
+                    quote(self.ctx.timezone.name()),
+                    values.join(", ")
+                )
+            }
+            Operator::InCidr | Operator::NotInCidr => {
+                return Err("CEL has no IP range functions".to_string())
+            }
+            Operator::WithinLast => return Err("CEL has no current time".to_string()),
+            Operator::IsEmpty => unreachable!("handled above"),
+        };
+
+        let mut guards = Vec::new();
+        for field in fields_of(left).into_iter().chain(fields_of(right)) {
+            if let Some(presence) = self.presence(&field)? {
+                if !guards.contains(&presence) {
+                    guards.push(presence);
+                }
+            }
+        }
+        guards.push(test);
+        Ok(guards.join(" && "))
+    }
+
+    /// A single value: a field read, a literal, or arithmetic in doubles
+    fn term(&self, operand: &Operand, data_type: DataType) -> Result<String, String> {
+        match operand {
+            Operand::Field { field } if field.data_type() == DataType::DateTime => {
+                Ok(format!("timestamp({})", self.path(field)?.reference()))
+            }
+            Operand::Field { field } => Ok(self.path(field)?.reference()),
+            Operand::Expression { expr } => self.expr(expr),
+            Operand::Aggregate { .. } => Err("CEL has no velocity aggregates".to_string()),
+            _ => match literal_of(operand, data_type, self.ctx) {
+                Some((value, data_type)) => self.literal(&value, data_type),
+                None => Err(format!("{} is not a single value", operand.display())),
+            },
+        }
+    }
+
+    fn literal(&self, literal: &str, data_type: DataType) -> Result<String, String> {
+        match data_type {
+            DataType::Number => Ok(match literal.trim().parse::<f64>() {
+                Ok(number) => cel_double(number),
+                Err(_) => quote(literal),
+            }),
+            DataType::String => Ok(quote(literal)),
+            DataType::Ip => Ok(quote(literal.trim())),
+            DataType::DateTime => parse_datetime(literal, self.ctx.timezone)
+                .map(|at| {
+                    format!(
+                        "timestamp({})",
+                        quote(&at.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+                    )
+                })
+                .ok_or_else(|| format!("\"{}\" is not a date/time", literal)),
+        }
+    }
+
+    fn expr(&self, expr: &Expr) -> Result<String, String> {
+        Ok(match expr {
+            Expr::Number { value } => cel_double(*value),
+            Expr::Field { field } => self.path(field)?.reference(),
+            Expr::Negate { expr } => format!("-({})", self.expr(expr)?),
+            Expr::Binary { op, left, right } => format!(
+                "({} {} {})",
+                self.expr(left)?,
+                op.symbol(),
+                self.expr(right)?
+            ),
+        })
+    }
+}
+
+/// The alternatives of an OR group, with nested OR groups flattened into them
+fn or_branches<'n>(
+    node: &'n ConditionNode,
+    ctx: &'n EvalContext,
+    branches: &mut Vec<&'n ConditionNode>,
+) {
+    match resolve(node, ctx) {
+        Some(ConditionNode::Group {
+            operator: LogicalOperator::Or,
+            children,
+            ..
+        }) if !children.is_empty() => {
+            for child in children {
+                or_branches(child, ctx, branches);
+            }
+        }
+        _ => branches.push(node),
+    }
+}
+
+/// Snippets stand for their conditions
+fn resolve<'n>(node: &'n ConditionNode, ctx: &'n EvalContext) -> Option<&'n ConditionNode> {
+    match node {
+        ConditionNode::SnippetRef { name, .. } => {
+            ctx.snippets.get(name).map(|snippet| &snippet.root)
+        }
+        _ => Some(node),
+    }
+}
+
+/// Fields an operand reads, including those inside arithmetic
+fn fields_of(operand: &Operand) -> Vec<Field> {
+    fn expr_fields(expr: &Expr, fields: &mut Vec<Field>) {
+        match expr {
+            Expr::Number { .. } => {}
+            Expr::Field { field } => fields.push(field.clone()),
+            Expr::Negate { expr } => expr_fields(expr, fields),
+            Expr::Binary { left, right, .. } => {
+                expr_fields(left, fields);
+                expr_fields(right, fields);
+            }
+        }
+    }
+    let mut fields = Vec::new();
+    match operand {
+        Operand::Field { field } => fields.push(field.clone()),
+        Operand::Expression { expr } => expr_fields(expr, &mut fields),
+        _ => {}
+    }
+    fields
+}
+
+// ============================================================================
+// Rego
+// ============================================================================
+
+struct RegoWriter<'a> {
+    options: &'a PolicyOptions,
+    ctx: &'a EvalContext,
+    /// Rule definitions in the order they are written
+    rules: Vec<String>,
+    uses_is_blank: bool,
+    errors: Vec<String>,
+}
+
+/// A Rego number: integers without a fraction, others as written by `Debug`
+fn rego_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 2f64.powi(53) {
+        format!("{}", value as i64)
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+/// A set literal; `{}` would be an empty object
+fn rego_set(values: &[String]) -> String {
+    if values.is_empty() {
+        "set()".to_string()
+    } else {
+        format!("{{{}}}", values.join(", "))
+    }
+}
+
+impl RegoWriter<'_> {
+    fn path(&self, field: &Field) -> Result<InputPath, String> {
+        input_path(self.options, field, "input")
+    }
+
+    /// Define `name` as true when `node` holds: one definition per branch of an OR
+    /// group, a single definition otherwise
+    fn define(&mut self, node: &ConditionNode, name: &str) {
+        let mut definitions = vec![format!("default {} := false\n", name)];
+        let mut branches = Vec::new();
+        or_branches(node, self.ctx, &mut branches);
+        let index = self.rules.len();
+        for (i, branch) in branches.iter().enumerate() {
+            let prefix = if name == "match" { "cond" } else { name };
+            let prefix = if branches.len() > 1 {
+                format!("{}_{}", prefix, i)
+            } else {
+                prefix.to_string()
+            };
+            let mut body = self.body(branch, &prefix);
+            if body.is_empty() {
+                body.push("true".to_string());
+            }
+            definitions.push(format!(
+                "{} if {{\n{}}}\n",
+                name,
+                body.iter()
+                    .map(|line| format!("\t{}\n", line))
+                    .collect::<String>()
+            ));
+        }
+        self.rules.insert(index, definitions.join("\n"));
+    }
+
+    /// Statements that all hold when `node` does. Nested OR groups become helper
+    /// rules named `prefix` plus the child's position.
+    fn body(&mut self, node: &ConditionNode, prefix: &str) -> Vec<String> {
+        let Some(node) = resolve(node, self.ctx) else {
+            if let ConditionNode::SnippetRef { name, .. } = node {
+                self.errors
+                    .push(format!("Snippet \"{}\" does not exist", name));
+            }
+            return vec!["false".to_string()];
+        };
+        match node {
+            ConditionNode::Leaf {
+                left,
+                operator,
+                right,
+                ..
+            } => self.leaf(left, operator, right).unwrap_or_else(|reason| {
+                self.errors.push(format!("{}: {}", node.display(), reason));
+                Vec::new()
+            }),
+            ConditionNode::Group {
+                operator: LogicalOperator::And,
+                children,
+                ..
+            } => {
+                let mut statements = Vec::new();
+                for (i, child) in children.iter().enumerate() {
+                    statements.extend(self.body(child, &format!("{}_{}", prefix, i)));
+                }
+                statements
+            }
+            ConditionNode::Group {
+                operator: LogicalOperator::Or,
+                children,
+                ..
+            } if children.len() <= 1 => match children.first() {
+                Some(child) => self.body(child, &format!("{}_0", prefix)),
+                None => vec!["false".to_string()],
+            },
+            ConditionNode::Group { .. } => {
+                self.define(node, prefix);
+                vec![prefix.to_string()]
+            }
+            ConditionNode::SnippetRef { .. } => unreachable!("resolved above"),
+        }
+    }
+
+    fn leaf(
+        &mut self,
+        left: &Operand,
+        operator: &Operator,
+        right: &Operand,
+    ) -> Result<Vec<String>, String> {
+        if *operator == Operator::IsEmpty {
+            let Operand::Field { field } = left else {
+                return Err("Is Empty needs a field".to_string());
+            };
+            let path = self.path(field)?;
+            let keys: Vec<String> = path.keys.iter().map(|key| quote(key)).collect();
+            self.uses_is_blank = true;
+            return Ok(vec![format!(
+                "is_blank(object.get({}, [{}], null))",
+                path.root,
+                keys.join(", ")
+            )]);
+        }
+
+        let data_type = comparison_type(left, right, self.ctx);
+        let lhs = self.term(left, data_type)?;
+        // Negated tests hold for null, which the evaluator treats as missing
+        let present = |writer: &Self| -> Result<Vec<String>, String> {
+            fields_of(left)
+                .iter()
+                .map(|field| Ok(format!("{} != null", writer.path(field)?.reference())))
+                .collect()
+        };
+        let statements = match operator {
+            Operator::Equals
+            | Operator::GreaterThan
+            | Operator::LessThan
+            | Operator::GreaterThanOrEqual
+            | Operator::LessThanOrEqual
+            | Operator::Before
+            | Operator::After => {
+                let symbol = match operator {
+                    Operator::Equals => "==",
+                    Operator::GreaterThan | Operator::After => ">",
+                    Operator::LessThan | Operator::Before => "<",
+                    Operator::GreaterThanOrEqual => ">=",
+                    _ => "<=",
+                };
+                vec![format!(
+                    "{} {} {}",
+                    lhs,
+                    symbol,
+                    self.term(right, data_type)?
+                )]
+            }
+            Operator::NotEquals => {
+                let mut statements = present(self)?;
+                statements.push(format!("{} != {}", lhs, self.term(right, data_type)?));
+                statements
+            }
+            Operator::Between => {
+                let Operand::Range { min, max } = right else {
+                    return Err("Between needs a lower and an upper bound".to_string());
+                };
+                let (Ok(min), Ok(max)) = (min.trim().parse(), max.trim().parse()) else {
+                    return Err("Between bounds must be numbers".to_string());
+                };
+                vec![
+                    format!("{} >= {}", lhs, rego_number(min)),
+                    format!("{} <= {}", lhs, rego_number(max)),
+                ]
+            }
+            Operator::Contains | Operator::StartsWith | Operator::EndsWith => {
+                let function = match operator {
+                    Operator::Contains => "contains",
+                    Operator::StartsWith => "startswith",
+                    _ => "endswith",
+                };
+                vec![format!(
+                    "{}({}, {})",
+                    function,
+                    lhs,
+                    self.term(right, DataType::String)?
+                )]
+            }
+            Operator::Regex => vec![format!(
+                "regex.match({}, {})",
+                self.term(right, DataType::String)?,
+                lhs
+            )],
+            Operator::In | Operator::NotIn => {
+                let (element_type, values) = list_of(right, self.ctx)?;
+                let values: Vec<String> = values
+                    .iter()
+                    .map(|value| self.literal(value, element_type))
+                    .collect::<Result<_, _>>()?;
+                if *operator == Operator::In {
+                    vec![format!("{} in {}", lhs, rego_set(&values))]
+                } else {
+                    let mut statements = present(self)?;
+                    statements.push(format!("not {} in {}", lhs, rego_set(&values)));
+                    statements
+                }
+            }
+            Operator::InCidr | Operator::NotInCidr => {
+                let (_, values) = list_of(right, self.ctx)?;
+                let ranges: Vec<String> = values
+                    .iter()
+                    .map(|value| {
+                        parse_ip_range(value)
+                            .map(|range| quote(&range.to_string()))
+                            .ok_or_else(|| format!("\"{}\" is not an IP range", value))
+                    })
+                    .collect::<Result<_, _>>()?;
+                let matches = format!(
+                    "count(net.cidr_contains_matches({}, {}))",
+                    rego_set(&ranges),
+                    lhs
+                );
+                if *operator == Operator::InCidr {
+                    vec![format!("{} > 0", matches)]
+                } else {
+                    let mut statements = present(self)?;
+                    statements.push(format!("{} == 0", matches));
+                    statements
+                }
+            }
+            Operator::WithinLast => {
+                let Operand::Duration { window } = right else {
+                    return Err("Within Last needs a duration".to_string());
+                };
+                vec![
+                    format!(
+                        "time.now_ns() - {} <= {}",
+                        lhs,
+                        window.seconds() * 1_000_000_000
+                    ),
+                    format!("{} <= time.now_ns()", lhs),
+                ]
+            }
+            Operator::HourOfDayIn | Operator::DayOfWeekIn => {
+                let (_, values) = list_of(right, self.ctx)?;
+                let values = clock_values(operator, &values)?;
+                let timezone = quote(self.ctx.timezone.name());
+                if *operator == Operator::HourOfDayIn {
+                    let hours: Vec<String> = values.iter().map(u32::to_string).collect();
+                    vec![format!(
+                        "time.clock([{}, {}])[0] in {}",
+                        lhs,
+                        timezone,
+                        rego_set(&hours)
+                    )]
+                } else {
+                    let days: Vec<String> =
+                        values.iter().map(|day| quote(weekday_name(*day))).collect();
+                    vec![format!(
+                        "time.weekday([{}, {}]) in {}",
+                        lhs,
+                        timezone,
+                        rego_set(&days)
+                    )]
+                }
+            }
+            Operator::IsEmpty => unreachable!("handled above"),
+        };
+        Ok(statements)
+    }
+
+    /// A single value; date/times are nanoseconds since the epoch
+    fn term(&self, operand: &Operand, data_type: DataType) -> Result<String, String> {
+        match operand {
+            Operand::Field { field } if field.data_type() == DataType::DateTime => Ok(format!(
+                "time.parse_rfc3339_ns({})",
+                self.path(field)?.reference()
+            )),
+            Operand::Field { field } => Ok(self.path(field)?.reference()),
+            Operand::Expression { expr } => self.expr(expr),
+            Operand::Aggregate { .. } => Err("Rego has no velocity aggregates".to_string()),
+            _ => match literal_of(operand, data_type, self.ctx) {
+                Some((value, data_type)) => self.literal(&value, data_type),
+                None => Err(format!("{} is not a single value", operand.display())),
+            },
+        }
+    }
+
+    fn literal(&self, literal: &str, data_type: DataType) -> Result<String, String> {
+        match data_type {
+            DataType::Number => Ok(match literal.trim().parse::<f64>() {
+                Ok(number) => rego_number(number),
+                Err(_) => quote(literal),
+            }),
+            DataType::String => Ok(quote(literal)),
+            DataType::Ip => Ok(quote(literal.trim())),
+            DataType::DateTime => parse_datetime(literal, self.ctx.timezone)
+                .and_then(|at| at.timestamp_nanos_opt())
+                .map(|nanos| nanos.to_string())
+                .ok_or_else(|| format!("\"{}\" is not a date/time", literal)),
+        }
+    }
+
+    fn expr(&self, expr: &Expr) -> Result<String, String> {
+        Ok(match expr {
+            Expr::Number { value } => rego_number(*value),
+            Expr::Field { field } => self.path(field)?.reference(),
+            Expr::Negate { expr } => format!("(0 - {})", self.expr(expr)?),
+            Expr::Binary { op, left, right } => format!(
+                "({} {} {})",
+                self.expr(left)?,
+                op.symbol(),
+                self.expr(right)?
+            ),
+        })
+    }
+}
@@ -0,0 +1,340 @@
+//! Rewriting condition trees into simpler equivalent ones: flattening and unwrapping
+//! redundant groups, sorting and deduplicating siblings, and converting to
+//! disjunctive or conjunctive normal form.
+//!
+//! Rewrites keep what a rule matches and, for scoring rules, every transaction's
+//! score: weights of merged nodes are added together. An empty group is a constant,
+//! as the evaluator reads it: an empty AND always matches and an empty OR never does.
+
+use crate::models::{ConditionNode, LogicalOperator};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Normal forms with more terms than this are refused rather than built
+pub const MAX_TERMS: usize = 256;
+
+/// The shape to rewrite a condition tree into
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalForm {
+    /// Simplified, keeping the tree's structure otherwise
+    #[default]
+    Simplified,
+    /// An OR of AND groups
+    Dnf,
+    /// An AND of OR groups
+    Cnf,
+}
+
+impl NormalForm {
+    pub fn all() -> Vec<NormalForm> {
+        vec![NormalForm::Simplified, NormalForm::Dnf, NormalForm::Cnf]
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NormalForm::Simplified => "simplified",
+            NormalForm::Dnf => "dnf",
+            NormalForm::Cnf => "cnf",
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            NormalForm::Simplified => "Simplified",
+            NormalForm::Dnf => "Disjunctive normal form (OR of ANDs)",
+            NormalForm::Cnf => "Conjunctive normal form (AND of ORs)",
+        }
+    }
+}
+
+/// Rewrite a tree into `form`; normal forms fail for weighted trees and when they
+/// would have more than [`MAX_TERMS`] terms
+pub fn normalize(node: &ConditionNode, form: NormalForm) -> Result<ConditionNode, String> {
+    match form {
+        NormalForm::Simplified => Ok(simplify(node)),
+        NormalForm::Dnf => normal_form(node, LogicalOperator::Or, form),
+        NormalForm::Cnf => normal_form(node, LogicalOperator::And, form),
+    }
+}
+
+/// Simplify a tree: groups nested in a group with the same operator are merged into
+/// it, single-child groups are replaced by their child, and identical siblings are
+/// merged and sorted with conditions before groups. Empty groups are folded like
+/// constants: an empty AND is dropped from an AND and turns an OR into an empty AND,
+/// and an empty OR is dropped from an OR and turns an AND into an empty OR. Folding
+/// that would lose weights of a scoring rule is skipped.
+///
+/// ```
+/// use rule_engine::models::{ConditionNode, LogicalOperator};
+/// use rule_engine::simplify::simplify;
+///
+/// let node: ConditionNode = serde_json::from_value(serde_json::json!({
+///     "type": "group", "id": "0b5c2b1e-3f3a-4d7e-8c55-2f0e6f4a9d01", "operator": "AND",
+///     "children": [{
+///         "type": "group", "id": "1a2b3c4d-5e6f-4a7b-8c9d-0e1f2a3b4c5d", "operator": "AND",
+///         "children": [{
+///             "type": "leaf", "id": "9d7e4c2a-1b3f-4e5d-8a6c-7f8e9d0a1b2c",
+///             "left": { "type": "field", "field": "transaction_amount" },
+///             "operator": "greater_than",
+///             "right": { "type": "value", "value": "1000" }
+///         }]
+///     }, {
+///         "type": "group", "id": "2b3c4d5e-6f7a-4b8c-9d0e-1f2a3b4c5d6e", "operator": "AND",
+///         "children": []
+///     }]
+/// }))
+/// .unwrap();
+///
+/// // The empty AND always matches, so it adds nothing to the outer AND
+/// let simplified = simplify(&node);
+/// assert!(simplified.is_leaf());
+/// assert_eq!(simplified.display(), "Transaction Amount Greater Than \"1000\"");
+/// ```
+pub fn simplify(node: &ConditionNode) -> ConditionNode {
+    let ConditionNode::Group {
+        id,
+        operator,
+        children,
+        weight,
+    } = node
+    else {
+        return node.clone();
+    };
+
+    let mut merged: Vec<ConditionNode> = Vec::new();
+    for child in children.iter().map(simplify) {
+        let flattened = match child {
+            // An empty group of the same operator changes nothing, unless it is an
+            // empty AND whose weight is always added
+            ConditionNode::Group {
+                operator: ref child_operator,
+                ref children,
+                weight: ref child_weight,
+                ..
+            } if children.is_empty() && child_operator == operator => {
+                if child_weight.is_none() || *child_operator == LogicalOperator::Or {
+                    continue;
+                }
+                vec![child]
+            }
+            ConditionNode::Group {
+                operator: ref child_operator,
+                children,
+                weight: None,
+                ..
+            } if child_operator == operator => children,
+            child => vec![child],
+        };
+        for child in flattened {
+            // A repeat adds nothing but its weight, unless weights inside it would
+            // be counted once instead of twice
+            let same = (!has_inner_weights(&child))
+                .then(|| merged.iter().position(|other| key(other) == key(&child)))
+                .flatten();
+            match same {
+                Some(index) => {
+                    let total = add_weights(merged[index].weight(), child.weight());
+                    merged[index].set_weight(total);
+                }
+                None => merged.push(child),
+            }
+        }
+    }
+    merged.sort_by_cached_key(|child| (child.is_group(), child.display(), key(child)));
+
+    // An empty group of the other operator decides the whole group: an empty OR makes
+    // an AND never match, and an empty AND makes an OR always match
+    let absorbing = match operator {
+        LogicalOperator::And => LogicalOperator::Or,
+        LogicalOperator::Or => LogicalOperator::And,
+    };
+    let is_absorbing = |child: &ConditionNode| {
+        matches!(child, ConditionNode::Group { operator, children, .. }
+            if *operator == absorbing && children.is_empty())
+    };
+    if merged.iter().any(is_absorbing) {
+        let weights_elsewhere = merged.iter().any(|child| {
+            !is_absorbing(child) && (child.weight().is_some() || has_inner_weights(child))
+        });
+        if !weights_elsewhere {
+            // A group that always matches keeps its weight and those of the empty
+            // ANDs deciding it; one that never matches has no weight to keep
+            let weight = match absorbing {
+                LogicalOperator::And => merged
+                    .iter()
+                    .map(ConditionNode::weight)
+                    .fold(*weight, add_weights),
+                LogicalOperator::Or => None,
+            };
+            return ConditionNode::Group {
+                id: *id,
+                operator: absorbing,
+                children: Vec::new(),
+                weight,
+            };
+        }
+    }
+
+    if merged.len() == 1 {
+        let mut only = merged.remove(0);
+        only.set_weight(add_weights(only.weight(), *weight));
+        return only;
+    }
+    ConditionNode::Group {
+        id: *id,
+        operator: operator.clone(),
+        children: merged,
+        weight: *weight,
+    }
+}
+
+fn add_weights(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (None, None) => None,
+        _ => Some(a.unwrap_or(0.0) + b.unwrap_or(0.0)),
+    }
+}
+
+/// Whether any node below this one has a weight
+fn has_inner_weights(node: &ConditionNode) -> bool {
+    match node {
+        ConditionNode::Group { children, .. } => children
+            .iter()
+            .any(|child| child.weight().is_some() || has_inner_weights(child)),
+        _ => false,
+    }
+}
+
+/// The node as JSON without ids or weights, so equal conditions have equal keys
+fn key(node: &ConditionNode) -> String {
+    fn strip(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(map) => {
+                map.remove("id");
+                map.remove("weight");
+                map.values_mut().for_each(strip);
+            }
+            serde_json::Value::Array(items) => items.iter_mut().for_each(strip),
+            _ => {}
+        }
+    }
+    let mut value = serde_json::to_value(node).unwrap_or_default();
+    strip(&mut value);
+    value.to_string()
+}
+
+/// Rewrite into groups of `outer` whose children are groups of the other operator
+/// over conditions and snippet references
+fn normal_form(
+    node: &ConditionNode,
+    outer: LogicalOperator,
+    form: NormalForm,
+) -> Result<ConditionNode, String> {
+    if node.weight().is_some() || has_inner_weights(node) {
+        return Err(format!(
+            "Weighted conditions cannot be rewritten into {}; weights would be lost",
+            form.display_name().to_lowercase()
+        ));
+    }
+    let simplified = simplify(node);
+    let mut terms = terms(&simplified, &outer, form)?;
+
+    // Drop repeated conditions within a term, then terms that another term absorbs:
+    // `a OR (a AND b)` is `a`, and `a AND (a OR b)` is `a`
+    let mut keyed: Vec<(HashSet<String>, Vec<ConditionNode>)> = Vec::new();
+    for term in terms.drain(..) {
+        let mut keys = HashSet::new();
+        let atoms: Vec<ConditionNode> = term
+            .into_iter()
+            .filter(|atom| keys.insert(key(atom)))
+            .collect();
+        keyed.push((keys, atoms));
+    }
+    let absorbed: Vec<bool> = keyed
+        .iter()
+        .enumerate()
+        .map(|(i, (keys, _))| {
+            keyed.iter().enumerate().any(|(j, (other, _))| {
+                j != i && other.is_subset(keys) && (other.len() < keys.len() || j < i)
+            })
+        })
+        .collect();
+
+    let inner = match outer {
+        LogicalOperator::And => LogicalOperator::Or,
+        LogicalOperator::Or => LogicalOperator::And,
+    };
+    // Conditions repeated across terms keep their id once and get fresh ids after
+    let mut used_ids = HashSet::new();
+    let mut fresh = |atom: ConditionNode| {
+        if used_ids.insert(atom.id()) {
+            atom
+        } else {
+            atom.with_new_ids()
+        }
+    };
+    let children = keyed
+        .into_iter()
+        .zip(absorbed)
+        .filter(|(_, absorbed)| !absorbed)
+        .map(|((_, atoms), _)| ConditionNode::Group {
+            id: Uuid::new_v4(),
+            operator: inner.clone(),
+            children: atoms.into_iter().map(&mut fresh).collect(),
+            weight: None,
+        })
+        .collect();
+    Ok(simplify(&ConditionNode::Group {
+        id: node.id(),
+        operator: outer,
+        children,
+        weight: None,
+    }))
+}
+
+/// The terms of a node's normal form: an `outer` group over terms that are each an
+/// inner group over their conditions
+fn terms(
+    node: &ConditionNode,
+    outer: &LogicalOperator,
+    form: NormalForm,
+) -> Result<Vec<Vec<ConditionNode>>, String> {
+    let ConditionNode::Group {
+        operator, children, ..
+    } = node
+    else {
+        return Ok(vec![vec![node.clone()]]);
+    };
+    if operator == outer {
+        let mut all = Vec::new();
+        for child in children {
+            all.extend(terms(child, outer, form)?);
+        }
+        return Ok(all);
+    }
+    // Distribute: one term for each way of picking a term from every child
+    let mut product: Vec<Vec<ConditionNode>> = vec![Vec::new()];
+    for child in children {
+        let child_terms = terms(child, outer, form)?;
+        if product.len() * child_terms.len() > MAX_TERMS {
+            return Err(format!(
+                "The {} would have more than {} terms",
+                form.display_name().to_lowercase(),
+                MAX_TERMS
+            ));
+        }
+        product = product
+            .iter()
+            .flat_map(|term| {
+                child_terms.iter().map(move |child_term| {
+                    let mut combined = term.clone();
+                    combined.extend(child_term.iter().cloned());
+                    combined
+                })
+            })
+            .collect();
+    }
+    Ok(product)
+}
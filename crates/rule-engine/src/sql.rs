@@ -0,0 +1,583 @@
+//! Transpile condition trees into parameterized SQL predicates, so rules can be
+//! backtested in a warehouse against historical transactions.
+
+use crate::evaluator::EvalContext;
+use crate::expression::{ArithOp, Expr};
+use crate::models::{
+    parse_datetime, parse_hour, parse_weekday, ConditionNode, DataType, Field, LogicalOperator,
+    Operand, Operator, Rule, RuleMode, WindowUnit,
+};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Always true and always false, in a form every dialect accepts
+const TRUE: &str = "1 = 1";
+const FALSE: &str = "1 = 0";
+
+/// The SQL dialect a predicate is written for
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SqlDialect {
+    /// `?` placeholders and date/times stored as Unix seconds. `LIKE` ignores ASCII case
+    /// unless `PRAGMA case_sensitive_like` is on, and `REGEXP` needs a loaded `regexp()`.
+    Sqlite,
+    /// `$1` placeholders and `timestamptz` date/times; IP ranges use the `inet` type
+    #[default]
+    Postgres,
+    /// Standard SQL with `?` placeholders and `TIMESTAMP WITH TIME ZONE` date/times
+    Ansi,
+}
+
+impl SqlDialect {
+    pub fn all() -> Vec<SqlDialect> {
+        vec![SqlDialect::Sqlite, SqlDialect::Postgres, SqlDialect::Ansi]
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            SqlDialect::Sqlite => "sqlite",
+            SqlDialect::Postgres => "postgres",
+            SqlDialect::Ansi => "ansi",
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        match self {
+            SqlDialect::Sqlite => "SQLite",
+            SqlDialect::Postgres => "PostgreSQL",
+            SqlDialect::Ansi => "ANSI SQL",
+        }
+    }
+}
+
+/// How to write a predicate: the dialect, and the column each field is stored in
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SqlOptions {
+    #[serde(default)]
+    pub dialect: SqlDialect,
+    /// Column for each field, optionally qualified (`t.amount`); unmapped fields
+    /// use the field name
+    #[serde(default)]
+    pub columns: HashMap<Field, String>,
+}
+
+/// A value bound to a placeholder
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum SqlParam {
+    Integer(i64),
+    Number(f64),
+    Text(String),
+}
+
+impl std::fmt::Display for SqlParam {
+    /// The value as a SQL literal
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SqlParam::Integer(n) => write!(f, "{}", n),
+            SqlParam::Number(n) => write!(f, "{}", n),
+            SqlParam::Text(s) => write!(f, "'{}'", s.replace('\'', "''")),
+        }
+    }
+}
+
+/// A predicate for a `WHERE` clause and the values of its placeholders, in order.
+///
+/// Conditions on a NULL column are unknown, which `WHERE` treats as false, just as
+/// the evaluator treats a missing field; Is Empty matches NULL explicitly.
+#[derive(Debug, Clone, Serialize)]
+pub struct SqlPredicate {
+    pub sql: String,
+    pub params: Vec<SqlParam>,
+}
+
+/// Validate a boolean rule and transpile its conditions. Local date/times, hours and
+/// weekdays are read in `ctx.timezone`, so pass the rule's timezone as for evaluation.
+pub fn rule_to_sql(
+    rule: &Rule,
+    options: &SqlOptions,
+    ctx: &EvalContext,
+) -> Result<SqlPredicate, Vec<String>> {
+    if rule.mode == RuleMode::Scoring {
+        return Err(vec![
+            "Scoring rules cannot be exported as SQL predicates; only boolean rules can"
+                .to_string(),
+        ]);
+    }
+    rule.validate(ctx)?;
+    condition_to_sql(&rule.root, options, ctx)
+}
+
+/// Transpile a condition tree, inlining snippets, reference lists and parameters from
+/// `ctx`. Fails listing the conditions the dialect cannot express, such as velocity
+/// aggregates.
+///
+/// ```
+/// use rule_engine::models::{ConditionNode, Field, Operand, Operator};
+/// use rule_engine::sql::{condition_to_sql, SqlDialect, SqlOptions, SqlParam};
+/// use rule_engine::EvalContext;
+///
+/// let node = ConditionNode::Leaf {
+///     id: uuid::Uuid::new_v4(),
+///     left: Operand::Field { field: Field::DeviceFingerprint },
+///     operator: Operator::Contains,
+///     right: Operand::Value { value: "50%_off".to_string() },
+///     weight: None,
+/// };
+/// let options = SqlOptions {
+///     dialect: SqlDialect::Postgres,
+///     columns: [(Field::DeviceFingerprint, "t.device".to_string())].into(),
+/// };
+/// let predicate = condition_to_sql(&node, &options, &EvalContext::default()).unwrap();
+/// assert_eq!(predicate.sql, r#""t"."device" LIKE $1 ESCAPE '\'"#);
+/// assert_eq!(predicate.params, vec![SqlParam::Text(r"%50\%\_off%".to_string())]);
+/// ```
+pub fn condition_to_sql(
+    node: &ConditionNode,
+    options: &SqlOptions,
+    ctx: &EvalContext,
+) -> Result<SqlPredicate, Vec<String>> {
+    let mut writer = SqlWriter {
+        options,
+        ctx,
+        params: Vec::new(),
+        errors: Vec::new(),
+    };
+    let sql = writer.node(node);
+    if writer.errors.is_empty() {
+        Ok(SqlPredicate {
+            sql,
+            params: writer.params,
+        })
+    } else {
+        Err(writer.errors)
+    }
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Escape `LIKE` wildcards so the value matches literally, with `\` as the escape character
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+struct SqlWriter<'a> {
+    options: &'a SqlOptions,
+    ctx: &'a EvalContext,
+    params: Vec<SqlParam>,
+    errors: Vec<String>,
+}
+
+impl SqlWriter<'_> {
+    fn dialect(&self) -> SqlDialect {
+        self.options.dialect
+    }
+
+    /// Add a parameter and return its placeholder. Placeholders must be written in
+    /// the order they are bound.
+    fn bind(&mut self, param: SqlParam) -> String {
+        self.params.push(param);
+        match self.dialect() {
+            SqlDialect::Postgres => format!("${}", self.params.len()),
+            SqlDialect::Sqlite | SqlDialect::Ansi => "?".to_string(),
+        }
+    }
+
+    fn column(&self, field: &Field) -> String {
+        let name = self
+            .options
+            .columns
+            .get(field)
+            .map(String::as_str)
+            .unwrap_or(field.as_str());
+        name.split('.')
+            .map(quote_identifier)
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    fn node(&mut self, node: &ConditionNode) -> String {
+        match node {
+            ConditionNode::Leaf {
+                left,
+                operator,
+                right,
+                ..
+            } => match self.leaf(left, operator, right) {
+                Ok(sql) => sql,
+                Err(reason) => {
+                    self.errors.push(format!("{}: {}", node.display(), reason));
+                    FALSE.to_string()
+                }
+            },
+            ConditionNode::Group {
+                operator, children, ..
+            } => {
+                let mut parts: Vec<String> =
+                    children.iter().map(|child| self.node(child)).collect();
+                match parts.len() {
+                    0 => match operator {
+                        LogicalOperator::And => TRUE.to_string(),
+                        LogicalOperator::Or => FALSE.to_string(),
+                    },
+                    1 => parts.remove(0),
+                    _ => format!("({})", parts.join(&format!(" {} ", operator))),
+                }
+            }
+            ConditionNode::SnippetRef { name, .. } => match self.ctx.snippets.get(name) {
+                Some(snippet) => self.node(&snippet.root),
+                None => {
+                    self.errors
+                        .push(format!("Snippet \"{}\" does not exist", name));
+                    FALSE.to_string()
+                }
+            },
+        }
+    }
+
+    fn operand_type(&self, operand: &Operand) -> DataType {
+        match operand {
+            Operand::Field { field } => field.data_type(),
+            Operand::Parameter { name } => self
+                .ctx
+                .parameters
+                .get(name)
+                .map_or(DataType::String, |parameter| parameter.data_type),
+            Operand::Value { value } if value.trim().parse::<f64>().is_err() => DataType::String,
+            _ => DataType::Number,
+        }
+    }
+
+    fn leaf(
+        &mut self,
+        left: &Operand,
+        operator: &Operator,
+        right: &Operand,
+    ) -> Result<String, String> {
+        // A literal on the left takes the type of a field on the right
+        let data_type = match (left, right) {
+            (Operand::Value { .. }, Operand::Field { field }) => field.data_type(),
+            _ => self.operand_type(left),
+        };
+        let lhs = self.scalar(left, data_type)?;
+
+        let sql = match operator {
+            Operator::IsEmpty if data_type == DataType::String => {
+                // Bound again, as positional placeholders are used once each
+                let again = self.scalar(left, data_type)?;
+                format!("({} IS NULL OR TRIM({}) = '')", lhs, again)
+            }
+            Operator::IsEmpty => format!("{} IS NULL", lhs),
+            Operator::Equals
+            | Operator::NotEquals
+            | Operator::GreaterThan
+            | Operator::LessThan
+            | Operator::GreaterThanOrEqual
+            | Operator::LessThanOrEqual
+            | Operator::Before
+            | Operator::After => {
+                let symbol = match operator {
+                    Operator::Equals => "=",
+                    Operator::NotEquals => "<>",
+                    Operator::GreaterThan | Operator::After => ">",
+                    Operator::LessThan | Operator::Before => "<",
+                    Operator::GreaterThanOrEqual => ">=",
+                    _ => "<=",
+                };
+                format!("{} {} {}", lhs, symbol, self.scalar(right, data_type)?)
+            }
+            Operator::Between => {
+                let Operand::Range { min, max } = right else {
+                    return Err("Between needs a lower and an upper bound".to_string());
+                };
+                let (min, max) = match (min.trim().parse(), max.trim().parse()) {
+                    (Ok(min), Ok(max)) => (min, max),
+                    _ => return Err("Between bounds must be numbers".to_string()),
+                };
+                format!(
+                    "{} BETWEEN {} AND {}",
+                    lhs,
+                    self.bind(SqlParam::Number(min)),
+                    self.bind(SqlParam::Number(max))
+                )
+            }
+            Operator::Contains | Operator::StartsWith | Operator::EndsWith => {
+                let value = self.pattern_literal(right)?;
+                let pattern = match operator {
+                    Operator::Contains => format!("%{}%", escape_like(&value)),
+                    Operator::StartsWith => format!("{}%", escape_like(&value)),
+                    _ => format!("%{}", escape_like(&value)),
+                };
+                format!(
+                    "{} LIKE {} ESCAPE '\\'",
+                    lhs,
+                    self.bind(SqlParam::Text(pattern))
+                )
+            }
+            Operator::Regex => {
+                let keyword = match self.dialect() {
+                    SqlDialect::Postgres => "~",
+                    SqlDialect::Sqlite => "REGEXP",
+                    SqlDialect::Ansi => {
+                        return Err("ANSI SQL has no regular expression match".to_string())
+                    }
+                };
+                format!(
+                    "{} {} {}",
+                    lhs,
+                    keyword,
+                    self.scalar(right, DataType::String)?
+                )
+            }
+            Operator::In | Operator::NotIn => {
+                let (element_type, values) = self.list(right)?;
+                let negated = *operator == Operator::NotIn;
+                if values.is_empty() {
+                    return Ok(self.empty_list(&lhs, negated));
+                }
+                let placeholders = values
+                    .iter()
+                    .map(|value| self.literal(value, element_type))
+                    .collect::<Result<Vec<_>, _>>()?;
+                format!(
+                    "{} {}IN ({})",
+                    lhs,
+                    if negated { "NOT " } else { "" },
+                    placeholders.join(", ")
+                )
+            }
+            Operator::InCidr | Operator::NotInCidr => {
+                if self.dialect() != SqlDialect::Postgres {
+                    return Err(format!(
+                        "{} has no IP range type",
+                        self.dialect().display_name()
+                    ));
+                }
+                let (_, ranges) = self.list(right)?;
+                let negated = *operator == Operator::NotInCidr;
+                if ranges.is_empty() {
+                    return Ok(self.empty_list(&lhs, negated));
+                }
+                let mut tests = Vec::new();
+                for (i, range) in ranges.iter().enumerate() {
+                    let address = if i == 0 {
+                        lhs.clone()
+                    } else {
+                        self.scalar(left, data_type)?
+                    };
+                    tests.push(format!(
+                        "CAST({} AS INET) <<= CAST({} AS INET)",
+                        address,
+                        self.bind(SqlParam::Text(range.trim().to_string()))
+                    ));
+                }
+                let any = if tests.len() == 1 {
+                    tests.remove(0)
+                } else {
+                    format!("({})", tests.join(" OR "))
+                };
+                if negated {
+                    format!("NOT {}", any)
+                } else {
+                    any
+                }
+            }
+            Operator::WithinLast => {
+                let Operand::Duration { window } = right else {
+                    return Err("Within Last needs a duration".to_string());
+                };
+                let (amount, unit) = match window.unit {
+                    WindowUnit::Minutes => (window.amount, "MINUTE"),
+                    WindowUnit::Hours => (window.amount, "HOUR"),
+                    WindowUnit::Days => (window.amount, "DAY"),
+                };
+                match self.dialect() {
+                    SqlDialect::Sqlite => format!(
+                        "{0} BETWEEN {1} - {2} AND {1}",
+                        lhs,
+                        "CAST(strftime('%s', 'now') AS INTEGER)",
+                        window.seconds()
+                    ),
+                    SqlDialect::Postgres => format!(
+                        "{} BETWEEN CURRENT_TIMESTAMP - INTERVAL '{} {}' AND CURRENT_TIMESTAMP",
+                        lhs, amount, unit
+                    ),
+                    SqlDialect::Ansi => format!(
+                        "{} BETWEEN CURRENT_TIMESTAMP - INTERVAL '{}' {} AND CURRENT_TIMESTAMP",
+                        lhs, amount, unit
+                    ),
+                }
+            }
+            Operator::HourOfDayIn | Operator::DayOfWeekIn => {
+                let hours = *operator == Operator::HourOfDayIn;
+                let part = match self.dialect() {
+                    SqlDialect::Postgres => format!(
+                        "EXTRACT({} FROM {} AT TIME ZONE {})",
+                        if hours { "HOUR" } else { "DOW" },
+                        lhs,
+                        self.bind(SqlParam::Text(self.ctx.timezone.name().to_string()))
+                    ),
+                    SqlDialect::Sqlite if self.ctx.timezone == Tz::UTC => format!(
+                        "CAST(strftime('{}', {}, 'unixepoch') AS INTEGER)",
+                        if hours { "%H" } else { "%w" },
+                        lhs
+                    ),
+                    SqlDialect::Sqlite => {
+                        return Err(format!(
+                            "SQLite cannot convert times to {}",
+                            self.ctx.timezone.name()
+                        ))
+                    }
+                    SqlDialect::Ansi => {
+                        return Err("ANSI SQL cannot convert times to a named timezone".to_string())
+                    }
+                };
+                let (_, values) = self.list(right)?;
+                let numbers = values
+                    .iter()
+                    .map(|value| {
+                        let number = if hours {
+                            parse_hour(value)
+                        } else {
+                            parse_weekday(value).map(|day| day.num_days_from_sunday())
+                        };
+                        number
+                            .map(|n| self.bind(SqlParam::Integer(i64::from(n))))
+                            .ok_or_else(|| format!("\"{}\" is not a valid list element", value))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                if numbers.is_empty() {
+                    return Ok(FALSE.to_string());
+                }
+                format!("{} IN ({})", part, numbers.join(", "))
+            }
+        };
+        Ok(sql)
+    }
+
+    /// An In list with no elements matches nothing; Not In matches any present value
+    fn empty_list(&self, lhs: &str, negated: bool) -> String {
+        if negated {
+            format!("{} IS NOT NULL", lhs)
+        } else {
+            FALSE.to_string()
+        }
+    }
+
+    /// SQL for a single-valued operand compared as `data_type`
+    fn scalar(&mut self, operand: &Operand, data_type: DataType) -> Result<String, String> {
+        match operand {
+            Operand::Field { field } => Ok(self.column(field)),
+            Operand::Value { value } => self.literal(value, data_type),
+            Operand::Parameter { name } => {
+                let parameter = self
+                    .ctx
+                    .parameters
+                    .get(name)
+                    .ok_or_else(|| format!("parameter ${} does not exist", name))?;
+                self.literal(&parameter.value, parameter.data_type)
+            }
+            Operand::Expression { expr } => Ok(self.expr(expr)),
+            Operand::Aggregate { .. } => {
+                Err("velocity aggregates have no SQL equivalent".to_string())
+            }
+            _ => Err(format!("{} is not a single value", operand.display())),
+        }
+    }
+
+    /// Bind a literal as `data_type`, reading local date/times in the rule's timezone
+    fn literal(&mut self, literal: &str, data_type: DataType) -> Result<String, String> {
+        let param = match data_type {
+            DataType::Number => match literal.trim().parse() {
+                Ok(number) => SqlParam::Number(number),
+                Err(_) => SqlParam::Text(literal.to_string()),
+            },
+            DataType::String => SqlParam::Text(literal.to_string()),
+            DataType::Ip => SqlParam::Text(literal.trim().to_string()),
+            DataType::DateTime => {
+                let at = parse_datetime(literal, self.ctx.timezone)
+                    .ok_or_else(|| format!("\"{}\" is not a date/time", literal))?;
+                return Ok(self.timestamp(at));
+            }
+        };
+        Ok(self.bind(param))
+    }
+
+    fn timestamp(&mut self, at: DateTime<Utc>) -> String {
+        match self.dialect() {
+            SqlDialect::Sqlite => self.bind(SqlParam::Integer(at.timestamp())),
+            SqlDialect::Postgres | SqlDialect::Ansi => {
+                let text = at.format("%Y-%m-%d %H:%M:%S+00:00").to_string();
+                format!(
+                    "CAST({} AS TIMESTAMP WITH TIME ZONE)",
+                    self.bind(SqlParam::Text(text))
+                )
+            }
+        }
+    }
+
+    /// The text a LIKE pattern is built from; it must be known when transpiling
+    fn pattern_literal(&self, operand: &Operand) -> Result<String, String> {
+        match operand {
+            Operand::Value { value } => Ok(value.clone()),
+            Operand::Parameter { name } => self
+                .ctx
+                .parameters
+                .get(name)
+                .map(|parameter| parameter.value.clone())
+                .ok_or_else(|| format!("parameter ${} does not exist", name)),
+            _ => Err("LIKE patterns need a literal value or a parameter".to_string()),
+        }
+    }
+
+    /// Elements of a literal list or a reference list, with their type
+    fn list(&self, operand: &Operand) -> Result<(DataType, Vec<String>), String> {
+        match operand {
+            Operand::List {
+                element_type,
+                values,
+            } => Ok((*element_type, values.clone())),
+            Operand::ListRef { name } => self
+                .ctx
+                .lists
+                .get(name)
+                .map(|list| (list.element_type, list.entries.clone()))
+                .ok_or_else(|| format!("reference list \"{}\" does not exist", name)),
+            _ => Err(format!("{} is not a list", operand.display())),
+        }
+    }
+
+    /// Arithmetic in floating point; dividing by zero gives NULL, so the condition
+    /// is false as in the evaluator
+    fn expr(&mut self, expr: &Expr) -> String {
+        match expr {
+            Expr::Number { value } => self.bind(SqlParam::Number(*value)),
+            Expr::Field { field } => self.column(field),
+            Expr::Negate { expr } => format!("(-{})", self.expr(expr)),
+            Expr::Binary {
+                op: ArithOp::Divide,
+                left,
+                right,
+            } => {
+                let left = self.expr(left);
+                let right = self.expr(right);
+                format!(
+                    "(CAST({} AS DOUBLE PRECISION) / NULLIF({}, 0))",
+                    left, right
+                )
+            }
+            Expr::Binary { op, left, right } => {
+                let left = self.expr(left);
+                let right = self.expr(right);
+                format!("({} {} {})", left, op.symbol(), right)
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use crate::evaluator::{evaluate, EvalContext};
+use crate::models::{ConditionNode, LogicalOperator, Transaction};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Hit counts for a single node over a dataset
+#[derive(Debug, Clone, Default)]
+pub struct NodeStats {
+    /// Records that satisfied the node
+    pub matched: usize,
+    /// Records where this node alone decided its parent group's result:
+    /// the only true child of an OR, or the only false child of an AND
+    pub decisive: usize,
+}
+
+/// Per-node hit statistics for a rule tree over a dataset
+#[derive(Debug, Clone, Default)]
+pub struct DatasetStats {
+    pub total: usize,
+    nodes: HashMap<Uuid, NodeStats>,
+}
+
+impl DatasetStats {
+    pub fn compute(root: &ConditionNode, dataset: &[Transaction], ctx: &EvalContext) -> Self {
+        let mut stats = Self {
+            total: dataset.len(),
+            nodes: HashMap::new(),
+        };
+        for tx in dataset {
+            stats.record(root, tx, ctx);
+        }
+        stats
+    }
+
+    pub fn get(&self, id: Uuid) -> NodeStats {
+        self.nodes.get(&id).cloned().unwrap_or_default()
+    }
+
+    /// Share of the dataset represented by `count`, as a percentage
+    pub fn percent(&self, count: usize) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            count as f64 * 100.0 / self.total as f64
+        }
+    }
+
+    /// Evaluate every node (no short-circuit) and record its outcome
+    fn record(&mut self, node: &ConditionNode, tx: &Transaction, ctx: &EvalContext) -> bool {
+        let result = match node {
+            // Snippets count as a single node; their conditions belong to the snippet
+            ConditionNode::Leaf { .. } | ConditionNode::SnippetRef { .. } => {
+                evaluate(node, tx, ctx)
+            }
+            ConditionNode::Group {
+                operator, children, ..
+            } => {
+                let results: Vec<bool> = children
+                    .iter()
+                    .map(|child| self.record(child, tx, ctx))
+                    .collect();
+                let true_count = results.iter().filter(|r| **r).count();
+
+                for (child, child_result) in children.iter().zip(&results) {
+                    let decisive = match operator {
+                        LogicalOperator::And => !child_result && true_count + 1 == results.len(),
+                        LogicalOperator::Or => *child_result && true_count == 1,
+                    };
+                    if decisive {
+                        self.nodes.entry(child.id()).or_default().decisive += 1;
+                    }
+                }
+
+                match operator {
+                    LogicalOperator::And => true_count == results.len(),
+                    LogicalOperator::Or => true_count > 0,
+                }
+            }
+        };
+
+        let entry = self.nodes.entry(node.id()).or_default();
+        if result {
+            entry.matched += 1;
+        }
+        result
+    }
+}
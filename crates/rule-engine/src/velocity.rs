@@ -0,0 +1,268 @@
+use crate::models::{Field, TimeWindow, Transaction};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds since the Unix epoch
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// A transaction seen by the velocity store
+#[derive(Debug, Clone)]
+struct Event {
+    at: u64,
+    tx: Arc<Transaction>,
+}
+
+/// Sliding-window history of evaluated transactions, indexed by every field value
+/// so aggregates can look up "this device" or "this user" without scanning everything.
+///
+/// Events older than [`TimeWindow::MAX_SECONDS`] are dropped from every group as new
+/// ones arrive, so groups that see no more transactions don't linger.
+#[derive(Debug, Default)]
+pub struct VelocityStore {
+    inner: Mutex<Groups>,
+}
+
+type GroupKey = (Field, String);
+
+#[derive(Debug, Default)]
+struct Groups {
+    events: HashMap<GroupKey, VecDeque<Event>>,
+    /// The groups that received an event at each time, so pruning only visits the
+    /// groups holding expired events
+    by_time: BTreeMap<u64, Vec<GroupKey>>,
+}
+
+impl Groups {
+    /// Drop events older than `cutoff`, and the groups left empty
+    fn prune(&mut self, cutoff: u64) {
+        let recent = self.by_time.split_off(&cutoff);
+        let expired = std::mem::replace(&mut self.by_time, recent);
+        for key in expired.into_values().flatten() {
+            let Some(events) = self.events.get_mut(&key) else {
+                continue;
+            };
+            while events.front().is_some_and(|event| event.at < cutoff) {
+                events.pop_front();
+            }
+            if events.is_empty() {
+                self.events.remove(&key);
+            }
+        }
+    }
+}
+
+/// The key a transaction is grouped under for a field, if it has the field
+pub fn group_key(tx: &Transaction, field: &Field) -> Option<String> {
+    match tx.get(field)? {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+impl VelocityStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a transaction observed at `at` under every field it carries
+    pub fn record(&self, tx: &Transaction, at: u64) {
+        let tx = Arc::new(tx.clone());
+        let mut groups = self.inner.lock().unwrap();
+        groups.prune(at.saturating_sub(TimeWindow::MAX_SECONDS));
+
+        for field in Field::all() {
+            let Some(key) = group_key(&tx, &field) else {
+                continue;
+            };
+            let key = (field, key);
+            groups.by_time.entry(at).or_default().push(key.clone());
+            let events = groups.events.entry(key).or_default();
+            // Keep each group ordered by time, even for out-of-order arrivals
+            let position = events.partition_point(|event| event.at <= at);
+            events.insert(
+                position,
+                Event {
+                    at,
+                    tx: Arc::clone(&tx),
+                },
+            );
+        }
+    }
+
+    /// Transactions in the group recorded within `window` before `now`
+    pub fn history(
+        &self,
+        group_by: &Field,
+        key: &str,
+        window: &TimeWindow,
+        now: u64,
+    ) -> Vec<Arc<Transaction>> {
+        let since = now.saturating_sub(window.seconds());
+        let groups = self.inner.lock().unwrap();
+        groups
+            .events
+            .get(&(group_by.clone(), key.to_string()))
+            .map(|events| {
+                events
+                    .iter()
+                    .filter(|event| event.at > since && event.at <= now)
+                    .map(|event| Arc::clone(&event.tx))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Number of distinct transactions held
+    pub fn len(&self) -> usize {
+        let groups = self.inner.lock().unwrap();
+        let mut seen: Vec<*const Transaction> = groups
+            .events
+            .values()
+            .flatten()
+            .map(|event| Arc::as_ptr(&event.tx))
+            .collect();
+        seen.sort();
+        seen.dedup();
+        seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().unwrap().events.is_empty()
+    }
+
+    pub fn clear(&self) {
+        *self.inner.lock().unwrap() = Groups::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::WindowUnit;
+    use serde_json::json;
+
+    const HOUR: TimeWindow = TimeWindow {
+        amount: 1,
+        unit: WindowUnit::Hours,
+    };
+
+    fn tx(device: &str, amount: i64) -> Transaction {
+        serde_json::from_value(json!({
+            "device_fingerprint": device,
+            "transaction_amount": amount,
+        }))
+        .unwrap()
+    }
+
+    /// Amounts of the device's transactions within the last hour before `now`, in order
+    fn amounts(store: &VelocityStore, device: &str, now: u64) -> Vec<i64> {
+        store
+            .history(&Field::DeviceFingerprint, device, &HOUR, now)
+            .iter()
+            .map(|tx| tx.0["transaction_amount"].as_i64().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn windows_end_at_now_and_exclude_their_start() {
+        let store = VelocityStore::new();
+        let now = 10_000;
+        store.record(&tx("d-1", 1), now - 3600);
+        store.record(&tx("d-1", 2), now - 3599);
+        store.record(&tx("d-1", 3), now);
+        store.record(&tx("d-1", 4), now + 1);
+
+        assert_eq!(amounts(&store, "d-1", now), vec![2, 3]);
+        // Transactions recorded after `now` are seen once time catches up
+        assert_eq!(amounts(&store, "d-1", now + 1), vec![3, 4]);
+        assert!(amounts(&store, "d-2", now).is_empty());
+    }
+
+    #[test]
+    fn history_is_in_time_order_whatever_the_recording_order() {
+        let store = VelocityStore::new();
+        store.record(&tx("d-1", 3), 300);
+        store.record(&tx("d-1", 1), 100);
+        store.record(&tx("d-1", 2), 200);
+        // Equal times keep the order they were recorded in
+        store.record(&tx("d-1", 22), 200);
+
+        assert_eq!(amounts(&store, "d-1", 300), vec![1, 2, 22, 3]);
+    }
+
+    #[test]
+    fn events_older_than_the_longest_window_are_pruned() {
+        let store = VelocityStore::new();
+        let start = 1_000;
+        store.record(&tx("d-1", 1), start);
+        store.record(&tx("d-1", 2), start + 1);
+        assert_eq!(amounts(&store, "d-1", start + 1), vec![1, 2]);
+
+        // Recording drops what is older than the cutoff, but keeps the cutoff itself
+        store.record(&tx("d-1", 3), start + 1 + TimeWindow::MAX_SECONDS);
+        assert_eq!(amounts(&store, "d-1", start + 1), vec![2]);
+
+        // Every group is pruned, not only those the new transaction is recorded under
+        let by_amount = store.history(&Field::TransactionAmount, "1", &HOUR, start);
+        assert!(by_amount.is_empty());
+        assert_eq!(store.len(), 2);
+        store.clear();
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn idle_groups_are_evicted_once_their_events_expire() {
+        let store = VelocityStore::new();
+        let group = |device: &str| (Field::DeviceFingerprint, device.to_string());
+        store.record(&tx("idle", 1), 1_000);
+        store.record(&tx("busy", 2), 1_000 + TimeWindow::MAX_SECONDS);
+        assert!(store
+            .inner
+            .lock()
+            .unwrap()
+            .events
+            .contains_key(&group("idle")));
+
+        // A transaction on another device is enough to evict the idle one
+        store.record(&tx("busy", 3), 1_001 + TimeWindow::MAX_SECONDS);
+        let groups = store.inner.lock().unwrap();
+        assert!(!groups.events.contains_key(&group("idle")));
+        assert!(groups.events.contains_key(&group("busy")));
+        assert!(groups.by_time.keys().all(|&at| at > 1_000));
+        drop(groups);
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn transactions_are_grouped_under_every_field_they_carry() {
+        let store = VelocityStore::new();
+        let shared: Transaction = serde_json::from_value(json!({
+            "device_fingerprint": "d-1",
+            "user_id": "u-1",
+            "transaction_amount": 100,
+            "user_country": null,
+        }))
+        .unwrap();
+        store.record(&shared, 50);
+        store.record(&tx("d-1", 200), 60);
+
+        assert_eq!(amounts(&store, "d-1", 60), vec![100, 200]);
+        let by_user = store.history(&Field::UserId, "u-1", &HOUR, 60);
+        assert_eq!(by_user.len(), 1);
+        // Numbers are grouped by their JSON text, and null is no group
+        let by_amount = store.history(&Field::TransactionAmount, "100", &HOUR, 60);
+        assert_eq!(by_amount.len(), 1);
+        assert!(store
+            .history(&Field::UserCountry, "null", &HOUR, 60)
+            .is_empty());
+        // One transaction under several groups is held once
+        assert_eq!(store.len(), 2);
+    }
+}
@@ -0,0 +1,230 @@
+use rule_engine::analysis::{analyze, WarningKind};
+use rule_engine::models::{
+    ConditionNode, DataType, Field, LogicalOperator, Operand, Operator, Snippet,
+};
+use rule_engine::EvalContext;
+
+pub mod common;
+use common::{field, group, leaf, list, snippet_ref, value};
+
+fn kinds(root: &ConditionNode) -> Vec<(WarningKind, String)> {
+    analyze(root, &EvalContext::default())
+        .into_iter()
+        .map(|warning| (warning.kind, warning.message))
+        .collect()
+}
+
+#[test]
+fn disjoint_ranges_in_an_and_never_match() {
+    let root = group(
+        LogicalOperator::And,
+        vec![
+            leaf(
+                field(Field::TransactionAmount),
+                Operator::GreaterThan,
+                value("1000"),
+            ),
+            leaf(field(Field::UserCountry), Operator::Equals, value("NG")),
+            leaf(
+                field(Field::TransactionAmount),
+                Operator::LessThan,
+                value("500"),
+            ),
+        ],
+    );
+    let warnings = kinds(&root);
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert_eq!(warnings[0].0, WarningKind::Unsatisfiable);
+}
+
+#[test]
+fn sets_that_exclude_each_other_never_match() {
+    let root = group(
+        LogicalOperator::And,
+        vec![
+            leaf(
+                field(Field::UserCountry),
+                Operator::In,
+                list(DataType::String, &["NG", "RU"]),
+            ),
+            leaf(
+                field(Field::UserCountry),
+                Operator::NotIn,
+                list(DataType::String, &["RU", "NG", "BR"]),
+            ),
+        ],
+    );
+    assert_eq!(kinds(&root)[0].0, WarningKind::Unsatisfiable);
+}
+
+#[test]
+fn complementary_conditions_in_an_or_always_match() {
+    let root = group(
+        LogicalOperator::Or,
+        vec![
+            leaf(
+                field(Field::TransactionAmount),
+                Operator::GreaterThan,
+                value("100"),
+            ),
+            group(
+                LogicalOperator::Or,
+                vec![
+                    leaf(
+                        field(Field::TransactionAmount),
+                        Operator::Equals,
+                        value("100"),
+                    ),
+                    leaf(
+                        field(Field::TransactionAmount),
+                        Operator::LessThan,
+                        value("100"),
+                    ),
+                ],
+            ),
+        ],
+    );
+    let warnings = kinds(&root);
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert_eq!(warnings[0].0, WarningKind::AlwaysTrue);
+}
+
+#[test]
+fn implied_conditions_are_redundant() {
+    let and = group(
+        LogicalOperator::And,
+        vec![
+            leaf(
+                field(Field::TransactionAmount),
+                Operator::GreaterThan,
+                value("100"),
+            ),
+            leaf(
+                field(Field::TransactionAmount),
+                Operator::GreaterThan,
+                value("1000"),
+            ),
+        ],
+    );
+    assert_eq!(
+        kinds(&and),
+        vec![(
+            WarningKind::Redundant,
+            "Transaction Amount Greater Than \"100\" is redundant: \
+             Transaction Amount Greater Than \"1000\" already implies it"
+                .to_string()
+        )]
+    );
+
+    let or = group(
+        LogicalOperator::Or,
+        vec![
+            leaf(
+                field(Field::UserCountry),
+                Operator::In,
+                list(DataType::String, &["NG", "RU"]),
+            ),
+            leaf(field(Field::UserCountry), Operator::Equals, value("RU")),
+        ],
+    );
+    let warnings = kinds(&or);
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert_eq!(warnings[0].0, WarningKind::Redundant);
+}
+
+#[test]
+fn repeated_siblings_are_duplicates() {
+    let root = group(
+        LogicalOperator::Or,
+        vec![
+            leaf(
+                field(Field::DeviceFingerprint),
+                Operator::Contains,
+                value("emu"),
+            ),
+            leaf(field(Field::UserId), Operator::Equals, value("u-1")),
+            leaf(
+                field(Field::DeviceFingerprint),
+                Operator::Contains,
+                value("emu"),
+            ),
+        ],
+    );
+    let warnings = kinds(&root);
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert_eq!(warnings[0].0, WarningKind::Duplicate);
+}
+
+#[test]
+fn satisfiable_rules_have_no_warnings() {
+    let root = group(
+        LogicalOperator::And,
+        vec![
+            leaf(
+                field(Field::TransactionAmount),
+                Operator::Between,
+                Operand::Range {
+                    min: "100".to_string(),
+                    max: "500".to_string(),
+                },
+            ),
+            leaf(
+                field(Field::TransactionAmount),
+                Operator::NotEquals,
+                value("250"),
+            ),
+            leaf(
+                field(Field::TransactionTime),
+                Operator::After,
+                value("2024-01-01"),
+            ),
+            leaf(
+                field(Field::TransactionTime),
+                Operator::Before,
+                value("2024-02-01"),
+            ),
+        ],
+    );
+    assert_eq!(kinds(&root), vec![]);
+}
+
+#[test]
+fn snippets_that_reference_each_other_are_left_to_validation() {
+    // Contexts read from files are not kept acyclic like the snippet store is
+    let snippet = |name: &str, root: ConditionNode| {
+        (
+            name.to_string(),
+            Snippet {
+                name: name.to_string(),
+                description: String::new(),
+                root,
+            },
+        )
+    };
+    let ctx = EvalContext {
+        snippets: [
+            snippet("a", group(LogicalOperator::And, vec![snippet_ref("b")])),
+            snippet(
+                "b",
+                group(
+                    LogicalOperator::Or,
+                    vec![
+                        leaf(field(Field::UserAge), Operator::LessThan, value("18")),
+                        snippet_ref("a"),
+                    ],
+                ),
+            ),
+        ]
+        .into_iter()
+        .collect(),
+        ..EvalContext::default()
+    };
+    let root = group(
+        LogicalOperator::And,
+        vec![
+            leaf(field(Field::UserAge), Operator::GreaterThan, value("21")),
+            snippet_ref("a"),
+        ],
+    );
+    assert_eq!(analyze(&root, &ctx), vec![]);
+}
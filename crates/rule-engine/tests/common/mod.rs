@@ -0,0 +1,83 @@
+//! Fixture factories shared by the integration tests and the benchmarks.
+//! Each target declares this module `pub`, so it is not told that the factories
+//! only other targets use are dead code.
+
+use rule_engine::models::{
+    ConditionNode, DataType, Field, LogicalOperator, Operand, Operator, Parameter, ReferenceList,
+    Rule, Snippet,
+};
+use uuid::Uuid;
+
+pub fn field(field: Field) -> Operand {
+    Operand::Field { field }
+}
+
+pub fn value(value: &str) -> Operand {
+    Operand::Value {
+        value: value.to_string(),
+    }
+}
+
+pub fn list(element_type: DataType, values: &[&str]) -> Operand {
+    Operand::List {
+        element_type,
+        values: values.iter().map(|v| v.to_string()).collect(),
+    }
+}
+
+pub fn leaf(left: Operand, operator: Operator, right: Operand) -> ConditionNode {
+    ConditionNode::Leaf {
+        id: Uuid::new_v4(),
+        left,
+        operator,
+        right,
+        weight: None,
+    }
+}
+
+pub fn group(operator: LogicalOperator, children: Vec<ConditionNode>) -> ConditionNode {
+    ConditionNode::Group {
+        id: Uuid::new_v4(),
+        operator,
+        children,
+        weight: None,
+    }
+}
+
+pub fn snippet_ref(name: &str) -> ConditionNode {
+    ConditionNode::SnippetRef {
+        id: Uuid::new_v4(),
+        name: name.to_string(),
+        weight: None,
+    }
+}
+
+pub fn reference_list(name: &str, element_type: DataType, entries: &[&str]) -> ReferenceList {
+    let mut list = ReferenceList::new(name.to_string(), String::new(), element_type);
+    list.add_entries(entries.iter().map(|entry| entry.to_string()));
+    list
+}
+
+pub fn parameter(name: &str, data_type: DataType, value: &str) -> Parameter {
+    Parameter {
+        name: name.to_string(),
+        description: String::new(),
+        data_type,
+        value: value.to_string(),
+    }
+}
+
+pub fn snippet(name: &str, root: ConditionNode) -> Snippet {
+    Snippet {
+        name: name.to_string(),
+        description: String::new(),
+        root,
+    }
+}
+
+/// A boolean rule over `root` that flags what it matches
+pub fn rule(name: &str, root: ConditionNode) -> Rule {
+    let mut rule = Rule::new(name.to_string(), String::new());
+    rule.root = root;
+    rule
+}
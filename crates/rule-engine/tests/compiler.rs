@@ -0,0 +1,606 @@
+use proptest::prelude::*;
+use rule_engine::evaluator::evaluate_rule;
+use rule_engine::expression::Expr;
+use rule_engine::models::{
+    Action, AggregateFunction, ConditionNode, DataType, Field, LogicalOperator, Operand, Operator,
+    RuleMode, ScoreThreshold, TimeWindow, Transaction, WindowUnit,
+};
+use rule_engine::{compile_rule, EvalContext, VelocityStore};
+use serde_json::json;
+use std::sync::Arc;
+
+pub mod common;
+use common::{
+    field, group, leaf, list, parameter, reference_list, rule, snippet, snippet_ref, value,
+};
+
+/// Evaluation time of every transaction, in Unix seconds (2023-11-14T22:13:20Z)
+const NOW: u64 = 1_700_000_000;
+
+fn context() -> EvalContext {
+    let mut ctx = EvalContext {
+        now: NOW,
+        ..EvalContext::default()
+    };
+    ctx.lists.insert(
+        "watchlist".to_string(),
+        reference_list("watchlist", DataType::String, &["NG", "BR"]),
+    );
+    for (name, data_type, value) in [
+        ("LIMIT", DataType::Number, "200"),
+        ("HOME", DataType::String, "FR"),
+    ] {
+        ctx.parameters
+            .insert(name.to_string(), parameter(name, data_type, value));
+    }
+    // Weights inside a snippet are ignored when scoring, by both evaluators
+    let mut large = leaf(
+        field(Field::TransactionAmount),
+        Operator::GreaterThan,
+        value("250"),
+    );
+    large.set_weight(Some(7.0));
+    for (name, root) in [
+        ("large", group(LogicalOperator::And, vec![large])),
+        (
+            "home",
+            group(
+                LogicalOperator::Or,
+                vec![
+                    leaf(
+                        field(Field::UserCountry),
+                        Operator::Equals,
+                        Operand::Parameter {
+                            name: "HOME".to_string(),
+                        },
+                    ),
+                    snippet_ref("large"),
+                ],
+            ),
+        ),
+    ] {
+        ctx.snippets.insert(name.to_string(), snippet(name, root));
+    }
+    let velocity = VelocityStore::new();
+    for (user, amount, ago) in [("u-1", 100, 60), ("u-1", 300, 1800), ("u-2", 50, 7200)] {
+        let tx = json!({ "user_id": user, "transaction_amount": amount });
+        velocity.record(&serde_json::from_value(tx).unwrap(), NOW - ago);
+    }
+    ctx.velocity = Arc::new(velocity);
+    ctx
+}
+
+fn amount() -> impl Strategy<Value = String> {
+    (0i64..4).prop_map(|n| (n * 100).to_string())
+}
+
+fn text() -> impl Strategy<Value = &'static str> {
+    prop::sample::select(vec!["NG", "FR", "N", "G", "1", "true", "u-1"])
+}
+
+fn string_field() -> impl Strategy<Value = Operand> {
+    prop::sample::select(vec![Field::UserCountry, Field::UserId]).prop_map(field)
+}
+
+fn number_field() -> impl Strategy<Value = Operand> {
+    prop::sample::select(vec![Field::TransactionAmount, Field::UserAge]).prop_map(field)
+}
+
+/// Conditions on every operator, with and without a fast path
+fn condition() -> impl Strategy<Value = ConditionNode> {
+    let comparison = prop::sample::select(vec![
+        Operator::Equals,
+        Operator::NotEquals,
+        Operator::GreaterThan,
+        Operator::LessThan,
+        Operator::GreaterThanOrEqual,
+        Operator::LessThanOrEqual,
+    ]);
+    let text_match = prop::sample::select(vec![
+        Operator::Equals,
+        Operator::NotEquals,
+        Operator::Contains,
+        Operator::StartsWith,
+        Operator::EndsWith,
+    ]);
+    let membership = prop::sample::select(vec![Operator::In, Operator::NotIn]);
+    let time = field(Field::TransactionTime);
+    prop_oneof![
+        // Fields against constants
+        (number_field(), comparison.clone(), amount()).prop_map(|(left, operator, n)| leaf(
+            left,
+            operator,
+            value(&n)
+        )),
+        (number_field(), amount(), amount()).prop_map(|(left, a, b)| leaf(
+            left,
+            Operator::Between,
+            Operand::Range {
+                min: a.clone().min(b.clone()),
+                max: a.max(b),
+            }
+        )),
+        (string_field(), text_match, text()).prop_map(|(left, operator, text)| leaf(
+            left,
+            operator,
+            value(text)
+        )),
+        (
+            string_field(),
+            prop::sample::select(vec!["^N", "G$", "^[0-9]+$", "u-"])
+        )
+            .prop_map(|(left, pattern)| leaf(left, Operator::Regex, value(pattern))),
+        (
+            string_field(),
+            membership.clone(),
+            prop::sample::subsequence(vec!["NG", "FR", "1", "u-1"], 1..4)
+        )
+            .prop_map(|(left, operator, values)| leaf(
+                left,
+                operator,
+                list(DataType::String, &values)
+            )),
+        (
+            number_field(),
+            membership.clone(),
+            prop::sample::subsequence(vec!["-0", "100", "200.0", "100.5"], 1..4)
+        )
+            .prop_map(|(left, operator, values)| leaf(
+                left,
+                operator,
+                list(DataType::Number, &values)
+            )),
+        membership.prop_map(|operator| leaf(
+            field(Field::UserCountry),
+            operator,
+            Operand::ListRef {
+                name: "watchlist".to_string()
+            }
+        )),
+        (
+            prop::sample::select(vec![Operator::InCidr, Operator::NotInCidr]),
+            prop::sample::subsequence(vec!["10.0.0.0/8", "192.168.1.1", "::1/128"], 1..3)
+        )
+            .prop_map(|(operator, ranges)| leaf(
+                field(Field::IpAddress),
+                operator,
+                list(DataType::Ip, &ranges)
+            )),
+        prop::sample::select(Field::all()).prop_map(|f| leaf(
+            field(f),
+            Operator::IsEmpty,
+            value("")
+        )),
+        // Parameters are resolved when compiling
+        comparison.clone().prop_map(|operator| leaf(
+            field(Field::TransactionAmount),
+            operator,
+            Operand::Parameter {
+                name: "LIMIT".to_string()
+            }
+        )),
+        Just(leaf(
+            field(Field::UserCountry),
+            Operator::NotEquals,
+            Operand::Parameter {
+                name: "HOME".to_string()
+            }
+        )),
+        // Everything below falls back to the tree evaluator's comparison
+        (comparison.clone(), number_field(), number_field())
+            .prop_map(|(operator, left, right)| leaf(left, operator, right)),
+        (comparison.clone(), amount(), number_field()).prop_map(|(operator, n, right)| leaf(
+            value(&n),
+            operator,
+            right
+        )),
+        (
+            prop::sample::select(vec![Operator::Equals, Operator::NotEquals]),
+            prop::sample::select(vec!["10.1.2.3", "192.168.1.1"])
+        )
+            .prop_map(|(operator, ip)| leaf(field(Field::IpAddress), operator, value(ip))),
+        (1u32..48).prop_map({
+            let time = time.clone();
+            move |hours| {
+                leaf(
+                    time.clone(),
+                    Operator::WithinLast,
+                    Operand::Duration {
+                        window: TimeWindow {
+                            amount: hours,
+                            unit: WindowUnit::Hours,
+                        },
+                    },
+                )
+            }
+        }),
+        (
+            prop::sample::select(vec![Operator::Before, Operator::After]),
+            prop::sample::select(vec!["2023-11-14T12:00:00Z", "2023-11-14 23:00"])
+        )
+            .prop_map({
+                let time = time.clone();
+                move |(operator, at)| leaf(time.clone(), operator, value(at))
+            }),
+        prop::sample::subsequence(vec!["3", "12", "22"], 1..3).prop_map({
+            let time = time.clone();
+            move |hours| {
+                leaf(
+                    time.clone(),
+                    Operator::HourOfDayIn,
+                    list(DataType::Number, &hours),
+                )
+            }
+        }),
+        prop::sample::subsequence(vec!["Mon", "Tue", "Fri"], 1..3).prop_map(move |days| leaf(
+            time.clone(),
+            Operator::DayOfWeekIn,
+            list(DataType::String, &days)
+        )),
+        (
+            prop::sample::select(vec![AggregateFunction::Count, AggregateFunction::Sum]),
+            prop::sample::select(vec![5u32, 45, 180]),
+            comparison.clone(),
+            0i64..4
+        )
+            .prop_map(|(function, minutes, operator, n)| leaf(
+                Operand::Aggregate {
+                    field: Some(Field::TransactionAmount),
+                    function,
+                    group_by: Field::UserId,
+                    window: TimeWindow {
+                        amount: minutes,
+                        unit: WindowUnit::Minutes,
+                    },
+                },
+                operator,
+                value(&(n * 100).to_string())
+            )),
+        (comparison, amount()).prop_map(|(operator, n)| leaf(
+            Operand::Expression {
+                expr: Expr::parse("transaction_amount / user_age").unwrap()
+            },
+            operator,
+            value(&n)
+        )),
+        prop::sample::select(vec!["large", "home"]).prop_map(snippet_ref),
+    ]
+}
+
+fn weight() -> impl Strategy<Value = Option<f64>> {
+    prop::option::weighted(0.4, (1i64..10).prop_map(|w| w as f64))
+}
+
+fn tree() -> impl Strategy<Value = ConditionNode> {
+    (condition(), weight())
+        .prop_map(|(mut node, weight)| {
+            node.set_weight(weight);
+            node
+        })
+        .prop_recursive(3, 24, 4, |inner| {
+            (
+                prop::sample::select(vec![LogicalOperator::And, LogicalOperator::Or]),
+                prop::collection::vec(inner, 1..4),
+                weight(),
+            )
+                .prop_map(|(operator, children, weight)| {
+                    let mut node = group(operator, children);
+                    node.set_weight(weight);
+                    node
+                })
+        })
+}
+
+/// Field values of every JSON kind, including ones of the wrong type for the field
+fn transaction() -> impl Strategy<Value = Transaction> {
+    let scalar = prop_oneof![
+        (-1i64..4).prop_map(|n| json!(n * 100)),
+        Just(json!(100.5)),
+        Just(json!(-0.0)),
+        Just(json!(true)),
+        prop::sample::select(vec!["NG", "FR", "BR", "1", "200", "u-1", "", " "])
+            .prop_map(|s| json!(s)),
+    ];
+    let ip = prop::sample::select(vec!["10.1.2.3", "192.168.1.1", "::1", "8.8.8.8", "nope"]);
+    let time = prop::sample::select(vec![
+        "2023-11-14T22:00:00Z",
+        "2023-11-14T03:30:00Z",
+        "2023-11-10T12:00:00Z",
+        "2023-11-15T01:00:00Z",
+        "soon",
+    ]);
+    (
+        prop::collection::btree_map(
+            prop::sample::select(vec![
+                "transaction_amount",
+                "user_age",
+                "user_country",
+                "user_id",
+            ]),
+            scalar,
+            0..4,
+        ),
+        prop::option::of(ip),
+        prop::option::of(time),
+    )
+        .prop_map(|(fields, ip, time)| {
+            let mut tx = serde_json::Map::new();
+            for (field, value) in fields {
+                tx.insert(field.to_string(), value);
+            }
+            if let Some(ip) = ip {
+                tx.insert("ip_address".to_string(), json!(ip));
+            }
+            if let Some(time) = time {
+                tx.insert("transaction_time".to_string(), json!(time));
+            }
+            Transaction(tx)
+        })
+}
+
+fn thresholds() -> impl Strategy<Value = Vec<ScoreThreshold>> {
+    prop::sample::subsequence(vec![1.0, 5.0, 12.0], 1..3).prop_map(|scores| {
+        scores
+            .into_iter()
+            .map(|min_score| ScoreThreshold {
+                min_score,
+                actions: vec![Action::Tag {
+                    label: format!("score {}", min_score),
+                }],
+            })
+            .collect()
+    })
+}
+
+proptest! {
+    #[test]
+    fn compiled_rules_agree_with_the_tree_walk(
+        root in tree(),
+        scoring in any::<bool>(),
+        thresholds in thresholds(),
+        txs in prop::collection::vec(transaction(), 16),
+    ) {
+        let ctx = context();
+        let mut rule = rule("Compiled", root);
+        if scoring {
+            rule.mode = RuleMode::Scoring;
+            rule.thresholds = thresholds;
+        }
+        // Scoring rules need a weighted condition; every other generated rule is valid
+        prop_assume!(!scoring || rule.root.has_weights());
+        let compiled = compile_rule(&rule, &ctx).unwrap();
+        for tx in &txs {
+            let outcome = evaluate_rule(&rule, tx, &ctx);
+            prop_assert_eq!(compiled.matches(tx, &ctx), outcome.matched);
+            prop_assert_eq!(compiled.actions(tx, &ctx), outcome.actions.as_slice());
+            prop_assert_eq!(
+                compiled.score(tx, &ctx),
+                outcome.score.map_or(0.0, |breakdown| breakdown.score)
+            );
+        }
+    }
+}
+
+#[test]
+fn lists_and_parameters_are_captured_when_compiling() {
+    let mut ctx = context();
+    let rule = rule(
+        "Watched",
+        leaf(
+            field(Field::UserCountry),
+            Operator::In,
+            Operand::ListRef {
+                name: "watchlist".to_string(),
+            },
+        ),
+    );
+    let compiled = compile_rule(&rule, &ctx).unwrap();
+    let tx: Transaction = serde_json::from_value(json!({ "user_country": "FR" })).unwrap();
+    assert!(!compiled.matches(&tx, &ctx));
+
+    ctx.lists
+        .get_mut("watchlist")
+        .unwrap()
+        .entries
+        .push("FR".to_string());
+    assert!(!compiled.matches(&tx, &ctx));
+    assert!(compile_rule(&rule, &ctx).unwrap().matches(&tx, &ctx));
+}
+
+/// Compiled and tree-walk results for each transaction, which must agree
+fn results(node: ConditionNode, txs: &[serde_json::Value]) -> Vec<bool> {
+    let ctx = context();
+    let rule = rule("Checked", node);
+    let compiled = compile_rule(&rule, &ctx).unwrap();
+    txs.iter()
+        .map(|tx| {
+            let tx: Transaction = serde_json::from_value(tx.clone()).unwrap();
+            let matched = compiled.matches(&tx, &ctx);
+            assert_eq!(matched, evaluate_rule(&rule, &tx, &ctx).matched, "{:?}", tx);
+            matched
+        })
+        .collect()
+}
+
+#[test]
+fn fast_paths_match_what_they_should() {
+    let amounts = [
+        json!({ "transaction_amount": 100 }),
+        json!({ "transaction_amount": 250.5 }),
+        json!({ "transaction_amount": "250" }),
+        json!({}),
+    ];
+    let amount = |operator, right| leaf(field(Field::TransactionAmount), operator, right);
+    assert_eq!(
+        results(amount(Operator::GreaterThan, value("200")), &amounts),
+        vec![false, true, false, false]
+    );
+    assert_eq!(
+        results(
+            amount(
+                Operator::Between,
+                Operand::Range {
+                    min: "100".to_string(),
+                    max: "250.5".to_string(),
+                }
+            ),
+            &amounts
+        ),
+        vec![true, true, false, false]
+    );
+    // Numbers equal numbers by value, and text by its spelling
+    assert_eq!(
+        results(amount(Operator::Equals, value("100.0")), &amounts),
+        vec![true, false, false, false]
+    );
+    assert_eq!(
+        results(amount(Operator::NotEquals, value("250")), &amounts),
+        vec![true, true, false, false]
+    );
+    assert_eq!(
+        results(amount(Operator::IsEmpty, value("")), &amounts),
+        vec![false, false, false, true]
+    );
+
+    // Numbers in text fields are matched by their text
+    assert_eq!(
+        results(
+            leaf(field(Field::UserId), Operator::EndsWith, value("50")),
+            &[
+                json!({ "user_id": 1250 }),
+                json!({ "user_id": "u-150" }),
+                json!({ "user_id": "u-1" }),
+                json!({}),
+            ]
+        ),
+        vec![true, true, false, false]
+    );
+
+    let countries = [
+        json!({ "user_country": "NG" }),
+        json!({ "user_country": "ng" }),
+        json!({ "user_country": 1 }),
+        json!({ "user_country": true }),
+    ];
+    let country = |operator, right| leaf(field(Field::UserCountry), operator, right);
+    assert_eq!(
+        results(country(Operator::Regex, value("^N")), &countries),
+        vec![true, false, false, false]
+    );
+    assert_eq!(
+        results(
+            country(Operator::In, list(DataType::String, &["NG", "1", "true"])),
+            &countries
+        ),
+        vec![true, false, true, true]
+    );
+    assert_eq!(
+        results(
+            country(
+                Operator::NotIn,
+                Operand::ListRef {
+                    name: "watchlist".to_string(),
+                }
+            ),
+            &countries
+        ),
+        vec![false, true, true, true]
+    );
+
+    let ips = [
+        json!({ "ip_address": "10.1.2.3" }),
+        json!({ "ip_address": "11.1.2.3" }),
+        json!({ "ip_address": "::1" }),
+        json!({ "ip_address": "nope" }),
+    ];
+    assert_eq!(
+        results(
+            leaf(
+                field(Field::IpAddress),
+                Operator::NotInCidr,
+                list(DataType::Ip, &["10.0.0.0/8", "::1"])
+            ),
+            &ips
+        ),
+        vec![false, true, false, false]
+    );
+}
+
+#[test]
+fn fallbacks_snippets_and_scores_give_the_documented_results() {
+    let ctx = context();
+    let tx = |value: serde_json::Value| -> Transaction { serde_json::from_value(value).unwrap() };
+    let hour = TimeWindow {
+        amount: 1,
+        unit: WindowUnit::Hours,
+    };
+    // u-1 spent 100 and 300 within the hour; the transaction itself counts too
+    let spent = leaf(
+        Operand::Aggregate {
+            function: AggregateFunction::Sum,
+            field: Some(Field::TransactionAmount),
+            group_by: Field::UserId,
+            window: hour,
+        },
+        Operator::GreaterThan,
+        Operand::Parameter {
+            name: "LIMIT".to_string(),
+        },
+    );
+    assert_eq!(
+        results(
+            spent.clone(),
+            &[
+                json!({ "user_id": "u-1", "transaction_amount": 1 }),
+                json!({ "user_id": "u-2", "transaction_amount": 150 }),
+                json!({ "user_id": "u-2", "transaction_amount": 250 }),
+            ]
+        ),
+        vec![true, false, true]
+    );
+    // "home" is FR, or any amount over 250 through the nested snippet
+    assert_eq!(
+        results(
+            snippet_ref("home"),
+            &[
+                json!({ "user_country": "FR" }),
+                json!({ "user_country": "NG", "transaction_amount": 300 }),
+                json!({ "user_country": "NG", "transaction_amount": 200 }),
+            ]
+        ),
+        vec![true, true, false]
+    );
+
+    let mut spent = spent;
+    spent.set_weight(Some(4.0));
+    let mut home = snippet_ref("home");
+    home.set_weight(Some(2.5));
+    let mut scored = rule("Scored", group(LogicalOperator::Or, vec![spent, home]));
+    scored.mode = RuleMode::Scoring;
+    scored.thresholds = [(2.0, "low"), (6.0, "high")]
+        .into_iter()
+        .map(|(min_score, label)| ScoreThreshold {
+            min_score,
+            actions: vec![Action::Tag {
+                label: label.to_string(),
+            }],
+        })
+        .collect();
+    let compiled = compile_rule(&scored, &ctx).unwrap();
+    let tag = |label: &str| {
+        vec![Action::Tag {
+            label: label.to_string(),
+        }]
+    };
+    // Both weights, though the snippet's own weight of 7 is ignored
+    let both = tx(json!({ "user_id": "u-1", "transaction_amount": 300 }));
+    assert_eq!(compiled.score(&both, &ctx), 6.5);
+    assert_eq!(compiled.actions(&both, &ctx), tag("high").as_slice());
+    let home_only = tx(json!({ "user_id": "u-3", "user_country": "FR" }));
+    assert_eq!(compiled.score(&home_only, &ctx), 2.5);
+    assert_eq!(compiled.actions(&home_only, &ctx), tag("low").as_slice());
+    let neither = tx(json!({ "user_id": "u-3" }));
+    assert_eq!(compiled.score(&neither, &ctx), 0.0);
+    assert!(!compiled.matches(&neither, &ctx));
+    assert!(compiled.actions(&neither, &ctx).is_empty());
+}
@@ -0,0 +1,897 @@
+use rule_engine::evaluator::{evaluate, TraceDetail, TraceNode};
+use rule_engine::models::{
+    Action, ConditionNode, DataType, Decision, Field, LogicalOperator, MatchStrategy, Operand,
+    Operator, Rule, RuleMode, RuleSet, RuleSetEntry, ScoreThreshold, TestCase, Transaction,
+};
+use rule_engine::{
+    evaluate_rule, evaluate_rule_set, run_test_cases, trace, EvalContext, RuleSetOutcome, Value,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+pub mod common;
+use common::{field, group, leaf, list, rule, snippet, snippet_ref, value};
+
+fn tx(value: serde_json::Value) -> Transaction {
+    serde_json::from_value(value).unwrap()
+}
+
+fn range(min: &str, max: &str) -> Operand {
+    Operand::Range {
+        min: min.to_string(),
+        max: max.to_string(),
+    }
+}
+
+/// Validation errors of a boolean rule over `node`
+fn errors(node: ConditionNode) -> Vec<String> {
+    rule("Checked", node)
+        .validate(&EvalContext::default())
+        .err()
+        .unwrap_or_default()
+}
+
+/// Whether `node` matches each transaction, in order
+fn matches(node: &ConditionNode, transactions: &[serde_json::Value]) -> Vec<bool> {
+    let ctx = EvalContext::default();
+    transactions
+        .iter()
+        .map(|t| evaluate(node, &tx(t.clone()), &ctx))
+        .collect()
+}
+
+#[test]
+fn regex_matches_anywhere_unless_anchored() {
+    let device = |pattern| {
+        leaf(
+            field(Field::DeviceFingerprint),
+            Operator::Regex,
+            value(pattern),
+        )
+    };
+    let transactions = [
+        json!({ "device_fingerprint": "emu-000123" }),
+        json!({ "device_fingerprint": "pixel-emu-7" }),
+        json!({}),
+    ];
+    assert_eq!(
+        matches(&device(r"^emu-\d+$"), &transactions),
+        vec![true, false, false]
+    );
+    assert_eq!(
+        matches(&device("emu"), &transactions),
+        vec![true, true, false]
+    );
+    assert!(errors(device(r"^emu-\d+$")).is_empty());
+}
+
+#[test]
+fn invalid_regexes_are_rejected_and_never_match() {
+    let node = leaf(
+        field(Field::DeviceFingerprint),
+        Operator::Regex,
+        value("emu-("),
+    );
+    let errors = errors(node.clone());
+    assert_eq!(errors.len(), 1);
+    assert!(
+        errors[0].starts_with("Invalid regex \"emu-(\""),
+        "{:?}",
+        errors
+    );
+    assert_eq!(
+        matches(&node, &[json!({ "device_fingerprint": "emu-(" })]),
+        vec![false]
+    );
+}
+
+#[test]
+fn between_includes_both_bounds() {
+    let node = leaf(
+        field(Field::TransactionAmount),
+        Operator::Between,
+        range("100", "200"),
+    );
+    let amounts = [99.99, 100.0, 150.0, 200.0, 200.01];
+    let transactions: Vec<_> = amounts
+        .iter()
+        .map(|amount| json!({ "transaction_amount": amount }))
+        .collect();
+    assert_eq!(
+        matches(&node, &transactions),
+        vec![false, true, true, true, false]
+    );
+    // Equal bounds match exactly one value
+    let node = leaf(
+        field(Field::TransactionAmount),
+        Operator::Between,
+        range("100", "100"),
+    );
+    assert!(errors(node.clone()).is_empty());
+    assert_eq!(matches(&node, &transactions[..3]), vec![false, true, false]);
+}
+
+#[test]
+fn between_bounds_are_checked() {
+    let amount = |right| leaf(field(Field::TransactionAmount), Operator::Between, right);
+    assert_eq!(
+        errors(amount(range("200", "100"))),
+        vec!["Between lower bound 200 is greater than upper bound 100"]
+    );
+    assert_eq!(
+        errors(amount(range("low", "100"))),
+        vec!["Between bounds must be numbers"]
+    );
+    assert_eq!(
+        errors(amount(value("100"))),
+        vec!["Between needs a lower and an upper bound"]
+    );
+    let equals = leaf(
+        field(Field::TransactionAmount),
+        Operator::Equals,
+        range("1", "2"),
+    );
+    assert_eq!(
+        errors(equals),
+        vec!["Operator Equals does not take a range"]
+    );
+}
+
+#[test]
+fn in_and_not_in_compare_numbers_by_value() {
+    let countries = list(DataType::String, &["NG", "BR"]);
+    let amounts = list(DataType::Number, &["100", "250.5"]);
+    let transactions = [
+        json!({ "user_country": "NG", "transaction_amount": 100.0 }),
+        json!({ "user_country": "FR", "transaction_amount": 250.50 }),
+        json!({ "user_country": "ng", "transaction_amount": 10 }),
+        json!({}),
+    ];
+    let country = |operator| leaf(field(Field::UserCountry), operator, countries.clone());
+    let amount = |operator| leaf(field(Field::TransactionAmount), operator, amounts.clone());
+
+    assert_eq!(
+        matches(&country(Operator::In), &transactions),
+        vec![true, false, false, false]
+    );
+    // A missing field is neither in nor out of a list
+    assert_eq!(
+        matches(&country(Operator::NotIn), &transactions),
+        vec![false, true, true, false]
+    );
+    assert_eq!(
+        matches(&amount(Operator::In), &transactions),
+        vec![true, true, false, false]
+    );
+    assert_eq!(
+        matches(&amount(Operator::NotIn), &transactions),
+        vec![false, false, true, false]
+    );
+}
+
+#[test]
+fn list_operators_need_a_valid_list() {
+    let country = |operator, right| leaf(field(Field::UserCountry), operator, right);
+    assert_eq!(
+        errors(country(Operator::In, list(DataType::String, &[]))),
+        vec!["In list cannot be empty"]
+    );
+    assert_eq!(
+        errors(country(
+            Operator::NotIn,
+            list(DataType::String, &["NG", "NG"])
+        )),
+        vec!["Duplicate list element \"NG\""]
+    );
+    assert_eq!(
+        errors(country(Operator::In, value("NG"))),
+        vec!["In needs a list of values"]
+    );
+}
+
+#[test]
+fn text_operators_and_is_empty() {
+    let device = |operator, text| leaf(field(Field::DeviceFingerprint), operator, value(text));
+    let transactions = [
+        json!({ "device_fingerprint": "emu-42" }),
+        json!({ "device_fingerprint": "  " }),
+        json!({}),
+    ];
+    assert_eq!(
+        matches(&device(Operator::StartsWith, "emu-"), &transactions),
+        vec![true, false, false]
+    );
+    assert_eq!(
+        matches(&device(Operator::EndsWith, "-42"), &transactions),
+        vec![true, false, false]
+    );
+    // Blank and missing values are both empty
+    assert_eq!(
+        matches(&device(Operator::IsEmpty, ""), &transactions),
+        vec![false, true, true]
+    );
+}
+
+/// Whether `node` matches each transaction time, evaluated in `timezone`
+fn matches_in(node: &ConditionNode, timezone: &str, times: &[&str]) -> Vec<bool> {
+    let ctx = EvalContext {
+        timezone: timezone.parse().unwrap(),
+        ..EvalContext::default()
+    };
+    times
+        .iter()
+        .map(|time| evaluate(node, &tx(json!({ "transaction_time": time })), &ctx))
+        .collect()
+}
+
+fn time_is(operator: Operator, right: Operand) -> ConditionNode {
+    leaf(field(Field::TransactionTime), operator, right)
+}
+
+#[test]
+fn hours_weekdays_and_dates_are_read_in_the_rule_timezone() {
+    // Friday 23:00 in UTC is already Saturday 01:00 in a UTC+2 zone
+    let late_friday = ["2024-05-31T23:00:00Z"];
+    let hours = |hours: &[&str]| time_is(Operator::HourOfDayIn, list(DataType::Number, hours));
+    let days = |days: &[&str]| time_is(Operator::DayOfWeekIn, list(DataType::String, days));
+    let before_june = time_is(Operator::Before, value("2024-06-01"));
+
+    assert_eq!(matches_in(&hours(&["23"]), "UTC", &late_friday), vec![true]);
+    assert_eq!(matches_in(&days(&["fri"]), "UTC", &late_friday), vec![true]);
+    assert_eq!(matches_in(&before_june, "UTC", &late_friday), vec![true]);
+
+    let zone = "Africa/Johannesburg";
+    assert_eq!(matches_in(&hours(&["23"]), zone, &late_friday), vec![false]);
+    assert_eq!(matches_in(&hours(&["1"]), zone, &late_friday), vec![true]);
+    assert_eq!(matches_in(&days(&["sat"]), zone, &late_friday), vec![true]);
+    // Local midnight on June 1st was 22:00 UTC
+    assert_eq!(matches_in(&before_june, zone, &late_friday), vec![false]);
+
+    // Times without an offset are local to the rule
+    let local = ["2024-05-31T23:00"];
+    assert_eq!(matches_in(&hours(&["23"]), zone, &local), vec![true]);
+    assert_eq!(matches_in(&days(&["fri"]), zone, &local), vec![true]);
+}
+
+#[test]
+fn local_hours_follow_daylight_saving_changes() {
+    let hours = |hours: &[&str]| time_is(Operator::HourOfDayIn, list(DataType::Number, hours));
+    let zone = "Europe/Berlin";
+    // Clocks went from 02:00 to 03:00 at 01:00 UTC on 31 March 2024
+    let spring = ["2024-03-31T00:30:00Z", "2024-03-31T01:30:00Z"];
+    assert_eq!(matches_in(&hours(&["1"]), zone, &spring), vec![true, false]);
+    assert_eq!(
+        matches_in(&hours(&["2"]), zone, &spring),
+        vec![false, false]
+    );
+    assert_eq!(matches_in(&hours(&["3"]), zone, &spring), vec![false, true]);
+    // ...and from 03:00 back to 02:00 at 01:00 UTC on 27 October, so 02:30 happened twice
+    let autumn = ["2024-10-27T00:30:00Z", "2024-10-27T01:30:00Z"];
+    assert_eq!(matches_in(&hours(&["2"]), zone, &autumn), vec![true, true]);
+
+    // An ambiguous local time is the earlier of the two
+    assert_eq!(
+        matches_in(
+            &time_is(Operator::Before, value("2024-10-27T01:00:00Z")),
+            zone,
+            &["2024-10-27T02:30"]
+        ),
+        vec![true]
+    );
+    // A local time skipped by the change is read with the offset before it
+    let skipped = ["2024-03-31T02:30"];
+    assert_eq!(
+        matches_in(
+            &time_is(Operator::After, value("2024-03-31T01:29:59Z")),
+            zone,
+            &skipped
+        ),
+        vec![true]
+    );
+    assert_eq!(
+        matches_in(
+            &time_is(Operator::Before, value("2024-03-31T01:30:01Z")),
+            zone,
+            &skipped
+        ),
+        vec![true]
+    );
+    assert_eq!(matches_in(&hours(&["3"]), zone, &skipped), vec![true]);
+}
+
+/// A rule taking `actions` for amounts over `amount`
+fn amount_over(name: &str, amount: &str, actions: Vec<Action>) -> Rule {
+    let mut rule = rule(
+        name,
+        leaf(
+            field(Field::TransactionAmount),
+            Operator::GreaterThan,
+            value(amount),
+        ),
+    );
+    rule.actions = actions;
+    rule
+}
+
+/// A rule set over `rules` in priority order, all enabled
+fn rule_set(strategy: MatchStrategy, rules: &[Rule]) -> RuleSet {
+    let mut set = RuleSet::new("Checkout".to_string(), String::new());
+    set.strategy = strategy;
+    set.entries = rules
+        .iter()
+        .map(|rule| RuleSetEntry {
+            rule_id: rule.id,
+            enabled: true,
+        })
+        .collect();
+    set
+}
+
+fn run(set: &RuleSet, rules: &[Rule], amount: f64) -> RuleSetOutcome {
+    let tx = tx(json!({ "transaction_amount": amount }));
+    evaluate_rule_set(set, rules, &tx, &EvalContext::default())
+}
+
+/// The evaluated rules in order, with whether each contributed
+fn contributions(outcome: &RuleSetOutcome) -> Vec<(&str, bool)> {
+    outcome
+        .rules
+        .iter()
+        .map(|rule| (rule.rule_name.as_str(), rule.contributed))
+        .collect()
+}
+
+#[test]
+fn first_match_stops_at_the_highest_priority_match() {
+    let rules = [
+        amount_over("huge", "1000", vec![Action::Block]),
+        amount_over("large", "100", vec![Action::FlagForReview]),
+        amount_over(
+            "any",
+            "10",
+            vec![Action::Tag {
+                label: "seen".to_string(),
+            }],
+        ),
+    ];
+    let mut set = rule_set(MatchStrategy::FirstMatch, &rules);
+
+    let outcome = run(&set, &rules, 500.0);
+    assert_eq!(
+        contributions(&outcome),
+        vec![("huge", false), ("large", true)]
+    );
+    assert_eq!(outcome.actions, vec![Action::FlagForReview]);
+    assert_eq!(outcome.decision, Decision::Review);
+    assert!(!outcome.defaulted);
+
+    // A match whose actions imply no decision leaves the default in place
+    set.default_decision = Decision::StepUp;
+    let outcome = run(&set, &rules, 50.0);
+    assert_eq!(
+        contributions(&outcome),
+        vec![("huge", false), ("large", false), ("any", true)]
+    );
+    assert_eq!(outcome.decision, Decision::StepUp);
+    assert!(outcome.defaulted);
+
+    // Disabled and deleted rules are skipped but keep their priority
+    set.entries[1].enabled = false;
+    let outcome = run(&set, &rules[..2], 500.0);
+    assert_eq!(contributions(&outcome), vec![("huge", false)]);
+    assert_eq!(outcome.decision, Decision::StepUp);
+    let outcome = run(&set, &rules, 500.0);
+    assert_eq!(
+        contributions(&outcome),
+        vec![("huge", false), ("any", true)]
+    );
+    assert_eq!(outcome.rules[1].priority, 3);
+}
+
+#[test]
+fn all_matches_applies_every_match_and_the_most_severe_decision() {
+    let tag = |label: &str| Action::Tag {
+        label: label.to_string(),
+    };
+    let rules = [
+        amount_over("large", "100", vec![Action::FlagForReview, tag("large")]),
+        amount_over("huge", "1000", vec![Action::Block, tag("large")]),
+        amount_over("medium", "50", vec![Action::RequireStepUp]),
+    ];
+    let set = rule_set(MatchStrategy::AllMatches, &rules);
+
+    let outcome = run(&set, &rules, 5000.0);
+    assert_eq!(
+        contributions(&outcome),
+        vec![("large", true), ("huge", true), ("medium", true)]
+    );
+    // In priority order, without the repeated tag
+    assert_eq!(
+        outcome.actions,
+        vec![
+            Action::FlagForReview,
+            tag("large"),
+            Action::Block,
+            Action::RequireStepUp
+        ]
+    );
+    assert_eq!(outcome.decision, Decision::Block);
+
+    let outcome = run(&set, &rules, 500.0);
+    assert_eq!(
+        contributions(&outcome),
+        vec![("large", true), ("huge", false), ("medium", true)]
+    );
+    assert_eq!(outcome.decision, Decision::StepUp);
+
+    let outcome = run(&set, &rules, 5.0);
+    assert!(outcome.actions.is_empty());
+    assert_eq!(outcome.decision, Decision::Allow);
+    assert!(outcome.defaulted);
+}
+
+#[test]
+fn highest_score_applies_the_best_scoring_match_only() {
+    let risk = |score| Action::AddRiskScore { score };
+    let rules = [
+        amount_over("huge", "1000", vec![risk(50), Action::FlagForReview]),
+        amount_over("large", "100", vec![risk(80), Action::RequireStepUp]),
+        amount_over("any", "10", vec![risk(30), risk(50), Action::Block]),
+    ];
+    let set = rule_set(MatchStrategy::HighestScore, &rules);
+
+    // "any" adds up to the same score as "large", and the tie goes to priority
+    let outcome = run(&set, &rules, 5000.0);
+    assert_eq!(
+        contributions(&outcome),
+        vec![("huge", false), ("large", true), ("any", false)]
+    );
+    let scores: Vec<f64> = outcome.rules.iter().map(|rule| rule.score).collect();
+    assert_eq!(scores, vec![50.0, 80.0, 80.0]);
+    assert_eq!(outcome.actions, vec![risk(80), Action::RequireStepUp]);
+    assert_eq!(outcome.decision, Decision::StepUp);
+
+    // Unmatched rules score nothing and cannot win
+    let outcome = run(&set, &rules, 50.0);
+    assert_eq!(
+        contributions(&outcome),
+        vec![("huge", false), ("large", false), ("any", true)]
+    );
+    assert_eq!(outcome.rules[0].score, 0.0);
+    assert_eq!(outcome.decision, Decision::Block);
+
+    let outcome = run(&set, &rules, 5.0);
+    assert!(outcome.rules.iter().all(|rule| !rule.contributed));
+    assert_eq!(outcome.decision, Decision::Allow);
+}
+
+/// Outcomes of a trace in tree order, `None` for skipped nodes
+fn outcomes(node: &TraceNode) -> Vec<Option<bool>> {
+    let mut all = vec![node.outcome];
+    match &node.detail {
+        TraceDetail::Leaf { .. } => {}
+        TraceDetail::Group { children, .. } => {
+            all.extend(children.iter().flat_map(outcomes));
+        }
+        TraceDetail::Snippet { root, .. } => {
+            all.extend(root.iter().flat_map(|root| outcomes(root)));
+        }
+    }
+    all
+}
+
+#[test]
+fn traces_record_resolved_values_and_agree_with_evaluation() {
+    let node = leaf(
+        field(Field::TransactionAmount),
+        Operator::Between,
+        range("100", "500"),
+    );
+    let ctx = EvalContext::default();
+
+    let traced = trace(&node, &tx(json!({ "transaction_amount": 250 })), &ctx);
+    assert_eq!(traced.outcome, Some(true));
+    let TraceDetail::Leaf {
+        left_value,
+        right_value,
+        ..
+    } = &traced.detail
+    else {
+        panic!("expected a leaf, got {:?}", traced.detail);
+    };
+    assert_eq!(left_value, &Some(Value::Number(250.0)));
+    assert_eq!(right_value, &Some(Value::Range(100.0, 500.0)));
+
+    // A missing field resolves to nothing and the leaf is false, not skipped
+    let traced = trace(&node, &tx(json!({})), &ctx);
+    assert_eq!(traced.outcome, Some(false));
+    assert!(!traced.is_skipped());
+    let TraceDetail::Leaf { left_value, .. } = &traced.detail else {
+        panic!("expected a leaf, got {:?}", traced.detail);
+    };
+    assert_eq!(left_value, &None);
+}
+
+#[test]
+fn traces_mark_short_circuited_siblings_as_skipped() {
+    let country = |code| leaf(field(Field::UserCountry), Operator::Equals, value(code));
+    let root = group(
+        LogicalOperator::Or,
+        vec![
+            group(LogicalOperator::And, vec![country("US"), country("NG")]),
+            country("NG"),
+            group(LogicalOperator::And, vec![country("NG"), country("GH")]),
+        ],
+    );
+    let ctx = EvalContext::default();
+    let nigeria = tx(json!({ "user_country": "NG" }));
+
+    // The AND stops at its false first child, the OR at its true second child,
+    // and everything under the skipped third child is skipped too
+    let traced = trace(&root, &nigeria, &ctx);
+    assert_eq!(
+        outcomes(&traced),
+        vec![
+            Some(true),
+            Some(false),
+            Some(false),
+            None,
+            Some(true),
+            None,
+            None,
+            None
+        ]
+    );
+    assert_eq!(traced.outcome, Some(evaluate(&root, &nigeria, &ctx)));
+
+    let json = serde_json::to_value(&traced).unwrap();
+    assert_eq!(json["type"], "group");
+    assert_eq!(json["children"][0]["children"][1]["outcome"], json!(null));
+    assert_eq!(
+        json["children"][0]["children"][1]["left_value"],
+        json!(null)
+    );
+    assert_eq!(json["children"][1]["left_value"], "NG");
+
+    // With no true child every child of the OR is evaluated
+    let traced = trace(&root, &tx(json!({ "user_country": "GB" })), &ctx);
+    assert_eq!(
+        outcomes(&traced),
+        vec![
+            Some(false),
+            Some(false),
+            Some(false),
+            None,
+            Some(false),
+            Some(false),
+            Some(false),
+            None
+        ]
+    );
+}
+
+#[test]
+fn traces_follow_snippets_into_their_conditions() {
+    let mut ctx = EvalContext::default();
+    ctx.snippets.insert(
+        "risky_country".to_string(),
+        snippet(
+            "risky_country",
+            leaf(
+                field(Field::UserCountry),
+                Operator::In,
+                list(DataType::String, &["NG", "GH"]),
+            ),
+        ),
+    );
+    let nigeria = tx(json!({ "user_country": "NG" }));
+
+    let traced = trace(&snippet_ref("risky_country"), &nigeria, &ctx);
+    assert_eq!(outcomes(&traced), vec![Some(true), Some(true)]);
+
+    // An unknown snippet is false and has no conditions to show
+    let traced = trace(&snippet_ref("deleted"), &nigeria, &ctx);
+    assert_eq!(traced.outcome, Some(false));
+    let TraceDetail::Snippet { root, .. } = &traced.detail else {
+        panic!("expected a snippet, got {:?}", traced.detail);
+    };
+    assert!(root.is_none());
+
+    // A skipped snippet is not expanded
+    let root = group(
+        LogicalOperator::And,
+        vec![
+            leaf(field(Field::UserCountry), Operator::Equals, value("US")),
+            snippet_ref("risky_country"),
+        ],
+    );
+    let traced = trace(&root, &nigeria, &ctx);
+    assert_eq!(outcomes(&traced), vec![Some(false), Some(false), None]);
+}
+
+fn test_case(name: &str, transaction: serde_json::Value, expect_flagged: bool) -> TestCase {
+    TestCase {
+        id: Uuid::new_v4(),
+        name: name.to_string(),
+        transaction: tx(transaction),
+        expect_flagged,
+    }
+}
+
+/// (name, flagged, passed) for each test case of `rule`
+fn run_cases(rule: &Rule) -> Vec<(String, bool, bool)> {
+    run_test_cases(rule, &EvalContext::default())
+        .into_iter()
+        .map(|result| {
+            (
+                result.test_case.name.clone(),
+                result.flagged,
+                result.passed(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_cases_pass_when_the_rule_flags_what_they_expect() {
+    let mut rule = amount_over("large", "100", vec![Action::Block]);
+    rule.test_cases = vec![
+        test_case("large", json!({ "transaction_amount": 500 }), true),
+        test_case("small", json!({ "transaction_amount": 50 }), false),
+        test_case("wrongly cleared", json!({ "transaction_amount": 50 }), true),
+        test_case(
+            "wrongly flagged",
+            json!({ "transaction_amount": 500 }),
+            false,
+        ),
+    ];
+    assert_eq!(
+        run_cases(&rule),
+        vec![
+            ("large".to_string(), true, true),
+            ("small".to_string(), false, true),
+            ("wrongly cleared".to_string(), false, false),
+            ("wrongly flagged".to_string(), true, false),
+        ]
+    );
+
+    // A scoring rule flags a transaction when it reaches a threshold
+    rule.mode = RuleMode::Scoring;
+    rule.root.set_weight(Some(40.0));
+    rule.thresholds = vec![ScoreThreshold {
+        min_score: 50.0,
+        actions: vec![Action::FlagForReview],
+    }];
+    let flagged: Vec<bool> = run_cases(&rule).iter().map(|case| case.1).collect();
+    assert_eq!(flagged, vec![false, false, false, false]);
+    rule.thresholds[0].min_score = 40.0;
+    let flagged: Vec<bool> = run_cases(&rule).iter().map(|case| case.1).collect();
+    assert_eq!(flagged, vec![true, false, false, true]);
+
+    rule.test_cases.clear();
+    assert!(run_cases(&rule).is_empty());
+}
+
+fn weighted(mut node: ConditionNode, weight: f64) -> ConditionNode {
+    node.set_weight(Some(weight));
+    node
+}
+
+fn threshold(min_score: f64, actions: Vec<Action>) -> ScoreThreshold {
+    ScoreThreshold { min_score, actions }
+}
+
+/// A scoring rule over `root` with the given thresholds
+fn scoring(root: ConditionNode, thresholds: Vec<ScoreThreshold>) -> Rule {
+    let mut rule = rule("Risk score", root);
+    rule.mode = RuleMode::Scoring;
+    rule.actions.clear();
+    rule.thresholds = thresholds;
+    rule
+}
+
+#[test]
+fn every_matched_weighted_node_adds_to_the_score() {
+    let large = weighted(
+        leaf(
+            field(Field::TransactionAmount),
+            Operator::GreaterThan,
+            value("100"),
+        ),
+        20.0,
+    );
+    let nigeria = weighted(
+        leaf(field(Field::UserCountry), Operator::Equals, value("NG")),
+        30.0,
+    );
+    let emulator = weighted(
+        leaf(
+            field(Field::DeviceFingerprint),
+            Operator::Regex,
+            value("^emu-"),
+        ),
+        25.0,
+    );
+    let device = weighted(group(LogicalOperator::Or, vec![emulator.clone()]), 10.0);
+    let rule = scoring(
+        group(
+            LogicalOperator::And,
+            vec![large.clone(), nigeria, device.clone()],
+        ),
+        vec![threshold(50.0, vec![Action::FlagForReview])],
+    );
+    assert!(rule.validate(&EvalContext::default()).is_ok());
+
+    // The AND as a whole fails, but its matched children still count
+    let tx = tx(json!({
+        "transaction_amount": 500,
+        "user_country": "US",
+        "device_fingerprint": "emu-1"
+    }));
+    let outcome = evaluate_rule(&rule, &tx, &EvalContext::default());
+    let breakdown = outcome.score.unwrap();
+    assert_eq!(breakdown.score, 55.0);
+    let contributions: Vec<_> = breakdown
+        .contributions
+        .iter()
+        .map(|contribution| (contribution.id, contribution.weight))
+        .collect();
+    assert_eq!(
+        contributions,
+        vec![
+            (large.id(), 20.0),
+            (emulator.id(), 25.0),
+            (device.id(), 10.0)
+        ]
+    );
+    assert!(outcome.matched);
+    assert_eq!(outcome.actions, vec![Action::FlagForReview]);
+}
+
+#[test]
+fn the_highest_threshold_reached_picks_the_actions() {
+    let country = |code, weight| {
+        weighted(
+            leaf(field(Field::UserCountry), Operator::Equals, value(code)),
+            weight,
+        )
+    };
+    let rule = scoring(
+        group(
+            LogicalOperator::Or,
+            vec![country("NG", 60.0), country("GH", 40.0), country("GB", 0.0)],
+        ),
+        vec![
+            threshold(80.0, vec![Action::Block]),
+            threshold(40.0, vec![Action::FlagForReview]),
+            threshold(60.0, vec![Action::RequireStepUp]),
+        ],
+    );
+    let outcome = |code| {
+        evaluate_rule(
+            &rule,
+            &tx(json!({ "user_country": code })),
+            &EvalContext::default(),
+        )
+    };
+
+    let nigeria = outcome("NG");
+    assert_eq!(nigeria.score.unwrap().score, 60.0);
+    assert_eq!(nigeria.actions, vec![Action::RequireStepUp]);
+    // Thresholds are inclusive
+    assert_eq!(outcome("GH").actions, vec![Action::FlagForReview]);
+
+    // Below every threshold the rule does not match, though it still has a score
+    let britain = outcome("GB");
+    assert!(!britain.matched);
+    assert!(britain.actions.is_empty());
+    let breakdown = britain.score.unwrap();
+    assert_eq!(breakdown.score, 0.0);
+    assert_eq!(breakdown.contributions.len(), 1);
+}
+
+#[test]
+fn scoring_rules_need_weights_and_valid_thresholds() {
+    let country = leaf(field(Field::UserCountry), Operator::Equals, value("NG"));
+    let errors = |rule: Rule| {
+        rule.validate(&EvalContext::default())
+            .err()
+            .unwrap_or_default()
+    };
+
+    assert_eq!(
+        errors(scoring(country.clone(), Vec::new())),
+        vec![
+            "Scoring rule must have at least one weighted condition",
+            "Scoring rule must have at least one score threshold",
+        ]
+    );
+    assert_eq!(
+        errors(scoring(
+            weighted(country, 10.0),
+            vec![
+                threshold(50.0, vec![Action::Block]),
+                threshold(f64::INFINITY, vec![Action::Block]),
+                threshold(50.0, Vec::new()),
+            ],
+        )),
+        vec![
+            "Score threshold must be a finite number",
+            "Duplicate score threshold: 50",
+            "Score threshold 50 must have at least one action",
+        ]
+    );
+}
+
+fn ip_in(operator: Operator, ranges: &[&str]) -> ConditionNode {
+    leaf(
+        field(Field::IpAddress),
+        operator,
+        list(DataType::Ip, ranges),
+    )
+}
+
+#[test]
+fn cidr_lists_accept_ranges_and_addresses_only() {
+    assert!(errors(ip_in(
+        Operator::InCidr,
+        &["10.0.0.0/8", "192.168.1.7", "2001:db8::/32"]
+    ))
+    .is_empty());
+    assert_eq!(
+        errors(ip_in(
+            Operator::InCidr,
+            &["10.0.0.0/33", "10.0.0.0/8/1", "internal", "fe80::/129"]
+        )),
+        vec![
+            "List element \"10.0.0.0/33\" is not an IP address or CIDR range",
+            "List element \"10.0.0.0/8/1\" is not an IP address or CIDR range",
+            "List element \"internal\" is not an IP address or CIDR range",
+            "List element \"fe80::/129\" is not an IP address or CIDR range",
+        ]
+    );
+    // The same network written twice is a duplicate
+    assert_eq!(
+        errors(ip_in(Operator::NotInCidr, &["10.0.0.0/8", " 10.0.0.0/8"])),
+        vec!["Duplicate list element \" 10.0.0.0/8\""]
+    );
+    assert_eq!(
+        errors(ip_in(Operator::InCidr, &[])),
+        vec!["In CIDR list cannot be empty"]
+    );
+    // Equality and In compare single addresses, so ranges belong in In CIDR
+    assert_eq!(
+        errors(ip_in(Operator::In, &["10.0.0.0/8"])),
+        vec!["\"10.0.0.0/8\" is not a valid IP address (use In CIDR for ranges)"]
+    );
+}
+
+#[test]
+fn cidr_ranges_match_the_addresses_inside_them() {
+    let transactions = [
+        json!({ "ip_address": "10.20.30.40" }),
+        json!({ "ip_address": "11.0.0.1" }),
+        json!({ "ip_address": "192.168.1.7" }),
+        json!({ "ip_address": "2001:db8::1" }),
+        json!({ "ip_address": "not an ip" }),
+        json!({}),
+    ];
+    let ranges = ["10.0.0.0/8", "192.168.1.7", "2001:db8::/32"];
+    assert_eq!(
+        matches(&ip_in(Operator::InCidr, &ranges), &transactions),
+        vec![true, false, true, true, false, false]
+    );
+    // Not In CIDR needs an address to be outside of the ranges
+    assert_eq!(
+        matches(&ip_in(Operator::NotInCidr, &ranges), &transactions),
+        vec![false, true, false, false, false, false]
+    );
+    // A bare address matches only itself
+    assert_eq!(
+        matches(
+            &ip_in(Operator::InCidr, &["192.168.1.7"]),
+            &[json!({ "ip_address": "192.168.1.8" })]
+        ),
+        vec![false]
+    );
+}
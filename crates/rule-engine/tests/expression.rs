@@ -0,0 +1,150 @@
+use proptest::prelude::*;
+use rule_engine::evaluator::evaluate_expr;
+use rule_engine::expression::{ArithOp, Expr};
+use rule_engine::models::{Field, Transaction};
+use serde_json::json;
+
+fn tx() -> Transaction {
+    serde_json::from_value(json!({
+        "transaction_amount": 120,
+        "account_age": 0,
+        "user_age": 30,
+    }))
+    .unwrap()
+}
+
+fn eval(source: &str) -> Option<f64> {
+    evaluate_expr(&Expr::parse(source).unwrap(), &tx())
+}
+
+fn check(source: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+    Expr::parse(source).unwrap().check(&mut errors);
+    errors
+}
+
+fn expr() -> impl Strategy<Value = Expr> {
+    let leaf = prop_oneof![
+        (0u32..1000).prop_map(|n| Expr::Number {
+            value: f64::from(n) / 4.0
+        }),
+        prop::sample::select(vec![
+            Field::TransactionAmount,
+            Field::UserAge,
+            Field::AccountAge
+        ])
+        .prop_map(|field| Expr::Field { field }),
+    ];
+    leaf.prop_recursive(4, 24, 2, |inner| {
+        prop_oneof![
+            inner.clone().prop_map(|expr| Expr::Negate {
+                expr: Box::new(expr)
+            }),
+            (
+                prop::sample::select(vec![
+                    ArithOp::Add,
+                    ArithOp::Subtract,
+                    ArithOp::Multiply,
+                    ArithOp::Divide
+                ]),
+                inner.clone(),
+                inner,
+            )
+                .prop_map(|(op, left, right)| Expr::Binary {
+                    op,
+                    left: Box::new(left),
+                    right: Box::new(right),
+                }),
+        ]
+    })
+}
+
+proptest! {
+    /// Rendering adds exactly the parentheses needed to parse back the same tree
+    #[test]
+    fn source_parses_back_to_the_same_expression(expr in expr()) {
+        prop_assert_eq!(Expr::parse(&expr.source()), Ok(expr));
+    }
+}
+
+#[test]
+fn multiplication_binds_tighter_and_operators_associate_left() {
+    assert_eq!(eval("2 + 3 * 4"), Some(14.0));
+    assert_eq!(eval("(2 + 3) * 4"), Some(20.0));
+    assert_eq!(eval("10 - 4 - 3"), Some(3.0));
+    assert_eq!(eval("10 - (4 - 3)"), Some(9.0));
+    assert_eq!(eval("24 / 4 / 2"), Some(3.0));
+    assert_eq!(eval("-2 * -3 + transaction_amount"), Some(126.0));
+    assert_eq!(eval("transaction_amount / user_age * 2"), Some(8.0));
+}
+
+#[test]
+fn display_uses_field_names_and_minimal_parentheses() {
+    let display = |source: &str| Expr::parse(source).unwrap().display();
+    assert_eq!(
+        display("transaction_amount - (user_age * 2)"),
+        "Transaction Amount - User Age * 2"
+    );
+    assert_eq!(display("(1 - 2) - (3 - 4)"), "1 - 2 - (3 - 4)");
+    assert_eq!(display("-(user_age + 1)"), "-(User Age + 1)");
+    assert_eq!(display("2.50 * ((account_age))"), "2.5 * Account Age");
+}
+
+#[test]
+fn division_by_zero_is_rejected_or_has_no_value() {
+    let errors = check("transaction_amount / 0");
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("divides by zero"), "{:?}", errors);
+    // Only literal zeros are known in advance; a zero field leaves no value to compare
+    assert!(check("transaction_amount / account_age").is_empty());
+    assert_eq!(eval("transaction_amount / account_age"), None);
+    assert_eq!(eval("transaction_amount / (user_age - 30)"), None);
+    // A missing field has no value either
+    assert_eq!(eval("average_amount_30d * 2"), None);
+}
+
+#[test]
+fn invalid_expressions_are_rejected() {
+    for (source, error) in [
+        ("", "empty"),
+        ("1 +", "ends unexpectedly"),
+        ("(1 + 2", "Missing closing"),
+        ("1 + 2)", "Unexpected \")\""),
+        ("bogus + 1", "Unknown field \"bogus\""),
+        ("1..2", "Invalid number"),
+        ("2 % 3", "Unexpected character"),
+    ] {
+        let message = Expr::parse(source).unwrap_err();
+        assert!(message.contains(error), "{}: {}", source, message);
+    }
+    let errors = check("user_id * 2");
+    assert!(errors[0].contains("not numeric"), "{:?}", errors);
+}
+
+#[test]
+fn nesting_and_length_are_limited() {
+    let nested = |depth: usize| {
+        format!(
+            "{}transaction_amount{}",
+            "(".repeat(depth),
+            ")".repeat(depth)
+        )
+    };
+    assert!(Expr::parse(&nested(Expr::MAX_NESTING)).is_ok());
+    let error = Expr::parse(&nested(Expr::MAX_NESTING + 1)).unwrap_err();
+    assert!(error.contains("nests more than"), "{}", error);
+
+    let negated = |depth: usize| format!("{}1", "-".repeat(depth));
+    assert_eq!(
+        evaluate_expr(&Expr::parse(&negated(Expr::MAX_NESTING)).unwrap(), &tx()),
+        Some(1.0)
+    );
+    assert!(Expr::parse(&negated(Expr::MAX_NESTING + 1)).is_err());
+
+    // Far beyond the limits, parsing fails instead of overflowing the stack
+    assert!(Expr::parse(&"-".repeat(100_000)).is_err());
+    assert!(Expr::parse(&nested(100_000)).is_err());
+    let chain = vec!["1"; 100_000].join(" + ");
+    let error = Expr::parse(&chain).unwrap_err();
+    assert!(error.contains("too long"), "{}", error);
+}
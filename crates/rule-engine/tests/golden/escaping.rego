@@ -0,0 +1,33 @@
+# Escaping "quotes"
+# Line one
+# Line two
+package rules.escaping_quotes
+
+import rego.v1
+
+default match := false
+
+match if {
+	request.device["finger-print"] == "say \"hi\"\\ now\nnext\ttab"
+}
+
+match if {
+	contains(request.user["in"], "naïve ✓ 😀")
+}
+
+match if {
+	regex.match("^u-\\d+\\.\"x\"$", request.user["in"])
+}
+
+match if {
+	is_blank(object.get(request, ["device", "finger-print"], null))
+}
+
+# Missing, null or whitespace, as Is Empty treats them
+is_blank(null) := true
+
+is_blank(value) := true if trim_space(value) == ""
+
+default actions := []
+
+actions := [{"type":"flag_for_review"},{"type":"tag","label":"policy"}] if match
@@ -0,0 +1,39 @@
+# Nested risk checks
+# High amounts from risky places or devices
+package rules.nested_risk_checks
+
+import rego.v1
+
+default match := false
+
+match if {
+	input.transaction_amount > 1000
+	cond_1
+	input.user_id != null
+	not input.user_id in {"u-1", "u-2"}
+}
+
+default cond_1 := false
+
+cond_1 if {
+	input.user_country in {"NG", "RU"}
+}
+
+cond_1 if {
+	startswith(input.device_fingerprint, "emu-")
+	input.transaction_currency != null
+	input.transaction_currency != "USD"
+}
+
+cond_1 if {
+	input.user_age < 21
+}
+
+cond_1 if {
+	input.account_age >= 0
+	input.account_age <= 7.5
+}
+
+default actions := []
+
+actions := [{"type":"flag_for_review"},{"type":"tag","label":"policy"}] if match
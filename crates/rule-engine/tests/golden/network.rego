@@ -0,0 +1,16 @@
+# Recent logins from blocked ranges
+package rules.recent_logins_from_blocked_ranges
+
+import rego.v1
+
+default match := false
+
+match if {
+	count(net.cidr_contains_matches({"10.0.0.0/8", "192.168.1.7/32", "2001:db8::/32"}, input.ip_address)) > 0
+	time.now_ns() - time.parse_rfc3339_ns(input.last_password_change) <= 172800000000000
+	time.parse_rfc3339_ns(input.last_password_change) <= time.now_ns()
+}
+
+default actions := []
+
+actions := [{"type":"flag_for_review"},{"type":"tag","label":"policy"}] if match
@@ -0,0 +1,17 @@
+# Night time transfers
+package rules.night_time_transfers
+
+import rego.v1
+
+default match := false
+
+match if {
+	time.parse_rfc3339_ns(input.transaction_time) > 1704063600000000000
+	time.clock([time.parse_rfc3339_ns(input.transaction_time), "Europe/Berlin"])[0] in {0, 1, 2}
+	time.weekday([time.parse_rfc3339_ns(input.transaction_time), "Europe/Berlin"]) in {"Saturday", "Sunday"}
+	(input.transaction_amount / input.average_amount_30d) > 3
+}
+
+default actions := []
+
+actions := [{"type":"flag_for_review"},{"type":"tag","label":"policy"}] if match
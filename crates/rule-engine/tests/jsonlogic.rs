@@ -0,0 +1,177 @@
+use proptest::prelude::*;
+use rule_engine::jsonlogic::{from_jsonlogic, to_jsonlogic};
+use rule_engine::models::{ConditionNode, DataType, Field, LogicalOperator, Operand, Operator};
+use rule_engine::EvalContext;
+use serde_json::{json, Value};
+
+pub mod common;
+use common::{field, group, leaf, value};
+
+fn fields_of(data_type: DataType) -> Vec<Field> {
+    Field::all()
+        .into_iter()
+        .filter(|field| field.data_type() == data_type)
+        .collect()
+}
+
+fn field_of(data_type: DataType) -> impl Strategy<Value = Operand> {
+    prop::sample::select(fields_of(data_type)).prop_map(|field| Operand::Field { field })
+}
+
+/// Numbers written the way a round trip through JSON writes them
+fn number() -> impl Strategy<Value = String> {
+    (-100_000i64..100_000).prop_map(|n| (n as f64 / 10.0).to_string())
+}
+
+fn text() -> impl Strategy<Value = String> {
+    "\\PC{1,12}"
+}
+
+fn list_of(element: impl Strategy<Value = String>) -> impl Strategy<Value = Vec<String>> {
+    prop::collection::btree_set(element, 1..5).prop_map(|values| values.into_iter().collect())
+}
+
+/// Conditions JSONLogic can express, in the form importing produces
+fn representable_leaf() -> impl Strategy<Value = ConditionNode> {
+    let comparison = prop::sample::select(vec![
+        Operator::Equals,
+        Operator::NotEquals,
+        Operator::GreaterThan,
+        Operator::GreaterThanOrEqual,
+        Operator::LessThan,
+        Operator::LessThanOrEqual,
+    ]);
+    let membership = prop::sample::select(vec![Operator::In, Operator::NotIn]);
+    prop_oneof![
+        (field_of(DataType::Number), comparison, number())
+            .prop_map(|(left, op, value)| leaf(left, op, Operand::Value { value })),
+        (
+            field_of(DataType::String),
+            prop::sample::select(vec![Operator::Equals, Operator::NotEquals]),
+            text()
+        )
+            .prop_map(|(left, op, value)| leaf(left, op, Operand::Value { value })),
+        (field_of(DataType::Number), field_of(DataType::Number))
+            .prop_map(|(left, right)| leaf(left, Operator::GreaterThan, right)),
+        (field_of(DataType::String), text())
+            .prop_map(|(left, value)| leaf(left, Operator::Contains, Operand::Value { value })),
+        (field_of(DataType::Number), 0i64..1000, 0i64..1000).prop_map(|(left, a, b)| leaf(
+            left,
+            Operator::Between,
+            Operand::Range {
+                min: a.min(b).to_string(),
+                max: a.max(b).to_string(),
+            }
+        )),
+        (field_of(DataType::Number), membership.clone(), list_of(number())).prop_map(
+            |(left, op, values)| leaf(left, op, Operand::list(DataType::Number, values))
+        ),
+        (field_of(DataType::String), membership, list_of("[A-Z]{2}")).prop_map(
+            |(left, op, values)| leaf(left, op, Operand::list(DataType::String, values))
+        ),
+    ]
+}
+
+fn representable_tree() -> impl Strategy<Value = ConditionNode> {
+    representable_leaf().prop_recursive(3, 24, 4, |inner| {
+        (
+            prop::sample::select(vec![LogicalOperator::And, LogicalOperator::Or]),
+            prop::collection::vec(inner, 1..4),
+        )
+            .prop_map(|(operator, children)| group(operator, children))
+    })
+}
+
+/// The node as JSON without its ids, which a round trip regenerates
+fn without_ids(node: &ConditionNode) -> Value {
+    fn strip(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                map.remove("id");
+                map.values_mut().for_each(strip);
+            }
+            Value::Array(items) => items.iter_mut().for_each(strip),
+            _ => {}
+        }
+    }
+    let mut value = serde_json::to_value(node).unwrap();
+    strip(&mut value);
+    value
+}
+
+proptest! {
+    #[test]
+    fn conditions_survive_export_and_import(node in representable_tree()) {
+        let ctx = EvalContext::default();
+        let logic = to_jsonlogic(&node, &ctx).unwrap();
+        let imported = from_jsonlogic(&logic).unwrap();
+        prop_assert_eq!(without_ids(&imported), without_ids(&node));
+    }
+
+    #[test]
+    fn jsonlogic_survives_import_and_export(node in representable_tree()) {
+        let ctx = EvalContext::default();
+        let logic = to_jsonlogic(&node, &ctx).unwrap();
+        let reexported = to_jsonlogic(&from_jsonlogic(&logic).unwrap(), &ctx).unwrap();
+        prop_assert_eq!(reexported, logic);
+    }
+}
+
+#[test]
+fn unrepresentable_conditions_are_reported() {
+    let node = group(
+        LogicalOperator::And,
+        vec![
+            leaf(field(Field::UserId), Operator::Regex, value("^u-")),
+            leaf(
+                field(Field::TransactionAmount),
+                Operator::GreaterThan,
+                value("10"),
+            ),
+            leaf(field(Field::IpAddress), Operator::IsEmpty, value("")),
+        ],
+    );
+    assert_eq!(
+        to_jsonlogic(&node, &EvalContext::default()).unwrap_err(),
+        vec![
+            "User ID Matches Regex \"^u-\": JSONLogic has no Matches Regex",
+            "IP Address Is Empty: JSONLogic has no Is Empty",
+        ]
+    );
+}
+
+#[test]
+fn unsupported_jsonlogic_is_reported() {
+    let logic = json!({"and": [
+        {"if": [true, 1, 2]},
+        {"==": [{"var": "user.name"}, "x"]},
+        {"<": [1, {"var": "user_age"}, 9]},
+        {"!": {"==": [{"var": "user_id"}, "u-1"]}},
+        {">": [{"var": ["user_age", 0]}, 18]},
+    ]});
+    assert_eq!(
+        from_jsonlogic(&logic).unwrap_err(),
+        vec![
+            r#"Unsupported operation "if" in {"if":[true,1,2]}"#,
+            r#"Unknown field "user.name""#,
+            r#"Unsupported operation "<" in {"<":[1,{"var":"user_age"},9]}"#,
+            r#"Only "in" can be negated, not {"!":{"==":[{"var":"user_id"},"u-1"]}}"#,
+            r#"Variable defaults are not supported in {"var":["user_age",0]}"#,
+        ]
+    );
+}
+
+#[test]
+fn expressions_and_contains_import() {
+    let logic = json!({"or": [
+        {">": [{"*": [{"var": "average_amount_30d"}, 3, 2]}, {"var": "transaction_amount"}]},
+        {"in": ["emu", {"var": "device_fingerprint"}]},
+        {"<=": [18, {"var": "user_age"}, 21]},
+    ]});
+    let node = from_jsonlogic(&logic).unwrap();
+    assert_eq!(
+        node.display(),
+        "(Average Amount (30d) * 3 * 2 Greater Than Transaction Amount OR \
+         Device Fingerprint Contains \"emu\" OR User Age Between 18 and 21)"
+    );
+}
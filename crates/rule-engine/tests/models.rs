@@ -0,0 +1,144 @@
+use rule_engine::models::{
+    find_snippet_cycle, Action, ConditionNode, Field, LogicalOperator, Operator, Rule, Snippet,
+};
+use rule_engine::EvalContext;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+pub mod common;
+use common::{field, group, leaf, rule, snippet, snippet_ref, value};
+
+/// A rule as saved before actions were typed, with a single `action` string
+fn legacy_rule(action: &str) -> serde_json::Value {
+    json!({
+        "id": "6f1c2d3e-4b5a-4c6d-8e7f-9a0b1c2d3e4f",
+        "name": "High value",
+        "description": "Main fraud detection rule for transactions",
+        "root": {
+            "type": "group",
+            "id": "0b5c2b1e-3f3a-4d7e-8c55-2f0e6f4a9d01",
+            "operator": "AND",
+            "children": [{
+                "type": "leaf",
+                "id": "9d7e4c2a-1b3f-4e5d-8a6c-7f8e9d0a1b2c",
+                "left": { "type": "field", "field": "transaction_amount" },
+                "operator": "greater_than",
+                "right": { "type": "value", "value": "1000" }
+            }]
+        },
+        "action": action
+    })
+}
+
+#[test]
+fn legacy_action_strings_are_read_as_actions() {
+    let rule: Rule = serde_json::from_value(legacy_rule("flag_for_review")).unwrap();
+    assert_eq!(rule.actions, vec![Action::FlagForReview]);
+
+    let rule: Rule = serde_json::from_value(legacy_rule("block")).unwrap();
+    assert_eq!(rule.actions, vec![Action::Block]);
+
+    // Names that were never actions are kept as tags
+    let rule: Rule = serde_json::from_value(legacy_rule("notify_ops")).unwrap();
+    assert_eq!(
+        rule.actions,
+        vec![Action::Tag {
+            label: "notify_ops".to_string()
+        }]
+    );
+
+    let rule: Rule = serde_json::from_value(legacy_rule("")).unwrap();
+    assert_eq!(rule.actions, vec![]);
+}
+
+#[test]
+fn legacy_rules_are_saved_with_typed_actions() {
+    let rule: Rule = serde_json::from_value(legacy_rule("block")).unwrap();
+    let saved = serde_json::to_value(&rule).unwrap();
+    assert_eq!(saved["actions"], json!([{ "type": "block" }]));
+    assert!(saved.get("action").is_none());
+
+    let reread: Rule = serde_json::from_value(saved).unwrap();
+    assert_eq!(reread.actions, vec![Action::Block]);
+}
+
+#[test]
+fn invalid_action_lists_are_still_rejected() {
+    let mut rule = legacy_rule("block");
+    let rule = rule.as_object_mut().unwrap();
+    rule.remove("action");
+    rule.insert("actions".to_string(), json!([{ "type": "launch_rockets" }]));
+    let err = serde_json::from_value::<Rule>(json!(rule)).unwrap_err();
+    assert!(err.to_string().contains("launch_rockets"), "{}", err);
+}
+
+/// Snippets named after each entry, referencing the snippets listed with it
+fn snippets(references: &[(&str, &[&str])]) -> BTreeMap<String, Snippet> {
+    references
+        .iter()
+        .map(|(name, referenced)| {
+            let mut children: Vec<ConditionNode> =
+                referenced.iter().map(|name| snippet_ref(name)).collect();
+            children.push(leaf(
+                field(Field::TransactionAmount),
+                Operator::GreaterThan,
+                value("100"),
+            ));
+            (
+                name.to_string(),
+                snippet(name, group(LogicalOperator::And, children)),
+            )
+        })
+        .collect()
+}
+
+fn cycle_of(name: &str, snippets: &BTreeMap<String, Snippet>) -> Option<Vec<String>> {
+    find_snippet_cycle(name, &snippets[name].root, snippets)
+}
+
+#[test]
+fn snippets_referencing_themselves_are_cycles() {
+    let direct = snippets(&[("loop", &["loop"])]);
+    assert_eq!(
+        cycle_of("loop", &direct),
+        Some(vec!["loop".to_string(), "loop".to_string()])
+    );
+
+    let pair = snippets(&[("a", &["b"]), ("b", &["a"])]);
+    assert_eq!(
+        cycle_of("a", &pair),
+        Some(vec!["a".to_string(), "b".to_string(), "a".to_string()])
+    );
+    assert_eq!(
+        cycle_of("b", &pair),
+        Some(vec!["b".to_string(), "a".to_string(), "b".to_string()])
+    );
+}
+
+#[test]
+fn shared_snippets_are_not_cycles() {
+    // top references left and right, which both reference shared
+    let diamond = snippets(&[
+        ("top", &["left", "right"]),
+        ("left", &["shared"]),
+        ("right", &["shared"]),
+        ("shared", &[]),
+    ]);
+    for name in diamond.keys() {
+        assert_eq!(cycle_of(name, &diamond), None, "{}", name);
+    }
+    // Unknown snippets are reported by validation, not as cycles
+    let dangling = snippets(&[("top", &["missing"])]);
+    assert_eq!(cycle_of("top", &dangling), None);
+}
+
+#[test]
+fn rules_using_cyclic_snippets_are_rejected() {
+    let ctx = EvalContext {
+        snippets: snippets(&[("a", &["b"]), ("b", &["a"]), ("ok", &[])]),
+        ..EvalContext::default()
+    };
+    let errors = rule("Cyclic", snippet_ref("a")).validate(&ctx).unwrap_err();
+    assert_eq!(errors, vec!["Snippet \"a\" references itself: a → b → a"]);
+    assert!(rule("Acyclic", snippet_ref("ok")).validate(&ctx).is_ok());
+}
@@ -0,0 +1,154 @@
+use proptest::prelude::*;
+use rule_engine::evaluator::evaluate_rule;
+use rule_engine::models::{
+    ConditionNode, DataType, Field, LogicalOperator, Operator, Rule, Transaction,
+};
+use rule_engine::overlap::{compare_rules, Relation};
+use rule_engine::EvalContext;
+use serde_json::json;
+
+pub mod common;
+use common::{field, group, leaf, list, rule, value};
+
+fn condition() -> impl Strategy<Value = ConditionNode> {
+    let comparison = prop::sample::select(vec![
+        Operator::GreaterThan,
+        Operator::LessThanOrEqual,
+        Operator::Equals,
+        Operator::NotEquals,
+    ]);
+    prop_oneof![
+        (comparison, 0i64..4).prop_map(|(operator, n)| leaf(
+            field(Field::TransactionAmount),
+            operator,
+            value(&(n * 100).to_string())
+        )),
+        (
+            prop::sample::select(vec![Operator::In, Operator::NotIn]),
+            prop::sample::subsequence(vec!["NG", "FR", "US"], 1..3)
+        )
+            .prop_map(|(operator, countries)| leaf(
+                field(Field::UserCountry),
+                operator,
+                list(DataType::String, &countries)
+            )),
+        Just(leaf(
+            field(Field::DeviceFingerprint),
+            Operator::StartsWith,
+            value("emu")
+        )),
+    ]
+}
+
+fn tree() -> impl Strategy<Value = ConditionNode> {
+    condition().prop_recursive(3, 12, 3, |inner| {
+        (
+            prop_oneof![Just(LogicalOperator::And), Just(LogicalOperator::Or)],
+            prop::collection::vec(inner, 1..4),
+        )
+            .prop_map(|(operator, children)| group(operator, children))
+    })
+}
+
+fn transaction() -> impl Strategy<Value = Transaction> {
+    (
+        prop::option::of(0i64..400),
+        prop::option::of(prop::sample::select(vec!["NG", "FR", "US", "BR"])),
+        prop::option::of(prop::sample::select(vec!["emu-1", "pixel"])),
+    )
+        .prop_map(|(amount, country, device)| {
+            let mut tx = serde_json::Map::new();
+            if let Some(amount) = amount {
+                tx.insert("transaction_amount".to_string(), json!(amount));
+            }
+            if let Some(country) = country {
+                tx.insert("user_country".to_string(), json!(country));
+            }
+            if let Some(device) = device {
+                tx.insert("device_fingerprint".to_string(), json!(device));
+            }
+            Transaction(tx)
+        })
+}
+
+proptest! {
+    /// What is proven symbolically holds for any transaction, not just the sample
+    #[test]
+    fn proven_relations_hold(
+        first in tree(),
+        second in tree(),
+        txs in prop::collection::vec(transaction(), 16),
+    ) {
+        let ctx = EvalContext::default();
+        let rules = [rule("First", first), rule("Second", second)];
+        let report = compare_rules(&rules, &[], &ctx);
+        let relation = report.pairs[0].relation;
+        for tx in &txs {
+            let a = evaluate_rule(&rules[0], tx, &ctx).matched;
+            let b = evaluate_rule(&rules[1], tx, &ctx).matched;
+            match relation {
+                Relation::Equivalent => prop_assert_eq!(a, b),
+                Relation::FirstWithinSecond => prop_assert!(!a || b),
+                Relation::SecondWithinFirst => prop_assert!(!b || a),
+                Relation::Disjoint => prop_assert!(!(a && b)),
+                Relation::Unknown => {}
+            }
+            if report.shadowing[0].proven() {
+                prop_assert!(!b || a);
+            }
+        }
+    }
+}
+
+#[test]
+fn overlap_is_measured_on_the_sample() {
+    let ng = rule(
+        "Nigeria",
+        leaf(field(Field::UserCountry), Operator::Equals, value("NG")),
+    );
+    let large = rule(
+        "Large",
+        leaf(
+            field(Field::TransactionAmount),
+            Operator::GreaterThan,
+            value("100"),
+        ),
+    );
+    let small = rule(
+        "Small",
+        leaf(
+            field(Field::TransactionAmount),
+            Operator::LessThanOrEqual,
+            value("100"),
+        ),
+    );
+    let sample = Transaction::parse_jsonl(
+        "{\"user_country\": \"NG\", \"transaction_amount\": 500}\n\
+         {\"user_country\": \"NG\", \"transaction_amount\": 50}\n\
+         {\"user_country\": \"FR\", \"transaction_amount\": 500}",
+    )
+    .unwrap();
+    let report = compare_rules(
+        &[ng.clone(), large.clone(), small.clone()],
+        &sample,
+        &EvalContext::default(),
+    );
+
+    let pair = |a: &Rule, b: &Rule| {
+        report
+            .pairs
+            .iter()
+            .find(|pair| pair.first == a.id && pair.second == b.id)
+            .unwrap()
+    };
+    assert_eq!(pair(&ng, &large).relation, Relation::Unknown);
+    assert_eq!(pair(&ng, &large).jaccard(), Some(1.0 / 3.0));
+    assert_eq!(pair(&large, &small).relation, Relation::Disjoint);
+    assert_eq!(pair(&large, &small).both_hits, 0);
+
+    // Behind "NG", the small-amount rule never decides on the sample, though it could
+    let small_shadowing = &report.shadowing[1];
+    assert_eq!(small_shadowing.rule_id, small.id);
+    assert!(small_shadowing.shadowed_on_sample());
+    assert!(!small_shadowing.proven());
+}
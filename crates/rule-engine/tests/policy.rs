@@ -0,0 +1,315 @@
+//! Golden-file tests for the CEL and Rego generators. Run with `UPDATE_GOLDEN=1` to
+//! rewrite the expected files after an intended change to the output.
+
+use rule_engine::models::{
+    Action, ConditionNode, DataType, Field, LogicalOperator, Operand, Operator, Rule, RuleMode,
+    TimeWindow, WindowUnit,
+};
+use rule_engine::policy::{rule_to_cel, rule_to_rego, PolicyOptions};
+use rule_engine::EvalContext;
+use std::path::PathBuf;
+
+pub mod common;
+use common::{field, group, leaf, list, value};
+
+fn rule(name: &str, description: &str, root: ConditionNode) -> Rule {
+    let mut rule = common::rule(name, root);
+    rule.description = description.to_string();
+    rule.actions.push(Action::Tag {
+        label: "policy".to_string(),
+    });
+    rule
+}
+
+fn context(rule: &Rule) -> EvalContext {
+    EvalContext {
+        timezone: rule.tz(),
+        ..EvalContext::default()
+    }
+}
+
+/// Compare against `tests/golden/<name>`, or rewrite it when `UPDATE_GOLDEN` is set
+fn assert_golden(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("cannot read {}: {}", path.display(), e));
+    assert_eq!(
+        actual,
+        expected,
+        "{} is out of date; rerun with UPDATE_GOLDEN=1 if the change is intended",
+        path.display()
+    );
+}
+
+/// AND and OR groups nested three deep, under both root operators
+fn nested_rule(root_operator: LogicalOperator) -> Rule {
+    rule(
+        "Nested risk checks",
+        "High amounts from risky places or devices",
+        group(
+            root_operator,
+            vec![
+                leaf(
+                    field(Field::TransactionAmount),
+                    Operator::GreaterThan,
+                    value("1000"),
+                ),
+                group(
+                    LogicalOperator::Or,
+                    vec![
+                        leaf(
+                            field(Field::UserCountry),
+                            Operator::In,
+                            list(DataType::String, &["NG", "RU"]),
+                        ),
+                        group(
+                            LogicalOperator::And,
+                            vec![
+                                leaf(
+                                    field(Field::DeviceFingerprint),
+                                    Operator::StartsWith,
+                                    value("emu-"),
+                                ),
+                                leaf(
+                                    field(Field::TransactionCurrency),
+                                    Operator::NotEquals,
+                                    value("USD"),
+                                ),
+                            ],
+                        ),
+                        group(
+                            LogicalOperator::Or,
+                            vec![
+                                leaf(field(Field::UserAge), Operator::LessThan, value("21")),
+                                leaf(
+                                    field(Field::AccountAge),
+                                    Operator::Between,
+                                    Operand::Range {
+                                        min: "0".to_string(),
+                                        max: "7.5".to_string(),
+                                    },
+                                ),
+                            ],
+                        ),
+                    ],
+                ),
+                leaf(
+                    field(Field::UserId),
+                    Operator::NotIn,
+                    list(DataType::String, &["u-1", "u-2"]),
+                ),
+            ],
+        ),
+    )
+}
+
+/// Strings that need escaping, and paths that need bracket syntax
+fn escaping_rule() -> Rule {
+    rule(
+        "Escaping \"quotes\"",
+        "Line one\nLine two",
+        group(
+            LogicalOperator::Or,
+            vec![
+                leaf(
+                    field(Field::DeviceFingerprint),
+                    Operator::Equals,
+                    value("say \"hi\"\\ now\nnext\ttab"),
+                ),
+                leaf(
+                    field(Field::UserId),
+                    Operator::Contains,
+                    value("naïve ✓ \u{1F600}"),
+                ),
+                leaf(
+                    field(Field::UserId),
+                    Operator::Regex,
+                    value("^u-\\d+\\.\"x\"$"),
+                ),
+                leaf(
+                    field(Field::DeviceFingerprint),
+                    Operator::IsEmpty,
+                    value(""),
+                ),
+            ],
+        ),
+    )
+}
+
+fn custom_paths() -> PolicyOptions {
+    PolicyOptions {
+        paths: [
+            (Field::TransactionAmount, "request.payment.amount"),
+            (Field::DeviceFingerprint, "request.device.finger-print"),
+            (Field::UserId, "request.user.in"),
+        ]
+        .into_iter()
+        .map(|(field, path)| (field, path.to_string()))
+        .collect(),
+    }
+}
+
+/// Date/time operators, which read local times in the rule's timezone
+fn time_rule() -> Rule {
+    let mut rule = rule(
+        "Night time transfers",
+        "",
+        group(
+            LogicalOperator::And,
+            vec![
+                leaf(
+                    field(Field::TransactionTime),
+                    Operator::After,
+                    value("2024-01-01T00:00"),
+                ),
+                leaf(
+                    field(Field::TransactionTime),
+                    Operator::HourOfDayIn,
+                    list(DataType::Number, &["0", "1", "2"]),
+                ),
+                leaf(
+                    field(Field::TransactionTime),
+                    Operator::DayOfWeekIn,
+                    list(DataType::String, &["sat", "sun"]),
+                ),
+                leaf(
+                    Operand::Expression {
+                        expr: serde_json::from_value(serde_json::json!({
+                            "type": "binary",
+                            "op": "divide",
+                            "left": { "type": "field", "field": "transaction_amount" },
+                            "right": { "type": "field", "field": "average_amount_30d" }
+                        }))
+                        .unwrap(),
+                    },
+                    Operator::GreaterThan,
+                    value("3"),
+                ),
+            ],
+        ),
+    );
+    rule.timezone = "Europe/Berlin".to_string();
+    rule
+}
+
+/// Operators only Rego can express
+fn network_rule() -> Rule {
+    rule(
+        "Recent logins from blocked ranges",
+        "",
+        group(
+            LogicalOperator::And,
+            vec![
+                leaf(
+                    field(Field::IpAddress),
+                    Operator::InCidr,
+                    list(
+                        DataType::Ip,
+                        &["10.0.0.0/8", "192.168.1.7", "2001:db8::/32"],
+                    ),
+                ),
+                leaf(
+                    field(Field::LastPasswordChange),
+                    Operator::WithinLast,
+                    Operand::Duration {
+                        window: TimeWindow {
+                            amount: 2,
+                            unit: WindowUnit::Days,
+                        },
+                    },
+                ),
+            ],
+        ),
+    )
+}
+
+#[test]
+fn nested_groups_under_and() {
+    let rule = nested_rule(LogicalOperator::And);
+    let options = PolicyOptions::default();
+    assert_golden(
+        "nested_and.cel",
+        &rule_to_cel(&rule, &options, &context(&rule)).unwrap(),
+    );
+    assert_golden(
+        "nested_and.rego",
+        &rule_to_rego(&rule, &options, &context(&rule)).unwrap(),
+    );
+}
+
+#[test]
+fn nested_groups_under_or() {
+    let rule = nested_rule(LogicalOperator::Or);
+    let options = PolicyOptions::default();
+    assert_golden(
+        "nested_or.cel",
+        &rule_to_cel(&rule, &options, &context(&rule)).unwrap(),
+    );
+    assert_golden(
+        "nested_or.rego",
+        &rule_to_rego(&rule, &options, &context(&rule)).unwrap(),
+    );
+}
+
+#[test]
+fn strings_and_paths_are_escaped() {
+    let rule = escaping_rule();
+    let options = custom_paths();
+    assert_golden(
+        "escaping.cel",
+        &rule_to_cel(&rule, &options, &context(&rule)).unwrap(),
+    );
+    assert_golden(
+        "escaping.rego",
+        &rule_to_rego(&rule, &options, &context(&rule)).unwrap(),
+    );
+}
+
+#[test]
+fn date_times_use_the_rule_timezone() {
+    let rule = time_rule();
+    let options = PolicyOptions::default();
+    assert_golden(
+        "time.cel",
+        &rule_to_cel(&rule, &options, &context(&rule)).unwrap(),
+    );
+    assert_golden(
+        "time.rego",
+        &rule_to_rego(&rule, &options, &context(&rule)).unwrap(),
+    );
+}
+
+#[test]
+fn ip_ranges_and_relative_times_are_rego_only() {
+    let rule = network_rule();
+    let options = PolicyOptions::default();
+    assert_golden(
+        "network.rego",
+        &rule_to_rego(&rule, &options, &context(&rule)).unwrap(),
+    );
+    let errors = rule_to_cel(&rule, &options, &context(&rule)).unwrap_err();
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert!(errors[0].ends_with("CEL has no IP range functions"));
+    assert!(errors[1].ends_with("CEL has no current time"));
+}
+
+#[test]
+fn scoring_rules_and_bad_paths_are_rejected() {
+    let mut rule = nested_rule(LogicalOperator::And);
+    let mut options = PolicyOptions::default();
+    options
+        .paths
+        .insert(Field::UserAge, "user..age".to_string());
+    let errors = rule_to_cel(&rule, &options, &context(&rule)).unwrap_err();
+    assert!(errors[0].ends_with("\"user..age\" is not a valid input path"));
+
+    rule.mode = RuleMode::Scoring;
+    let errors = rule_to_rego(&rule, &PolicyOptions::default(), &context(&rule)).unwrap_err();
+    assert!(errors.iter().any(|e| e.starts_with("Scoring rules")));
+}
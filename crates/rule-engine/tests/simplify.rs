@@ -0,0 +1,249 @@
+use proptest::prelude::*;
+use rule_engine::evaluator::{evaluate, score};
+use rule_engine::models::{ConditionNode, Field, LogicalOperator, Operator, Transaction};
+use rule_engine::simplify::{normalize, simplify, NormalForm, MAX_TERMS};
+use rule_engine::EvalContext;
+use serde_json::json;
+use uuid::Uuid;
+
+pub mod common;
+use common::{field, group, leaf, value};
+
+/// A small pool of conditions, so generated trees repeat and nest them
+fn condition() -> impl Strategy<Value = ConditionNode> {
+    prop_oneof![
+        (0i64..4).prop_map(|n| leaf(
+            field(Field::TransactionAmount),
+            Operator::GreaterThan,
+            value(&(n * 100).to_string())
+        )),
+        prop::sample::select(vec!["NG", "FR"]).prop_map(|country| leaf(
+            field(Field::UserCountry),
+            Operator::Equals,
+            value(country)
+        )),
+        Just(leaf(field(Field::UserId), Operator::IsEmpty, value(""))),
+    ]
+}
+
+fn operator() -> impl Strategy<Value = LogicalOperator> {
+    prop_oneof![Just(LogicalOperator::And), Just(LogicalOperator::Or)]
+}
+
+fn weight() -> impl Strategy<Value = Option<f64>> {
+    prop::option::weighted(0.3, (1i64..10).prop_map(|w| w as f64))
+}
+
+fn tree() -> impl Strategy<Value = ConditionNode> {
+    (condition(), weight())
+        .prop_map(|(mut node, weight)| {
+            node.set_weight(weight);
+            node
+        })
+        .prop_recursive(4, 32, 4, |inner| {
+            (operator(), prop::collection::vec(inner, 0..4), weight()).prop_map(
+                |(operator, children, weight)| ConditionNode::Group {
+                    id: Uuid::new_v4(),
+                    operator,
+                    children,
+                    weight,
+                },
+            )
+        })
+}
+
+fn transaction() -> impl Strategy<Value = Transaction> {
+    (
+        prop::option::of(0i64..400),
+        prop::option::of(prop::sample::select(vec!["NG", "FR", "US"])),
+        prop::option::of(prop::sample::select(vec!["", "u-1"])),
+    )
+        .prop_map(|(amount, country, user)| {
+            let mut tx = serde_json::Map::new();
+            if let Some(amount) = amount {
+                tx.insert("transaction_amount".to_string(), json!(amount));
+            }
+            if let Some(country) = country {
+                tx.insert("user_country".to_string(), json!(country));
+            }
+            if let Some(user) = user {
+                tx.insert("user_id".to_string(), json!(user));
+            }
+            Transaction(tx)
+        })
+}
+
+fn without_weights(node: &ConditionNode) -> ConditionNode {
+    let mut node = node.clone();
+    node.set_weight(None);
+    if let ConditionNode::Group { children, .. } = &mut node {
+        *children = children.iter().map(without_weights).collect();
+    }
+    node
+}
+
+proptest! {
+    #[test]
+    fn simplifying_keeps_matches_and_scores(node in tree(), txs in prop::collection::vec(transaction(), 8)) {
+        let ctx = EvalContext::default();
+        let simplified = simplify(&node);
+        for tx in &txs {
+            prop_assert_eq!(evaluate(&node, tx, &ctx), evaluate(&simplified, tx, &ctx));
+            prop_assert_eq!(score(&node, tx, &ctx).score, score(&simplified, tx, &ctx).score);
+        }
+    }
+
+    #[test]
+    fn normal_forms_keep_matches(node in tree(), txs in prop::collection::vec(transaction(), 8)) {
+        let node = without_weights(&node);
+        let ctx = EvalContext::default();
+        for form in [NormalForm::Dnf, NormalForm::Cnf] {
+            let Ok(normal) = normalize(&node, form) else { continue };
+            for tx in &txs {
+                prop_assert_eq!(evaluate(&node, tx, &ctx), evaluate(&normal, tx, &ctx));
+            }
+        }
+    }
+
+    #[test]
+    fn simplifying_twice_changes_nothing(node in tree()) {
+        let once = simplify(&node);
+        prop_assert_eq!(
+            serde_json::to_value(simplify(&once)).unwrap(),
+            serde_json::to_value(&once).unwrap()
+        );
+    }
+}
+
+#[test]
+fn nested_groups_are_flattened_and_deduplicated() {
+    let amount = leaf(
+        field(Field::TransactionAmount),
+        Operator::GreaterThan,
+        value("100"),
+    );
+    let country = leaf(field(Field::UserCountry), Operator::Equals, value("NG"));
+    let node = group(
+        LogicalOperator::And,
+        vec![
+            group(
+                LogicalOperator::And,
+                vec![group(LogicalOperator::And, vec![country.clone()])],
+            ),
+            group(LogicalOperator::And, vec![]),
+            amount.clone(),
+            country.with_new_ids(),
+        ],
+    );
+    assert_eq!(
+        simplify(&node).display(),
+        "(Transaction Amount Greater Than \"100\" AND User Country Equals \"NG\")"
+    );
+}
+
+#[test]
+fn empty_groups_fold_like_constants() {
+    let amount = leaf(
+        field(Field::TransactionAmount),
+        Operator::GreaterThan,
+        value("100"),
+    );
+    let never = group(LogicalOperator::Or, vec![]);
+    let always = group(LogicalOperator::And, vec![]);
+
+    // AND(a, OR()) never matches, and OR(a, AND()) always does
+    let and = simplify(&group(
+        LogicalOperator::And,
+        vec![amount.clone(), never.clone()],
+    ));
+    assert_eq!(serde_json::to_value(&and).unwrap()["operator"], json!("OR"));
+    assert_eq!(and.display(), never.display());
+    let or = simplify(&group(
+        LogicalOperator::Or,
+        vec![amount.clone(), always.clone()],
+    ));
+    assert_eq!(serde_json::to_value(&or).unwrap()["operator"], json!("AND"));
+    assert_eq!(or.display(), always.display());
+
+    // OR(a, OR()) is a
+    let or = simplify(&group(LogicalOperator::Or, vec![amount.clone(), never]));
+    assert!(or.is_leaf());
+
+    // Folding AND(a, AND()) into the constant would drop the condition's weight
+    let mut weighted = amount.clone();
+    weighted.set_weight(Some(5.0));
+    let or = simplify(&group(LogicalOperator::Or, vec![weighted, always]));
+    let ConditionNode::Group { children, .. } = &or else {
+        panic!("expected a group, got {}", or.display());
+    };
+    assert_eq!(children.len(), 2);
+}
+
+#[test]
+fn normal_forms_distribute_and_absorb() {
+    let a = leaf(
+        field(Field::TransactionAmount),
+        Operator::GreaterThan,
+        value("100"),
+    );
+    let b = leaf(field(Field::UserCountry), Operator::Equals, value("NG"));
+    let c = leaf(field(Field::UserCountry), Operator::Equals, value("FR"));
+    // a AND (b OR c) OR a
+    let node = group(
+        LogicalOperator::Or,
+        vec![
+            group(
+                LogicalOperator::And,
+                vec![
+                    a.clone(),
+                    group(LogicalOperator::Or, vec![b.clone(), c.clone()]),
+                ],
+            ),
+            b.clone(),
+        ],
+    );
+    assert_eq!(
+        normalize(&node, NormalForm::Dnf).unwrap().display(),
+        "(User Country Equals \"NG\" OR (Transaction Amount Greater Than \"100\" AND User Country Equals \"FR\"))"
+    );
+    assert_eq!(
+        normalize(&node, NormalForm::Cnf).unwrap().display(),
+        "((Transaction Amount Greater Than \"100\" OR User Country Equals \"NG\") AND (User Country Equals \"FR\" OR User Country Equals \"NG\"))"
+    );
+}
+
+#[test]
+fn normal_forms_refuse_weights_and_blowups() {
+    let mut weighted = leaf(field(Field::UserId), Operator::IsEmpty, value(""));
+    weighted.set_weight(Some(5.0));
+    assert!(normalize(
+        &group(LogicalOperator::And, vec![weighted]),
+        NormalForm::Dnf
+    )
+    .is_err());
+
+    // (a1 OR b1) AND (a2 OR b2) AND ... has 2^n terms in disjunctive form
+    let clauses = (0..9)
+        .map(|n| {
+            group(
+                LogicalOperator::Or,
+                vec![
+                    leaf(
+                        field(Field::TransactionAmount),
+                        Operator::GreaterThan,
+                        value(&n.to_string()),
+                    ),
+                    leaf(
+                        field(Field::UserAge),
+                        Operator::LessThan,
+                        value(&n.to_string()),
+                    ),
+                ],
+            )
+        })
+        .collect();
+    let node = group(LogicalOperator::And, clauses);
+    assert!(2usize.pow(9) > MAX_TERMS);
+    assert!(normalize(&node, NormalForm::Dnf).is_err());
+    assert!(normalize(&node, NormalForm::Cnf).is_ok());
+}
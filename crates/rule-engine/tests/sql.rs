@@ -0,0 +1,357 @@
+use proptest::prelude::*;
+use rule_engine::evaluator::evaluate;
+use rule_engine::models::{
+    ConditionNode, DataType, Field, LogicalOperator, Operand, Operator, RuleMode, Transaction,
+};
+use rule_engine::sql::{condition_to_sql, rule_to_sql, SqlDialect, SqlOptions, SqlParam};
+use rule_engine::EvalContext;
+use rusqlite::Connection;
+use serde_json::json;
+
+pub mod common;
+use common::{field, group, leaf, list, parameter, reference_list, rule, value};
+
+fn context() -> EvalContext {
+    let mut ctx = EvalContext::default();
+    ctx.lists.insert(
+        "watchlist".to_string(),
+        reference_list("watchlist", DataType::String, &["NG", "BR"]),
+    );
+    ctx.parameters.insert(
+        "LIMIT".to_string(),
+        parameter("LIMIT", DataType::Number, "200"),
+    );
+    ctx
+}
+
+fn options(dialect: SqlDialect) -> SqlOptions {
+    SqlOptions {
+        dialect,
+        ..SqlOptions::default()
+    }
+}
+
+/// Rows of `transactions`, with NULL for missing fields, in a fresh SQLite database
+fn database(transactions: &[Transaction]) -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    conn.execute_batch(
+        "CREATE TABLE transactions (
+            transaction_amount REAL,
+            user_country TEXT,
+            device_fingerprint TEXT,
+            user_id TEXT
+        )",
+    )
+    .unwrap();
+    for tx in transactions {
+        conn.execute(
+            "INSERT INTO transactions VALUES (?, ?, ?, ?)",
+            (
+                tx.0.get("transaction_amount").and_then(|v| v.as_f64()),
+                tx.0.get("user_country").and_then(|v| v.as_str()),
+                tx.0.get("device_fingerprint").and_then(|v| v.as_str()),
+                tx.0.get("user_id").and_then(|v| v.as_str()),
+            ),
+        )
+        .unwrap();
+    }
+    conn
+}
+
+/// Positions of the transactions the predicate selects in SQLite
+fn selected(conn: &Connection, node: &ConditionNode, ctx: &EvalContext) -> Vec<usize> {
+    let predicate = condition_to_sql(node, &options(SqlDialect::Sqlite), ctx).unwrap();
+    let params = predicate.params.iter().map(|param| match param {
+        SqlParam::Integer(n) => rusqlite::types::Value::Integer(*n),
+        SqlParam::Number(n) => rusqlite::types::Value::Real(*n),
+        SqlParam::Text(s) => rusqlite::types::Value::Text(s.clone()),
+    });
+    let mut statement = conn
+        .prepare(&format!(
+            "SELECT rowid FROM transactions WHERE {} ORDER BY rowid",
+            predicate.sql
+        ))
+        .unwrap();
+    statement
+        .query_map(rusqlite::params_from_iter(params), |row| {
+            row.get::<_, i64>(0)
+        })
+        .unwrap()
+        .map(|rowid| rowid.unwrap() as usize - 1)
+        .collect()
+}
+
+/// Positions of the transactions the evaluator matches
+fn matched(transactions: &[Transaction], node: &ConditionNode, ctx: &EvalContext) -> Vec<usize> {
+    (0..transactions.len())
+        .filter(|&i| evaluate(node, &transactions[i], ctx))
+        .collect()
+}
+
+fn condition() -> impl Strategy<Value = ConditionNode> {
+    let comparison = prop::sample::select(vec![
+        Operator::GreaterThan,
+        Operator::LessThanOrEqual,
+        Operator::Equals,
+        Operator::NotEquals,
+    ]);
+    let membership = prop::sample::select(vec![Operator::In, Operator::NotIn]);
+    let pattern = prop::sample::select(vec![
+        Operator::Contains,
+        Operator::StartsWith,
+        Operator::EndsWith,
+    ]);
+    prop_oneof![
+        (comparison.clone(), 0i64..4).prop_map(|(operator, n)| leaf(
+            field(Field::TransactionAmount),
+            operator,
+            value(&(n * 100).to_string())
+        )),
+        comparison.prop_map(|operator| leaf(
+            field(Field::TransactionAmount),
+            operator,
+            Operand::Parameter {
+                name: "LIMIT".to_string()
+            }
+        )),
+        (
+            membership.clone(),
+            prop::sample::subsequence(vec!["NG", "FR", "US"], 0..3)
+        )
+            .prop_map(|(operator, countries)| leaf(
+                field(Field::UserCountry),
+                operator,
+                list(DataType::String, &countries)
+            )),
+        membership.prop_map(|operator| leaf(
+            field(Field::UserCountry),
+            operator,
+            Operand::ListRef {
+                name: "watchlist".to_string()
+            }
+        )),
+        (
+            pattern,
+            prop::sample::select(vec!["50%", "%_off", "_", "emu", "\\"])
+        )
+            .prop_map(|(operator, text)| leaf(
+                field(Field::DeviceFingerprint),
+                operator,
+                value(text)
+            )),
+        Just(leaf(field(Field::UserId), Operator::IsEmpty, value(""))),
+    ]
+}
+
+fn tree() -> impl Strategy<Value = ConditionNode> {
+    condition().prop_recursive(3, 12, 3, |inner| {
+        (
+            prop_oneof![Just(LogicalOperator::And), Just(LogicalOperator::Or)],
+            prop::collection::vec(inner, 0..4),
+        )
+            .prop_map(|(operator, children)| group(operator, children))
+    })
+}
+
+fn transaction() -> impl Strategy<Value = Transaction> {
+    (
+        prop::option::of(0i64..400),
+        prop::option::of(prop::sample::select(vec!["NG", "FR", "US", "BR"])),
+        prop::option::of(prop::sample::select(vec![
+            "50%_off",
+            "50x-off",
+            "emu-1",
+            "pixel_off",
+            "a\\b",
+            "",
+        ])),
+        prop::option::of(prop::sample::select(vec!["", " ", "u-1"])),
+    )
+        .prop_map(|(amount, country, device, user)| {
+            let mut tx = serde_json::Map::new();
+            if let Some(amount) = amount {
+                tx.insert("transaction_amount".to_string(), json!(amount));
+            }
+            if let Some(country) = country {
+                tx.insert("user_country".to_string(), json!(country));
+            }
+            if let Some(device) = device {
+                tx.insert("device_fingerprint".to_string(), json!(device));
+            }
+            if let Some(user) = user {
+                tx.insert("user_id".to_string(), json!(user));
+            }
+            Transaction(tx)
+        })
+}
+
+proptest! {
+    /// SQLite selects exactly the rows the evaluator matches, NULLs included
+    #[test]
+    fn sqlite_selects_what_the_evaluator_matches(
+        node in tree(),
+        txs in prop::collection::vec(transaction(), 16),
+    ) {
+        let ctx = context();
+        let conn = database(&txs);
+        prop_assert_eq!(selected(&conn, &node, &ctx), matched(&txs, &node, &ctx));
+    }
+
+    #[test]
+    fn placeholders_are_numbered_in_binding_order(node in tree()) {
+        let ctx = context();
+        let numbered = regex::Regex::new(r"\$\d+").unwrap();
+        for dialect in SqlDialect::all() {
+            let predicate = condition_to_sql(&node, &options(dialect), &ctx).unwrap();
+            let placeholders: Vec<String> = match dialect {
+                SqlDialect::Postgres => numbered
+                    .find_iter(&predicate.sql)
+                    .map(|m| m.as_str().to_string())
+                    .collect(),
+                SqlDialect::Sqlite | SqlDialect::Ansi => {
+                    predicate.sql.matches('?').map(str::to_string).collect()
+                }
+            };
+            let expected: Vec<String> = (1..=predicate.params.len())
+                .map(|n| match dialect {
+                    SqlDialect::Postgres => format!("${}", n),
+                    SqlDialect::Sqlite | SqlDialect::Ansi => "?".to_string(),
+                })
+                .collect();
+            prop_assert_eq!(placeholders, expected);
+        }
+    }
+}
+
+#[test]
+fn lists_and_missing_fields_follow_the_evaluator() {
+    let ctx = context();
+    let txs = Transaction::parse_jsonl(
+        "{\"user_country\": \"NG\"}\n\
+         {\"user_country\": \"FR\"}\n\
+         {\"transaction_amount\": 50}",
+    )
+    .unwrap();
+    let conn = database(&txs);
+    let country = |operator, right| leaf(field(Field::UserCountry), operator, right);
+
+    let cases = [
+        (
+            country(Operator::In, list(DataType::String, &["NG", "US"])),
+            vec![0],
+        ),
+        (
+            country(Operator::NotIn, list(DataType::String, &["NG", "US"])),
+            vec![1],
+        ),
+        // An empty list contains nothing, and a missing country is in no list
+        (country(Operator::In, list(DataType::String, &[])), vec![]),
+        (
+            country(Operator::NotIn, list(DataType::String, &[])),
+            vec![0, 1],
+        ),
+        (
+            country(
+                Operator::NotIn,
+                Operand::ListRef {
+                    name: "watchlist".to_string(),
+                },
+            ),
+            vec![1],
+        ),
+        (country(Operator::NotEquals, value("NG")), vec![1]),
+        (country(Operator::IsEmpty, value("")), vec![2]),
+    ];
+    for (node, expected) in cases {
+        assert_eq!(selected(&conn, &node, &ctx), expected, "{}", node.display());
+        assert_eq!(matched(&txs, &node, &ctx), expected, "{}", node.display());
+    }
+}
+
+#[test]
+fn like_wildcards_in_values_match_literally() {
+    let ctx = context();
+    let txs = Transaction::parse_jsonl(
+        "{\"device_fingerprint\": \"50%_off\"}\n\
+         {\"device_fingerprint\": \"50xyoff\"}\n\
+         {\"device_fingerprint\": \"500 off\"}",
+    )
+    .unwrap();
+    let conn = database(&txs);
+    let node = leaf(
+        field(Field::DeviceFingerprint),
+        Operator::Contains,
+        value("%_"),
+    );
+    assert_eq!(selected(&conn, &node, &ctx), vec![0]);
+
+    let node = leaf(
+        field(Field::DeviceFingerprint),
+        Operator::StartsWith,
+        value("50_"),
+    );
+    assert!(selected(&conn, &node, &ctx).is_empty());
+}
+
+#[test]
+fn each_dialect_writes_its_own_placeholders() {
+    let ctx = context();
+    let node = group(
+        LogicalOperator::And,
+        vec![
+            leaf(
+                field(Field::TransactionAmount),
+                Operator::GreaterThan,
+                Operand::Parameter {
+                    name: "LIMIT".to_string(),
+                },
+            ),
+            leaf(
+                field(Field::UserCountry),
+                Operator::In,
+                list(DataType::String, &["NG", "FR"]),
+            ),
+        ],
+    );
+    let params = vec![
+        SqlParam::Number(200.0),
+        SqlParam::Text("NG".to_string()),
+        SqlParam::Text("FR".to_string()),
+    ];
+    for (dialect, sql) in [
+        (
+            SqlDialect::Sqlite,
+            r#"("transaction_amount" > ? AND "user_country" IN (?, ?))"#,
+        ),
+        (
+            SqlDialect::Postgres,
+            r#"("transaction_amount" > $1 AND "user_country" IN ($2, $3))"#,
+        ),
+        (
+            SqlDialect::Ansi,
+            r#"("transaction_amount" > ? AND "user_country" IN (?, ?))"#,
+        ),
+    ] {
+        let predicate = condition_to_sql(&node, &options(dialect), &ctx).unwrap();
+        assert_eq!(predicate.sql, sql);
+        assert_eq!(predicate.params, params);
+    }
+}
+
+#[test]
+fn scoring_rules_are_not_exported() {
+    let mut scoring = rule(
+        "Scored",
+        leaf(
+            field(Field::TransactionAmount),
+            Operator::GreaterThan,
+            value("100"),
+        ),
+    );
+    let ctx = context();
+    assert!(rule_to_sql(&scoring, &options(SqlDialect::Postgres), &ctx).is_ok());
+
+    scoring.mode = RuleMode::Scoring;
+    let errors = rule_to_sql(&scoring, &options(SqlDialect::Postgres), &ctx).unwrap_err();
+    assert!(errors[0].contains("Scoring rules"), "{:?}", errors);
+}
@@ -0,0 +1,73 @@
+use rule_engine::models::{ConditionNode, Field, LogicalOperator, Operator, Transaction};
+use rule_engine::stats::DatasetStats;
+use rule_engine::EvalContext;
+use serde_json::json;
+
+pub mod common;
+use common::{field, group, leaf, value};
+
+fn dataset(records: &[serde_json::Value]) -> Vec<Transaction> {
+    records
+        .iter()
+        .map(|record| serde_json::from_value(record.clone()).unwrap())
+        .collect()
+}
+
+/// (matched, decisive) for a node
+fn hits(stats: &DatasetStats, node: &ConditionNode) -> (usize, usize) {
+    let stats = stats.get(node.id());
+    (stats.matched, stats.decisive)
+}
+
+#[test]
+fn every_node_is_counted_and_credited_when_it_decides_its_group() {
+    let large = leaf(
+        field(Field::TransactionAmount),
+        Operator::GreaterThan,
+        value("100"),
+    );
+    let nigeria = leaf(field(Field::UserCountry), Operator::Equals, value("NG"));
+    let emulator = leaf(
+        field(Field::DeviceFingerprint),
+        Operator::Regex,
+        value("^emu-"),
+    );
+    let both = group(LogicalOperator::And, vec![large.clone(), nigeria.clone()]);
+    let root = group(LogicalOperator::Or, vec![both.clone(), emulator.clone()]);
+    let dataset = dataset(&[
+        json!({ "transaction_amount": 500, "user_country": "NG", "device_fingerprint": "pixel" }),
+        json!({ "transaction_amount": 500, "user_country": "US", "device_fingerprint": "emu-1" }),
+        json!({ "transaction_amount": 50, "user_country": "US", "device_fingerprint": "pixel" }),
+        json!({ "transaction_amount": 500, "user_country": "NG", "device_fingerprint": "emu-2" }),
+    ]);
+
+    let stats = DatasetStats::compute(&root, &dataset, &EvalContext::default());
+    assert_eq!(stats.total, 4);
+    assert_eq!(hits(&stats, &root), (3, 0));
+    // The AND alone made the OR true for the first record
+    assert_eq!(hits(&stats, &both), (2, 1));
+    // Evaluated on every record, even where the OR was already decided
+    assert_eq!(hits(&stats, &emulator), (2, 1));
+    // The only false child of the AND on the second record; on the third both were false
+    assert_eq!(hits(&stats, &nigeria), (2, 1));
+    assert_eq!(hits(&stats, &large), (3, 0));
+    assert_eq!(stats.percent(3), 75.0);
+}
+
+#[test]
+fn unknown_nodes_and_empty_datasets_have_no_hits() {
+    let root = leaf(field(Field::UserCountry), Operator::Equals, value("NG"));
+    let stats = DatasetStats::compute(&root, &[], &EvalContext::default());
+    assert_eq!(stats.total, 0);
+    assert_eq!(hits(&stats, &root), (0, 0));
+    assert_eq!(stats.percent(0), 0.0);
+
+    let other = leaf(field(Field::UserCountry), Operator::Equals, value("NG"));
+    let stats = DatasetStats::compute(
+        &root,
+        &dataset(&[json!({ "user_country": "NG" })]),
+        &EvalContext::default(),
+    );
+    assert_eq!(hits(&stats, &root), (1, 0));
+    assert_eq!(hits(&stats, &other), (0, 0));
+}
@@ -1,5 +1,6 @@
 use axum::{
     extract::Request,
+    http::{header, HeaderMap, StatusCode},
     middleware::Next,
     response::{IntoResponse, Redirect, Response},
 };
@@ -74,10 +75,12 @@ impl SessionStore {
         }
     }
 
+    #[allow(dead_code)]
     pub fn delete_session(&self, session_id: &str) {
         self.sessions.lock().unwrap().remove(session_id);
     }
 
+    #[allow(dead_code)]
     pub fn cleanup_expired(&self) {
         let mut sessions = self.sessions.lock().unwrap();
         sessions.retain(|_, session| !session.is_expired());
@@ -88,7 +91,7 @@ impl SessionStore {
 static SESSION_STORE: OnceLock<SessionStore> = OnceLock::new();
 
 pub fn get_session_store() -> &'static SessionStore {
-    SESSION_STORE.get_or_init(|| SessionStore::new())
+    SESSION_STORE.get_or_init(SessionStore::new)
 }
 
 /// Extract session ID from cookie header
@@ -145,6 +148,59 @@ pub async fn auth_middleware(request: Request, next: Next) -> Response {
     }
 }
 
+/// API keys accepted by the `/api/v1` endpoints
+static API_KEYS: OnceLock<Vec<String>> = OnceLock::new();
+
+/// API keys, read once from the comma-separated `RULES_API_KEYS` environment variable.
+/// Without any, every API request is refused.
+pub fn api_keys() -> &'static [String] {
+    API_KEYS.get_or_init(|| {
+        std::env::var("RULES_API_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+}
+
+/// Extract the token from an `Authorization: Bearer <token>` header
+fn extract_bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// Compare every byte, so the response time does not tell how much of a key was right
+fn same_key(key: &str, token: &str) -> bool {
+    key.len() == token.len()
+        && key
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// API middleware - requires a configured API key as a bearer token
+pub async fn api_key_middleware(request: Request, next: Next) -> Response {
+    let authorized = extract_bearer_token(request.headers())
+        .is_some_and(|token| api_keys().iter().any(|key| same_key(key, token)));
+    if authorized {
+        return next.run(request).await;
+    }
+
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        axum::Json(serde_json::json!({ "error": "A valid API key is required" })),
+    )
+        .into_response()
+}
+
 /// Public routes middleware - redirect to / if already logged in
 pub async fn public_only_middleware(request: Request, next: Next) -> Response {
     let session_id = extract_session_id(&request);
@@ -0,0 +1,479 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use rule_engine::analysis::analyze_rule;
+use rule_engine::compiler::compile_rule;
+use rule_engine::evaluator::EvalContext;
+use rule_engine::models::{
+    Action, ConditionNode, Field, Parameter, ReferenceList, Rule, Snippet, Transaction,
+};
+use rule_engine::policy::{rule_to_cel, rule_to_rego, PolicyOptions};
+use rule_engine::velocity::{unix_now, VelocityStore};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Check, format, evaluate and compare fraud rule JSON files without running the server
+#[derive(Parser)]
+#[command(name = "rules", version)]
+struct Cli {
+    /// JSON file with the `lists`, `parameters` and `snippets` rules may reference;
+    /// without it, rules can reference none
+    #[arg(long, global = true)]
+    context: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Validate rule files and print warnings about likely mistakes; exits non-zero
+    /// when any has errors
+    Validate {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Print rule files in canonical form
+    Fmt {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Rewrite the files in place
+        #[arg(long, conflicts_with = "check")]
+        write: bool,
+        /// Only report files that are not in canonical form; exits non-zero if any
+        #[arg(long)]
+        check: bool,
+    },
+    /// Evaluate newline-delimited JSON transactions from stdin against a rule,
+    /// printing one JSON result per transaction
+    Eval { file: PathBuf },
+    /// Show what changed between two versions of a rule; exits non-zero if they differ
+    Diff { old: PathBuf, new: PathBuf },
+    /// Print a rule as a CEL expression or a Rego module
+    Export {
+        file: PathBuf,
+        #[arg(long, value_enum)]
+        format: PolicyFormat,
+        /// Input path to read a field from, as `field=path`; may be repeated
+        #[arg(long = "path", value_parser = parse_field_path)]
+        paths: Vec<(Field, String)>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PolicyFormat {
+    Cel,
+    Rego,
+}
+
+fn parse_field_path(arg: &str) -> Result<(Field, String), String> {
+    let (field, path) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("expected field=path, got \"{}\"", arg))?;
+    let field = serde_json::from_value(serde_json::Value::String(field.to_string()))
+        .map_err(|_| format!("unknown field \"{}\"", field))?;
+    Ok((field, path.to_string()))
+}
+
+/// Shared data rules are validated and evaluated against
+#[derive(Default, Deserialize)]
+struct ContextFile {
+    #[serde(default)]
+    lists: Vec<ReferenceList>,
+    #[serde(default)]
+    parameters: Vec<Parameter>,
+    #[serde(default)]
+    snippets: Vec<Snippet>,
+}
+
+fn load_context(path: Option<&Path>) -> Result<EvalContext, String> {
+    let Some(path) = path else {
+        return Ok(EvalContext::default());
+    };
+    let text =
+        std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let context: ContextFile =
+        serde_json::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+    Ok(EvalContext {
+        lists: context
+            .lists
+            .into_iter()
+            .map(|list| (list.name.clone(), list))
+            .collect(),
+        parameters: context
+            .parameters
+            .into_iter()
+            .map(|parameter| (parameter.name.clone(), parameter))
+            .collect(),
+        snippets: context
+            .snippets
+            .into_iter()
+            .map(|snippet| (snippet.name.clone(), snippet))
+            .collect(),
+        ..EvalContext::default()
+    })
+}
+
+fn read_rule(path: &Path) -> Result<(Rule, String), String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let rule = serde_json::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+    Ok((rule, text))
+}
+
+/// Pretty-printed JSON with a trailing newline, as the rule serializes
+fn canonical(rule: &Rule) -> String {
+    let mut text = serde_json::to_string_pretty(rule).unwrap_or_default();
+    text.push('\n');
+    text
+}
+
+fn validate(files: &[PathBuf], ctx: &EvalContext) -> Result<bool, String> {
+    let mut valid = true;
+    for path in files {
+        let (rule, _) = read_rule(path)?;
+        let ctx = EvalContext {
+            timezone: rule.tz(),
+            ..ctx.clone()
+        };
+        // Warnings are only worth reading once the rule is valid
+        match rule.validate(&ctx) {
+            Ok(()) => {
+                for warning in analyze_rule(&rule, &ctx) {
+                    println!("{}: warning: {}", path.display(), warning.message);
+                }
+                println!("{}: ok", path.display());
+            }
+            Err(errors) => {
+                valid = false;
+                for error in errors {
+                    println!("{}: {}", path.display(), error);
+                }
+            }
+        }
+    }
+    Ok(valid)
+}
+
+fn fmt(files: &[PathBuf], write: bool, check: bool) -> Result<bool, String> {
+    let mut formatted = true;
+    for path in files {
+        let (rule, text) = read_rule(path)?;
+        let canonical = canonical(&rule);
+        if check {
+            if text != canonical {
+                formatted = false;
+                println!("{}: not formatted", path.display());
+            }
+        } else if write {
+            if text != canonical {
+                std::fs::write(path, &canonical)
+                    .map_err(|err| format!("{}: {}", path.display(), err))?;
+                println!("{}: formatted", path.display());
+            }
+        } else {
+            print!("{}", canonical);
+        }
+    }
+    Ok(formatted)
+}
+
+/// One line of `eval` output
+#[derive(Serialize)]
+struct EvalLine<'a> {
+    line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    matched: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    actions: Option<&'a [Action]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Evaluate stdin line by line. Each transaction is recorded after it is evaluated,
+/// so velocity aggregates see the transactions before it, evaluated at the current time.
+fn eval(file: &Path, ctx: &EvalContext) -> Result<bool, String> {
+    let (rule, _) = read_rule(file)?;
+    let ctx = EvalContext {
+        velocity: Arc::new(VelocityStore::new()),
+        now: unix_now(),
+        timezone: rule.tz(),
+        ..ctx.clone()
+    };
+    let compiled = compile_rule(&rule, &ctx).map_err(|errors| {
+        format!(
+            "{} is not valid:\n  {}",
+            file.display(),
+            errors.join("\n  ")
+        )
+    })?;
+
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    let mut all_parsed = true;
+    for (index, line) in std::io::stdin().lock().lines().enumerate() {
+        let line = line.map_err(|err| err.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = match serde_json::from_str::<Transaction>(&line) {
+            Ok(tx) => {
+                let outcome = compiled.outcome(&tx, &ctx);
+                let result = EvalLine {
+                    line: index + 1,
+                    matched: Some(outcome.matched),
+                    score: outcome.score,
+                    actions: Some(outcome.actions),
+                    error: None,
+                };
+                let json = serde_json::to_string(&result);
+                ctx.velocity.record(&tx, ctx.now);
+                json
+            }
+            Err(err) => {
+                all_parsed = false;
+                serde_json::to_string(&EvalLine {
+                    line: index + 1,
+                    matched: None,
+                    score: None,
+                    actions: None,
+                    error: Some(format!("Invalid transaction JSON: {}", err)),
+                })
+            }
+        }
+        .map_err(|err| err.to_string())?;
+        writeln!(out, "{}", result).map_err(|err| err.to_string())?;
+    }
+    Ok(all_parsed)
+}
+
+/// A condition node as it appears in a diff: where it is and what it says
+struct NodeSummary {
+    path: String,
+    text: String,
+}
+
+fn summarize(node: &ConditionNode) -> String {
+    let text = match node {
+        ConditionNode::Group { operator, .. } => format!("{} group", operator),
+        _ => node.display(),
+    };
+    match node.weight() {
+        Some(weight) => format!("{} [weight {}]", text, weight),
+        None => text,
+    }
+}
+
+/// Where a node sits in the tree, numbered as in the editor: "0" is the root and
+/// "0-1" its second child
+fn node_path(indices: &[usize]) -> String {
+    std::iter::once(0)
+        .chain(indices.iter().copied())
+        .map(|index| index.to_string())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn collect_nodes(
+    node: &ConditionNode,
+    path: &mut Vec<usize>,
+    nodes: &mut BTreeMap<Uuid, NodeSummary>,
+) {
+    nodes.insert(
+        node.id(),
+        NodeSummary {
+            path: node_path(path),
+            text: summarize(node),
+        },
+    );
+    if let ConditionNode::Group { children, .. } = node {
+        for (index, child) in children.iter().enumerate() {
+            path.push(index);
+            collect_nodes(child, path, nodes);
+            path.pop();
+        }
+    }
+}
+
+fn nodes_of(rule: &Rule) -> BTreeMap<Uuid, NodeSummary> {
+    let mut nodes = BTreeMap::new();
+    collect_nodes(&rule.root, &mut Vec::new(), &mut nodes);
+    nodes
+}
+
+/// Lines for items only in `old` (`-`) or only in `new` (`+`)
+fn diff_lists(label: &str, old: &[String], new: &[String], lines: &mut Vec<String>) {
+    for item in old.iter().filter(|item| !new.contains(item)) {
+        lines.push(format!("- {}: {}", label, item));
+    }
+    for item in new.iter().filter(|item| !old.contains(item)) {
+        lines.push(format!("+ {}: {}", label, item));
+    }
+}
+
+fn rule_diff(old: &Rule, new: &Rule) -> Vec<String> {
+    let mut lines = Vec::new();
+    let fields = [
+        ("name", old.name.clone(), new.name.clone()),
+        (
+            "description",
+            old.description.clone(),
+            new.description.clone(),
+        ),
+        (
+            "mode",
+            format!("{:?}", old.mode).to_lowercase(),
+            format!("{:?}", new.mode).to_lowercase(),
+        ),
+        ("timezone", old.timezone.clone(), new.timezone.clone()),
+    ];
+    for (label, old, new) in fields {
+        if old != new {
+            lines.push(format!("~ {}: {:?} → {:?}", label, old, new));
+        }
+    }
+
+    // Conditions are matched by id, so edits and moves show up as such
+    let (old_nodes, new_nodes) = (nodes_of(old), nodes_of(new));
+    for (id, node) in &old_nodes {
+        match new_nodes.get(id) {
+            None => lines.push(format!("- condition {}: {}", node.path, node.text)),
+            Some(new_node) => {
+                let path = if new_node.path == node.path {
+                    node.path.clone()
+                } else {
+                    format!("moved {} → {}", node.path, new_node.path)
+                };
+                if new_node.text != node.text {
+                    lines.push(format!(
+                        "~ condition {}: {} → {}",
+                        path, node.text, new_node.text
+                    ));
+                } else if new_node.path != node.path {
+                    lines.push(format!("~ condition {}: {}", path, node.text));
+                }
+            }
+        }
+    }
+    for (id, node) in &new_nodes {
+        if !old_nodes.contains_key(id) {
+            lines.push(format!("+ condition {}: {}", node.path, node.text));
+        }
+    }
+
+    let actions =
+        |rule: &Rule| -> Vec<String> { rule.actions.iter().map(Action::display).collect() };
+    diff_lists("action", &actions(old), &actions(new), &mut lines);
+
+    let thresholds = |rule: &Rule| -> Vec<String> {
+        rule.thresholds
+            .iter()
+            .map(|threshold| {
+                format!(
+                    "score ≥ {}: {}",
+                    threshold.min_score,
+                    threshold
+                        .actions
+                        .iter()
+                        .map(Action::display)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+            .collect()
+    };
+    diff_lists("threshold", &thresholds(old), &thresholds(new), &mut lines);
+
+    let test_cases = |rule: &Rule| -> Vec<String> {
+        rule.test_cases
+            .iter()
+            .map(|test_case| {
+                format!(
+                    "{} (expect {})",
+                    test_case.name,
+                    if test_case.expect_flagged {
+                        "flagged"
+                    } else {
+                        "not flagged"
+                    }
+                )
+            })
+            .collect()
+    };
+    diff_lists("test case", &test_cases(old), &test_cases(new), &mut lines);
+    lines
+}
+
+fn diff(old: &Path, new: &Path) -> Result<bool, String> {
+    let (old_rule, _) = read_rule(old)?;
+    let (new_rule, _) = read_rule(new)?;
+    let lines = rule_diff(&old_rule, &new_rule);
+    for line in &lines {
+        println!("{}", line);
+    }
+    Ok(lines.is_empty())
+}
+
+fn export(
+    file: &Path,
+    format: PolicyFormat,
+    paths: Vec<(Field, String)>,
+    ctx: &EvalContext,
+) -> Result<bool, String> {
+    let (rule, _) = read_rule(file)?;
+    let ctx = EvalContext {
+        timezone: rule.tz(),
+        ..ctx.clone()
+    };
+    let options = PolicyOptions {
+        paths: paths.into_iter().collect(),
+    };
+    let source = match format {
+        PolicyFormat::Cel => rule_to_cel(&rule, &options, &ctx),
+        PolicyFormat::Rego => rule_to_rego(&rule, &options, &ctx),
+    }
+    .map_err(|errors| {
+        format!(
+            "{} cannot be exported:\n  {}",
+            file.display(),
+            errors.join("\n  ")
+        )
+    })?;
+    print!("{}", source);
+    Ok(true)
+}
+
+fn run(cli: Cli) -> Result<bool, String> {
+    let ctx = load_context(cli.context.as_deref())?;
+    match cli.command {
+        Command::Validate { files } => validate(&files, &ctx),
+        Command::Fmt {
+            files,
+            write,
+            check,
+        } => fmt(&files, write, check),
+        Command::Eval { file } => eval(&file, &ctx),
+        Command::Diff { old, new } => diff(&old, &new),
+        Command::Export {
+            file,
+            format,
+            paths,
+        } => export(&file, format, paths, &ctx),
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::from(2)
+        }
+    }
+}
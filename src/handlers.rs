@@ -1,32 +1,145 @@
 use crate::auth::get_session_store;
-use crate::models::{
-    parse_path, ConditionNode, Field, LogicalOperator, Operand, Operator, Rule, RuleStore,
+use crate::stores::{
+    DatasetStore, ListStore, ParameterStore, RuleSetStore, RuleStore, SnippetStore,
 };
 use askama::Template;
 use axum::{
-    extract::Path,
-    response::{Html, IntoResponse, Response},
+    body::{Body, Bytes},
+    extract::{Multipart, Path},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
     Form,
 };
-use serde::Deserialize;
-use std::sync::OnceLock;
+use chrono_tz::Tz;
+use rule_engine::analysis::{analyze_rule, Warning};
+use rule_engine::compiler::{compile_rule, CompiledRule};
+use rule_engine::evaluator::{
+    evaluate_rule, evaluate_rule_set, run_test_cases, trace, EvalContext, RuleOutcome,
+    RuleSetOutcome, TestCaseResult, TraceDetail, TraceNode, Value,
+};
+use rule_engine::expression::Expr;
+use rule_engine::jsonlogic::{from_jsonlogic, to_jsonlogic};
+use rule_engine::models::{
+    Action, AggregateFunction, ConditionNode, DataType, Decision, EntryImport, Field,
+    LogicalOperator, MatchStrategy, Operand, Operator, Parameter, ReferenceList, Rule, RuleMode,
+    RuleSet, RuleSetEntry, ScoreThreshold, Snippet, TestCase, TimeWindow, Transaction, WindowUnit,
+};
+use rule_engine::overlap::{compare_rules, Relation};
+use rule_engine::simplify::{normalize, NormalForm};
+use rule_engine::sql::{rule_to_sql, SqlDialect, SqlOptions, SqlPredicate};
+use rule_engine::stats::DatasetStats;
+use rule_engine::velocity::{unix_now, VelocityStore};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::Instant;
+use tokio::sync::Semaphore;
 use uuid::Uuid;
 
 // Global rule store (in a real app, you'd use proper state management)
 static RULE_STORE: OnceLock<RuleStore> = OnceLock::new();
 
 fn get_store() -> &'static RuleStore {
-    RULE_STORE.get_or_init(|| RuleStore::new())
+    RULE_STORE.get_or_init(RuleStore::new)
+}
+
+// Sample transactions used for hit statistics
+static DATASET_STORE: OnceLock<DatasetStore> = OnceLock::new();
+
+fn get_dataset_store() -> &'static DatasetStore {
+    DATASET_STORE.get_or_init(DatasetStore::new)
+}
+
+// Reference lists shared by rules
+static LIST_STORE: OnceLock<ListStore> = OnceLock::new();
+
+fn get_list_store() -> &'static ListStore {
+    LIST_STORE.get_or_init(ListStore::new)
+}
+
+// Named constants shared by rules
+static PARAMETER_STORE: OnceLock<ParameterStore> = OnceLock::new();
+
+fn get_parameter_store() -> &'static ParameterStore {
+    PARAMETER_STORE.get_or_init(ParameterStore::new)
+}
+
+// Reusable condition groups shared by rules
+static SNIPPET_STORE: OnceLock<SnippetStore> = OnceLock::new();
+
+fn get_snippet_store() -> &'static SnippetStore {
+    SNIPPET_STORE.get_or_init(SnippetStore::new)
+}
+
+// History of transactions tested in the editor, for aggregate operands
+static VELOCITY_STORE: OnceLock<Arc<VelocityStore>> = OnceLock::new();
+
+fn get_velocity_store() -> &'static Arc<VelocityStore> {
+    VELOCITY_STORE.get_or_init(|| Arc::new(VelocityStore::new()))
+}
+
+// History of transactions evaluated through the API, kept apart from the editor's so
+// testing rules never sways live decisions
+static API_VELOCITY_STORE: OnceLock<Arc<VelocityStore>> = OnceLock::new();
+
+fn get_api_velocity_store() -> &'static Arc<VelocityStore> {
+    API_VELOCITY_STORE.get_or_init(|| Arc::new(VelocityStore::new()))
+}
+
+// Ordered collections of rules that make one decision
+static RULE_SET_STORE: OnceLock<RuleSetStore> = OnceLock::new();
+
+fn get_rule_set_store() -> &'static RuleSetStore {
+    RULE_SET_STORE.get_or_init(RuleSetStore::new)
+}
+
+/// Parse a path string like "0-1-2" into indices [1, 2]
+/// The first "0" is always the root, so we skip it
+fn parse_path(path: &str) -> Vec<usize> {
+    if path == "0" {
+        return vec![];
+    }
+
+    path.split('-')
+        .skip(1) // Skip the root "0"
+        .filter_map(|s| s.parse().ok())
+        .collect()
+}
+
+/// Snapshot of the shared data rules are evaluated against, as of now, in UTC
+fn shared_eval_context() -> EvalContext {
+    EvalContext {
+        lists: get_list_store().snapshot(),
+        parameters: get_parameter_store().snapshot(),
+        snippets: get_snippet_store().snapshot(),
+        velocity: Arc::clone(get_velocity_store()),
+        now: unix_now(),
+        ..EvalContext::default()
+    }
+}
+
+/// Snapshot of the shared data a rule is evaluated against, in the rule's timezone
+fn eval_context(rule: &Rule) -> EvalContext {
+    EvalContext {
+        timezone: rule.tz(),
+        ..shared_eval_context()
+    }
+}
+
+/// Context for a rule's test cases: aggregates see no history, so each case's outcome
+/// depends only on its own transaction and not on what was tested before it
+fn test_case_context(rule: &Rule) -> EvalContext {
+    EvalContext {
+        velocity: Arc::new(VelocityStore::new()),
+        ..eval_context(rule)
+    }
 }
 
 // Templates
 #[derive(Template)]
 #[template(path = "index.html")]
 struct IndexTemplate {
-    rule: Rule,
-    rule_id: Uuid,
-    rule_json: String,
-    tree_html: String,
+    rule_view_html: String, // Pre-rendered rule view
 }
 
 #[derive(Template)]
@@ -34,8 +147,17 @@ struct IndexTemplate {
 struct RuleViewTemplate {
     rule: Rule,
     rule_id: Uuid,
+    /// Every rule, for the rule picker
+    rules: Vec<Rule>,
     rule_json: String,
     tree_html: String, // Pre-rendered tree HTML
+    dataset_size: usize,
+    dataset_hits: usize,
+    dataset_error: Option<String>,
+    test_results: Vec<TestCaseResult>,
+    tests_failed: usize,
+    published_current: bool,
+    publish_errors: Vec<String>,
 }
 
 #[derive(Template)]
@@ -43,22 +165,201 @@ struct RuleViewTemplate {
 struct ValidationResultTemplate {
     success: bool,
     errors: Vec<String>,
+    /// Likely mistakes that do not make the rule invalid
+    warnings: Vec<Warning>,
+}
+
+#[derive(Template)]
+#[template(path = "sql_export.html")]
+struct SqlExportTemplate {
+    dialect: SqlDialect,
+    predicate: Option<SqlPredicate>,
+    errors: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "jsonlogic_result.html")]
+struct JsonLogicTemplate {
+    logic: Option<String>,
+    errors: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "simplify_preview.html")]
+struct SimplifyPreviewTemplate {
+    form: NormalForm,
+    before: String,
+    /// The rewritten conditions, when rewriting succeeded
+    after: Option<String>,
+    changed: bool,
+    errors: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "lists.html")]
+struct ListsPageTemplate {
+    content_html: String, // Pre-rendered lists index or list detail
+}
+
+#[derive(Template)]
+#[template(path = "lists_index.html")]
+struct ListsIndexTemplate {
+    lists: Vec<ReferenceList>,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "list_detail.html")]
+struct ListDetailTemplate {
+    list: ReferenceList,
+    used_by: Vec<String>,
+    message: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "parameters.html")]
+struct ParametersPageTemplate {
+    content_html: String, // Pre-rendered parameter table or parameter detail
+}
+
+#[derive(Template)]
+#[template(path = "parameters_index.html")]
+struct ParametersIndexTemplate {
+    parameters: Vec<Parameter>,
+    error: Option<String>,
+}
+
+/// Conditions of one rule that use a parameter
+struct ParameterUsage {
+    rule: String,
+    conditions: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "parameter_detail.html")]
+struct ParameterDetailTemplate {
+    parameter: Parameter,
+    used_by: Vec<ParameterUsage>,
+    message: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "snippets.html")]
+struct SnippetsPageTemplate {
+    content_html: String, // Pre-rendered snippet table or snippet detail
+}
+
+#[derive(Template)]
+#[template(path = "snippets_index.html")]
+struct SnippetsIndexTemplate {
+    snippets: Vec<Snippet>,
+}
+
+#[derive(Template)]
+#[template(path = "snippet_detail.html")]
+struct SnippetDetailTemplate {
+    snippet: Snippet,
+    used_by: Vec<String>,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "rule_sets.html")]
+struct RuleSetsPageTemplate {
+    content_html: String, // Pre-rendered rule set table or rule set editor
+}
+
+#[derive(Template)]
+#[template(path = "rule_sets_index.html")]
+struct RuleSetsIndexTemplate {
+    rule_sets: Vec<RuleSet>,
+    error: Option<String>,
+}
+
+/// A rule set entry joined with its rule, for the editor
+struct RuleSetMember {
+    rule: Rule,
+    enabled: bool,
+    priority: usize,
+}
+
+#[derive(Template)]
+#[template(path = "rule_set_detail.html")]
+struct RuleSetDetailTemplate {
+    rule_set: RuleSet,
+    members: Vec<RuleSetMember>,
+    /// Rules that are not in the set yet
+    available: Vec<Rule>,
+    strategies: Vec<MatchStrategy>,
+    decisions: Vec<Decision>,
+    message: Option<String>,
+    error: Option<String>,
+}
+
+/// Two enabled rules of a set and how they overlap
+struct OverlapPairRow {
+    first: String,
+    second: String,
+    relation: Relation,
+    first_hits: usize,
+    second_hits: usize,
+    both_hits: usize,
+    /// Jaccard overlap of hits as a percentage, when either rule matched
+    jaccard: Option<String>,
+}
+
+/// An enabled rule and how much of it the rules before it decide first
+struct ShadowingRow {
+    priority: usize,
+    name: String,
+    hits: usize,
+    taken: usize,
+    /// Names of the earlier rules proven to cover this one
+    covered_by: Vec<String>,
+    shadowed_on_sample: bool,
+}
+
+#[derive(Template)]
+#[template(path = "rule_set_overlap.html")]
+struct RuleSetOverlapTemplate {
+    sample_size: usize,
+    /// Shadowing only applies when the first matching rule decides
+    first_match: bool,
+    pairs: Vec<OverlapPairRow>,
+    shadowing: Vec<ShadowingRow>,
+}
+
+#[derive(Template)]
+#[template(path = "rule_set_result.html")]
+struct RuleSetResultTemplate {
+    outcome: Option<RuleSetOutcome>,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "trace_result.html")]
+struct TraceResultTemplate {
+    outcome: Option<RuleOutcome>,
+    trace_html: String,
+    trace_json: String,
+    error: Option<String>,
+    /// Transactions held for aggregates, after recording this one
+    velocity_size: usize,
 }
 
 // Handlers
 pub async fn index() -> impl IntoResponse {
     let store = get_store();
     if let Some(rule) = store.get_rule() {
-        let rule_id = rule.id;
-        let tree_html = render_tree_node(&rule.root, "0".to_string(), 0);
-        let rule_json = serde_json::to_string_pretty(&rule).unwrap_or_else(|_| "{}".to_string());
-        let template = IndexTemplate {
-            rule,
-            rule_id,
-            rule_json,
-            tree_html,
-        };
-        HtmlTemplate(template).into_response()
+        match build_rule_view(rule).render() {
+            Ok(rule_view_html) => HtmlTemplate(IndexTemplate { rule_view_html }).into_response(),
+            Err(err) => HtmlTemplate(IndexTemplate {
+                rule_view_html: format!("<div>Failed to render rule: {}</div>", err),
+            })
+            .into_response(),
+        }
     } else {
         Html("<div>Rule not found</div>".to_string()).into_response()
     }
@@ -67,34 +368,95 @@ pub async fn index() -> impl IntoResponse {
 pub async fn new_condition_form(Path(path): Path<String>) -> impl IntoResponse {
     // Return the form with the path baked into the action
     let fields = Field::all();
+    let field_options = |side: &str| {
+        fields
+            .iter()
+            .map(|f| {
+                // Only offer right-side fields of the same type as the left side
+                let filter = if side == "right" {
+                    format!(
+                        r#" x-show="!leftFieldType || leftFieldType === '{}'""#,
+                        f.data_type().as_str()
+                    )
+                } else {
+                    String::new()
+                };
+                format!(
+                    r#"<option value="{}" data-type="{}"{}>{}</option>"#,
+                    f.as_str(),
+                    f.data_type().as_str(),
+                    filter,
+                    f.display_name()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    // Only offer reference lists whose entries match the left side's type
+    let list_ref_options = get_list_store()
+        .all()
+        .iter()
+        .map(|list| {
+            format!(
+                r#"<option value="{name}" x-show="!leftFieldType || leftFieldType === '{data_type}'">LIST({name}) · {count} entries</option>"#,
+                name = list.name,
+                data_type = list.element_type.as_str(),
+                count = list.entries.len(),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    // Only offer parameters whose type matches the left side
+    let parameter_options = get_parameter_store()
+        .all()
+        .iter()
+        .map(|parameter| {
+            format!(
+                r#"<option value="{name}" x-show="!leftFieldType || leftFieldType === '{data_type}'">${name} = {value}</option>"#,
+                name = parameter.name,
+                data_type = parameter.data_type.as_str(),
+                value = escape_html(&parameter.value),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
 
     // Build the form HTML with the correct path
     let form_html = format!(
         r##"<div class="card condition-form">
         <h4>Add Condition to Group</h4>
-        <form hx-post="/rule/node/{}/add-condition"
+        <form hx-post="/rule/node/{path}/add-condition"
               hx-target="#rule-container"
               hx-swap="innerHTML">
-            <div class="form-row" x-data="{{ leftFieldType: null }}">
+            <div class="form-row" x-data="{{ leftFieldType: null, operator: '' }}">
                 <div class="form-group">
                     <label>Left Side</label>
                     <div class="operand-selector" x-data="{{ type: 'field' }}">
                         <div class="operand-input-group">
-                            <button type="button" 
-                                    @click="type = (type === 'field' ? 'value' : 'field')"
+                            <button type="button"
+                                    @click="type = {{ field: 'value', value: 'aggregate', aggregate: 'expression', expression: 'field' }}[type];
+                                            if (type === 'aggregate' || type === 'expression') {{
+                                                leftFieldType = 'number';
+                                                htmx.ajax('GET', '/rule/conditions/operators-and-right?left_type=' + type, {{ target: '#operator-group', swap: 'innerHTML' }});
+                                            }}"
                                     class="operand-toggle"
-                                    :title="type === 'field' ? 'Switch to value' : 'Switch to field'">
+                                    :title="{{ field: 'Switch to value', value: 'Switch to aggregate', aggregate: 'Switch to expression', expression: 'Switch to field' }}[type]">
                                 <span x-show="type === 'field'">📊</span>
                                 <span x-show="type === 'value'">✏️</span>
+                                <span x-show="type === 'aggregate'" x-cloak>Σ</span>
+                                <span x-show="type === 'expression'" x-cloak>ƒ</span>
                             </button>
                             <div class="operand-input">
+                                {aggregate_builder}
+                                {left_expression_input}
                                 <select x-show="type === 'field'" name="left_field" :required="type === 'field'" :disabled="type !== 'field'" x-cloak
                                         hx-get="/rule/conditions/operators-and-right" hx-target="#operator-group" hx-swap="innerHTML"
                                         hx-include="[name='left_type'], [name='left_field']"
-                                        @change="const numericFields = ['transaction_amount', 'user_age', 'transaction_count24h', 'account_age'];
-                                                 leftFieldType = numericFields.includes($event.target.value) ? 'number' : 'string';">
+                                        @change="leftFieldType = $event.target.selectedOptions[0].dataset.type || null;">
                                     <option value="">Select a field...</option>
-                                    {}
+                                    {left_options}
                                 </select>
                                 <input x-show="type === 'value'" type="text" name="left_value" placeholder="Enter value..."
                                        :required="type === 'value'" :disabled="type !== 'value'" x-cloak
@@ -102,6 +464,7 @@ pub async fn new_condition_form(Path(path): Path<String>) -> impl IntoResponse {
                                        hx-trigger="input changed delay:200ms" hx-include="[name='left_type'], [name='left_value']"
                                        @input="const val = $event.target.value; if (val === '') {{ leftFieldType = null; }} 
                                                else if (!isNaN(val) && val.trim() !== '') {{ leftFieldType = 'number'; }} 
+                                               else if (/^(\d{{1,3}}\.){{3}}\d{{1,3}}$|^[0-9a-fA-F]*:[0-9a-fA-F:.]*$/.test(val.trim())) {{ leftFieldType = 'ip'; }} 
                                                else {{ leftFieldType = 'string'; }}">
                             </div>
                         </div>
@@ -116,73 +479,380 @@ pub async fn new_condition_form(Path(path): Path<String>) -> impl IntoResponse {
                     </select>
                 </div>
                 
-                <div class="form-group" id="right-side-group">
+                <div class="form-group" id="right-side-group" x-show="operator !== 'is_empty'">
                     <label>Right Side</label>
-                    <p class="hint" x-show="leftFieldType === 'number'" x-cloak style="font-size: 0.85em; color: #666; margin-bottom: 0.5rem;">
+                    <p class="hint" x-show="leftFieldType === 'number' && !['between', 'in', 'not_in'].includes(operator)" x-cloak style="font-size: 0.85em; color: #666; margin-bottom: 0.5rem;">
                         💡 Tip: Use a number value or another numeric field
                     </p>
-                    <p class="hint" x-show="leftFieldType === 'string'" x-cloak style="font-size: 0.85em; color: #666; margin-bottom: 0.5rem;">
+                    <p class="hint" x-show="leftFieldType === 'string' && !['regex', 'in', 'not_in'].includes(operator)" x-cloak style="font-size: 0.85em; color: #666; margin-bottom: 0.5rem;">
                         💡 Tip: Use a text value or another text field
                     </p>
-                    <div class="operand-selector" x-data="{{ type: 'field' }}">
+                    <p class="hint" x-show="leftFieldType === 'ip' && !['in_cidr', 'not_in_cidr'].includes(operator)" x-cloak style="font-size: 0.85em; color: #666; margin-bottom: 0.5rem;">
+                        💡 Tip: Use an IPv4 or IPv6 address, or In CIDR to match ranges
+                    </p>
+                    <p class="hint" x-show="leftFieldType === 'datetime' && ['before', 'after'].includes(operator)" x-cloak style="font-size: 0.85em; color: #666; margin-bottom: 0.5rem;">
+                        💡 Tip: Local times are read in the rule's timezone
+                    </p>
+                    <p class="hint" x-show="['in', 'not_in', 'in_cidr', 'not_in_cidr', 'hour_of_day_in', 'day_of_week_in'].includes(operator)" x-cloak style="font-size: 0.85em; color: #666; margin-bottom: 0.5rem;">
+                        💡 Tip: Press Enter or comma after each value
+                    </p>
+                    <fieldset class="range-inputs" x-show="operator === 'between'" :disabled="operator !== 'between'" x-cloak>
+                        <input type="number" name="right_min" placeholder="From..." step="any" required>
+                        <span>and</span>
+                        <input type="number" name="right_max" placeholder="To..." step="any" required>
+                    </fieldset>
+                    <fieldset class="duration-inputs" x-show="operator === 'within_last'" :disabled="operator !== 'within_last'" x-cloak>
+                        <input type="number" name="right_duration_amount" value="1" min="1" step="1" required>
+                        <select name="right_duration_unit">
+                            {duration_unit_options}
+                        </select>
+                    </fieldset>
+                    <fieldset class="list-inputs" x-show="['in', 'not_in', 'in_cidr', 'not_in_cidr', 'hour_of_day_in', 'day_of_week_in'].includes(operator)"
+                              :disabled="!['in', 'not_in', 'in_cidr', 'not_in_cidr', 'hour_of_day_in', 'day_of_week_in'].includes(operator)" x-cloak>
+                        <div x-data="{{ listSource: 'inline' }}">
+                            <select x-model="listSource" class="list-source">
+                                <option value="inline">Values</option>
+                                <option value="ref">Reference list</option>
+                            </select>
+                            <div x-show="listSource === 'inline'">
+                                {list_input}
+                            </div>
+                            <select x-show="listSource === 'ref'" name="right_list_ref" x-cloak
+                                    :required="listSource === 'ref'" :disabled="listSource !== 'ref'">
+                                <option value="">Select a list...</option>
+                                {list_ref_options}
+                            </select>
+                        </div>
+                    </fieldset>
+                    <fieldset class="operand-selector" x-data="{{ type: 'field' }}"
+                              x-show="!['between', 'in', 'not_in', 'in_cidr', 'not_in_cidr', 'within_last', 'hour_of_day_in', 'day_of_week_in'].includes(operator)"
+                              :disabled="['between', 'in', 'not_in', 'in_cidr', 'not_in_cidr', 'within_last', 'hour_of_day_in', 'day_of_week_in', 'is_empty'].includes(operator)">
                         <div class="operand-input-group">
                             <button type="button" 
-                                    @click="type = (type === 'field' ? 'value' : 'field')"
+                                    @click="type = {{ field: 'value', value: 'parameter', parameter: leftFieldType === 'number' ? 'expression' : 'field', expression: 'field' }}[type]"
                                     class="operand-toggle"
-                                    :title="type === 'field' ? 'Switch to value' : 'Switch to field'">
+                                    :title="{{ field: 'Switch to value', value: 'Switch to parameter', parameter: leftFieldType === 'number' ? 'Switch to expression' : 'Switch to field', expression: 'Switch to field' }}[type]">
                                 <span x-show="type === 'field'">📊</span>
                                 <span x-show="type === 'value'">✏️</span>
+                                <span x-show="type === 'parameter'" x-cloak>$</span>
+                                <span x-show="type === 'expression'" x-cloak>ƒ</span>
                             </button>
                             <div class="operand-input">
-                                <select x-show="type === 'field'" name="right_field" :required="type === 'field'" :disabled="type !== 'field'" x-cloak
-                                        x-show="!leftFieldType || (leftFieldType === 'number' && ['transaction_amount', 'user_age', 'transaction_count24h', 'account_age'].includes('{{{{ field.as_str() }}}}')) || (leftFieldType === 'string' && !['transaction_amount', 'user_age', 'transaction_count24h', 'account_age'].includes('{{{{ field.as_str() }}}}'))">
+                                {right_expression_input}
+                                <select x-show="type === 'parameter'" name="right_parameter" :required="type === 'parameter'" :disabled="type !== 'parameter'" x-cloak>
+                                    <option value="">Select a parameter...</option>
+                                    {parameter_options}
+                                </select>
+                                <select x-show="type === 'field'" name="right_field" :required="type === 'field'" :disabled="type !== 'field'" x-cloak>
                                     <option value="">Select a field...</option>
-                                    {}
+                                    {right_options}
                                 </select>
                                 <input x-show="type === 'value' && leftFieldType === 'number'" type="number" name="right_value" placeholder="Enter a number..."
                                        step="any" :required="type === 'value' && leftFieldType === 'number'" :disabled="type !== 'value' || leftFieldType !== 'number'" x-cloak>
-                                <input x-show="type === 'value' && leftFieldType !== 'number'" type="text" name="right_value" placeholder="Enter value..."
-                                       :required="type === 'value' && leftFieldType !== 'number'" :disabled="type !== 'value' || leftFieldType === 'number'" x-cloak>
+                                <input x-show="type === 'value' && leftFieldType === 'datetime'" type="datetime-local" name="right_value"
+                                       :required="type === 'value' && leftFieldType === 'datetime'" :disabled="type !== 'value' || leftFieldType !== 'datetime'" x-cloak>
+                                <input x-show="type === 'value' && !['number', 'datetime'].includes(leftFieldType)" type="text" name="right_value"
+                                       :placeholder="operator === 'regex' ? 'e.g. ^[a-z0-9._%+-]+@example\\.com$' : (leftFieldType === 'ip' ? 'e.g. 203.0.113.10 or 2001:db8::1' : 'Enter value...')"
+                                       :required="type === 'value' && !['number', 'datetime'].includes(leftFieldType)" :disabled="type !== 'value' || ['number', 'datetime'].includes(leftFieldType)" x-cloak>
                             </div>
                         </div>
                         <input type="hidden" name="right_type" :value="type">
-                    </div>
+                    </fieldset>
                 </div>
             </div>
             
+            <div id="condition-form-error"></div>
             <div class="form-actions">
                 <button type="submit" class="btn btn-primary">Add Condition</button>
                 <button type="button" class="btn btn-secondary" onclick="this.closest('.card').innerHTML = ''">Cancel</button>
             </div>
         </form>
     </div>"##,
-        path,
-        fields
-            .iter()
-            .map(|f| format!(
+        path = path,
+        left_options = field_options("left"),
+        right_options = field_options("right"),
+        // Hours are numbers and weekdays are names, whatever the left side holds
+        list_input = render_chip_input(
+            "right_list",
+            r#":value="{ hour_of_day_in: 'number', day_of_week_in: 'string' }[operator] || leftFieldType || 'string'""#,
+            r#":placeholder="{ in_cidr: 'e.g. 10.0.0.0/8', not_in_cidr: 'e.g. 10.0.0.0/8', hour_of_day_in: 'e.g. 0, 1, 2 (0-23)', day_of_week_in: 'e.g. sat, sun' }[operator] || 'Add a value...'""#,
+        ),
+        duration_unit_options = render_window_unit_options(WindowUnit::Days),
+        list_ref_options = list_ref_options,
+        parameter_options = parameter_options,
+        aggregate_builder = render_aggregate_builder("left"),
+        left_expression_input = render_expression_input("left"),
+        right_expression_input = render_expression_input("right"),
+    );
+
+    Html(form_html).into_response()
+}
+
+/// Render the builder for an aggregate operand on one side of a condition.
+/// Its inputs are named `{side}_agg_*` and only enabled while that side's type is `aggregate`.
+fn render_aggregate_builder(side: &str) -> String {
+    let function_options = AggregateFunction::all()
+        .iter()
+        .map(|function| {
+            format!(
                 r#"<option value="{}">{}</option>"#,
-                f.as_str(),
-                f.display_name()
-            ))
-            .collect::<Vec<_>>()
-            .join("\n"),
-        fields
-            .iter()
-            .map(|f| format!(
+                function.as_str(),
+                function.display_name()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    // Sum, average, minimum and maximum only make sense for numeric fields
+    let target_options = Field::all()
+        .iter()
+        .map(|field| {
+            let filter = if field.data_type() == DataType::Number {
+                String::new()
+            } else {
+                r#" x-show="!['sum', 'avg', 'min', 'max'].includes(aggFunction)""#.to_string()
+            };
+            format!(
+                r#"<option value="{}"{}>{}</option>"#,
+                field.as_str(),
+                filter,
+                field.display_name()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let group_options = Field::all()
+        .iter()
+        .filter(|field| field.data_type() != DataType::Number)
+        .map(|field| {
+            format!(
                 r#"<option value="{}">{}</option>"#,
-                f.as_str(),
-                f.display_name()
-            ))
-            .collect::<Vec<_>>()
-            .join("\n"),
+                field.as_str(),
+                field.display_name()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let unit_options = render_window_unit_options(WindowUnit::Hours);
+
+    format!(
+        r#"<fieldset class="aggregate-builder" x-show="type === 'aggregate'" :disabled="type !== 'aggregate'"
+          x-data="{{ aggFunction: 'count' }}" x-cloak>
+    <select name="{side}_agg_function" x-model="aggFunction" title="Aggregate function">
+        {function_options}
+    </select>
+    <select name="{side}_agg_field" x-show="aggFunction !== 'count'" :disabled="aggFunction === 'count'"
+            :required="aggFunction !== 'count'" title="Field to aggregate">
+        <option value="">of field...</option>
+        {target_options}
+    </select>
+    <label>per
+        <select name="{side}_agg_group_by" required title="Group transactions by">
+            {group_options}
+        </select>
+    </label>
+    <label>over last
+        <input type="number" name="{side}_agg_window_amount" value="1" min="1" step="1" required>
+        <select name="{side}_agg_window_unit">
+            {unit_options}
+        </select>
+    </label>
+</fieldset>"#,
+        side = side,
+        function_options = function_options,
+        target_options = target_options,
+        group_options = group_options,
+        unit_options = unit_options,
+    )
+}
+
+/// Render the text input for an expression operand, with a live preview of how it parses
+fn render_expression_input(side: &str) -> String {
+    format!(
+        r#"<div class="expression-input" x-show="type === 'expression'" x-cloak>
+    <input type="text" name="{side}_expression" placeholder="e.g. 3 * average_amount_30d"
+           :required="type === 'expression'" :disabled="type !== 'expression'"
+           hx-get="/rule/conditions/expression-preview" hx-trigger="input changed delay:300ms"
+           hx-target="next .expression-preview" hx-swap="innerHTML">
+    <div class="expression-preview"></div>
+</div>"#,
+        side = side,
+    )
+}
+
+fn render_window_unit_options(selected: WindowUnit) -> String {
+    WindowUnit::all()
+        .iter()
+        .map(|unit| {
+            let selected = if *unit == selected { " selected" } else { "" };
+            format!(
+                r#"<option value="{0}"{1}>{0}</option>"#,
+                unit.as_str(),
+                selected
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Render a chip-style multi-value input. Values are collected client-side, de-duplicated,
+/// and submitted as a JSON array in `name`, with the element type in `{name}_type`.
+fn render_chip_input(name: &str, type_attr: &str, placeholder_attr: &str) -> String {
+    format!(
+        r#"<div class="chip-input" x-data="{{ items: [], draft: '',
+        add() {{
+            for (const part of this.draft.split(',')) {{
+                const item = part.trim();
+                if (item !== '' && !this.items.includes(item)) {{ this.items.push(item); }}
+            }}
+            this.draft = '';
+        }} }}">
+    <template x-for="(item, i) in items" :key="item">
+        <span class="chip"><span x-text="item"></span><button type="button" class="chip-remove" @click="items.splice(i, 1)" title="Remove">×</button></span>
+    </template>
+    <input type="text" class="chip-draft" x-model="draft" {placeholder_attr}
+           @keydown.enter.prevent="add()" @keydown.comma.prevent="add()" @blur="add()"
+           @keydown.backspace="if (draft === '') items.pop()">
+    <input type="hidden" name="{name}" :value="JSON.stringify(items)">
+    <input type="hidden" name="{name}_type" {type_attr}>
+</div>"#,
+        name = name,
+        type_attr = type_attr,
+        placeholder_attr = placeholder_attr,
+    )
+}
+
+/// Build the rule view for a rule, computing hit statistics over the sample dataset
+/// and running the rule's test cases
+fn build_rule_view(rule: Rule) -> RuleViewTemplate {
+    let dataset = get_dataset_store().get_transactions();
+    let ctx = eval_context(&rule);
+    let stats = DatasetStats::compute(&rule.root, &dataset, &ctx);
+    let tree_ctx = TreeRenderContext {
+        stats: &stats,
+        scoring: rule.is_scoring(),
+        parameters: &ctx.parameters,
+        snippets: &ctx.snippets,
+    };
+    let tree_html = render_tree_node(&rule.root, "0".to_string(), 0, &tree_ctx);
+    // The preview shows the rule as exported, with snippets expanded inline
+    let rule_json = serde_json::to_string_pretty(&rule.expand_snippets(&ctx.snippets))
+        .unwrap_or_else(|_| "{}".to_string());
+    let draft_json = serde_json::to_string(&rule).ok();
+    let test_results = run_test_cases(&rule, &test_case_context(&rule));
+    let tests_failed = test_results.iter().filter(|r| !r.passed()).count();
+    let published_current = get_store()
+        .get_published()
+        .and_then(|published| serde_json::to_string(&published).ok())
+        .is_some_and(|published_json| Some(published_json) == draft_json);
+    RuleViewTemplate {
+        rule_id: rule.id,
+        rules: get_store().all_rules(),
+        dataset_size: stats.total,
+        dataset_hits: stats.get(rule.root.id()).matched,
+        rule,
+        rule_json,
+        tree_html,
+        dataset_error: None,
+        test_results,
+        tests_failed,
+        published_current,
+        publish_errors: Vec::new(),
+    }
+}
+
+/// Render the rule view fragment that replaces `#rule-container`
+fn render_rule_view(rule: Rule) -> Response {
+    HtmlTemplate(build_rule_view(rule)).into_response()
+}
+
+/// Render the hit-statistics badges for a node
+fn render_stats_badges(node: &ConditionNode, stats: &DatasetStats, is_root: bool) -> String {
+    if stats.total == 0 {
+        return String::new();
+    }
+
+    let node_stats = stats.get(node.id());
+    let hits_badge = format!(
+        r#"<span class="stat-badge" title="Sample transactions satisfying this node">{}/{} · {:.0}%</span>"#,
+        node_stats.matched,
+        stats.total,
+        stats.percent(node_stats.matched),
     );
+    if is_root {
+        return hits_badge;
+    }
 
-    Html(form_html).into_response()
+    format!(
+        r#"{}<span class="stat-badge stat-decisive" title="Transactions where this node alone decided its parent group">decisive {} · {:.0}%</span>"#,
+        hits_badge,
+        node_stats.decisive,
+        stats.percent(node_stats.decisive),
+    )
+}
+
+/// Everything besides the node itself needed to render the rule tree
+struct TreeRenderContext<'a> {
+    stats: &'a DatasetStats,
+    scoring: bool,
+    /// Current parameter values, shown next to parameter names
+    parameters: &'a BTreeMap<String, Parameter>,
+    /// Snippets, summarised next to the references to them
+    snippets: &'a BTreeMap<String, Snippet>,
+}
+
+/// Render an operand in the rule tree. Parameters show their name and current value.
+fn render_operand(operand: &Operand, ctx: &TreeRenderContext) -> String {
+    match operand {
+        Operand::Parameter { name } => match ctx.parameters.get(name) {
+            Some(parameter) => format!(
+                r#"<span class="param-ref" title="{description}">${name}</span> <span class="param-value">= {value}</span>"#,
+                description = escape_html(&parameter.description),
+                name = escape_html(name),
+                value = escape_html(&parameter.value),
+            ),
+            None => format!(
+                r#"<span class="param-ref param-missing" title="Parameter does not exist">${}</span>"#,
+                escape_html(name)
+            ),
+        },
+        _ => escape_html(&operand.display()),
+    }
+}
+
+/// Render the weight input for a node of a scoring rule
+fn render_weight_control(node: &ConditionNode, path: &str, ctx: &TreeRenderContext) -> String {
+    if !ctx.scoring {
+        return String::new();
+    }
+
+    format!(
+        r##"<label class="weight-control" title="Score added when this node matches">
+                weight
+                <input type="number"
+                       step="any"
+                       name="weight"
+                       value="{weight}"
+                       placeholder="none"
+                       hx-post="/rule/node/{path}/weight"
+                       hx-trigger="change"
+                       hx-target="#rule-container"
+                       hx-swap="innerHTML">
+            </label>"##,
+        weight = node.weight().map(|w| w.to_string()).unwrap_or_default(),
+        path = path,
+    )
 }
 
 /// Render a tree node recursively
-fn render_tree_node(node: &ConditionNode, path: String, depth: usize) -> String {
+fn render_tree_node(
+    node: &ConditionNode,
+    path: String,
+    depth: usize,
+    ctx: &TreeRenderContext,
+) -> String {
     let indent = depth * 20;
+    let stats_badges = render_stats_badges(node, ctx.stats, path == "0");
+    let weight_control = render_weight_control(node, &path, ctx);
 
     match node {
         ConditionNode::Leaf {
@@ -191,9 +861,14 @@ fn render_tree_node(node: &ConditionNode, path: String, depth: usize) -> String
             right,
             ..
         } => {
-            let left_display = left.display();
+            let left_display = render_operand(left, ctx);
             let operator_display = operator.display_name();
-            let right_display = right.display();
+            // Unary operators have no right side to show
+            let right_display = if operator.is_unary() {
+                String::new()
+            } else {
+                render_operand(right, ctx)
+            };
 
             format!(
                 r##"<div id="node-{path}" class="condition-leaf" style="margin-left: {indent}px">
@@ -201,7 +876,9 @@ fn render_tree_node(node: &ConditionNode, path: String, depth: usize) -> String
                         <span class="condition-field">{left_display}</span>
                         <span class="condition-operator">{operator_display}</span>
                         <span class="condition-value">{right_display}</span>
+                        {stats_badges}
                     </div>
+                    {weight_control}
                     <button class="btn-delete"
                             hx-delete="/rule/node/{path}"
                             hx-target="#rule-container"
@@ -213,6 +890,8 @@ fn render_tree_node(node: &ConditionNode, path: String, depth: usize) -> String
                 left_display = left_display,
                 operator_display = operator_display,
                 right_display = right_display,
+                stats_badges = stats_badges,
+                weight_control = weight_control,
             )
         }
         ConditionNode::Group {
@@ -221,7 +900,9 @@ fn render_tree_node(node: &ConditionNode, path: String, depth: usize) -> String
             let children_html: String = children
                 .iter()
                 .enumerate()
-                .map(|(i, child)| render_tree_node(child, format!("{}-{}", path, i), depth + 1))
+                .map(|(i, child)| {
+                    render_tree_node(child, format!("{}-{}", path, i), depth + 1, ctx)
+                })
                 .collect::<Vec<_>>()
                 .join("\n");
 
@@ -260,6 +941,8 @@ fn render_tree_node(node: &ConditionNode, path: String, depth: usize) -> String
                             <option value="and" {and_sel}>AND</option>
                             <option value="or" {or_sel}>OR</option>
                         </select>
+                        {stats_badges}
+                        {weight_control}
                         {delete_btn}
                     </div>
                     <div class="group-children">
@@ -278,16 +961,67 @@ fn render_tree_node(node: &ConditionNode, path: String, depth: usize) -> String
                                 hx-swap="innerHTML">
                             + Add Group
                         </button>
+                        <button class="btn btn-small btn-secondary"
+                                hx-get="/rule/node/{path}/add-snippet-form"
+                                hx-target="#condition-form-container"
+                                hx-swap="innerHTML">
+                            + Add Snippet
+                        </button>
+                        <button class="btn btn-small btn-secondary"
+                                hx-get="/rule/node/{path}/save-snippet-form"
+                                hx-target="#condition-form-container"
+                                hx-swap="innerHTML">
+                            Save as Snippet
+                        </button>
                     </div>
                 </div>"##,
                 path = path,
                 indent = indent,
                 and_sel = and_sel,
                 or_sel = or_sel,
+                stats_badges = stats_badges,
+                weight_control = weight_control,
                 delete_btn = delete_btn,
                 children_html = children_html,
             )
         }
+        // Snippets are shown collapsed; their conditions are edited on the snippet page
+        ConditionNode::SnippetRef { name, .. } => {
+            let summary = match ctx.snippets.get(name) {
+                Some(snippet) => format!(
+                    r#"<span class="snippet-summary" title="{description}">{conditions}</span>"#,
+                    description = escape_html(&snippet.description),
+                    conditions = escape_html(&snippet.root.display()),
+                ),
+                None => {
+                    r#"<span class="snippet-summary param-missing">Snippet does not exist</span>"#
+                        .to_string()
+                }
+            };
+
+            format!(
+                r##"<div id="node-{path}" class="condition-leaf snippet-ref" style="margin-left: {indent}px">
+                    <div class="condition-content">
+                        <span class="snippet-label">SNIPPET</span>
+                        <a href="/snippets/{name}" class="snippet-link">{name}</a>
+                        {summary}
+                        {stats_badges}
+                    </div>
+                    {weight_control}
+                    <button class="btn-delete"
+                            hx-delete="/rule/node/{path}"
+                            hx-target="#rule-container"
+                            hx-swap="innerHTML"
+                            hx-confirm="Remove this snippet reference?">✕</button>
+                </div>"##,
+                path = path,
+                indent = indent,
+                name = escape_html(name),
+                summary = summary,
+                stats_badges = stats_badges,
+                weight_control = weight_control,
+            )
+        }
     }
 }
 
@@ -296,10 +1030,109 @@ pub struct AddConditionForm {
     left_type: String,
     left_field: Option<String>,
     left_value: Option<String>,
+    left_expression: Option<String>,
+    #[serde(flatten)]
+    left_aggregate: AggregateForm,
     operator: String,
+    // Absent when the operator does not use the field/value selector
+    #[serde(default)]
     right_type: String,
     right_field: Option<String>,
     right_value: Option<String>,
+    right_expression: Option<String>,
+    /// Name of a parameter, for a `parameter` right side
+    right_parameter: Option<String>,
+    right_min: Option<String>,
+    right_max: Option<String>,
+    /// JSON array of list elements for In / Not In
+    right_list: Option<String>,
+    right_list_type: Option<String>,
+    /// Name of a reference list, used instead of inline elements
+    right_list_ref: Option<String>,
+    /// Duration for Within Last
+    right_duration_amount: Option<String>,
+    right_duration_unit: Option<String>,
+}
+
+/// Inputs of the aggregate builder for the left side
+#[derive(Deserialize)]
+pub struct AggregateForm {
+    left_agg_function: Option<String>,
+    left_agg_field: Option<String>,
+    left_agg_group_by: Option<String>,
+    left_agg_window_amount: Option<String>,
+    left_agg_window_unit: Option<String>,
+}
+
+impl AggregateForm {
+    fn parse(&self) -> Result<Operand, String> {
+        let function: AggregateFunction = self
+            .left_agg_function
+            .as_deref()
+            .and_then(|f| serde_json::from_str(&format!("\"{}\"", f)).ok())
+            .ok_or("Choose an aggregate function")?;
+        let group_by = self
+            .left_agg_group_by
+            .as_deref()
+            .and_then(parse_field)
+            .ok_or("Choose a field to group by")?;
+        let amount = self
+            .left_agg_window_amount
+            .as_deref()
+            .and_then(|a| a.trim().parse().ok())
+            .ok_or("Window length must be a whole number")?;
+        let unit = self
+            .left_agg_window_unit
+            .as_deref()
+            .and_then(|u| serde_json::from_str(&format!("\"{}\"", u)).ok())
+            .unwrap_or(WindowUnit::Hours);
+        let field = if function.needs_field() {
+            self.left_agg_field.as_deref().and_then(parse_field)
+        } else {
+            None
+        };
+        Ok(Operand::Aggregate {
+            function,
+            field,
+            group_by,
+            window: TimeWindow { amount, unit },
+        })
+    }
+}
+
+/// Show an error inside the open condition form instead of replacing the rule view
+fn condition_form_error(message: &str) -> Response {
+    form_error("#condition-form-error", message)
+}
+
+/// Show an error in the given slot of a form that otherwise replaces the rule view
+fn form_error(slot: &'static str, message: &str) -> Response {
+    (
+        [("HX-Retarget", slot), ("HX-Reswap", "innerHTML")],
+        Html(format!(
+            r#"<div class="alert alert-error">{}</div>"#,
+            escape_html(message)
+        )),
+    )
+        .into_response()
+}
+
+fn parse_duration_operand(amount: Option<&str>, unit: Option<&str>) -> Result<Operand, String> {
+    let amount = amount
+        .and_then(|a| a.trim().parse().ok())
+        .ok_or("Duration must be a whole number")?;
+    let unit = unit
+        .and_then(|u| serde_json::from_str(&format!("\"{}\"", u)).ok())
+        .unwrap_or(WindowUnit::Days);
+    Ok(Operand::Duration {
+        window: TimeWindow { amount, unit },
+    })
+}
+
+fn parse_expression_operand(source: Option<&str>) -> Result<Operand, String> {
+    Expr::parse(source.unwrap_or_default())
+        .map(|expr| Operand::Expression { expr })
+        .map_err(|err| format!("Invalid expression: {}", err))
 }
 
 pub async fn add_condition(
@@ -312,7 +1145,17 @@ pub async fn add_condition(
         let operator: Operator = serde_json::from_str(&format!("\"{}\"", form.operator)).unwrap();
 
         // Parse left operand
-        let left = if form.left_type == "field" {
+        let left = if form.left_type == "aggregate" {
+            match form.left_aggregate.parse() {
+                Ok(aggregate) => aggregate,
+                Err(err) => return condition_form_error(&err),
+            }
+        } else if form.left_type == "expression" {
+            match parse_expression_operand(form.left_expression.as_deref()) {
+                Ok(expression) => expression,
+                Err(err) => return condition_form_error(&err),
+            }
+        } else if form.left_type == "field" {
             let field: Field =
                 serde_json::from_str(&format!("\"{}\"", form.left_field.unwrap_or_default()))
                     .unwrap();
@@ -324,7 +1167,46 @@ pub async fn add_condition(
         };
 
         // Parse right operand
-        let right = if form.right_type == "field" {
+        let right = if operator.is_unary() {
+            Operand::Value {
+                value: String::new(),
+            }
+        } else if operator == Operator::Between {
+            Operand::Range {
+                min: form.right_min.unwrap_or_default(),
+                max: form.right_max.unwrap_or_default(),
+            }
+        } else if operator == Operator::WithinLast {
+            match parse_duration_operand(
+                form.right_duration_amount.as_deref(),
+                form.right_duration_unit.as_deref(),
+            ) {
+                Ok(duration) => duration,
+                Err(err) => return condition_form_error(&err),
+            }
+        } else if let Some(name) = form.right_list_ref.filter(|name| !name.is_empty()) {
+            Operand::ListRef { name }
+        } else if operator.takes_list() {
+            let element_type = form
+                .right_list_type
+                .and_then(|t| serde_json::from_str(&format!("\"{}\"", t)).ok())
+                .unwrap_or(DataType::String);
+            let values = form
+                .right_list
+                .and_then(|list| serde_json::from_str(&list).ok())
+                .unwrap_or_default();
+            Operand::list(element_type, values)
+        } else if form.right_type == "parameter" {
+            match form.right_parameter.filter(|name| !name.is_empty()) {
+                Some(name) => Operand::Parameter { name },
+                None => return condition_form_error("Choose a parameter"),
+            }
+        } else if form.right_type == "expression" {
+            match parse_expression_operand(form.right_expression.as_deref()) {
+                Ok(expression) => expression,
+                Err(err) => return condition_form_error(&err),
+            }
+        } else if form.right_type == "field" {
             let field: Field =
                 serde_json::from_str(&format!("\"{}\"", form.right_field.unwrap_or_default()))
                     .unwrap();
@@ -340,25 +1222,17 @@ pub async fn add_condition(
             left,
             operator,
             right,
+            weight: None,
         };
 
         // Add to tree at path
         let indices = parse_path(&path);
         rule.root.add_child_at_path(&indices, condition);
 
-        let rule_id = rule.id;
         store.update_rule(rule.clone());
 
         // Re-render the entire rule view
-        let tree_html = render_tree_node(&rule.root, "0".to_string(), 0);
-        let rule_json = serde_json::to_string_pretty(&rule).unwrap_or_else(|_| "{}".to_string());
-        let template = RuleViewTemplate {
-            rule,
-            rule_id,
-            rule_json,
-            tree_html,
-        };
-        HtmlTemplate(template).into_response()
+        render_rule_view(rule)
     } else {
         Html("<div>Rule not found</div>".to_string()).into_response()
     }
@@ -368,23 +1242,13 @@ pub async fn delete_node(Path(path): Path<String>) -> Response {
     let store = get_store();
 
     if let Some(mut rule) = store.get_rule() {
-        let rule_id = rule.id;
-
         // Delete node at path
         let indices = parse_path(&path);
         rule.root.delete_at_path(&indices);
         store.update_rule(rule.clone());
 
         // Re-render the entire rule view
-        let tree_html = render_tree_node(&rule.root, "0".to_string(), 0);
-        let rule_json = serde_json::to_string_pretty(&rule).unwrap_or_else(|_| "{}".to_string());
-        let template = RuleViewTemplate {
-            rule,
-            rule_id,
-            rule_json,
-            tree_html,
-        };
-        HtmlTemplate(template).into_response()
+        render_rule_view(rule)
     } else {
         Html("").into_response()
     }
@@ -394,13 +1258,12 @@ pub async fn add_group(Path(path): Path<String>) -> Response {
     let store = get_store();
 
     if let Some(mut rule) = store.get_rule() {
-        let rule_id = rule.id;
-
         // Create new group
         let new_group = ConditionNode::Group {
             id: Uuid::new_v4(),
             operator: LogicalOperator::And,
             children: vec![],
+            weight: None,
         };
 
         // Add to tree at path
@@ -409,20 +1272,411 @@ pub async fn add_group(Path(path): Path<String>) -> Response {
         store.update_rule(rule.clone());
 
         // Re-render the entire rule view
-        let tree_html = render_tree_node(&rule.root, "0".to_string(), 0);
-        let rule_json = serde_json::to_string_pretty(&rule).unwrap_or_else(|_| "{}".to_string());
-        let template = RuleViewTemplate {
-            rule,
-            rule_id,
-            rule_json,
-            tree_html,
+        render_rule_view(rule)
+    } else {
+        Html("").into_response()
+    }
+}
+
+pub async fn add_snippet_form(Path(path): Path<String>) -> Response {
+    let snippets = get_snippet_store().all();
+    if snippets.is_empty() {
+        return Html(
+            r#"<div class="card condition-form"><p class="text-muted">No snippets yet. Save a group as a snippet first.</p></div>"#
+                .to_string(),
+        )
+        .into_response();
+    }
+
+    let snippet_options = snippets
+        .iter()
+        .map(|snippet| {
+            format!(
+                r#"<option value="{name}" title="{conditions}">{name} · {description}</option>"#,
+                name = snippet.name,
+                conditions = escape_html(&snippet.root.display()),
+                description = escape_html(&snippet.description),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Html(format!(
+        r##"<div class="card condition-form">
+        <h4>Add Snippet to Group</h4>
+        <form hx-post="/rule/node/{path}/add-snippet"
+              hx-target="#rule-container"
+              hx-swap="innerHTML">
+            <div class="form-group">
+                <label for="snippet-name">Snippet</label>
+                <select id="snippet-name" name="name" required>
+                    {snippet_options}
+                </select>
+            </div>
+            <div id="condition-form-error"></div>
+            <div class="form-actions">
+                <button type="submit" class="btn btn-primary">Add Snippet</button>
+                <button type="button" class="btn btn-secondary" onclick="this.closest('.card').innerHTML = ''">Cancel</button>
+            </div>
+        </form>
+    </div>"##,
+        path = path,
+        snippet_options = snippet_options,
+    ))
+    .into_response()
+}
+
+pub async fn add_snippet(
+    Path(path): Path<String>,
+    Form(form): Form<std::collections::HashMap<String, String>>,
+) -> Response {
+    let store = get_store();
+
+    if let Some(mut rule) = store.get_rule() {
+        let Some(name) = form
+            .get("name")
+            .filter(|name| get_snippet_store().get(name).is_some())
+        else {
+            return condition_form_error("Choose an existing snippet");
         };
-        HtmlTemplate(template).into_response()
+
+        let reference = ConditionNode::SnippetRef {
+            id: Uuid::new_v4(),
+            name: name.clone(),
+            weight: None,
+        };
+        let indices = parse_path(&path);
+        rule.root.add_child_at_path(&indices, reference);
+        store.update_rule(rule.clone());
+
+        render_rule_view(rule)
+    } else {
+        Html("").into_response()
+    }
+}
+
+pub async fn save_snippet_form(Path(path): Path<String>) -> Response {
+    let existing_names = get_snippet_store()
+        .all()
+        .iter()
+        .map(|snippet| format!(r#"<option value="{}">"#, snippet.name))
+        .collect::<Vec<_>>()
+        .join("\n");
+    // The root group stays in place; any other group can be swapped for the reference
+    let replace_option = if path == "0" {
+        String::new()
+    } else {
+        r#"<label>
+                <input type="checkbox" name="replace_group" value="true" checked>
+                Replace this group with a reference to the snippet
+            </label>"#
+            .to_string()
+    };
+
+    Html(format!(
+        r##"<div class="card condition-form">
+        <h4>Save Group as Snippet</h4>
+        <form hx-post="/rule/node/{path}/save-snippet"
+              hx-target="#rule-container"
+              hx-swap="innerHTML">
+            <div class="form-row">
+                <div class="form-group">
+                    <label for="snippet-name">Name</label>
+                    <input type="text" id="snippet-name" name="name" placeholder="e.g., new_account"
+                           pattern="[a-z0-9_]+" list="snippet-names" required>
+                    <datalist id="snippet-names">
+                        {existing_names}
+                    </datalist>
+                </div>
+                <div class="form-group">
+                    <label for="snippet-description">Description</label>
+                    <input type="text" id="snippet-description" name="description" placeholder="What the conditions detect">
+                </div>
+            </div>
+            {replace_option}
+            <p class="hint" style="font-size: 0.85em; color: #666;">
+                💡 Saving under an existing name updates that snippet everywhere it is used
+            </p>
+            <div id="condition-form-error"></div>
+            <div class="form-actions">
+                <button type="submit" class="btn btn-primary">Save Snippet</button>
+                <button type="button" class="btn btn-secondary" onclick="this.closest('.card').innerHTML = ''">Cancel</button>
+            </div>
+        </form>
+    </div>"##,
+        path = path,
+        existing_names = existing_names,
+        replace_option = replace_option,
+    ))
+    .into_response()
+}
+
+#[derive(Deserialize)]
+pub struct SaveSnippetForm {
+    name: String,
+    #[serde(default)]
+    description: String,
+    replace_group: Option<String>,
+}
+
+/// Save the group at `path` as a named snippet, optionally replacing it with a reference
+pub async fn save_snippet(Path(path): Path<String>, Form(form): Form<SaveSnippetForm>) -> Response {
+    let store = get_store();
+
+    if let Some(mut rule) = store.get_rule() {
+        let name = form.name.trim().to_string();
+        if let Err(err) = Snippet::validate_name(&name) {
+            return condition_form_error(&err);
+        }
+        let indices = parse_path(&path);
+        let Some(group @ ConditionNode::Group { .. }) = rule.root.get_at_path(&indices).cloned()
+        else {
+            return condition_form_error("Only groups can be saved as snippets");
+        };
+
+        let snippet = Snippet {
+            name: name.clone(),
+            description: form.description.trim().to_string(),
+            root: group.with_new_ids(),
+        };
+        if let Err(err) = get_snippet_store().save(snippet) {
+            return condition_form_error(&err);
+        }
+
+        if form.replace_group.is_some() && !indices.is_empty() {
+            if let Some(node) = rule.root.get_at_path_mut(&indices) {
+                *node = ConditionNode::SnippetRef {
+                    id: group.id(),
+                    name,
+                    weight: group.weight(),
+                };
+            }
+            store.update_rule(rule.clone());
+        }
+
+        render_rule_view(rule)
     } else {
         Html("").into_response()
     }
 }
 
+/// Download the rule as JSON, with snippets expanded inline so it stands alone
+pub async fn export_rule() -> Response {
+    let store = get_store();
+
+    match store.get_rule() {
+        Some(rule) => {
+            let expanded = rule.expand_snippets(&get_snippet_store().snapshot());
+            let json = serde_json::to_string_pretty(&expanded).unwrap_or_else(|_| "{}".to_string());
+            (
+                [
+                    ("Content-Type", "application/json".to_string()),
+                    (
+                        "Content-Disposition",
+                        format!("attachment; filename=\"rule-{}.json\"", rule.id),
+                    ),
+                ],
+                json,
+            )
+                .into_response()
+        }
+        None => Html("<div>Rule not found</div>".to_string()).into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SqlExportForm {
+    dialect: String,
+}
+
+/// Transpile the rule to a parameterized WHERE clause in the chosen dialect
+pub async fn export_rule_sql(Form(form): Form<SqlExportForm>) -> Response {
+    let Some(rule) = get_store().get_rule() else {
+        return Html("<div>Rule not found</div>".to_string()).into_response();
+    };
+    let options = SqlOptions {
+        dialect: parse_choice(&form.dialect).unwrap_or_default(),
+        ..SqlOptions::default()
+    };
+    let template = match rule_to_sql(&rule, &options, &eval_context(&rule)) {
+        Ok(predicate) => SqlExportTemplate {
+            dialect: options.dialect,
+            predicate: Some(predicate),
+            errors: Vec::new(),
+        },
+        Err(errors) => SqlExportTemplate {
+            dialect: options.dialect,
+            predicate: None,
+            errors,
+        },
+    };
+    HtmlTemplate(template).into_response()
+}
+
+/// Show the rule's conditions as JSONLogic, with snippets, lists and parameters inlined
+pub async fn export_rule_jsonlogic() -> Response {
+    let Some(rule) = get_store().get_rule() else {
+        return Html("<div>Rule not found</div>".to_string()).into_response();
+    };
+    let template = match to_jsonlogic(&rule.root, &eval_context(&rule)) {
+        Ok(logic) => JsonLogicTemplate {
+            logic: serde_json::to_string_pretty(&logic).ok(),
+            errors: Vec::new(),
+        },
+        Err(errors) => JsonLogicTemplate {
+            logic: None,
+            errors,
+        },
+    };
+    HtmlTemplate(template).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct JsonLogicForm {
+    logic: String,
+}
+
+/// Replace the rule's conditions with an imported JSONLogic expression. A single
+/// condition is wrapped in an AND group, as the rule tree's root is always a group.
+pub async fn import_rule_jsonlogic(Form(form): Form<JsonLogicForm>) -> Response {
+    let store = get_store();
+    let Some(mut rule) = store.get_rule() else {
+        return Html("<div>Rule not found</div>".to_string()).into_response();
+    };
+    let imported = serde_json::from_str(&form.logic)
+        .map_err(|err| vec![format!("Invalid JSON: {}", err)])
+        .and_then(|logic| from_jsonlogic(&logic));
+    let root = match imported {
+        Ok(root @ ConditionNode::Group { .. }) => root,
+        Ok(node) => ConditionNode::Group {
+            id: Uuid::new_v4(),
+            operator: LogicalOperator::And,
+            children: vec![node],
+            weight: None,
+        },
+        Err(errors) => {
+            return (
+                [
+                    ("HX-Retarget", "#jsonlogic-result"),
+                    ("HX-Reswap", "innerHTML"),
+                ],
+                HtmlTemplate(JsonLogicTemplate {
+                    logic: None,
+                    errors,
+                }),
+            )
+                .into_response();
+        }
+    };
+    rule.root = root;
+    store.update_rule(rule.clone());
+    render_rule_view(rule)
+}
+
+#[derive(Deserialize)]
+pub struct SimplifyForm {
+    form: String,
+}
+
+/// The rule's conditions rewritten into `form`, kept under a root group as the
+/// editor expects
+fn simplified_root(rule: &Rule, form: NormalForm) -> Result<ConditionNode, String> {
+    Ok(match normalize(&rule.root, form)? {
+        root @ ConditionNode::Group { .. } => root,
+        node => ConditionNode::Group {
+            id: rule.root.id(),
+            operator: LogicalOperator::And,
+            children: vec![node],
+            weight: None,
+        },
+    })
+}
+
+/// Conditions one per line, indented under their group's operator
+fn outline(node: &ConditionNode, depth: usize, out: &mut String) {
+    let indent = "    ".repeat(depth);
+    let weight = node
+        .weight()
+        .map(|weight| format!("  [weight {}]", weight))
+        .unwrap_or_default();
+    match node {
+        ConditionNode::Group {
+            operator, children, ..
+        } => {
+            out.push_str(&format!("{}{}{}\n", indent, operator, weight));
+            for child in children {
+                outline(child, depth + 1, out);
+            }
+        }
+        _ => out.push_str(&format!("{}{}{}\n", indent, node.display(), weight)),
+    }
+}
+
+fn outline_of(node: &ConditionNode) -> String {
+    let mut out = String::new();
+    outline(node, 0, &mut out);
+    out
+}
+
+/// Show the rule's conditions next to their simplified or normal form, without saving
+pub async fn preview_simplify(Form(form): Form<SimplifyForm>) -> Response {
+    let Some(rule) = get_store().get_rule() else {
+        return Html("<div>Rule not found</div>".to_string()).into_response();
+    };
+    let form = parse_choice(&form.form).unwrap_or_default();
+    let before = outline_of(&rule.root);
+    let template = match simplified_root(&rule, form) {
+        Ok(root) => {
+            let after = outline_of(&root);
+            SimplifyPreviewTemplate {
+                form,
+                changed: after != before,
+                before,
+                after: Some(after),
+                errors: Vec::new(),
+            }
+        }
+        Err(error) => SimplifyPreviewTemplate {
+            form,
+            before,
+            after: None,
+            changed: false,
+            errors: vec![error],
+        },
+    };
+    HtmlTemplate(template).into_response()
+}
+
+/// Replace the rule's conditions with their simplified or normal form
+pub async fn apply_simplify(Form(form): Form<SimplifyForm>) -> Response {
+    let store = get_store();
+    let Some(mut rule) = store.get_rule() else {
+        return Html("<div>Rule not found</div>".to_string()).into_response();
+    };
+    let form = parse_choice(&form.form).unwrap_or_default();
+    match simplified_root(&rule, form) {
+        Ok(root) => {
+            rule.root = root;
+            store.update_rule(rule.clone());
+            render_rule_view(rule)
+        }
+        Err(error) => (
+            [
+                ("HX-Retarget", "#simplify-preview"),
+                ("HX-Reswap", "innerHTML"),
+            ],
+            HtmlTemplate(SimplifyPreviewTemplate {
+                form,
+                before: outline_of(&rule.root),
+                after: None,
+                changed: false,
+                errors: vec![error],
+            }),
+        )
+            .into_response(),
+    }
+}
+
 pub async fn update_operator(
     Path(path): Path<String>,
     Form(form): Form<std::collections::HashMap<String, String>>,
@@ -430,8 +1684,6 @@ pub async fn update_operator(
     let store = get_store();
 
     if let Some(mut rule) = store.get_rule() {
-        let rule_id = rule.id;
-
         // Get the operator
         let operator_str = form.get("operator").map(|s| s.as_str()).unwrap_or("and");
         let operator = if operator_str == "or" {
@@ -453,66 +1705,114 @@ pub async fn update_operator(
         store.update_rule(rule.clone());
 
         // Re-render the entire rule view
-        let tree_html = render_tree_node(&rule.root, "0".to_string(), 0);
-        let rule_json = serde_json::to_string_pretty(&rule).unwrap_or_else(|_| "{}".to_string());
-        let template = RuleViewTemplate {
-            rule,
-            rule_id,
-            rule_json,
-            tree_html,
+        render_rule_view(rule)
+    } else {
+        Html("").into_response()
+    }
+}
+
+pub async fn update_weight(
+    Path(path): Path<String>,
+    Form(form): Form<std::collections::HashMap<String, String>>,
+) -> Response {
+    let store = get_store();
+
+    if let Some(mut rule) = store.get_rule() {
+        // An empty or unparseable weight clears it
+        let weight = form
+            .get("weight")
+            .and_then(|w| w.trim().parse::<f64>().ok());
+
+        let indices = parse_path(&path);
+        if let Some(node) = rule.root.get_at_path_mut(&indices) {
+            node.set_weight(weight);
+        }
+
+        store.update_rule(rule.clone());
+        render_rule_view(rule)
+    } else {
+        Html("").into_response()
+    }
+}
+
+pub async fn update_mode(Form(form): Form<std::collections::HashMap<String, String>>) -> Response {
+    let store = get_store();
+
+    if let Some(mut rule) = store.get_rule() {
+        rule.mode = if form.get("mode").map(|s| s.as_str()) == Some("scoring") {
+            RuleMode::Scoring
+        } else {
+            RuleMode::Boolean
         };
-        HtmlTemplate(template).into_response()
+
+        store.update_rule(rule.clone());
+        render_rule_view(rule)
+    } else {
+        Html("").into_response()
+    }
+}
+
+/// Set the timezone the rule reads local date/times, hours and weekdays in.
+/// Unknown names are kept so validation can report them.
+pub async fn update_timezone(
+    Form(form): Form<std::collections::HashMap<String, String>>,
+) -> Response {
+    let store = get_store();
+
+    if let Some(mut rule) = store.get_rule() {
+        rule.timezone = form
+            .get("timezone")
+            .map(|tz| tz.trim())
+            .filter(|tz| !tz.is_empty())
+            .unwrap_or("UTC")
+            .to_string();
+
+        store.update_rule(rule.clone());
+        render_rule_view(rule)
     } else {
         Html("").into_response()
     }
 }
 
 #[derive(Deserialize)]
-pub struct FieldQuery {
-    field: String,
+pub struct CreateRuleForm {
+    name: String,
+    #[serde(default)]
+    description: String,
 }
 
-pub async fn get_operators_for_field(
-    axum::extract::Query(query): axum::extract::Query<FieldQuery>,
-) -> Response {
-    let field_str = &query.field;
+/// Create a rule and open it in the editor
+pub async fn create_rule(Form(form): Form<CreateRuleForm>) -> Response {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return condition_form_error("Rule name cannot be empty");
+    }
+    let rule = Rule::new(name.to_string(), form.description.trim().to_string());
+    get_store().add_rule(rule.clone());
+    render_rule_view(rule)
+}
 
-    // Parse the field to determine which operators are valid
-    let operators = if let Ok(field) = serde_json::from_str::<Field>(&format!("\"{}\"", field_str))
-    {
-        match field {
-            // Numeric fields: comparison operators
-            Field::TransactionAmount
-            | Field::UserAge
-            | Field::TransactionCount24h
-            | Field::AccountAge => {
-                vec![
-                    Operator::Equals,
-                    Operator::NotEquals,
-                    Operator::GreaterThan,
-                    Operator::LessThan,
-                    Operator::GreaterThanOrEqual,
-                    Operator::LessThanOrEqual,
-                ]
-            }
-            // String fields: equality and contains
-            Field::TransactionCurrency
-            | Field::UserCountry
-            | Field::IpAddress
-            | Field::DeviceFingerprint => {
-                vec![
-                    Operator::Equals,
-                    Operator::NotEquals,
-                    Operator::Contains,
-                    Operator::In,
-                ]
-            }
-        }
+/// Open a rule in the editor
+pub async fn open_rule(Path(id): Path<Uuid>) -> Response {
+    if get_store().select(id) {
+        Redirect::to("/").into_response()
     } else {
-        Operator::all()
-    };
+        Html("<div>Rule not found</div>".to_string()).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FieldQuery {
+    field: String,
+}
+
+/// Parse a field from its snake_case name
+fn parse_field(field: &str) -> Option<Field> {
+    serde_json::from_str::<Field>(&format!("\"{}\"", field)).ok()
+}
 
-    let options_html = operators
+fn render_operator_options(operators: &[Operator]) -> String {
+    operators
         .iter()
         .map(|op| {
             format!(
@@ -522,7 +1822,32 @@ pub async fn get_operators_for_field(
             )
         })
         .collect::<Vec<_>>()
-        .join("\n");
+        .join("\n")
+}
+
+/// Operator dropdown for the condition form; tells Alpine which operator is picked
+/// so the right side can adapt (two inputs for Between, none for Is Empty)
+fn render_condition_operator_select(operators: &[Operator]) -> String {
+    format!(
+        r##"<label for="operator">Operator</label>
+<select id="operator" name="operator" required
+        x-init="operator = ''"
+        @change="operator = $event.target.value">
+    <option value="">Select an operator...</option>
+    {}
+</select>"##,
+        render_operator_options(operators)
+    )
+}
+
+pub async fn get_operators_for_field(
+    axum::extract::Query(query): axum::extract::Query<FieldQuery>,
+) -> Response {
+    // Parse the field to determine which operators are valid
+    let operators = match parse_field(&query.field) {
+        Some(field) => Operator::for_type(field.data_type()),
+        None => Operator::all(),
+    };
 
     let html = format!(
         r##"<label for="operator">Operator</label>
@@ -536,7 +1861,7 @@ pub async fn get_operators_for_field(
     <option value="">Select an operator...</option>
     {}
 </select>"##,
-        options_html
+        render_operator_options(&operators)
     );
 
     Html(html).into_response()
@@ -551,17 +1876,114 @@ pub struct ValueInputQuery {
 pub async fn get_value_input_for_field(
     axum::extract::Query(query): axum::extract::Query<ValueInputQuery>,
 ) -> Response {
-    let field_str = &query.field;
-    let _operator_str = &query.operator;
-
-    // Determine the appropriate input type based on the field
-    let html = if let Ok(field) = serde_json::from_str::<Field>(&format!("\"{}\"", field_str)) {
-        match field {
-            // Numeric fields: number input
-            Field::TransactionAmount
-            | Field::UserAge
-            | Field::TransactionCount24h
-            | Field::AccountAge => r#"<label for="value">Value</label>
+    let field = parse_field(&query.field);
+    let operator = serde_json::from_str::<Operator>(&format!("\"{}\"", query.operator)).ok();
+
+    // Operators with their own value widgets come first, then the input type follows the field
+    let html = match (field, operator) {
+        (None, _) => r#"<label for="value">Value</label>
+<input 
+    type="text" 
+    id="value" 
+    name="value" 
+    placeholder="Select a field first..."
+    required
+    disabled>"#
+            .to_string(),
+        (Some(_), Some(Operator::IsEmpty)) => {
+            r#"<p class="text-muted">No value needed</p>"#.to_string()
+        }
+        (Some(_), Some(Operator::Between)) => r#"<label for="value_min">Range</label>
+<div class="range-inputs">
+    <input 
+        type="number" 
+        id="value_min" 
+        name="value_min" 
+        placeholder="From..."
+        step="any"
+        required>
+    <span>and</span>
+    <input 
+        type="number" 
+        name="value_max" 
+        placeholder="To..."
+        step="any"
+        required>
+</div>"#
+            .to_string(),
+        (Some(field), Some(Operator::In | Operator::NotIn)) => format!(
+            r#"<label>Values</label>
+{}"#,
+            render_chip_input(
+                "value",
+                &format!(r#"value="{}""#, field.data_type().as_str()),
+                r#"placeholder="Add a value...""#,
+            )
+        ),
+        (Some(_), Some(Operator::InCidr | Operator::NotInCidr)) => format!(
+            r#"<label>CIDR Ranges</label>
+{}"#,
+            render_chip_input(
+                "value",
+                r#"value="ip""#,
+                r#"placeholder="e.g., 10.0.0.0/8, 2001:db8::/32""#,
+            )
+        ),
+        (Some(_), Some(Operator::WithinLast)) => format!(
+            r#"<label for="value">Within Last</label>
+<div class="duration-inputs">
+    <input 
+        type="number" 
+        id="value" 
+        name="value" 
+        value="1"
+        min="1"
+        step="1"
+        required>
+    <select name="value_unit">
+        {}
+    </select>
+</div>"#,
+            render_window_unit_options(WindowUnit::Days)
+        ),
+        (Some(_), Some(Operator::HourOfDayIn)) => format!(
+            r#"<label>Hours (0-23, rule timezone)</label>
+{}"#,
+            render_chip_input(
+                "value",
+                r#"value="number""#,
+                r#"placeholder="e.g., 0, 1, 2, 3""#,
+            )
+        ),
+        (Some(_), Some(Operator::DayOfWeekIn)) => format!(
+            r#"<label>Days of the Week</label>
+{}"#,
+            render_chip_input(
+                "value",
+                r#"value="string""#,
+                r#"placeholder="e.g., sat, sun" list="weekday-suggestions""#,
+            ) + r#"
+<datalist id="weekday-suggestions">
+    <option value="mon">
+    <option value="tue">
+    <option value="wed">
+    <option value="thu">
+    <option value="fri">
+    <option value="sat">
+    <option value="sun">
+</datalist>"#
+        ),
+        (Some(_), Some(Operator::Regex)) => r#"<label for="value">Pattern</label>
+<input 
+    type="text" 
+    id="value" 
+    name="value" 
+    placeholder="e.g., ^[a-z0-9._%+-]+@example\.com$"
+    required>"#
+            .to_string(),
+        // Numeric fields: number input
+        (Some(field), _) if field.data_type() == DataType::Number => {
+            r#"<label for="value">Value</label>
 <input 
     type="number" 
     id="value" 
@@ -569,24 +1991,48 @@ pub async fn get_value_input_for_field(
     placeholder="Enter a number..."
     step="any"
     required>"#
-                .to_string(),
-            // String fields: text input with suggestions
-            Field::TransactionCurrency => r#"<label for="value">Value</label>
+                .to_string()
+        }
+        // IP fields: address input accepting IPv4 and IPv6
+        (Some(field), _) if field.data_type() == DataType::Ip => {
+            r#"<label for="value">IP Address</label>
 <input 
     type="text" 
     id="value" 
     name="value" 
-    placeholder="e.g., USD, EUR, GBP..."
-    list="currency-suggestions"
-    required>
-<datalist id="currency-suggestions">
-    <option value="USD">
-    <option value="EUR">
-    <option value="GBP">
-    <option value="JPY">
-</datalist>"#
-                .to_string(),
-            Field::UserCountry => r#"<label for="value">Value</label>
+    placeholder="e.g., 203.0.113.10 or 2001:db8::1"
+    pattern="[0-9A-Fa-f:.]+"
+    title="An IPv4 or IPv6 address"
+    required>"#
+                .to_string()
+        }
+        // Date/time fields: local date/time picker, read in the rule's timezone
+        (Some(field), _) if field.data_type() == DataType::DateTime => {
+            r#"<label for="value">Date/Time</label>
+<input 
+    type="datetime-local" 
+    id="value" 
+    name="value" 
+    required>"#
+                .to_string()
+        }
+        // String fields: text input with suggestions
+        (Some(Field::TransactionCurrency), _) => r#"<label for="value">Value</label>
+<input 
+    type="text" 
+    id="value" 
+    name="value" 
+    placeholder="e.g., USD, EUR, GBP..."
+    list="currency-suggestions"
+    required>
+<datalist id="currency-suggestions">
+    <option value="USD">
+    <option value="EUR">
+    <option value="GBP">
+    <option value="JPY">
+</datalist>"#
+            .to_string(),
+        (Some(Field::UserCountry), _) => r#"<label for="value">Value</label>
 <input 
     type="text" 
     id="value" 
@@ -600,173 +2046,1712 @@ pub async fn get_value_input_for_field(
     <option value="FR">
     <option value="DE">
 </datalist>"#
-                .to_string(),
-            // Default: text input
-            _ => r#"<label for="value">Value</label>
+            .to_string(),
+        // Default: text input
+        (Some(_), _) => r#"<label for="value">Value</label>
 <input 
     type="text" 
     id="value" 
     name="value" 
     placeholder="Enter value..."
     required>"#
-                .to_string(),
+            .to_string(),
+    };
+
+    Html(html).into_response()
+}
+
+pub async fn get_operators_and_right_hint(
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Response {
+    let left_type = params
+        .get("left_type")
+        .map(|s| s.as_str())
+        .unwrap_or("field");
+    let left_field_str = params.get("left_field").map(|s| s.as_str()).unwrap_or("");
+
+    // Determine operators based on left side; aggregates are numbers
+    let operators = match parse_field(left_field_str) {
+        _ if left_type == "aggregate" || left_type == "expression" => {
+            Operator::for_type(DataType::Number)
         }
+        Some(field) if left_type == "field" => Operator::for_type(field.data_type()),
+        _ => Operator::all(),
+    };
+
+    Html(render_condition_operator_select(&operators)).into_response()
+}
+
+/// Show how an expression typed into the condition form parses, or why it does not
+pub async fn preview_expression(
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Response {
+    let source = params
+        .get("left_expression")
+        .or_else(|| params.get("right_expression"))
+        .map(|s| s.as_str())
+        .unwrap_or("");
+    if source.trim().is_empty() {
+        return Html(String::new()).into_response();
+    }
+
+    let html = match Expr::parse(source) {
+        Ok(expr) => {
+            let mut errors = Vec::new();
+            expr.check(&mut errors);
+            if errors.is_empty() {
+                format!(
+                    r#"<span class="text-muted">= {}</span>"#,
+                    escape_html(&expr.display())
+                )
+            } else {
+                format!(
+                    r#"<span class="expression-error">{}</span>"#,
+                    escape_html(&errors.join("; "))
+                )
+            }
+        }
+        Err(err) => format!(
+            r#"<span class="expression-error">{}</span>"#,
+            escape_html(&err)
+        ),
+    };
+    Html(html).into_response()
+}
+
+pub async fn get_operators_for_value(
+    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+) -> Response {
+    let left_value = params.get("left_value").map(|s| s.as_str()).unwrap_or("");
+
+    // Determine if the value is numeric, an IP address or a string
+    let operators = if !left_value.is_empty() && left_value.parse::<f64>().is_ok() {
+        Operator::for_type(DataType::Number)
+    } else if left_value.trim().parse::<std::net::IpAddr>().is_ok() {
+        Operator::for_type(DataType::Ip)
     } else {
-        r#"<label for="value">Value</label>
-<input 
-    type="text" 
-    id="value" 
-    name="value" 
-    placeholder="Select a field first..."
-    required
-    disabled>"#
-            .to_string()
+        Operator::for_type(DataType::String)
+    };
+
+    Html(render_condition_operator_select(&operators)).into_response()
+}
+
+pub async fn validate_rule() -> Response {
+    let store = get_store();
+
+    if let Some(rule) = store.get_rule() {
+        let ctx = eval_context(&rule);
+        let warnings = analyze_rule(&rule, &ctx);
+        let template = match rule.validate(&ctx) {
+            Ok(_) => ValidationResultTemplate {
+                success: true,
+                errors: vec![],
+                warnings,
+            },
+            Err(errors) => ValidationResultTemplate {
+                success: false,
+                errors,
+                warnings,
+            },
+        };
+        HtmlTemplate(template).into_response()
+    } else {
+        Html("<div>Rule not found</div>".to_string()).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TestTransactionForm {
+    transaction: String,
+}
+
+/// Evaluate the rule against a single transaction and explain the outcome node by node
+pub async fn test_transaction(Form(form): Form<TestTransactionForm>) -> Response {
+    let store = get_store();
+
+    if let Some(rule) = store.get_rule() {
+        let template = match serde_json::from_str::<Transaction>(&form.transaction) {
+            Ok(tx) => {
+                let ctx = eval_context(&rule);
+                let root_trace = trace(&rule.root, &tx, &ctx);
+                let outcome = evaluate_rule(&rule, &tx, &ctx);
+                // Evaluated transactions feed later aggregates
+                ctx.velocity.record(&tx, ctx.now);
+                TraceResultTemplate {
+                    outcome: Some(outcome),
+                    trace_html: render_trace_node(&root_trace, 0),
+                    trace_json: serde_json::to_string_pretty(&root_trace)
+                        .unwrap_or_else(|_| "{}".to_string()),
+                    error: None,
+                    velocity_size: ctx.velocity.len(),
+                }
+            }
+            Err(err) => TraceResultTemplate {
+                outcome: None,
+                trace_html: String::new(),
+                trace_json: String::new(),
+                error: Some(format!("Invalid transaction JSON: {}", err)),
+                velocity_size: get_velocity_store().len(),
+            },
+        };
+        HtmlTemplate(template).into_response()
+    } else {
+        Html("<div>Rule not found</div>".to_string()).into_response()
+    }
+}
+
+/// Forget every transaction recorded for aggregates
+pub async fn reset_velocity() -> Response {
+    get_velocity_store().clear();
+    Html(r#"<p class="text-muted">Velocity history cleared.</p>"#.to_string()).into_response()
+}
+
+/// Render an evaluation trace recursively, highlighting each node's outcome
+fn render_trace_node(node: &TraceNode, depth: usize) -> String {
+    let indent = depth * 20;
+    let (outcome_class, outcome_label) = match node.outcome {
+        Some(true) => ("trace-true", "✓ true"),
+        Some(false) => ("trace-false", "✗ false"),
+        None => ("trace-skipped", "skipped"),
+    };
+
+    match &node.detail {
+        TraceDetail::Leaf {
+            left,
+            left_value,
+            operator,
+            right,
+            right_value,
+        } => {
+            format!(
+                r##"<div class="condition-leaf trace-node {outcome_class}" style="margin-left: {indent}px">
+                    <div class="condition-content">
+                        <span class="condition-field">{left_display}</span>
+                        {left_resolved}
+                        <span class="condition-operator">{operator_display}</span>
+                        <span class="condition-value">{right_display}</span>
+                        {right_resolved}
+                    </div>
+                    <span class="trace-outcome">{outcome_label}</span>
+                </div>"##,
+                outcome_class = outcome_class,
+                indent = indent,
+                left_display = escape_html(&left.display()),
+                left_resolved = render_resolved_value(left_value, node.is_skipped()),
+                operator_display = operator.display_name(),
+                right_display = if operator.is_unary() {
+                    String::new()
+                } else {
+                    escape_html(&right.display())
+                },
+                right_resolved = render_resolved_value(right_value, node.is_skipped()),
+                outcome_label = outcome_label,
+            )
+        }
+        TraceDetail::Group { operator, children } => {
+            let children_html: String = children
+                .iter()
+                .map(|child| render_trace_node(child, depth + 1))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            format!(
+                r##"<div class="condition-group trace-node {outcome_class}" style="margin-left: {indent}px">
+                    <div class="group-header">
+                        <span class="group-operator">{operator}</span>
+                        <span class="trace-outcome">{outcome_label}</span>
+                    </div>
+                    <div class="group-children">
+                        {children_html}
+                    </div>
+                </div>"##,
+                outcome_class = outcome_class,
+                indent = indent,
+                operator = operator,
+                outcome_label = outcome_label,
+                children_html = children_html,
+            )
+        }
+        TraceDetail::Snippet { name, root } => {
+            let root_html = match root {
+                Some(root) => render_trace_node(root, depth + 1),
+                None if node.is_skipped() => String::new(),
+                None => r#"<span class="trace-value trace-missing">snippet does not exist</span>"#
+                    .to_string(),
+            };
+
+            format!(
+                r##"<div class="condition-group trace-node {outcome_class}" style="margin-left: {indent}px">
+                    <div class="group-header">
+                        <span class="snippet-label">SNIPPET</span>
+                        <a href="/snippets/{name}" class="snippet-link">{name}</a>
+                        <span class="trace-outcome">{outcome_label}</span>
+                    </div>
+                    <div class="group-children">
+                        {root_html}
+                    </div>
+                </div>"##,
+                outcome_class = outcome_class,
+                indent = indent,
+                name = escape_html(name),
+                outcome_label = outcome_label,
+                root_html = root_html,
+            )
+        }
+    }
+}
+
+/// Render the value an operand resolved to during evaluation
+fn render_resolved_value(value: &Option<Value>, skipped: bool) -> String {
+    match value {
+        Some(value) => format!(
+            r#"<span class="trace-value">= {}</span>"#,
+            escape_html(&value.to_string())
+        ),
+        None if skipped => String::new(),
+        None => r#"<span class="trace-value trace-missing">missing</span>"#.to_string(),
+    }
+}
+
+/// Escape user-provided text before embedding it in hand-built HTML
+fn escape_html(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[derive(Deserialize)]
+pub struct AddActionForm {
+    kind: String,
+    score: Option<String>,
+    label: Option<String>,
+    url: Option<String>,
+}
+
+impl AddActionForm {
+    fn parse(&self) -> Result<Action, String> {
+        match self.kind.as_str() {
+            "block" => Ok(Action::Block),
+            "flag_for_review" => Ok(Action::FlagForReview),
+            "require_step_up" => Ok(Action::RequireStepUp),
+            "add_risk_score" => self
+                .score
+                .as_deref()
+                .unwrap_or_default()
+                .trim()
+                .parse()
+                .map(|score| Action::AddRiskScore { score })
+                .map_err(|_| "Risk score must be a whole number".to_string()),
+            "tag" => Ok(Action::Tag {
+                label: self.label.as_deref().unwrap_or_default().trim().to_string(),
+            }),
+            "send_webhook" => Ok(Action::SendWebhook {
+                url: self.url.as_deref().unwrap_or_default().trim().to_string(),
+            }),
+            other => Err(format!("Unknown action: {}", other)),
+        }
+    }
+}
+
+pub async fn add_action(Form(form): Form<AddActionForm>) -> Response {
+    let store = get_store();
+
+    if let Some(mut rule) = store.get_rule() {
+        match form.parse() {
+            Ok(action) => {
+                rule.actions.push(action);
+                store.update_rule(rule.clone());
+                render_rule_view(rule)
+            }
+            Err(err) => form_error("#action-form-error", &err),
+        }
+    } else {
+        Html("<div>Rule not found</div>".to_string()).into_response()
+    }
+}
+
+pub async fn delete_action(Path(index): Path<usize>) -> Response {
+    let store = get_store();
+
+    if let Some(mut rule) = store.get_rule() {
+        if index < rule.actions.len() {
+            rule.actions.remove(index);
+        }
+        store.update_rule(rule.clone());
+        render_rule_view(rule)
+    } else {
+        Html("").into_response()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AddThresholdForm {
+    min_score: String,
+    #[serde(flatten)]
+    action: AddActionForm,
+}
+
+/// Add an action to the score threshold with the given minimum score, creating it if needed
+pub async fn add_threshold(Form(form): Form<AddThresholdForm>) -> Response {
+    let store = get_store();
+
+    if let Some(mut rule) = store.get_rule() {
+        let min_score = form
+            .min_score
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| "Minimum score must be a number".to_string());
+
+        match min_score.and_then(|min_score| Ok((min_score, form.action.parse()?))) {
+            Ok((min_score, action)) => {
+                match rule
+                    .thresholds
+                    .iter_mut()
+                    .find(|threshold| threshold.min_score == min_score)
+                {
+                    Some(threshold) => threshold.actions.push(action),
+                    None => {
+                        rule.thresholds.push(ScoreThreshold {
+                            min_score,
+                            actions: vec![action],
+                        });
+                        rule.thresholds
+                            .sort_by(|a, b| a.min_score.total_cmp(&b.min_score));
+                    }
+                }
+                store.update_rule(rule.clone());
+                render_rule_view(rule)
+            }
+            Err(err) => form_error("#threshold-form-error", &err),
+        }
+    } else {
+        Html("<div>Rule not found</div>".to_string()).into_response()
+    }
+}
+
+pub async fn delete_threshold(Path(index): Path<usize>) -> Response {
+    let store = get_store();
+
+    if let Some(mut rule) = store.get_rule() {
+        if index < rule.thresholds.len() {
+            rule.thresholds.remove(index);
+        }
+        store.update_rule(rule.clone());
+        render_rule_view(rule)
+    } else {
+        Html("").into_response()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AddTestCaseForm {
+    name: String,
+    transaction: String,
+    expected: String,
+}
+
+pub async fn add_test_case(Form(form): Form<AddTestCaseForm>) -> Response {
+    let store = get_store();
+
+    if let Some(mut rule) = store.get_rule() {
+        match serde_json::from_str::<Transaction>(&form.transaction) {
+            Ok(transaction) => {
+                rule.test_cases.push(TestCase {
+                    id: Uuid::new_v4(),
+                    name: form.name,
+                    transaction,
+                    expect_flagged: form.expected == "flagged",
+                });
+                store.update_rule(rule.clone());
+                render_rule_view(rule)
+            }
+            Err(err) => form_error(
+                "#test-case-form-error",
+                &format!("Invalid transaction JSON: {}", err),
+            ),
+        }
+    } else {
+        Html("<div>Rule not found</div>".to_string()).into_response()
+    }
+}
+
+pub async fn delete_test_case(Path(id): Path<Uuid>) -> Response {
+    let store = get_store();
+
+    if let Some(mut rule) = store.get_rule() {
+        rule.test_cases.retain(|test_case| test_case.id != id);
+        store.update_rule(rule.clone());
+        render_rule_view(rule)
+    } else {
+        Html("").into_response()
+    }
+}
+
+/// Publish the current rule, unless it is invalid or any of its test cases fail
+pub async fn publish_rule() -> Response {
+    let store = get_store();
+
+    if let Some(rule) = store.get_rule() {
+        let ctx = test_case_context(&rule);
+        let mut publish_errors = rule.validate(&ctx).err().unwrap_or_default();
+        publish_errors.extend(
+            run_test_cases(&rule, &ctx)
+                .iter()
+                .filter(|result| !result.passed())
+                .map(|result| format!("Test case \"{}\" fails", result.test_case.name)),
+        );
+
+        if publish_errors.is_empty() {
+            store.publish(rule.clone());
+        }
+        let mut view = build_rule_view(rule);
+        view.publish_errors = publish_errors;
+        HtmlTemplate(view).into_response()
+    } else {
+        Html("<div>Rule not found</div>".to_string()).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DatasetForm {
+    transactions: String,
+}
+
+/// Replace the sample dataset used for hit statistics with the submitted JSONL
+pub async fn upload_dataset(Form(form): Form<DatasetForm>) -> Response {
+    let store = get_store();
+
+    if let Some(rule) = store.get_rule() {
+        let dataset_error = match Transaction::parse_jsonl(&form.transactions) {
+            Ok(transactions) => {
+                get_dataset_store().replace(transactions);
+                None
+            }
+            Err(err) => Some(err),
+        };
+        let mut view = build_rule_view(rule);
+        view.dataset_error = dataset_error;
+        HtmlTemplate(view).into_response()
+    } else {
+        Html("<div>Rule not found</div>".to_string()).into_response()
+    }
+}
+
+// ============================================================================
+// Reference List Handlers
+// ============================================================================
+
+/// Every draft and published rule, labelled e.g. "Fraud Detection Rule (draft)"
+fn labelled_rule_versions() -> Vec<(String, Rule)> {
+    let store = get_store();
+    let drafts = store
+        .all_rules()
+        .into_iter()
+        .map(|rule| (format!("{} (draft)", rule.name), rule));
+    let published = store
+        .all_published()
+        .into_iter()
+        .map(|rule| (format!("{} (published)", rule.name), rule));
+    drafts.chain(published).collect()
+}
+
+/// Rules that reference a list, in draft or published form
+fn list_usages(name: &str) -> Vec<String> {
+    let mut usages: Vec<String> = labelled_rule_versions()
+        .into_iter()
+        .filter(|(_, rule)| rule.references_list(name))
+        .map(|(label, _)| label)
+        .collect();
+    for snippet in get_snippet_store().all() {
+        if snippet
+            .root
+            .referenced_lists()
+            .iter()
+            .any(|used| used == name)
+        {
+            usages.push(format!("Snippet {}", snippet.name));
+        }
+    }
+    usages
+}
+
+fn build_list_detail(list: ReferenceList) -> ListDetailTemplate {
+    ListDetailTemplate {
+        used_by: list_usages(&list.name),
+        list,
+        message: None,
+        error: None,
+    }
+}
+
+fn lists_index() -> ListsIndexTemplate {
+    ListsIndexTemplate {
+        lists: get_list_store().all(),
+        error: None,
+    }
+}
+
+/// Wrap a rendered list fragment in the full reference lists page
+fn render_lists_page(content: impl Template) -> Response {
+    match content.render() {
+        Ok(content_html) => HtmlTemplate(ListsPageTemplate { content_html }).into_response(),
+        Err(err) => HtmlTemplate(ListsPageTemplate {
+            content_html: format!("<div>Failed to render lists: {}</div>", err),
+        })
+        .into_response(),
+    }
+}
+
+fn list_not_found(name: &str) -> Response {
+    Html(format!(
+        "<div class=\"alert alert-error\">List {} not found</div>",
+        escape_html(name)
+    ))
+    .into_response()
+}
+
+/// Summarise an import for display
+fn describe_import(import: &EntryImport) -> String {
+    let mut parts = vec![format!("Added {} entries", import.added)];
+    if import.duplicates > 0 {
+        parts.push(format!("skipped {} duplicates", import.duplicates));
+    }
+    if !import.rejected.is_empty() {
+        parts.push(format!(
+            "rejected {} invalid: {}",
+            import.rejected.len(),
+            import.rejected.join(", ")
+        ));
+    }
+    parts.join("; ")
+}
+
+pub async fn lists_page() -> Response {
+    render_lists_page(lists_index())
+}
+
+pub async fn list_page(Path(name): Path<String>) -> Response {
+    match get_list_store().get(&name) {
+        Some(list) => render_lists_page(build_list_detail(list)),
+        None => list_not_found(&name),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateListForm {
+    name: String,
+    element_type: String,
+    #[serde(default)]
+    description: String,
+}
+
+pub async fn create_list(Form(form): Form<CreateListForm>) -> Response {
+    let list_store = get_list_store();
+    let name = form.name.trim().to_string();
+
+    let error = match ReferenceList::validate_name(&name) {
+        Err(err) => Some(err),
+        Ok(()) if list_store.get(&name).is_some() => {
+            Some(format!("A list named \"{}\" already exists", name))
+        }
+        Ok(()) => None,
+    };
+    if let Some(error) = error {
+        let mut index = lists_index();
+        index.error = Some(error);
+        return HtmlTemplate(index).into_response();
+    }
+
+    let element_type = match form.element_type.as_str() {
+        "number" => DataType::Number,
+        "ip" => DataType::Ip,
+        _ => DataType::String,
+    };
+    let list = ReferenceList::new(
+        name.clone(),
+        form.description.trim().to_string(),
+        element_type,
+    );
+    list_store.upsert(list.clone());
+
+    (
+        [("HX-Push-Url", format!("/lists/{}", name))],
+        HtmlTemplate(build_list_detail(list)),
+    )
+        .into_response()
+}
+
+/// Delete a list, unless a draft or published rule still references it
+pub async fn delete_list(Path(name): Path<String>) -> Response {
+    let list_store = get_list_store();
+    let Some(list) = list_store.get(&name) else {
+        return list_not_found(&name);
+    };
+
+    let mut detail = build_list_detail(list);
+    if !detail.used_by.is_empty() {
+        detail.error = Some(format!(
+            "Cannot delete {}: it is referenced by {}",
+            name,
+            detail.used_by.join(", ")
+        ));
+        return HtmlTemplate(detail).into_response();
+    }
+
+    list_store.delete(&name);
+    ([("HX-Push-Url", "/lists")], HtmlTemplate(lists_index())).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct AddEntriesForm {
+    entries: String,
+}
+
+pub async fn add_list_entries(
+    Path(name): Path<String>,
+    Form(form): Form<AddEntriesForm>,
+) -> Response {
+    let list_store = get_list_store();
+    let Some(mut list) = list_store.get(&name) else {
+        return list_not_found(&name);
+    };
+
+    let import = list.add_entries(form.entries.lines().map(str::to_string));
+    list_store.upsert(list.clone());
+
+    let mut detail = build_list_detail(list);
+    detail.message = Some(describe_import(&import));
+    HtmlTemplate(detail).into_response()
+}
+
+pub async fn delete_list_entry(Path((name, index)): Path<(String, usize)>) -> Response {
+    let list_store = get_list_store();
+    let Some(mut list) = list_store.get(&name) else {
+        return list_not_found(&name);
+    };
+
+    if index < list.entries.len() {
+        list.entries.remove(index);
+        list_store.upsert(list.clone());
+    }
+    HtmlTemplate(build_list_detail(list)).into_response()
+}
+
+/// Bulk-load entries from the first column of an uploaded CSV file
+pub async fn upload_list_csv(Path(name): Path<String>, mut multipart: Multipart) -> Response {
+    let list_store = get_list_store();
+    let Some(mut list) = list_store.get(&name) else {
+        return list_not_found(&name);
+    };
+
+    let mut data = String::new();
+    let mut replace = false;
+    let mut has_header = false;
+    while let Ok(Some(field)) = multipart.next_field().await {
+        match field.name() {
+            Some("file") => {
+                if let Ok(bytes) = field.bytes().await {
+                    data = String::from_utf8_lossy(&bytes).into_owned();
+                }
+            }
+            Some("mode") => replace = field.text().await.is_ok_and(|mode| mode == "replace"),
+            Some("has_header") => has_header = true,
+            _ => {}
+        }
+    }
+
+    let detail = match ReferenceList::parse_csv(&data, has_header) {
+        Ok(entries) => {
+            if replace {
+                list.entries.clear();
+            }
+            let import = list.add_entries(entries);
+            list_store.upsert(list.clone());
+            let summary = describe_import(&import);
+            let mut detail = build_list_detail(list);
+            detail.message = Some(if replace {
+                format!("Replaced all entries. {}", summary)
+            } else {
+                summary
+            });
+            detail
+        }
+        Err(err) => {
+            let mut detail = build_list_detail(list);
+            detail.error = Some(err);
+            detail
+        }
+    };
+    HtmlTemplate(detail).into_response()
+}
+
+// ============================================================================
+// Parameter Handlers
+// ============================================================================
+
+/// Conditions that use a parameter, grouped by draft rule, published rule and snippet
+fn parameter_usages(name: &str) -> Vec<ParameterUsage> {
+    let rules = labelled_rule_versions()
+        .into_iter()
+        .map(|(label, rule)| (label, rule.root));
+    let snippets = get_snippet_store()
+        .all()
+        .into_iter()
+        .map(|snippet| (format!("Snippet {}", snippet.name), snippet.root));
+
+    rules
+        .chain(snippets)
+        .filter_map(|(owner, root)| {
+            let conditions: Vec<String> = root
+                .leaves_using_parameter(name)
+                .iter()
+                .map(|leaf| leaf.display())
+                .collect();
+            (!conditions.is_empty()).then_some(ParameterUsage {
+                rule: owner,
+                conditions,
+            })
+        })
+        .collect()
+}
+
+fn build_parameter_detail(parameter: Parameter) -> ParameterDetailTemplate {
+    ParameterDetailTemplate {
+        used_by: parameter_usages(&parameter.name),
+        parameter,
+        message: None,
+        error: None,
+    }
+}
+
+fn parameters_index() -> ParametersIndexTemplate {
+    ParametersIndexTemplate {
+        parameters: get_parameter_store().all(),
+        error: None,
+    }
+}
+
+/// Wrap a rendered parameter fragment in the full parameters page
+fn render_parameters_page(content: impl Template) -> Response {
+    match content.render() {
+        Ok(content_html) => HtmlTemplate(ParametersPageTemplate { content_html }).into_response(),
+        Err(err) => HtmlTemplate(ParametersPageTemplate {
+            content_html: format!("<div>Failed to render parameters: {}</div>", err),
+        })
+        .into_response(),
+    }
+}
+
+fn parameter_not_found(name: &str) -> Response {
+    Html(format!(
+        "<div class=\"alert alert-error\">Parameter ${} not found</div>",
+        escape_html(name)
+    ))
+    .into_response()
+}
+
+fn parse_parameter_type(data_type: &str) -> DataType {
+    match data_type {
+        "number" => DataType::Number,
+        "ip" => DataType::Ip,
+        "datetime" => DataType::DateTime,
+        _ => DataType::String,
+    }
+}
+
+pub async fn parameters_page() -> Response {
+    render_parameters_page(parameters_index())
+}
+
+pub async fn parameter_page(Path(name): Path<String>) -> Response {
+    match get_parameter_store().get(&name) {
+        Some(parameter) => render_parameters_page(build_parameter_detail(parameter)),
+        None => parameter_not_found(&name),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateParameterForm {
+    name: String,
+    data_type: String,
+    value: String,
+    #[serde(default)]
+    description: String,
+}
+
+pub async fn create_parameter(Form(form): Form<CreateParameterForm>) -> Response {
+    let parameter_store = get_parameter_store();
+    let name = form.name.trim().trim_start_matches('$').to_string();
+    let parameter = Parameter {
+        name: name.clone(),
+        description: form.description.trim().to_string(),
+        data_type: parse_parameter_type(&form.data_type),
+        value: form.value.trim().to_string(),
+    };
+
+    let error = match Parameter::validate_name(&name) {
+        Err(err) => Some(err),
+        Ok(()) if parameter_store.get(&name).is_some() => {
+            Some(format!("A parameter named ${} already exists", name))
+        }
+        Ok(()) => parameter.check_value(&parameter.value).err(),
+    };
+    if let Some(error) = error {
+        let mut index = parameters_index();
+        index.error = Some(error);
+        return HtmlTemplate(index).into_response();
+    }
+
+    parameter_store.upsert(parameter.clone());
+    (
+        [("HX-Push-Url", format!("/parameters/{}", name))],
+        HtmlTemplate(build_parameter_detail(parameter)),
+    )
+        .into_response()
+}
+
+#[derive(Deserialize)]
+pub struct UpdateParameterForm {
+    value: String,
+    #[serde(default)]
+    description: String,
+}
+
+/// Change a parameter's value; every rule using it picks up the new value
+pub async fn update_parameter(
+    Path(name): Path<String>,
+    Form(form): Form<UpdateParameterForm>,
+) -> Response {
+    let parameter_store = get_parameter_store();
+    let Some(mut parameter) = parameter_store.get(&name) else {
+        return parameter_not_found(&name);
+    };
+
+    let value = form.value.trim().to_string();
+    if let Err(err) = parameter.check_value(&value) {
+        let mut detail = build_parameter_detail(parameter);
+        detail.error = Some(err);
+        return HtmlTemplate(detail).into_response();
+    }
+
+    let message = if parameter.value == value {
+        format!("Saved ${}", name)
+    } else {
+        format!("Changed ${} from {} to {}", name, parameter.value, value)
+    };
+    parameter.value = value;
+    parameter.description = form.description.trim().to_string();
+    parameter_store.upsert(parameter.clone());
+
+    let mut detail = build_parameter_detail(parameter);
+    detail.message = Some(message);
+    HtmlTemplate(detail).into_response()
+}
+
+/// Delete a parameter, unless a draft or published rule still uses it
+pub async fn delete_parameter(Path(name): Path<String>) -> Response {
+    let parameter_store = get_parameter_store();
+    let Some(parameter) = parameter_store.get(&name) else {
+        return parameter_not_found(&name);
+    };
+
+    let mut detail = build_parameter_detail(parameter);
+    if !detail.used_by.is_empty() {
+        detail.error = Some(format!(
+            "Cannot delete ${}: it is used by {}",
+            name,
+            detail
+                .used_by
+                .iter()
+                .map(|usage| usage.rule.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+        return HtmlTemplate(detail).into_response();
+    }
+
+    parameter_store.delete(&name);
+    (
+        [("HX-Push-Url", "/parameters")],
+        HtmlTemplate(parameters_index()),
+    )
+        .into_response()
+}
+
+// ============================================================================
+// Snippet Handlers
+// ============================================================================
+
+/// Rules and other snippets that reference a snippet
+fn snippet_usages(name: &str) -> Vec<String> {
+    let mut usages: Vec<String> = labelled_rule_versions()
+        .into_iter()
+        .filter(|(_, rule)| rule.references_snippet(name))
+        .map(|(label, _)| label)
+        .collect();
+    for snippet in get_snippet_store().all() {
+        if snippet
+            .root
+            .referenced_snippets()
+            .iter()
+            .any(|used| used == name)
+        {
+            usages.push(format!("Snippet {}", snippet.name));
+        }
+    }
+    usages
+}
+
+fn build_snippet_detail(snippet: Snippet) -> SnippetDetailTemplate {
+    SnippetDetailTemplate {
+        used_by: snippet_usages(&snippet.name),
+        snippet,
+        error: None,
+    }
+}
+
+/// Wrap a rendered snippet fragment in the full snippets page
+fn render_snippets_page(content: impl Template) -> Response {
+    match content.render() {
+        Ok(content_html) => HtmlTemplate(SnippetsPageTemplate { content_html }).into_response(),
+        Err(err) => HtmlTemplate(SnippetsPageTemplate {
+            content_html: format!("<div>Failed to render snippets: {}</div>", err),
+        })
+        .into_response(),
+    }
+}
+
+fn snippet_not_found(name: &str) -> Response {
+    Html(format!(
+        "<div class=\"alert alert-error\">Snippet {} not found</div>",
+        escape_html(name)
+    ))
+    .into_response()
+}
+
+pub async fn snippets_page() -> Response {
+    render_snippets_page(SnippetsIndexTemplate {
+        snippets: get_snippet_store().all(),
+    })
+}
+
+pub async fn snippet_page(Path(name): Path<String>) -> Response {
+    match get_snippet_store().get(&name) {
+        Some(snippet) => render_snippets_page(build_snippet_detail(snippet)),
+        None => snippet_not_found(&name),
+    }
+}
+
+/// Delete a snippet, unless a rule or another snippet still references it
+pub async fn delete_snippet(Path(name): Path<String>) -> Response {
+    let snippet_store = get_snippet_store();
+    let Some(snippet) = snippet_store.get(&name) else {
+        return snippet_not_found(&name);
+    };
+
+    let mut detail = build_snippet_detail(snippet);
+    if !detail.used_by.is_empty() {
+        detail.error = Some(format!(
+            "Cannot delete {}: it is referenced by {}",
+            name,
+            detail.used_by.join(", ")
+        ));
+        return HtmlTemplate(detail).into_response();
+    }
+
+    snippet_store.delete(&name);
+    (
+        [("HX-Push-Url", "/snippets")],
+        HtmlTemplate(SnippetsIndexTemplate {
+            snippets: snippet_store.all(),
+        }),
+    )
+        .into_response()
+}
+
+// ============================================================================
+// Rule Set Handlers
+// ============================================================================
+
+fn build_rule_set_detail(rule_set: RuleSet) -> RuleSetDetailTemplate {
+    let rules = get_store().all_rules();
+    let members = rule_set
+        .entries
+        .iter()
+        .enumerate()
+        .filter_map(|(index, entry)| {
+            let rule = rules.iter().find(|rule| rule.id == entry.rule_id)?;
+            Some(RuleSetMember {
+                rule: rule.clone(),
+                enabled: entry.enabled,
+                priority: index + 1,
+            })
+        })
+        .collect();
+    let available = rules
+        .into_iter()
+        .filter(|rule| !rule_set.contains(rule.id))
+        .collect();
+    RuleSetDetailTemplate {
+        rule_set,
+        members,
+        available,
+        strategies: MatchStrategy::all(),
+        decisions: Decision::all(),
+        message: None,
+        error: None,
+    }
+}
+
+fn rule_sets_index() -> RuleSetsIndexTemplate {
+    RuleSetsIndexTemplate {
+        rule_sets: get_rule_set_store().all(),
+        error: None,
+    }
+}
+
+/// Wrap a rendered rule set fragment in the full rule sets page
+fn render_rule_sets_page(content: impl Template) -> Response {
+    match content.render() {
+        Ok(content_html) => HtmlTemplate(RuleSetsPageTemplate { content_html }).into_response(),
+        Err(err) => HtmlTemplate(RuleSetsPageTemplate {
+            content_html: format!("<div>Failed to render rule sets: {}</div>", err),
+        })
+        .into_response(),
+    }
+}
+
+fn rule_set_not_found(id: Uuid) -> Response {
+    Html(format!(
+        "<div class=\"alert alert-error\">Rule set {} not found</div>",
+        id
+    ))
+    .into_response()
+}
+
+/// Parse a snake_case enum value such as a match strategy or decision
+fn parse_choice<T: serde::de::DeserializeOwned>(value: &str) -> Option<T> {
+    serde_json::from_str::<T>(&format!("\"{}\"", value)).ok()
+}
+
+/// Save a changed rule set and render its editor
+fn save_rule_set(rule_set: RuleSet) -> Response {
+    get_rule_set_store().upsert(rule_set.clone());
+    HtmlTemplate(build_rule_set_detail(rule_set)).into_response()
+}
+
+pub async fn rule_sets_page() -> Response {
+    render_rule_sets_page(rule_sets_index())
+}
+
+pub async fn rule_set_page(Path(id): Path<Uuid>) -> Response {
+    match get_rule_set_store().get(id) {
+        Some(rule_set) => render_rule_sets_page(build_rule_set_detail(rule_set)),
+        None => rule_set_not_found(id),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateRuleSetForm {
+    name: String,
+    #[serde(default)]
+    description: String,
+}
+
+pub async fn create_rule_set(Form(form): Form<CreateRuleSetForm>) -> Response {
+    let name = form.name.trim();
+    if name.is_empty() {
+        let mut index = rule_sets_index();
+        index.error = Some("Rule set name cannot be empty".to_string());
+        return HtmlTemplate(index).into_response();
+    }
+
+    let rule_set = RuleSet::new(name.to_string(), form.description.trim().to_string());
+    get_rule_set_store().upsert(rule_set.clone());
+    (
+        [("HX-Push-Url", format!("/rule-sets/{}", rule_set.id))],
+        HtmlTemplate(build_rule_set_detail(rule_set)),
+    )
+        .into_response()
+}
+
+#[derive(Deserialize)]
+pub struct UpdateRuleSetForm {
+    name: String,
+    #[serde(default)]
+    description: String,
+    strategy: String,
+    default_decision: String,
+}
+
+/// Save a rule set's name, match strategy and default decision
+pub async fn update_rule_set(
+    Path(id): Path<Uuid>,
+    Form(form): Form<UpdateRuleSetForm>,
+) -> Response {
+    let Some(mut rule_set) = get_rule_set_store().get(id) else {
+        return rule_set_not_found(id);
+    };
+
+    let (Some(strategy), Some(default_decision)) = (
+        parse_choice::<MatchStrategy>(&form.strategy),
+        parse_choice::<Decision>(&form.default_decision),
+    ) else {
+        let mut detail = build_rule_set_detail(rule_set);
+        detail.error = Some("Unknown match strategy or decision".to_string());
+        return HtmlTemplate(detail).into_response();
+    };
+    let name = form.name.trim();
+    if name.is_empty() {
+        let mut detail = build_rule_set_detail(rule_set);
+        detail.error = Some("Rule set name cannot be empty".to_string());
+        return HtmlTemplate(detail).into_response();
+    }
+
+    rule_set.name = name.to_string();
+    rule_set.description = form.description.trim().to_string();
+    rule_set.strategy = strategy;
+    rule_set.default_decision = default_decision;
+    get_rule_set_store().upsert(rule_set.clone());
+
+    let mut detail = build_rule_set_detail(rule_set);
+    detail.message = Some("Saved".to_string());
+    HtmlTemplate(detail).into_response()
+}
+
+pub async fn delete_rule_set(Path(id): Path<Uuid>) -> Response {
+    if get_rule_set_store().delete(id).is_none() {
+        return rule_set_not_found(id);
+    }
+    (
+        [("HX-Push-Url", "/rule-sets")],
+        HtmlTemplate(rule_sets_index()),
+    )
+        .into_response()
+}
+
+#[derive(Deserialize)]
+pub struct AddRuleSetMemberForm {
+    rule_id: Uuid,
+}
+
+/// Add a rule at the lowest priority
+pub async fn add_rule_set_member(
+    Path(id): Path<Uuid>,
+    Form(form): Form<AddRuleSetMemberForm>,
+) -> Response {
+    let Some(mut rule_set) = get_rule_set_store().get(id) else {
+        return rule_set_not_found(id);
+    };
+
+    if get_store().get_rule_by_id(form.rule_id).is_some() && !rule_set.contains(form.rule_id) {
+        rule_set.entries.push(RuleSetEntry {
+            rule_id: form.rule_id,
+            enabled: true,
+        });
+    }
+    save_rule_set(rule_set)
+}
+
+pub async fn remove_rule_set_member(Path((id, rule_id)): Path<(Uuid, Uuid)>) -> Response {
+    let Some(mut rule_set) = get_rule_set_store().get(id) else {
+        return rule_set_not_found(id);
+    };
+
+    rule_set.entries.retain(|entry| entry.rule_id != rule_id);
+    save_rule_set(rule_set)
+}
+
+/// Enable or disable a rule without losing its place in the order
+pub async fn toggle_rule_set_member(Path((id, rule_id)): Path<(Uuid, Uuid)>) -> Response {
+    let Some(mut rule_set) = get_rule_set_store().get(id) else {
+        return rule_set_not_found(id);
+    };
+
+    if let Some(entry) = rule_set
+        .entries
+        .iter_mut()
+        .find(|entry| entry.rule_id == rule_id)
+    {
+        entry.enabled = !entry.enabled;
+    }
+    save_rule_set(rule_set)
+}
+
+#[derive(Deserialize)]
+pub struct ReorderRuleSetForm {
+    /// Comma-separated rule ids, highest priority first
+    order: String,
+}
+
+/// Apply the order the rules were dragged into
+pub async fn reorder_rule_set(
+    Path(id): Path<Uuid>,
+    Form(form): Form<ReorderRuleSetForm>,
+) -> Response {
+    let Some(mut rule_set) = get_rule_set_store().get(id) else {
+        return rule_set_not_found(id);
     };
 
-    Html(html).into_response()
+    let order: Vec<Uuid> = form
+        .order
+        .split(',')
+        .filter_map(|id| id.trim().parse().ok())
+        .collect();
+    rule_set.reorder(&order);
+    save_rule_set(rule_set)
 }
 
-pub async fn get_operators_and_right_hint(
-    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
+/// Evaluate a transaction against the current drafts of the set's rules
+pub async fn test_rule_set(
+    Path(id): Path<Uuid>,
+    Form(form): Form<TestTransactionForm>,
 ) -> Response {
-    let left_type = params
-        .get("left_type")
-        .map(|s| s.as_str())
-        .unwrap_or("field");
-    let left_field_str = params.get("left_field").map(|s| s.as_str()).unwrap_or("");
+    let Some(rule_set) = get_rule_set_store().get(id) else {
+        return rule_set_not_found(id);
+    };
 
-    // Determine operators based on left side
-    let operators = if left_type == "field" && !left_field_str.is_empty() {
-        if let Ok(field) = serde_json::from_str::<Field>(&format!("\"{}\"", left_field_str)) {
-            match field {
-                // Numeric fields: comparison operators
-                Field::TransactionAmount
-                | Field::UserAge
-                | Field::TransactionCount24h
-                | Field::AccountAge => {
-                    vec![
-                        Operator::Equals,
-                        Operator::NotEquals,
-                        Operator::GreaterThan,
-                        Operator::LessThan,
-                        Operator::GreaterThanOrEqual,
-                        Operator::LessThanOrEqual,
-                    ]
-                }
-                // String fields: equality and contains
-                Field::TransactionCurrency
-                | Field::UserCountry
-                | Field::IpAddress
-                | Field::DeviceFingerprint => {
-                    vec![
-                        Operator::Equals,
-                        Operator::NotEquals,
-                        Operator::Contains,
-                        Operator::In,
-                    ]
-                }
+    let template = match serde_json::from_str::<Transaction>(&form.transaction) {
+        Ok(tx) => {
+            let ctx = shared_eval_context();
+            let outcome = evaluate_rule_set(&rule_set, &get_store().all_rules(), &tx, &ctx);
+            // Evaluated transactions feed later aggregates
+            ctx.velocity.record(&tx, ctx.now);
+            RuleSetResultTemplate {
+                outcome: Some(outcome),
+                error: None,
             }
-        } else {
-            Operator::all()
         }
-    } else {
-        Operator::all()
+        Err(err) => RuleSetResultTemplate {
+            outcome: None,
+            error: Some(format!("Invalid transaction JSON: {}", err)),
+        },
+    };
+    HtmlTemplate(template).into_response()
+}
+
+/// Compare the set's enabled rules pairwise, and with their priority, over the
+/// sample dataset
+pub async fn rule_set_overlap(Path(id): Path<Uuid>) -> Response {
+    let Some(rule_set) = get_rule_set_store().get(id) else {
+        return rule_set_not_found(id);
     };
 
-    let options_html = operators
+    let rules = get_store().all_rules();
+    let members: Vec<(usize, Rule)> = rule_set
+        .entries
         .iter()
-        .map(|op| {
-            format!(
-                r#"<option value="{}">{}</option>"#,
-                op.as_str(),
-                op.display_name()
-            )
+        .enumerate()
+        .filter(|(_, entry)| entry.enabled)
+        .filter_map(|(index, entry)| {
+            let rule = rules.iter().find(|rule| rule.id == entry.rule_id)?;
+            Some((index + 1, rule.clone()))
         })
-        .collect::<Vec<_>>()
-        .join("\n");
-
-    let html = format!(
-        r##"<label for="operator">Operator</label>
-<select id="operator" name="operator" required>
-    <option value="">Select an operator...</option>
-    {}
-</select>"##,
-        options_html
+        .collect();
+    let ordered: Vec<Rule> = members.iter().map(|(_, rule)| rule.clone()).collect();
+    let report = compare_rules(
+        &ordered,
+        &get_dataset_store().get_transactions(),
+        &shared_eval_context(),
     );
 
-    Html(html).into_response()
+    let name = |id: Uuid| {
+        ordered
+            .iter()
+            .find(|rule| rule.id == id)
+            .map(|rule| rule.name.clone())
+            .unwrap_or_default()
+    };
+    let mut pairs: Vec<(Option<f64>, OverlapPairRow)> = report
+        .pairs
+        .iter()
+        .map(|pair| {
+            let jaccard = pair.jaccard();
+            let row = OverlapPairRow {
+                first: name(pair.first),
+                second: name(pair.second),
+                relation: pair.relation,
+                first_hits: pair.first_hits,
+                second_hits: pair.second_hits,
+                both_hits: pair.both_hits,
+                jaccard: jaccard.map(|j| format!("{:.1}%", j * 100.0)),
+            };
+            (jaccard, row)
+        })
+        .collect();
+    // Most overlapping first; pairs that never matched last
+    pairs.sort_by(|(a, _), (b, _)| b.unwrap_or(-1.0).total_cmp(&a.unwrap_or(-1.0)));
+    let shadowing = report
+        .shadowing
+        .iter()
+        .zip(members.iter().skip(1))
+        .map(|(shadowing, (priority, rule))| ShadowingRow {
+            priority: *priority,
+            name: rule.name.clone(),
+            hits: shadowing.hits,
+            taken: shadowing.taken,
+            covered_by: shadowing.covered_by.iter().map(|id| name(*id)).collect(),
+            shadowed_on_sample: shadowing.shadowed_on_sample(),
+        })
+        .collect();
+
+    HtmlTemplate(RuleSetOverlapTemplate {
+        sample_size: report.sample_size,
+        first_match: rule_set.strategy == MatchStrategy::FirstMatch,
+        pairs: pairs.into_iter().map(|(_, row)| row).collect(),
+        shadowing,
+    })
+    .into_response()
 }
 
-pub async fn get_operators_for_value(
-    axum::extract::Query(params): axum::extract::Query<std::collections::HashMap<String, String>>,
-) -> Response {
-    let left_value = params.get("left_value").map(|s| s.as_str()).unwrap_or("");
+// ============================================================================
+// Evaluation API Handlers
+// ============================================================================
 
-    // Determine if the value is numeric or string
-    let is_numeric = !left_value.is_empty() && left_value.parse::<f64>().is_ok();
+/// Largest body accepted by `POST /api/v1/evaluate`
+pub const MAX_EVALUATE_BODY_BYTES: usize = 64 * 1024;
+/// Largest body accepted by `POST /api/v1/evaluate/batch`
+pub const MAX_BATCH_BODY_BYTES: usize = 8 * 1024 * 1024;
+/// Most transactions in one batch
+const MAX_BATCH_TRANSACTIONS: usize = 10_000;
+/// Requests evaluated at once; further requests wait for a free slot
+const MAX_CONCURRENT_EVALUATIONS: usize = 8;
 
-    let operators = if is_numeric {
-        // Numeric value: comparison operators
-        vec![
-            Operator::Equals,
-            Operator::NotEquals,
-            Operator::GreaterThan,
-            Operator::LessThan,
-            Operator::GreaterThanOrEqual,
-            Operator::LessThanOrEqual,
-        ]
-    } else {
-        // String value: equality and contains
-        vec![
-            Operator::Equals,
-            Operator::NotEquals,
-            Operator::Contains,
-            Operator::In,
-        ]
-    };
+static EVALUATION_PERMITS: OnceLock<Semaphore> = OnceLock::new();
 
-    let options_html = operators
-        .iter()
-        .map(|op| {
-            format!(
-                r#"<option value="{}">{}</option>"#,
-                op.as_str(),
-                op.display_name()
+fn get_evaluation_permits() -> &'static Semaphore {
+    EVALUATION_PERMITS.get_or_init(|| Semaphore::new(MAX_CONCURRENT_EVALUATIONS))
+}
+
+/// A published rule that matched a transaction
+#[derive(Serialize)]
+struct MatchedRule {
+    id: Uuid,
+    name: String,
+    actions: Vec<Action>,
+}
+
+/// The decision the published rules make for one transaction
+#[derive(Serialize)]
+struct EvaluationResult {
+    /// 1-based line of the transaction, for batches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+    decision: Decision,
+    matched_rules: Vec<MatchedRule>,
+    /// Actions of every matched rule, without duplicates
+    actions: Vec<Action>,
+}
+
+#[derive(Serialize)]
+struct EvaluationError {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+    error: String,
+}
+
+/// The published rules, compiled with their timezones
+struct PublishedRules(Vec<(Rule, CompiledRule, Tz)>);
+
+/// Revisions of the published rules, lists, parameters and snippets, in that order
+type Revisions = [u64; 4];
+
+fn revisions() -> Revisions {
+    [
+        get_store().published_revision(),
+        get_list_store().revision(),
+        get_parameter_store().revision(),
+        get_snippet_store().revision(),
+    ]
+}
+
+/// Compiled published rules, with the revisions they were compiled from
+type CachedRules = Option<(Revisions, Arc<PublishedRules>)>;
+
+// Published rules compiled for the evaluation API
+static PUBLISHED_RULES: OnceLock<RwLock<CachedRules>> = OnceLock::new();
+
+impl PublishedRules {
+    /// The compiled published rules, compiled again once a rule is published or a
+    /// list, parameter or snippet changes
+    fn current() -> Arc<PublishedRules> {
+        // Read the revisions before compiling, so a change made meanwhile is not missed
+        let revisions = revisions();
+        let cache = PUBLISHED_RULES.get_or_init(RwLock::default);
+        if let Some((compiled_from, rules)) = cache.read().unwrap().as_ref() {
+            if *compiled_from == revisions {
+                return Arc::clone(rules);
+            }
+        }
+        let rules = Arc::new(PublishedRules::compile());
+        *cache.write().unwrap() = Some((revisions, Arc::clone(&rules)));
+        rules
+    }
+
+    /// Rules that no longer compile (e.g. a referenced list was changed) are skipped
+    fn compile() -> Self {
+        let mut ctx = shared_eval_context();
+        let rules = get_store()
+            .all_published()
+            .into_iter()
+            .filter_map(|rule| {
+                ctx.timezone = rule.tz();
+                match compile_rule(&rule, &ctx) {
+                    Ok(compiled) => Some((rule, compiled, ctx.timezone)),
+                    Err(errors) => {
+                        tracing::warn!(rule = %rule.name, ?errors, "skipping published rule");
+                        None
+                    }
+                }
+            })
+            .collect();
+        PublishedRules(rules)
+    }
+
+    /// Evaluate a transaction against every rule as of now, then record it at that
+    /// time so aggregates count it for the transactions after it. The decision is the
+    /// most severe one implied by the matched actions, or Allow.
+    fn evaluate(&self, tx: &Transaction, line: Option<usize>) -> EvaluationResult {
+        // Lists, parameters and snippets were captured when compiling
+        let mut ctx = EvalContext {
+            velocity: Arc::clone(get_api_velocity_store()),
+            now: unix_now(),
+            ..EvalContext::default()
+        };
+        let mut matched_rules = Vec::new();
+        let mut actions: Vec<Action> = Vec::new();
+        for (rule, compiled, timezone) in &self.0 {
+            ctx.timezone = *timezone;
+            let outcome = compiled.outcome(tx, &ctx);
+            if !outcome.matched {
+                continue;
+            }
+            let rule_actions = outcome.actions.to_vec();
+            for action in &rule_actions {
+                if !actions.contains(action) {
+                    actions.push(action.clone());
+                }
+            }
+            matched_rules.push(MatchedRule {
+                id: rule.id,
+                name: rule.name.clone(),
+                actions: rule_actions,
+            });
+        }
+        ctx.velocity.record(tx, ctx.now);
+
+        EvaluationResult {
+            line,
+            decision: actions
+                .iter()
+                .filter_map(Decision::from_action)
+                .max()
+                .unwrap_or_default(),
+            matched_rules,
+            actions,
+        }
+    }
+}
+
+fn json_error(status: StatusCode, error: String) -> Response {
+    (status, axum::Json(EvaluationError { line: None, error })).into_response()
+}
+
+/// Evaluate one JSON transaction against the published rules
+pub async fn api_evaluate(body: Bytes) -> Response {
+    let span = tracing::info_span!(
+        "api_evaluate",
+        rules = tracing::field::Empty,
+        latency_us = tracing::field::Empty
+    );
+    let started = Instant::now();
+
+    let tx = match serde_json::from_slice::<Transaction>(&body) {
+        Ok(tx) => tx,
+        Err(err) => {
+            return json_error(
+                StatusCode::BAD_REQUEST,
+                format!("Invalid transaction JSON: {}", err),
             )
-        })
-        .collect::<Vec<_>>()
-        .join("\n");
+        }
+    };
+    let Ok(_permit) = get_evaluation_permits().acquire().await else {
+        return json_error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Evaluation is shutting down".to_string(),
+        );
+    };
 
-    let html = format!(
-        r##"<label for="operator">Operator</label>
-<select id="operator" name="operator" required>
-    <option value="">Select an operator...</option>
-    {}
-</select>"##,
-        options_html
+    let result = tokio::task::spawn_blocking(move || {
+        let rules = PublishedRules::current();
+        (rules.0.len(), rules.evaluate(&tx, None))
+    })
+    .await;
+    let response = match result {
+        Ok((rule_count, result)) => {
+            span.record("rules", rule_count);
+            axum::Json(result).into_response()
+        }
+        Err(err) => json_error(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
+    };
+
+    span.record("latency_us", started.elapsed().as_micros() as u64);
+    tracing::info!(parent: &span, "evaluated transaction");
+    response
+}
+
+/// Evaluate newline-delimited JSON transactions against the published rules.
+///
+/// Results stream back as NDJSON in input order, one line per non-blank input line;
+/// a line that is not a transaction gets an `{"line", "error"}` object instead.
+pub async fn api_evaluate_batch(body: Bytes) -> Response {
+    let span = tracing::info_span!(
+        "api_evaluate_batch",
+        transactions = tracing::field::Empty,
+        rules = tracing::field::Empty,
+        latency_us = tracing::field::Empty
     );
+    let started = Instant::now();
 
-    Html(html).into_response()
+    let Ok(input) = String::from_utf8(body.to_vec()) else {
+        return json_error(
+            StatusCode::BAD_REQUEST,
+            "Batch body must be UTF-8 NDJSON".to_string(),
+        );
+    };
+    let lines: Vec<(usize, String)> = input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| (index + 1, line.to_string()))
+        .collect();
+    if lines.len() > MAX_BATCH_TRANSACTIONS {
+        return json_error(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!(
+                "Batch has {} transactions; the limit is {}",
+                lines.len(),
+                MAX_BATCH_TRANSACTIONS
+            ),
+        );
+    }
+    span.record("transactions", lines.len());
+
+    let Ok(permit) = get_evaluation_permits().acquire().await else {
+        return json_error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Evaluation is shutting down".to_string(),
+        );
+    };
+
+    // Evaluate on a blocking thread and stream each result as soon as it is ready
+    let (sender, receiver) = tokio::sync::mpsc::channel::<Bytes>(64);
+    tokio::task::spawn_blocking(move || {
+        // Hold the slot until the whole batch is evaluated
+        let _permit = permit;
+        let rules = PublishedRules::current();
+        span.record("rules", rules.0.len());
+        for (line, text) in lines {
+            let mut output = match serde_json::from_str::<Transaction>(&text) {
+                Ok(tx) => serde_json::to_vec(&rules.evaluate(&tx, Some(line))),
+                Err(err) => serde_json::to_vec(&EvaluationError {
+                    line: Some(line),
+                    error: format!("Invalid transaction JSON: {}", err),
+                }),
+            }
+            .unwrap_or_default();
+            output.push(b'\n');
+            if sender.blocking_send(Bytes::from(output)).is_err() {
+                // The client went away
+                break;
+            }
+        }
+        span.record("latency_us", started.elapsed().as_micros() as u64);
+        tracing::info!(parent: &span, "evaluated batch");
+    });
+
+    let stream = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        receiver
+            .recv()
+            .await
+            .map(|chunk| (Ok::<_, std::convert::Infallible>(chunk), receiver))
+    });
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(stream),
+    )
+        .into_response()
 }
 
-pub async fn validate_rule() -> Response {
-    let store = get_store();
+#[derive(Serialize)]
+struct ConversionErrors {
+    errors: Vec<String>,
+}
 
-    if let Some(rule) = store.get_rule() {
-        let result = rule.validate();
-        let template = match result {
-            Ok(_) => ValidationResultTemplate {
-                success: true,
-                errors: vec![],
-            },
-            Err(errors) => ValidationResultTemplate {
-                success: false,
-                errors,
-            },
-        };
-        HtmlTemplate(template).into_response()
+fn conversion_errors(errors: Vec<String>) -> Response {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        axum::Json(ConversionErrors { errors }),
+    )
+        .into_response()
+}
+
+fn find_published(id: Uuid) -> Option<Rule> {
+    get_store()
+        .all_published()
+        .into_iter()
+        .find(|rule| rule.id == id)
+}
+
+/// Transpile a published rule to a parameterized WHERE clause. The body picks the
+/// dialect and maps fields to columns, e.g.
+/// `{"dialect": "sqlite", "columns": {"transaction_amount": "t.amount"}}`;
+/// an empty body gives PostgreSQL with columns named after the fields.
+pub async fn api_rule_sql(Path(id): Path<Uuid>, body: Bytes) -> Response {
+    let options = if body.iter().all(u8::is_ascii_whitespace) {
+        SqlOptions::default()
     } else {
-        Html("<div>Rule not found</div>".to_string()).into_response()
+        match serde_json::from_slice::<SqlOptions>(&body) {
+            Ok(options) => options,
+            Err(err) => {
+                return json_error(
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid SQL export options: {}", err),
+                )
+            }
+        }
+    };
+    let Some(rule) = find_published(id) else {
+        return json_error(StatusCode::NOT_FOUND, format!("No published rule {}", id));
+    };
+    match rule_to_sql(&rule, &options, &eval_context(&rule)) {
+        Ok(predicate) => axum::Json(predicate).into_response(),
+        Err(errors) => conversion_errors(errors),
+    }
+}
+
+/// A published rule's conditions as JSONLogic
+pub async fn api_rule_jsonlogic(Path(id): Path<Uuid>) -> Response {
+    let Some(rule) = find_published(id) else {
+        return json_error(StatusCode::NOT_FOUND, format!("No published rule {}", id));
+    };
+    match to_jsonlogic(&rule.root, &eval_context(&rule)) {
+        Ok(logic) => axum::Json(logic).into_response(),
+        Err(errors) => conversion_errors(errors),
+    }
+}
+
+/// Convert a JSONLogic expression to a condition tree, to paste into a rule file
+pub async fn api_import_jsonlogic(body: Bytes) -> Response {
+    let logic = match serde_json::from_slice::<serde_json::Value>(&body) {
+        Ok(logic) => logic,
+        Err(err) => return json_error(StatusCode::BAD_REQUEST, format!("Invalid JSON: {}", err)),
+    };
+    match from_jsonlogic(&logic) {
+        Ok(node) => axum::Json(node).into_response(),
+        Err(errors) => conversion_errors(errors),
     }
 }
 
@@ -846,3 +3831,406 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+    use serde_json::json;
+    use std::sync::Once;
+    use tower::ServiceExt;
+
+    const API_KEY: &str = "test-key";
+
+    /// The API routes, accepting `API_KEY`
+    fn api() -> axum::Router {
+        static CONFIGURE: Once = Once::new();
+        CONFIGURE.call_once(|| std::env::set_var("RULES_API_KEYS", API_KEY));
+        crate::api_routes()
+    }
+
+    async fn call(uri: &str, key: Option<&str>, body: String) -> (StatusCode, String) {
+        let mut request = Request::post(uri);
+        if let Some(key) = key {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", key));
+        }
+        let response = api()
+            .oneshot(request.body(Body::from(body)).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    async fn text(response: Response) -> String {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    /// Ids of the rules that matched, from one evaluation result
+    fn matched_ids(result: &serde_json::Value) -> Vec<String> {
+        result["matched_rules"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|rule| rule["id"].as_str().unwrap().to_string())
+            .collect()
+    }
+
+    fn leaf(left: Operand, operator: Operator, right: Operand) -> ConditionNode {
+        ConditionNode::Leaf {
+            id: Uuid::new_v4(),
+            left,
+            operator,
+            right,
+            weight: None,
+        }
+    }
+
+    fn device_is(device: &str) -> ConditionNode {
+        leaf(
+            Operand::Field {
+                field: Field::DeviceFingerprint,
+            },
+            Operator::Equals,
+            Operand::Value {
+                value: device.to_string(),
+            },
+        )
+    }
+
+    /// Publish a rule over `children` that blocks what it matches
+    fn publish(id: Option<Uuid>, children: Vec<ConditionNode>) -> Uuid {
+        let mut rule = Rule::new("API test".to_string(), String::new());
+        rule.id = id.unwrap_or(rule.id);
+        rule.root = ConditionNode::Group {
+            id: Uuid::new_v4(),
+            operator: LogicalOperator::And,
+            children,
+            weight: None,
+        };
+        rule.actions = vec![Action::Block];
+        get_store().update_rule(rule.clone());
+        get_store().publish(rule.clone());
+        rule.id
+    }
+
+    fn recorded(device: &str) -> usize {
+        let hour = TimeWindow {
+            amount: 1,
+            unit: WindowUnit::Hours,
+        };
+        get_api_velocity_store()
+            .history(&Field::DeviceFingerprint, device, &hour, unix_now())
+            .len()
+    }
+
+    #[test]
+    fn tree_escapes_values_and_parameters() {
+        let script = "<script>alert(1)</script>";
+        let root = ConditionNode::Group {
+            id: Uuid::new_v4(),
+            operator: LogicalOperator::Or,
+            children: vec![
+                device_is(script),
+                leaf(
+                    Operand::Field {
+                        field: Field::UserCountry,
+                    },
+                    Operator::In,
+                    Operand::List {
+                        element_type: DataType::String,
+                        values: vec![script.to_string()],
+                    },
+                ),
+                leaf(
+                    Operand::Field {
+                        field: Field::UserId,
+                    },
+                    Operator::Equals,
+                    Operand::Parameter {
+                        name: script.to_string(),
+                    },
+                ),
+            ],
+            weight: None,
+        };
+        let ctx = EvalContext::default();
+        let tree_ctx = TreeRenderContext {
+            stats: &DatasetStats::compute(&root, &[], &ctx),
+            scoring: false,
+            parameters: &ctx.parameters,
+            snippets: &ctx.snippets,
+        };
+        let html = render_tree_node(&root, "0".to_string(), 0, &tree_ctx);
+        assert!(!html.contains("<script>"), "{}", html);
+        assert_eq!(html.matches("&lt;script&gt;").count(), 3);
+    }
+
+    #[test]
+    fn trace_shows_values_and_marks_skipped_conditions() {
+        let root = ConditionNode::Group {
+            id: Uuid::new_v4(),
+            operator: LogicalOperator::And,
+            children: vec![device_is("emu-1"), device_is("<b>emu</b>")],
+            weight: None,
+        };
+        let ctx = EvalContext::default();
+        let render = |tx: serde_json::Value| {
+            let tx: Transaction = serde_json::from_value(tx).unwrap();
+            render_trace_node(&trace(&root, &tx, &ctx), 0)
+        };
+
+        // The first condition fails, so the second is never evaluated
+        let html = render(json!({ "device_fingerprint": "pixel" }));
+        assert!(
+            html.contains(r#"<span class="trace-value">= pixel</span>"#),
+            "{}",
+            html
+        );
+        assert_eq!(html.matches("trace-false").count(), 2);
+        assert_eq!(html.matches(r#"class="trace-outcome">skipped<"#).count(), 1);
+        assert!(html.contains("&lt;b&gt;emu&lt;/b&gt;"));
+        // Skipped operands were not resolved, rather than missing
+        assert!(!html.contains("trace-missing"));
+
+        let html = render(json!({}));
+        assert_eq!(html.matches("trace-missing").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn api_refuses_requests_without_a_valid_key() {
+        // Each test uses its own device, as the stores are shared
+        let device = Uuid::new_v4().to_string();
+        let tx = json!({ "device_fingerprint": device }).to_string();
+        for key in [None, Some("wrong-key"), Some("")] {
+            let (status, body) = call("/api/v1/evaluate", key, tx.clone()).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+            let (status, _) = call("/api/v1/evaluate/batch", key, tx.clone()).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+        }
+        let (status, _) = call("/api/v1/jsonlogic/import", None, "{}".to_string()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        // Refused transactions are not recorded for aggregates
+        assert_eq!(recorded(&device), 0);
+
+        let (status, _) = call("/api/v1/evaluate", Some(API_KEY), tx).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(recorded(&device), 1);
+    }
+
+    #[tokio::test]
+    async fn editor_tests_neither_feed_nor_see_api_aggregates() {
+        let device = Uuid::new_v4().to_string();
+        let tx = json!({ "device_fingerprint": device }).to_string();
+        test_transaction(Form(TestTransactionForm {
+            transaction: tx.clone(),
+        }))
+        .await;
+        let hour = TimeWindow {
+            amount: 1,
+            unit: WindowUnit::Hours,
+        };
+        let tested =
+            get_velocity_store().history(&Field::DeviceFingerprint, &device, &hour, unix_now());
+        assert_eq!(tested.len(), 1);
+        assert_eq!(recorded(&device), 0);
+
+        // Test cases see no history at all, wherever transactions were recorded
+        let (status, _) = call("/api/v1/evaluate", Some(API_KEY), tx.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        let mut rule = Rule::new("Repeat device".to_string(), String::new());
+        rule.root = leaf(
+            Operand::Aggregate {
+                function: AggregateFunction::Count,
+                field: None,
+                group_by: Field::DeviceFingerprint,
+                window: hour,
+            },
+            Operator::GreaterThanOrEqual,
+            Operand::Value {
+                value: "2".to_string(),
+            },
+        );
+        rule.test_cases.push(TestCase {
+            id: Uuid::new_v4(),
+            name: "First sighting".to_string(),
+            transaction: serde_json::from_str(&tx).unwrap(),
+            expect_flagged: false,
+        });
+        let results = run_test_cases(&rule, &test_case_context(&rule));
+        assert!(results[0].passed());
+    }
+
+    #[tokio::test]
+    async fn failing_test_cases_block_publishing() {
+        let device = Uuid::new_v4().to_string();
+        let mut rule = Rule::new("Known device".to_string(), String::new());
+        rule.root = device_is(&device);
+        rule.test_cases.push(TestCase {
+            id: Uuid::new_v4(),
+            name: "Known device passes".to_string(),
+            transaction: serde_json::from_value(json!({ "device_fingerprint": device })).unwrap(),
+            expect_flagged: false,
+        });
+        get_store().add_rule(rule.clone());
+        let published = || get_store().all_published().iter().any(|p| p.id == rule.id);
+
+        let body = text(publish_rule().await).await;
+        assert!(body.contains("Publishing blocked"), "{}", body);
+        assert!(body.contains("Known device passes"));
+        assert!(!published());
+
+        rule.test_cases[0].expect_flagged = true;
+        get_store().update_rule(rule.clone());
+        publish_rule().await;
+        assert!(published());
+    }
+
+    #[tokio::test]
+    async fn lists_are_managed_and_kept_while_referenced() {
+        let name = format!("devices_{}", Uuid::new_v4().simple());
+        let create = |name: &str| {
+            create_list(Form(CreateListForm {
+                name: name.to_string(),
+                element_type: "string".to_string(),
+                description: "Known devices".to_string(),
+            }))
+        };
+        create(&name).await;
+        assert_eq!(
+            get_list_store().get(&name).unwrap().description,
+            "Known devices"
+        );
+        let body = text(create(&name).await).await;
+        assert!(body.contains("already exists"), "{}", body);
+        let body = text(create("Bad Name").await).await;
+        assert!(get_list_store().get("Bad Name").is_none(), "{}", body);
+
+        let entries = |entries: &str| AddEntriesForm {
+            entries: entries.to_string(),
+        };
+        add_list_entries(Path(name.clone()), Form(entries("emu-1\n emu-2 \n\nemu-1"))).await;
+        assert_eq!(
+            get_list_store().get(&name).unwrap().entries,
+            vec!["emu-1", "emu-2"]
+        );
+        delete_list_entry(Path((name.clone(), 0))).await;
+        delete_list_entry(Path((name.clone(), 5))).await;
+        assert_eq!(get_list_store().get(&name).unwrap().entries, vec!["emu-2"]);
+
+        // A snippet using the list keeps it from being deleted
+        let snippet_name = format!("known_{}", Uuid::new_v4().simple());
+        get_snippet_store()
+            .save(Snippet {
+                name: snippet_name.clone(),
+                description: String::new(),
+                root: leaf(
+                    Operand::Field {
+                        field: Field::DeviceFingerprint,
+                    },
+                    Operator::In,
+                    Operand::ListRef { name: name.clone() },
+                ),
+            })
+            .unwrap();
+        let body = text(delete_list(Path(name.clone())).await).await;
+        assert!(body.contains("Cannot delete"), "{}", body);
+        assert!(body.contains(&format!("Snippet {}", snippet_name)));
+        assert!(get_list_store().get(&name).is_some());
+
+        get_snippet_store().delete(&snippet_name);
+        delete_list(Path(name.clone())).await;
+        assert!(get_list_store().get(&name).is_none());
+    }
+
+    #[tokio::test]
+    async fn evaluation_follows_published_rules_and_list_changes() {
+        let device = Uuid::new_v4().to_string();
+        let list_name = format!("devices_{}", device.replace('-', "_"));
+        let mut list = ReferenceList::new(list_name.clone(), String::new(), DataType::String);
+        list.add_entries([device.clone()]);
+        get_list_store().upsert(list.clone());
+        let in_list = leaf(
+            Operand::Field {
+                field: Field::DeviceFingerprint,
+            },
+            Operator::In,
+            Operand::ListRef { name: list_name },
+        );
+        let id = publish(None, vec![in_list]).to_string();
+
+        let tx = json!({ "device_fingerprint": device }).to_string();
+        let evaluate = || async {
+            let (status, body) = call("/api/v1/evaluate", Some(API_KEY), tx.clone()).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            serde_json::from_str::<serde_json::Value>(&body).unwrap()
+        };
+        let result = evaluate().await;
+        assert!(matched_ids(&result).contains(&id));
+        assert_eq!(result["decision"], json!("block"));
+
+        // Changing the list takes effect without publishing again
+        list.entries.clear();
+        get_list_store().upsert(list);
+        assert!(!matched_ids(&evaluate().await).contains(&id));
+
+        // So does publishing a new version of the rule
+        publish(Some(id.parse().unwrap()), vec![device_is(&device)]);
+        assert!(matched_ids(&evaluate().await).contains(&id));
+    }
+
+    #[tokio::test]
+    async fn batch_aggregates_count_earlier_transactions_of_the_batch() {
+        let device = Uuid::new_v4().to_string();
+        // The count includes the transaction being evaluated
+        let third_time = leaf(
+            Operand::Aggregate {
+                function: AggregateFunction::Count,
+                field: None,
+                group_by: Field::DeviceFingerprint,
+                window: TimeWindow {
+                    amount: 1,
+                    unit: WindowUnit::Hours,
+                },
+            },
+            Operator::GreaterThanOrEqual,
+            Operand::Value {
+                value: "3".to_string(),
+            },
+        );
+        let id = publish(None, vec![device_is(&device), third_time]).to_string();
+
+        let tx = json!({ "device_fingerprint": device }).to_string();
+        let batch = format!("{0}\n{0}\nnot json\n\n{0}\n", tx);
+        let (status, body) = call("/api/v1/evaluate/batch", Some(API_KEY), batch).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        let results: Vec<serde_json::Value> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(results.len(), 4);
+        assert_eq!(
+            results
+                .iter()
+                .map(|r| r["line"].clone())
+                .collect::<Vec<_>>(),
+            vec![json!(1), json!(2), json!(3), json!(5)]
+        );
+        assert!(!matched_ids(&results[0]).contains(&id));
+        assert!(!matched_ids(&results[1]).contains(&id));
+        assert!(results[2]["error"].is_string());
+        assert!(matched_ids(&results[3]).contains(&id));
+
+        // A later single evaluation counts the whole batch
+        assert_eq!(recorded(&device), 3);
+        let (_, body) = call("/api/v1/evaluate", Some(API_KEY), tx).await;
+        let result: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert!(matched_ids(&result).contains(&id));
+    }
+}
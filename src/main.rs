@@ -1,8 +1,9 @@
 mod auth;
 mod handlers;
-mod models;
+mod stores;
 
 use axum::{
+    extract::DefaultBodyLimit,
     middleware,
     routing::{get, post},
     Router,
@@ -25,11 +26,24 @@ async fn main() {
     // Build our application with routes
     let protected_routes = Router::new()
         .route("/", get(handlers::index))
+        .route("/rules", post(handlers::create_rule))
+        .route("/rules/:id", get(handlers::open_rule))
         // Tree-based routes with paths
         .route("/rule/node/:path/add-condition-form", get(handlers::new_condition_form))
         .route("/rule/node/:path/add-condition", post(handlers::add_condition))
         .route("/rule/node/:path/add-group", post(handlers::add_group))
+        .route(
+            "/rule/node/:path/add-snippet-form",
+            get(handlers::add_snippet_form),
+        )
+        .route("/rule/node/:path/add-snippet", post(handlers::add_snippet))
+        .route(
+            "/rule/node/:path/save-snippet-form",
+            get(handlers::save_snippet_form),
+        )
+        .route("/rule/node/:path/save-snippet", post(handlers::save_snippet))
         .route("/rule/node/:path/operator", post(handlers::update_operator))
+        .route("/rule/node/:path/weight", post(handlers::update_weight))
         .route("/rule/node/:path", axum::routing::delete(handlers::delete_node))
         // Dependent dropdown routes
         .route(
@@ -48,16 +62,106 @@ async fn main() {
             "/rule/conditions/operators-for-value",
             get(handlers::get_operators_for_value),
         )
+        .route(
+            "/rule/conditions/expression-preview",
+            get(handlers::preview_expression),
+        )
         .route("/rule/validate", post(handlers::validate_rule))
+        .route("/rule/dataset", post(handlers::upload_dataset))
+        .route("/rule/test-transaction", post(handlers::test_transaction))
+        .route("/rule/velocity/reset", post(handlers::reset_velocity))
+        .route("/rule/test-cases", post(handlers::add_test_case))
+        .route(
+            "/rule/test-cases/:id",
+            axum::routing::delete(handlers::delete_test_case),
+        )
+        .route("/rule/publish", post(handlers::publish_rule))
+        .route("/rule/export", get(handlers::export_rule))
+        .route("/rule/export/sql", post(handlers::export_rule_sql))
+        .route("/rule/export/jsonlogic", post(handlers::export_rule_jsonlogic))
+        .route("/rule/import/jsonlogic", post(handlers::import_rule_jsonlogic))
+        .route("/rule/simplify/preview", post(handlers::preview_simplify))
+        .route("/rule/simplify/apply", post(handlers::apply_simplify))
+        .route("/rule/mode", post(handlers::update_mode))
+        .route("/rule/timezone", post(handlers::update_timezone))
+        .route("/rule/thresholds", post(handlers::add_threshold))
+        .route(
+            "/rule/thresholds/:index",
+            axum::routing::delete(handlers::delete_threshold),
+        )
+        .route("/rule/actions", post(handlers::add_action))
+        .route(
+            "/rule/actions/:index",
+            axum::routing::delete(handlers::delete_action),
+        )
+        // Reference lists
+        .route("/lists", get(handlers::lists_page).post(handlers::create_list))
+        .route(
+            "/lists/:name",
+            get(handlers::list_page).delete(handlers::delete_list),
+        )
+        .route("/lists/:name/entries", post(handlers::add_list_entries))
+        .route(
+            "/lists/:name/entries/:index",
+            axum::routing::delete(handlers::delete_list_entry),
+        )
+        .route("/lists/:name/upload", post(handlers::upload_list_csv))
+        // Snippets
+        .route("/snippets", get(handlers::snippets_page))
+        .route(
+            "/snippets/:name",
+            get(handlers::snippet_page).delete(handlers::delete_snippet),
+        )
+        // Parameters
+        .route(
+            "/parameters",
+            get(handlers::parameters_page).post(handlers::create_parameter),
+        )
+        .route(
+            "/parameters/:name",
+            get(handlers::parameter_page)
+                .post(handlers::update_parameter)
+                .delete(handlers::delete_parameter),
+        )
+        // Rule sets
+        .route(
+            "/rule-sets",
+            get(handlers::rule_sets_page).post(handlers::create_rule_set),
+        )
+        .route(
+            "/rule-sets/:id",
+            get(handlers::rule_set_page)
+                .post(handlers::update_rule_set)
+                .delete(handlers::delete_rule_set),
+        )
+        .route("/rule-sets/:id/rules", post(handlers::add_rule_set_member))
+        .route(
+            "/rule-sets/:id/rules/:rule_id",
+            axum::routing::delete(handlers::remove_rule_set_member),
+        )
+        .route(
+            "/rule-sets/:id/rules/:rule_id/toggle",
+            post(handlers::toggle_rule_set_member),
+        )
+        .route("/rule-sets/:id/order", post(handlers::reorder_rule_set))
+        .route("/rule-sets/:id/test", post(handlers::test_rule_set))
+        .route("/rule-sets/:id/overlap", get(handlers::rule_set_overlap))
         .layer(middleware::from_fn(auth::auth_middleware));
 
     let public_routes = Router::new()
         .route("/login", get(handlers::login_page).post(handlers::do_login))
         .layer(middleware::from_fn(auth::public_only_middleware));
 
+    if auth::api_keys().is_empty() {
+        tracing::warn!(
+            "RULES_API_KEYS is not set; the /api/v1 endpoints will refuse every request"
+        );
+    }
+
     let app = Router::new()
         .merge(protected_routes)
         .merge(public_routes)
+        .merge(api_routes())
         .route("/logout", post(handlers::logout))
         .nest_service("/static", ServeDir::new("static"))
         .layer(TraceLayer::new_for_http());
@@ -68,3 +172,22 @@ async fn main() {
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
+
+/// Machine-to-machine evaluation API, outside the session login and behind API keys
+fn api_routes() -> Router {
+    Router::new()
+        .route(
+            "/api/v1/evaluate",
+            post(handlers::api_evaluate)
+                .layer(DefaultBodyLimit::max(handlers::MAX_EVALUATE_BODY_BYTES)),
+        )
+        .route(
+            "/api/v1/evaluate/batch",
+            post(handlers::api_evaluate_batch)
+                .layer(DefaultBodyLimit::max(handlers::MAX_BATCH_BODY_BYTES)),
+        )
+        .route("/api/v1/rules/:id/sql", post(handlers::api_rule_sql))
+        .route("/api/v1/rules/:id/jsonlogic", get(handlers::api_rule_jsonlogic))
+        .route("/api/v1/jsonlogic/import", post(handlers::api_import_jsonlogic))
+        .layer(middleware::from_fn(auth::api_key_middleware))
+}
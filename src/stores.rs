@@ -0,0 +1,526 @@
+//! In-memory stores for the web app's rules, lists, parameters, snippets, rule sets
+//! and sample dataset (in a real app, this would be a database), seeded with sample data.
+
+use rule_engine::models::{
+    find_snippet_cycle, ConditionNode, DataType, Field, LogicalOperator, Operand, Operator,
+    Parameter, ReferenceList, Rule, RuleSet, Snippet, Transaction,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+/// A counter bumped on every change to a store, so data derived from it can tell
+/// when it is stale
+#[derive(Clone, Default)]
+struct Revision(Arc<AtomicU64>);
+
+impl Revision {
+    fn get(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    fn bump(&self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[derive(Clone)]
+pub struct RuleStore {
+    /// Draft rules in creation order
+    rules: Arc<Mutex<Vec<Rule>>>,
+    /// The rule currently open in the editor
+    current: Arc<Mutex<Option<Uuid>>>,
+    published: Arc<Mutex<HashMap<Uuid, Rule>>>,
+    /// Changes to the published rules; editing drafts does not count
+    published_revision: Revision,
+}
+
+impl Default for RuleStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RuleStore {
+    pub fn new() -> Self {
+        // Initialize with a default rule
+        let default_rule = Rule::new(
+            "Fraud Detection Rule".to_string(),
+            "Main fraud detection rule for transactions".to_string(),
+        );
+        Self {
+            current: Arc::new(Mutex::new(Some(default_rule.id))),
+            rules: Arc::new(Mutex::new(vec![default_rule])),
+            published: Arc::new(Mutex::new(HashMap::new())),
+            published_revision: Revision::default(),
+        }
+    }
+
+    /// The draft of the rule currently open in the editor
+    pub fn get_rule(&self) -> Option<Rule> {
+        let current = (*self.current.lock().unwrap())?;
+        self.get_rule_by_id(current)
+    }
+
+    pub fn get_rule_by_id(&self, id: Uuid) -> Option<Rule> {
+        self.rules
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|rule| rule.id == id)
+            .cloned()
+    }
+
+    pub fn all_rules(&self) -> Vec<Rule> {
+        self.rules.lock().unwrap().clone()
+    }
+
+    /// Replace the draft with the same id, adding it if it is new
+    pub fn update_rule(&self, rule: Rule) {
+        let mut rules = self.rules.lock().unwrap();
+        match rules.iter_mut().find(|existing| existing.id == rule.id) {
+            Some(existing) => *existing = rule,
+            None => rules.push(rule),
+        }
+    }
+
+    /// Add a new rule and open it in the editor
+    pub fn add_rule(&self, rule: Rule) {
+        *self.current.lock().unwrap() = Some(rule.id);
+        self.update_rule(rule);
+    }
+
+    /// Open an existing rule in the editor
+    pub fn select(&self, id: Uuid) -> bool {
+        let exists = self.rules.lock().unwrap().iter().any(|rule| rule.id == id);
+        if exists {
+            *self.current.lock().unwrap() = Some(id);
+        }
+        exists
+    }
+
+    /// The snapshot of the current rule that was last published
+    pub fn get_published(&self) -> Option<Rule> {
+        let current = (*self.current.lock().unwrap())?;
+        self.published.lock().unwrap().get(&current).cloned()
+    }
+
+    pub fn all_published(&self) -> Vec<Rule> {
+        let published = self.published.lock().unwrap();
+        self.rules
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|rule| published.get(&rule.id).cloned())
+            .collect()
+    }
+
+    pub fn publish(&self, rule: Rule) {
+        self.published.lock().unwrap().insert(rule.id, rule);
+        self.published_revision.bump();
+    }
+
+    pub fn published_revision(&self) -> u64 {
+        self.published_revision.get()
+    }
+}
+
+/// In-memory store for snippets, keyed by name
+#[derive(Clone)]
+pub struct SnippetStore {
+    snippets: Arc<Mutex<BTreeMap<String, Snippet>>>,
+    revision: Revision,
+}
+
+impl Default for SnippetStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SnippetStore {
+    pub fn new() -> Self {
+        let new_account = Snippet {
+            name: "new_account".to_string(),
+            description: "Young account that is already transacting a lot".to_string(),
+            root: ConditionNode::Group {
+                id: Uuid::new_v4(),
+                operator: LogicalOperator::And,
+                children: vec![
+                    ConditionNode::Leaf {
+                        id: Uuid::new_v4(),
+                        left: Operand::Field {
+                            field: Field::AccountAge,
+                        },
+                        operator: Operator::LessThan,
+                        right: Operand::Value {
+                            value: "7".to_string(),
+                        },
+                        weight: None,
+                    },
+                    ConditionNode::Leaf {
+                        id: Uuid::new_v4(),
+                        left: Operand::Field {
+                            field: Field::TransactionCount24h,
+                        },
+                        operator: Operator::GreaterThan,
+                        right: Operand::Value {
+                            value: "3".to_string(),
+                        },
+                        weight: None,
+                    },
+                ],
+                weight: None,
+            },
+        };
+        let snippets = [(new_account.name.clone(), new_account)].into();
+        Self {
+            snippets: Arc::new(Mutex::new(snippets)),
+            revision: Revision::default(),
+        }
+    }
+
+    pub fn all(&self) -> Vec<Snippet> {
+        self.snippets.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Snippet> {
+        self.snippets.lock().unwrap().get(name).cloned()
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, Snippet> {
+        self.snippets.lock().unwrap().clone()
+    }
+
+    /// Save a snippet, replacing any snippet of the same name, unless its
+    /// conditions would reference it through other snippets
+    pub fn save(&self, snippet: Snippet) -> Result<(), String> {
+        let mut snippets = self.snippets.lock().unwrap();
+        if let Some(cycle) = find_snippet_cycle(&snippet.name, &snippet.root, &snippets) {
+            return Err(format!(
+                "Snippet \"{}\" would reference itself: {}",
+                snippet.name,
+                cycle.join(" → ")
+            ));
+        }
+        snippets.insert(snippet.name.clone(), snippet);
+        self.revision.bump();
+        Ok(())
+    }
+
+    pub fn delete(&self, name: &str) -> Option<Snippet> {
+        let removed = self.snippets.lock().unwrap().remove(name);
+        self.revision.bump();
+        removed
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision.get()
+    }
+}
+
+/// In-memory store for parameters, keyed by name
+#[derive(Clone)]
+pub struct ParameterStore {
+    parameters: Arc<Mutex<BTreeMap<String, Parameter>>>,
+    revision: Revision,
+}
+
+impl Default for ParameterStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ParameterStore {
+    pub fn new() -> Self {
+        let seeds = [
+            (
+                "HIGH_AMOUNT",
+                "Transaction amount that warrants a closer look",
+                DataType::Number,
+                "1000",
+            ),
+            (
+                "NEW_ACCOUNT_DAYS",
+                "Accounts younger than this many days are new",
+                DataType::Number,
+                "30",
+            ),
+            (
+                "HOME_CURRENCY",
+                "Currency most customers transact in",
+                DataType::String,
+                "USD",
+            ),
+        ];
+        let parameters = seeds
+            .into_iter()
+            .map(|(name, description, data_type, value)| {
+                (
+                    name.to_string(),
+                    Parameter {
+                        name: name.to_string(),
+                        description: description.to_string(),
+                        data_type,
+                        value: value.to_string(),
+                    },
+                )
+            })
+            .collect();
+        Self {
+            parameters: Arc::new(Mutex::new(parameters)),
+            revision: Revision::default(),
+        }
+    }
+
+    pub fn all(&self) -> Vec<Parameter> {
+        self.parameters.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<Parameter> {
+        self.parameters.lock().unwrap().get(name).cloned()
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, Parameter> {
+        self.parameters.lock().unwrap().clone()
+    }
+
+    pub fn upsert(&self, parameter: Parameter) {
+        self.parameters
+            .lock()
+            .unwrap()
+            .insert(parameter.name.clone(), parameter);
+        self.revision.bump();
+    }
+
+    pub fn delete(&self, name: &str) -> Option<Parameter> {
+        let removed = self.parameters.lock().unwrap().remove(name);
+        self.revision.bump();
+        removed
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision.get()
+    }
+}
+
+/// In-memory store for reference lists, keyed by name
+#[derive(Clone)]
+pub struct ListStore {
+    lists: Arc<Mutex<BTreeMap<String, ReferenceList>>>,
+    revision: Revision,
+}
+
+impl Default for ListStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ListStore {
+    pub fn new() -> Self {
+        // Seed with lists that match the bundled sample dataset
+        let seeds = [
+            (
+                "high_risk_ips",
+                "IP addresses seen in confirmed fraud",
+                DataType::Ip,
+                &["192.0.2.44", "192.0.2.77", "198.51.100.200"][..],
+            ),
+            (
+                "blocked_devices",
+                "Emulator and known-bad device fingerprints",
+                DataType::String,
+                &["emu-000001", "emu-000002", "emu-000003"][..],
+            ),
+            (
+                "high_risk_countries",
+                "Card BIN countries with elevated chargeback rates",
+                DataType::String,
+                &["NG", "RU", "BR"][..],
+            ),
+        ];
+        let lists = seeds
+            .into_iter()
+            .map(|(name, description, element_type, entries)| {
+                let mut list =
+                    ReferenceList::new(name.to_string(), description.to_string(), element_type);
+                list.add_entries(entries.iter().map(|e| e.to_string()));
+                (name.to_string(), list)
+            })
+            .collect();
+        Self {
+            lists: Arc::new(Mutex::new(lists)),
+            revision: Revision::default(),
+        }
+    }
+
+    pub fn all(&self) -> Vec<ReferenceList> {
+        self.lists.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<ReferenceList> {
+        self.lists.lock().unwrap().get(name).cloned()
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, ReferenceList> {
+        self.lists.lock().unwrap().clone()
+    }
+
+    pub fn upsert(&self, list: ReferenceList) {
+        self.lists.lock().unwrap().insert(list.name.clone(), list);
+        self.revision.bump();
+    }
+
+    pub fn delete(&self, name: &str) -> Option<ReferenceList> {
+        let removed = self.lists.lock().unwrap().remove(name);
+        self.revision.bump();
+        removed
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision.get()
+    }
+}
+
+/// In-memory store for rule sets, in creation order
+#[derive(Clone)]
+pub struct RuleSetStore {
+    rule_sets: Arc<Mutex<Vec<RuleSet>>>,
+}
+
+impl Default for RuleSetStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RuleSetStore {
+    pub fn new() -> Self {
+        Self {
+            rule_sets: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn all(&self) -> Vec<RuleSet> {
+        self.rule_sets.lock().unwrap().clone()
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<RuleSet> {
+        self.rule_sets
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|set| set.id == id)
+            .cloned()
+    }
+
+    /// Replace the rule set with the same id, adding it if it is new
+    pub fn upsert(&self, rule_set: RuleSet) {
+        let mut rule_sets = self.rule_sets.lock().unwrap();
+        match rule_sets
+            .iter_mut()
+            .find(|existing| existing.id == rule_set.id)
+        {
+            Some(existing) => *existing = rule_set,
+            None => rule_sets.push(rule_set),
+        }
+    }
+
+    pub fn delete(&self, id: Uuid) -> Option<RuleSet> {
+        let mut rule_sets = self.rule_sets.lock().unwrap();
+        let index = rule_sets.iter().position(|set| set.id == id)?;
+        Some(rule_sets.remove(index))
+    }
+}
+
+/// Sample transactions used to compute hit statistics on the rule tree
+#[derive(Clone)]
+pub struct DatasetStore {
+    transactions: Arc<Mutex<Vec<Transaction>>>,
+}
+
+impl Default for DatasetStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DatasetStore {
+    pub fn new() -> Self {
+        // Start with the bundled sample dataset
+        let transactions =
+            Transaction::parse_jsonl(include_str!("../data/sample_transactions.jsonl"))
+                .unwrap_or_default();
+        Self {
+            transactions: Arc::new(Mutex::new(transactions)),
+        }
+    }
+
+    pub fn get_transactions(&self) -> Vec<Transaction> {
+        self.transactions.lock().unwrap().clone()
+    }
+
+    pub fn replace(&self, transactions: Vec<Transaction>) {
+        *self.transactions.lock().unwrap() = transactions;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snippet(name: &str, root: ConditionNode) -> Snippet {
+        Snippet {
+            name: name.to_string(),
+            description: String::new(),
+            root,
+        }
+    }
+
+    fn reference(name: &str) -> ConditionNode {
+        ConditionNode::SnippetRef {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            weight: None,
+        }
+    }
+
+    #[test]
+    fn snippets_that_would_reference_themselves_are_not_saved() {
+        let store = SnippetStore::new();
+        let revision = store.revision();
+        let error = store.save(snippet("self", reference("self"))).unwrap_err();
+        assert_eq!(
+            error,
+            "Snippet \"self\" would reference itself: self → self"
+        );
+        assert!(store.get("self").is_none());
+
+        // Closing a loop through a saved snippet is refused too
+        store
+            .save(snippet("wrapper", reference("new_account")))
+            .unwrap();
+        let seed = store.get("new_account").unwrap();
+        let looped = ConditionNode::Group {
+            id: Uuid::new_v4(),
+            operator: LogicalOperator::Or,
+            children: vec![seed.root.clone(), reference("wrapper")],
+            weight: None,
+        };
+        let error = store.save(snippet("new_account", looped)).unwrap_err();
+        assert!(
+            error.ends_with("new_account → wrapper → new_account"),
+            "{}",
+            error
+        );
+        assert_eq!(
+            format!("{:?}", store.get("new_account").unwrap().root),
+            format!("{:?}", seed.root)
+        );
+        // Only the successful save counts as a change
+        assert_eq!(store.revision(), revision + 1);
+    }
+}
@@ -0,0 +1,220 @@
+//! Runs the `rules` command-line tool on rule files in a scratch directory
+
+use rule_engine::models::{
+    Action, ConditionNode, Field, LogicalOperator, Operand, Operator, Rule, RuleMode,
+    ScoreThreshold,
+};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use uuid::Uuid;
+
+/// A fresh directory for one test's files
+fn scratch_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rules-cli-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn rules(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rules"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout_lines(output: &Output) -> Vec<String> {
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+fn amount_above(amount: &str) -> ConditionNode {
+    ConditionNode::Leaf {
+        id: Uuid::new_v4(),
+        left: Operand::Field {
+            field: Field::TransactionAmount,
+        },
+        operator: Operator::GreaterThan,
+        right: Operand::Value {
+            value: amount.to_string(),
+        },
+        weight: None,
+    }
+}
+
+/// A rule flagging transactions above 100
+fn sample_rule() -> Rule {
+    let mut rule = Rule::new("Large amounts".to_string(), String::new());
+    rule.root = ConditionNode::Group {
+        id: Uuid::new_v4(),
+        operator: LogicalOperator::And,
+        children: vec![amount_above("100")],
+        weight: None,
+    };
+    rule
+}
+
+fn write_rule(dir: &Path, name: &str, rule: &Rule) -> String {
+    let path = dir.join(name);
+    let mut text = serde_json::to_string_pretty(rule).unwrap();
+    text.push('\n');
+    std::fs::write(&path, text).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn diff_lists_changed_added_and_moved_conditions() {
+    let dir = scratch_dir();
+    let old = sample_rule();
+    let mut new = old.clone();
+    new.name = "Very large amounts".to_string();
+    new.actions.push(Action::Block);
+    let ConditionNode::Group { children, .. } = &mut new.root else {
+        unreachable!()
+    };
+    let ConditionNode::Leaf { right, .. } = &mut children[0] else {
+        unreachable!()
+    };
+    *right = Operand::Value {
+        value: "500".to_string(),
+    };
+    let added = amount_above("1000");
+    children.insert(0, added.clone());
+    let old_path = write_rule(&dir, "old.json", &old);
+    let new_path = write_rule(&dir, "new.json", &new);
+
+    let output = rules(&["diff", &old_path, &new_path], "");
+    assert_eq!(output.status.code(), Some(1));
+    let ConditionNode::Group { children, .. } = &old.root else {
+        unreachable!()
+    };
+    assert_eq!(
+        stdout_lines(&output),
+        vec![
+            r#"~ name: "Large amounts" → "Very large amounts""#.to_string(),
+            format!(
+                "~ condition moved 0-0 → 0-1: {} → {}",
+                children[0].display(),
+                amount_above("500").display()
+            ),
+            format!("+ condition 0-0: {}", added.display()),
+            "+ action: Block".to_string(),
+        ]
+    );
+
+    let output = rules(&["diff", &old_path, &old_path], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn fmt_check_fails_until_files_are_formatted() {
+    let dir = scratch_dir();
+    let formatted = write_rule(&dir, "formatted.json", &sample_rule());
+    let compact = dir.join("compact.json");
+    std::fs::write(&compact, serde_json::to_string(&sample_rule()).unwrap()).unwrap();
+    let compact = compact.to_string_lossy().into_owned();
+
+    let output = rules(&["fmt", "--check", &formatted, &compact], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stdout_lines(&output),
+        vec![format!("{}: not formatted", compact)]
+    );
+
+    let output = rules(&["fmt", "--write", &compact], "");
+    assert_eq!(output.status.code(), Some(0));
+    let output = rules(&["fmt", "--check", &formatted, &compact], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn validate_and_fmt_need_files() {
+    for args in [&["validate"][..], &["fmt", "--check"][..]] {
+        let output = rules(args, "");
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("required"));
+    }
+}
+
+#[test]
+fn eval_prints_one_result_per_transaction() {
+    let dir = scratch_dir();
+    let mut rule = sample_rule();
+    rule.actions = vec![Action::Block];
+    let boolean = write_rule(&dir, "boolean.json", &rule);
+
+    let stdin = "{\"transaction_amount\": 150}\n\
+                 {\"transaction_amount\": 50}\n\
+                 \n\
+                 not json\n";
+    let output = rules(&["eval", &boolean], stdin);
+    // Exits non-zero because a line was not a transaction
+    assert_eq!(output.status.code(), Some(1));
+    let results: Vec<Value> = stdout_lines(&output)
+        .iter()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(results.len(), 3);
+    assert_eq!(
+        results[0],
+        json!({ "line": 1, "matched": true, "actions": [{ "type": "block" }] })
+    );
+    assert_eq!(
+        results[1],
+        json!({ "line": 2, "matched": false, "actions": [] })
+    );
+    assert_eq!(results[2]["line"], json!(4));
+    assert!(results[2]["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("Invalid transaction JSON"));
+
+    // Scoring rules also print the score, and the actions of the threshold reached
+    rule.mode = RuleMode::Scoring;
+    rule.actions.clear();
+    rule.root.set_weight(Some(40.0));
+    rule.thresholds = vec![ScoreThreshold {
+        min_score: 30.0,
+        actions: vec![Action::FlagForReview],
+    }];
+    let scoring = write_rule(&dir, "scoring.json", &rule);
+    let output = rules(
+        &["eval", &scoring],
+        "{\"transaction_amount\": 150}\n{\"transaction_amount\": 50}\n",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    let results: Vec<Value> = stdout_lines(&output)
+        .iter()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        results,
+        vec![
+            json!({
+                "line": 1,
+                "matched": true,
+                "score": 40.0,
+                "actions": [{ "type": "flag_for_review" }]
+            }),
+            json!({ "line": 2, "matched": false, "score": 0.0, "actions": [] }),
+        ]
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}